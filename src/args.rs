@@ -1,7 +1,31 @@
 //! Module to process any command line arguments supplied to `wsconvert`
 
-use crate::ws_filters::Excludes;
-use clap::{crate_version, App, Arg};
+use clap::{crate_version, App, AppSettings, Arg, ErrorKind, SubCommand};
+use std::env;
+use std::ffi::OsString;
+use std::path::Path;
+use std::process;
+use std::time::Duration;
+use wsconvert::bench::Features;
+use wsconvert::exit_status::ExitStatus;
+use wsconvert::log_file::LogFormat;
+use wsconvert::provenance::{Placement, Provenance};
+use wsconvert::render::{Format, PageBreakStyle, RenderOptions};
+use wsconvert::transliterate::Charset;
+use wsconvert::ws_dot_cmd::SuffixMode;
+use wsconvert::ws_filters::{Excludes, Options};
+use wsconvert::ws_overline::OverlineStyle;
+use wsconvert::ws_special::{CustomFraction, FractionStyle, Locale, Symbols};
+use wsconvert::ws_wrappers::{CustomWrapper, Style, UnderlineStyle, Wrappers};
+use wsconvert::{
+    BatchSettings, Converted, Filler, InputFormat, Limits, Mode, RuledHeadings, Split, WalkPolicy,
+};
+
+// Environment variables
+
+const ENV_OPTS: &str = "WSCONVERT_OPTS"; // Default options (overridden by command line)
+const ENV_NO_COLOR: &str = "NO_COLOR"; // Disable colored diagnostics if set (non-empty)
+const ENV_SOURCE_DATE: &str = "SOURCE_DATE_EPOCH"; // Fixed time for reproducible output
 
 // Log output settings
 
@@ -14,47 +38,326 @@ const LOG_TRACE: &str = "trace";
 
 const LOG_VALUES: [&str; 6] = [LOG_OFF, LOG_ERROR, LOG_WARN, LOG_INFO, LOG_DEBUG, LOG_TRACE];
 
-// Exclude filter settings
-
-const EXCLUDE_DOT_CMDS: &str = "dot-cmds";
-const EXCLUDE_RE_ALIGN: &str = "re-align";
-const EXCLUDE_SPECIALS: &str = "specials";
-const EXCLUDE_OVERLINE: &str = "overline";
-const EXCLUDE_WRAPPERS: &str = "wrappers";
-const EXCLUDE_CONTROLS: &str = "controls";
-
-const EXCLUDE_VALUES: [&str; 6] = [
-    EXCLUDE_DOT_CMDS,
-    EXCLUDE_RE_ALIGN,
-    EXCLUDE_SPECIALS,
-    EXCLUDE_OVERLINE,
-    EXCLUDE_WRAPPERS,
-    EXCLUDE_CONTROLS,
+const LOG_FORMAT_TEXT: &str = "text";
+const LOG_FORMAT_JSON: &str = "json";
+
+const LOG_FORMAT_VALUES: [&str; 2] = [LOG_FORMAT_TEXT, LOG_FORMAT_JSON];
+
+// Output format settings
+
+const FORMAT_TEXT: &str = "text";
+const FORMAT_HTML: &str = "html";
+const FORMAT_EPUB: &str = "epub";
+const FORMAT_DOCX: &str = "docx";
+const FORMAT_ODT: &str = "odt";
+const FORMAT_GROFF: &str = "groff";
+const FORMAT_PRINT: &str = "print";
+const FORMAT_JSON: &str = "json";
+const FORMAT_PANDOC: &str = "pandoc";
+
+const FORMAT_VALUES: [&str; 9] = [
+    FORMAT_TEXT,
+    FORMAT_HTML,
+    FORMAT_EPUB,
+    FORMAT_DOCX,
+    FORMAT_ODT,
+    FORMAT_GROFF,
+    FORMAT_PRINT,
+    FORMAT_JSON,
+    FORMAT_PANDOC,
+];
+
+// Input filler settings
+
+const FILLER_KEEP: &str = "keep";
+const FILLER_STRIP: &str = "strip";
+const FILLER_FLAG: &str = "flag";
+
+const FILLER_VALUES: [&str; 3] = [FILLER_KEEP, FILLER_STRIP, FILLER_FLAG];
+
+// Previously converted input settings
+const CONVERTED_REFUSE: &str = "refuse";
+const CONVERTED_PASS: &str = "pass";
+const CONVERTED_CONVERT: &str = "convert";
+
+const CONVERTED_VALUES: [&str; 3] = [CONVERTED_REFUSE, CONVERTED_PASS, CONVERTED_CONVERT];
+
+// Input format settings
+const INPUT_FORMAT_WORDSTAR: &str = "wordstar";
+const INPUT_FORMAT_NEWWORD: &str = "newword";
+
+const INPUT_FORMAT_VALUES: [&str; 2] = [INPUT_FORMAT_WORDSTAR, INPUT_FORMAT_NEWWORD];
+
+// Filter style settings
+
+const OVERLINE_COMBINING: &str = "combining";
+const OVERLINE_SLASH: &str = "slash";
+const OVERLINE_TILDE: &str = "tilde";
+const OVERLINE_STAR: &str = "star";
+
+const OVERLINE_VALUES: [&str; 4] = [
+    OVERLINE_COMBINING,
+    OVERLINE_SLASH,
+    OVERLINE_TILDE,
+    OVERLINE_STAR,
+];
+
+const UNDERLINE_COMBINING: &str = "combining";
+const UNDERLINE_U_TAG: &str = "u-tag";
+const UNDERLINE_INS_TAG: &str = "ins-tag";
+const UNDERLINE_DOUBLE_UNDERSCORE: &str = "double-underscore";
+const UNDERLINE_NONE: &str = "none";
+
+const UNDERLINE_VALUES: [&str; 5] = [
+    UNDERLINE_COMBINING,
+    UNDERLINE_U_TAG,
+    UNDERLINE_INS_TAG,
+    UNDERLINE_DOUBLE_UNDERSCORE,
+    UNDERLINE_NONE,
+];
+
+const SUFFIX_DROP: &str = "drop";
+const SUFFIX_TEXT: &str = "text";
+const SUFFIX_COMMENT: &str = "comment";
+
+const SUFFIX_VALUES: [&str; 3] = [SUFFIX_DROP, SUFFIX_TEXT, SUFFIX_COMMENT];
+
+const CHARSET_UNICODE: &str = "unicode";
+const CHARSET_LATIN1: &str = "latin1";
+const CHARSET_ASCII: &str = "ascii";
+
+const CHARSET_VALUES: [&str; 3] = [CHARSET_UNICODE, CHARSET_LATIN1, CHARSET_ASCII];
+
+const SPLIT_PAGE: &str = "page";
+const SPLIT_H1: &str = "h1";
+
+const SPLIT_VALUES: [&str; 2] = [SPLIT_PAGE, SPLIT_H1];
+
+const PROVENANCE_EMBED: &str = "embed";
+const PROVENANCE_SIDECAR: &str = "sidecar";
+
+const PROVENANCE_VALUES: [&str; 2] = [PROVENANCE_EMBED, PROVENANCE_SIDECAR];
+
+// Page break settings (any other value is used as custom text)
+
+const PAGE_BREAK_BARS: &str = "bars";
+const PAGE_BREAK_MARKDOWN: &str = "markdown";
+const PAGE_BREAK_HTML: &str = "html";
+const PAGE_BREAK_FORM_FEED: &str = "form-feed";
+
+const FRACTION_UNICODE: &str = "unicode";
+const FRACTION_SLASH: &str = "slash";
+
+const FRACTION_VALUES: [&str; 2] = [FRACTION_UNICODE, FRACTION_SLASH];
+
+const LOCALE_NONE: &str = "none";
+const LOCALE_ENGLISH: &str = "english";
+const LOCALE_CONTINENTAL: &str = "continental";
+const LOCALE_FRENCH: &str = "french";
+const LOCALE_GERMAN: &str = "german";
+const LOCALE_SPANISH: &str = "spanish";
+
+const LOCALE_VALUES: [&str; 6] = [
+    LOCALE_NONE,
+    LOCALE_ENGLISH,
+    LOCALE_CONTINENTAL,
+    LOCALE_FRENCH,
+    LOCALE_GERMAN,
+    LOCALE_SPANISH,
+];
+
+const RULED_NONE: &str = "none";
+const RULED_SETEXT: &str = "setext";
+const RULED_UNDERLINE: &str = "underline";
+
+const RULED_VALUES: [&str; 3] = [RULED_NONE, RULED_SETEXT, RULED_UNDERLINE];
+
+const MODE_STRICT: &str = "strict";
+const MODE_PERMISSIVE: &str = "permissive";
+
+const MODE_VALUES: [&str; 2] = [MODE_STRICT, MODE_PERMISSIVE];
+
+const SYMBOLS_NONE: &str = "none";
+const SYMBOLS_SUPERSCRIPT: &str = "superscript";
+const SYMBOLS_PLAIN: &str = "plain";
+
+const SYMBOLS_VALUES: [&str; 3] = [SYMBOLS_NONE, SYMBOLS_SUPERSCRIPT, SYMBOLS_PLAIN];
+
+// Custom wrapper style settings
+
+const STYLE_NONE: &str = "none";
+const STYLE_BOLD: &str = "bold";
+const STYLE_ITALIC: &str = "italic";
+const STYLE_UNDERLINE: &str = "underline";
+const STYLE_STRIKETHROUGH: &str = "strikethrough";
+const STYLE_OVERLINE: &str = "overline";
+const STYLE_SUPERSCRIPT: &str = "superscript";
+const STYLE_SUBSCRIPT: &str = "subscript";
+const STYLE_ALTERNATE: &str = "alternate";
+
+const STYLE_VALUES: [&str; 9] = [
+    STYLE_NONE,
+    STYLE_BOLD,
+    STYLE_ITALIC,
+    STYLE_UNDERLINE,
+    STYLE_STRIKETHROUGH,
+    STYLE_OVERLINE,
+    STYLE_SUPERSCRIPT,
+    STYLE_SUBSCRIPT,
+    STYLE_ALTERNATE,
 ];
 
+// Subcommands
+
+const CMD_STATS: &str = "stats";
+const CMD_GENERATE: &str = "generate";
+const CMD_VERIFY_CORPUS: &str = "verify-corpus";
+const CMD_BATCH: &str = "batch";
+const CMD_ASCIIFY: &str = "asciify";
+const CMD_FILTERS: &str = "filters";
+
+// Generated document settings
+
+const DEFAULT_SIZE: usize = 1024 * 1024;
+const DEFAULT_DENSITY: f64 = 0.1;
+
+// Output file settings
+
+const GZIP_EXTENSION: &str = "gz"; // Extension left out when choosing the format
+
+/// Actions that can be selected on the command line
+#[derive(Debug, Clone, PartialEq)]
+pub enum Command {
+    Convert, // Convert input to output (no subcommand)
+    Stats,   // Report statistics on the conversion without writing output
+    // Write a generated WordStar document (of a size, formatting density and features)
+    Generate {
+        size: usize,
+        density: f64,
+        features: Features,
+    },
+    // Compare conversions of the files in a directory with expected outputs
+    VerifyCorpus {
+        dir: String,
+    },
+    // Convert each input to its own output file and summarise the outcomes
+    Batch(BatchSettings),
+    Asciify, // Write the 7-bit ASCII intermediate text without filtering
+    Filters, // Filter input that is already 7-bit ASCII text
+    // Convert input to text, re-encode it and convert again to check for text loss
+    SelfTest,
+    Strip, // Write only the plain text of input in a single fast pass
+}
+
 /// Holds the values obtained by processing command line arguments
 #[derive(Debug)]
 pub struct Args {
-    pub infile: String,
+    pub command: Command,
+    pub infiles: Vec<String>,
     pub outfile: String,
     pub log_level: log::LevelFilter,
-    pub excludes: Excludes,
+    pub log_format: LogFormat,
+    pub no_color: bool,
+    pub rules: Option<String>,
+    pub dot_cmds: Option<String>,
+    pub log_file: Option<String>,
+    pub textconv: bool,
+    pub options: Options,
 }
 
-/// Returns an `Args` structure containing the processed arguments (if any)
-/// from the command line input
-///
 impl Args {
+    /// Returns an `Args` structure containing the processed arguments (if any)
+    /// from the command line input, preceded by any default options from the
+    /// `WSCONVERT_OPTS` environment variable
+    ///
     pub fn parse() -> Self {
+        let defaults = env::var(ENV_OPTS).unwrap_or_default();
+        let no_color = env::var_os(ENV_NO_COLOR).is_some_and(|value| !value.is_empty());
+        Self::parse_from(with_defaults(env::args_os(), &defaults), no_color)
+    }
+
+    /// Returns an `Args` structure for converting the given file as a git
+    /// `diff.textconv` driver, i.e. to plain text on `stdout` with default
+    /// settings (ignoring any other options, so that the output is stable)
+    /// and without logging or statistics
+    ///
+    /// # Arguments
+    ///
+    /// * `infile` - Path to input file
+    /// * `no_color` - Flag to disable colored diagnostics
+    ///
+    fn textconv(infile: &str, no_color: bool) -> Self {
+        Self {
+            command: Command::Convert,
+            infiles: vec![infile.to_string()],
+            outfile: String::new(),
+            log_level: log::LevelFilter::Off,
+            log_format: LogFormat::Text,
+            no_color,
+            rules: None,
+            dot_cmds: None,
+            log_file: None,
+            textconv: true,
+            options: Options {
+                quiet: true,
+                ..Default::default()
+            },
+        }
+    }
+
+    /// Returns an `Args` structure containing the processed arguments (if any)
+    /// from the given list (starting with the program name)
+    ///
+    /// # Arguments
+    ///
+    /// * `argv` - List of arguments, including any defaults
+    /// * `no_color` - Flag to disable colored diagnostics
+    ///
+    fn parse_from(argv: Vec<OsString>, no_color: bool) -> Self {
+        let arguments: Vec<String> = argv
+            .iter()
+            .skip(1)
+            .map(|arg| arg.to_string_lossy().into_owned())
+            .collect();
         let matches = App::new("wsconvert")
             .about("Converts old WordStar files into readable format")
             .version(crate_version!())
+            .setting(AppSettings::AllArgsOverrideSelf)
+            // Stops input filenames close to a subcommand name (e.g. "stats.ws")
+            // being rejected as a mistyped subcommand
+            .setting(AppSettings::AllowExternalSubcommands)
+            .after_help(
+                "Default options may be given in the WSCONVERT_OPTS environment variable \
+                 (separated by spaces), which are overridden by those on the command line.\n\n\
+                 Exit status: 0 if converted cleanly, 1 if converted with warnings (whether \
+                 or not the log level shows them), 2 if some files failed (or did not pass \
+                 a check) and 3 if a fatal error occurred.",
+            )
             .arg(
                 Arg::with_name("infile")
                     .short("i")
                     .long("infile")
                     .takes_value(true)
-                    .help("Read from a file instead of stdin"),
+                    .multiple(true)
+                    .number_of_values(1)
+                    .help("Read from a file instead of stdin (may be repeated, '-' for stdin)"),
+            )
+            .arg(
+                Arg::with_name("inputs")
+                    .value_name("INPUT")
+                    .multiple(true)
+                    .help("Further files to read after any given with --infile ('-' for stdin)"),
+            )
+            .arg(
+                Arg::with_name("textconv")
+                    .long("textconv")
+                    .takes_value(true)
+                    .value_name("FILE")
+                    .conflicts_with_all(&["infile", "inputs", "outfile"])
+                    .help(
+                        "Write FILE as plain text to stdout with default settings and no \
+                         statistics (for use as a git diff.textconv driver)",
+                    ),
             )
             .arg(
                 Arg::with_name("outfile")
@@ -72,38 +375,818 @@ impl Args {
                     .case_insensitive(true)
                     .help("Logging level"),
             )
+            .arg(
+                Arg::with_name("log-format")
+                    .long("log-format")
+                    .takes_value(true)
+                    .possible_values(&LOG_FORMAT_VALUES)
+                    .case_insensitive(true)
+                    .help("Format of log records (json gives one JSON object per line)"),
+            )
             .arg(
                 Arg::with_name("x-names")
                     .short("x")
                     .long("exclude")
                     .takes_value(true)
-                    .possible_values(&EXCLUDE_VALUES)
+                    .possible_values(&Excludes::NAMES)
                     .multiple(true)
+                    .number_of_values(1)
                     .use_delimiter(true)
                     .case_insensitive(true)
                     .help("Filters to exclude"),
             )
-            .get_matches();
+            .arg(
+                Arg::with_name("filler")
+                    .long("filler")
+                    .takes_value(true)
+                    .possible_values(&FILLER_VALUES)
+                    .case_insensitive(true)
+                    .help("Treatment of runs of NUL or 0xE5 filler bytes from damaged disks"),
+            )
+            .arg(
+                Arg::with_name("converted")
+                    .long("converted")
+                    .takes_value(true)
+                    .possible_values(&CONVERTED_VALUES)
+                    .case_insensitive(true)
+                    .help("Treatment of input that looks like output from a previous conversion"),
+            )
+            .arg(
+                Arg::with_name("input-format")
+                    .long("input-format")
+                    .takes_value(true)
+                    .possible_values(&INPUT_FORMAT_VALUES)
+                    .case_insensitive(true)
+                    .help(
+                        "Word processor that created the input (for its dot commands and controls)",
+                    ),
+            )
+            .arg(
+                Arg::with_name("self-test")
+                    .long("self-test")
+                    .conflicts_with("outfile")
+                    .help(
+                        "Convert to text, re-encode as WordStar and convert again, reporting \
+                         any lines that differ (as a check for text lost in conversion)",
+                    ),
+            )
+            .arg(
+                Arg::with_name("strip")
+                    .long("strip")
+                    .conflicts_with_all(&["self-test", "split-output"])
+                    .help(
+                        "Write only the plain text, removing all WordStar controls and dot \
+                         commands in a single fast pass without any filters or formatting",
+                    ),
+            )
+            .arg(
+                Arg::with_name("detect-columns")
+                    .long("detect-columns")
+                    .help("Detect paragraphs laid out in columns and linearize them"),
+            )
+            .arg(
+                Arg::with_name("outline")
+                    .long("outline")
+                    .help("Treat lines numbered 1., 1.1, 1.1.1 etc. as nested headings"),
+            )
+            .arg(
+                Arg::with_name("ruled-headings")
+                    .long("ruled-headings")
+                    .takes_value(true)
+                    .possible_values(&RULED_VALUES)
+                    .case_insensitive(true)
+                    .help(
+                        "Treat lines ruled off by a line of =, - or _ as headings, with a \
+                         Markdown setext ruling or an underlined title in text output",
+                    ),
+            )
+            .arg(
+                Arg::with_name("overline-style")
+                    .long("overline-style")
+                    .takes_value(true)
+                    .possible_values(&OVERLINE_VALUES)
+                    .case_insensitive(true)
+                    .help("Representation of overlined text"),
+            )
+            .arg(
+                Arg::with_name("underline-style")
+                    .long("underline-style")
+                    .takes_value(true)
+                    .possible_values(&UNDERLINE_VALUES)
+                    .case_insensitive(true)
+                    .help("Representation of underlined text in text and HTML output"),
+            )
+            .arg(
+                Arg::with_name("page-break")
+                    .long("page-break")
+                    .takes_value(true)
+                    .value_name("STYLE")
+                    .help(
+                        "Representation of page breaks in text output: bars (default), \
+                         markdown, html, form-feed or any other text to use as it is",
+                    ),
+            )
+            .arg(Arg::with_name("reproducible").long("reproducible").help(
+                "Give byte-identical output for identical input and options, recording \
+                         SOURCE_DATE_EPOCH (or else 1970) as the modification time in EPUB output",
+            ))
+            .arg(
+                Arg::with_name("provenance")
+                    .long("provenance")
+                    .takes_value(true)
+                    .possible_values(&PROVENANCE_VALUES)
+                    .case_insensitive(true)
+                    .requires_if(PROVENANCE_SIDECAR, "outfile")
+                    .help(
+                        "Record the tool version, arguments, input checksums and date in the \
+                         output (text, HTML or JSON) or in a .provenance.json file beside it",
+                    ),
+            )
+            .arg(
+                Arg::with_name("lenient-overline")
+                    .long("lenient-overline")
+                    .help("Accept overline sequences with counts that differ by one"),
+            )
+            .arg(
+                Arg::with_name("max-output-line")
+                    .long("max-output-line")
+                    .takes_value(true)
+                    .value_name("CHARS")
+                    .validator(|s| match s.trim().parse::<usize>() {
+                        Ok(max) if max > 0 => Ok(()),
+                        _ => Err("expected a number of characters greater than 0".to_string()),
+                    })
+                    .help(
+                        "Warn about output lines longer than CHARS (e.g. from combining \
+                         characters) in text or print output",
+                    ),
+            )
+            .arg(
+                Arg::with_name("long-line-markup")
+                    .long("long-line-markup")
+                    .requires("max-output-line")
+                    .help(
+                        "Mark up underline, strikethrough and overline instead of using combining \
+                         characters in text or print lines longer than --max-output-line",
+                    ),
+            )
+            .arg(
+                Arg::with_name("fraction-style")
+                    .long("fraction-style")
+                    .takes_value(true)
+                    .possible_values(&FRACTION_VALUES)
+                    .case_insensitive(true)
+                    .help("Representation of simple fractions"),
+            )
+            .arg(
+                Arg::with_name("locale")
+                    .long("locale")
+                    .takes_value(true)
+                    .possible_values(&LOCALE_VALUES)
+                    .case_insensitive(true)
+                    .help(
+                        "Conventions for tidying up temperatures and ordinal numbers (and for \
+                         French, German or Spanish also `-accented letters and << >> quotes)",
+                    ),
+            )
+            .arg(
+                Arg::with_name("symbols")
+                    .long("symbols")
+                    .takes_value(true)
+                    .possible_values(&SYMBOLS_VALUES)
+                    .case_insensitive(true)
+                    .help(
+                        "Convert ^TTM^T, (c) and (r) into Unicode signs, keeping superscripted \
+                         ordinal suffixes (e.g. 1^Tst^T) superscripted or as plain text",
+                    ),
+            )
+            .arg(
+                Arg::with_name("fraction")
+                    .long("fraction")
+                    .takes_value(true)
+                    .value_name("N/D=TEXT")
+                    .multiple(true)
+                    .number_of_values(1)
+                    .validator(|s| match get_fraction(&s) {
+                        Some(_) => Ok(()),
+                        None => Err(
+                            "expected a numerator and denominator separated by '/', then '=' \
+                             and the replacement text (e.g. 5/6=\u{215A})"
+                                .to_string(),
+                        ),
+                    })
+                    .help("Additional fraction and the text that replaces it"),
+            )
+            .arg(
+                Arg::with_name("rules")
+                    .long("rules")
+                    .takes_value(true)
+                    .value_name("FILE")
+                    .help(
+                        "Read additional special sequences from FILE, one 'PATTERN => TEXT' per \
+                         line (with control characters as escapes, e.g. \\x13)",
+                    ),
+            )
+            .arg(
+                Arg::with_name("dot-cmds")
+                    .long("dot-cmds")
+                    .takes_value(true)
+                    .value_name("FILE")
+                    .help(
+                        "Read dot command handlers from FILE, one 'COMMAND = ACTION' per line \
+                         (drop, keep, header, header(N), page-break or \"template {text}\")",
+                    ),
+            )
+            .arg(
+                Arg::with_name("log-file")
+                    .long("log-file")
+                    .takes_value(true)
+                    .value_name("FILE")
+                    .help("Also write all warnings logged during conversion to FILE"),
+            )
+            .arg(
+                Arg::with_name("no-escape-unknown")
+                    .long("no-escape-unknown")
+                    .help("Keep unrecognised control characters instead of escaping them as '^X'"),
+            )
+            .arg(
+                Arg::with_name("preserve-controls")
+                    .long("preserve-controls")
+                    .takes_value(true)
+                    .value_name("CODES")
+                    .multiple(true)
+                    .require_delimiter(true)
+                    .validator(|s| match get_control_code(s.trim()) {
+                        Some(_) => Ok(()),
+                        None => Err("expected a control code (e.g. 09, 0x0C or ^L)".to_string()),
+                    })
+                    .help(
+                        "Pass these control characters through untouched, e.g. \"09,0C\" for \
+                         real tabs and form feeds (also with --strip), which DOCX and ODT \
+                         output write as tab stops and page breaks",
+                    ),
+            )
+            .arg(
+                Arg::with_name("profile")
+                    .long("profile")
+                    .help("Print the time taken and lines changed by each pipeline stage"),
+            )
+            .arg(
+                Arg::with_name("compress")
+                    .long("compress")
+                    .help("Gzip the output (adding .gz to the output file name)"),
+            )
+            .arg(Arg::with_name("in-memory").long("in-memory").help(
+                "Convert in memory and write the output in one go, as is done for \
+                         small uncompressed inputs, instead of via temporary files",
+            ))
+            .arg(
+                Arg::with_name("charset")
+                    .long("charset")
+                    .takes_value(true)
+                    .possible_values(&CHARSET_VALUES)
+                    .case_insensitive(true)
+                    .help(
+                        "Transliterate text output into Latin-1 or ASCII characters only \
+                         (e.g. \"1/2\" for \"\u{00BD}\" and \"*bold*\" for bold letters)",
+                    ),
+            )
+            .arg(
+                Arg::with_name("split-output")
+                    .long("split-output")
+                    .takes_value(true)
+                    .possible_values(&SPLIT_VALUES)
+                    .case_insensitive(true)
+                    .requires("outfile")
+                    .help(
+                        "Write a separate file for each page or top-level heading, named \
+                         from the output file name with the part number as -NNN inserted \
+                         before the extension (e.g. book-001.html) or in place of a \
+                         placeholder of n in braces",
+                    ),
+            )
+            .arg(
+                Arg::with_name("blank-dot-cmds")
+                    .long("blank-dot-cmds")
+                    .help(
+                        "Replace removed dot command lines with blank lines to keep line numbers",
+                    ),
+            )
+            .arg(
+                Arg::with_name("lenient-dot-cmds")
+                    .long("lenient-dot-cmds")
+                    .help("Repair damaged dot commands (e.g. \".H e\") and log each repair"),
+            )
+            .arg(
+                Arg::with_name("mode")
+                    .long("mode")
+                    .takes_value(true)
+                    .possible_values(&MODE_VALUES)
+                    .case_insensitive(true)
+                    .help(
+                        "Fail on any deviation from the WordStar format (e.g. unmatched \
+                         toggles or malformed dot commands) or recover on a best-effort basis \
+                         (default)",
+                    ),
+            )
+            .arg(
+                Arg::with_name("dot-cmd-suffix")
+                    .long("dot-cmd-suffix")
+                    .takes_value(true)
+                    .possible_values(&SUFFIX_VALUES)
+                    .case_insensitive(true)
+                    .help(
+                        "Keep any comment (after \"..\") or other text following a dot command \
+                         as text (default), as a comment in HTML or groff output, or drop it",
+                    ),
+            )
+            .arg(
+                Arg::with_name("header-template")
+                    .long("header-template")
+                    .takes_value(true)
+                    .value_name("TEMPLATE")
+                    .empty_values(true)
+                    .help(
+                        "Render header and footer text with TEMPLATE (e.g. \"## {text}\"), or \
+                         remove it if TEMPLATE is empty, instead of underlining it",
+                    ),
+            )
+            .arg(
+                Arg::with_name("wrapper")
+                    .short("w")
+                    .long("wrapper")
+                    .takes_value(true)
+                    .value_name("CODE=STYLE")
+                    .multiple(true)
+                    .number_of_values(1)
+                    .validator(|s| match get_wrapper(&s) {
+                        Some(_) => Ok(()),
+                        None => Err(format!(
+                            "expected a control code (e.g. ^Q or 0x11) other than a \
+                             standard wrapper, then '=' and a style ({})",
+                            STYLE_VALUES.join(", ")
+                        )),
+                    })
+                    .help("Additional wrapper control code and the style it toggles"),
+            )
+            .arg(
+                Arg::with_name("output-format")
+                    .short("f")
+                    .long("output-format")
+                    .takes_value(true)
+                    .possible_values(&FORMAT_VALUES)
+                    .case_insensitive(true)
+                    .help("Output format (default inferred from output file extension)"),
+            )
+            .arg(
+                Arg::with_name("pitch-class")
+                    .long("pitch-class")
+                    .takes_value(true)
+                    .value_name("CLASS")
+                    .help(
+                        "Mark alternate pitch text in HTML with this CSS class instead of <code>",
+                    ),
+            )
+            .arg(
+                Arg::with_name("standalone")
+                    .long("standalone")
+                    .help("Write HTML output as a complete document rather than a fragment"),
+            )
+            .arg(
+                Arg::with_name("css")
+                    .long("css")
+                    .takes_value(true)
+                    .value_name("PATH")
+                    .help(
+                        "Link complete HTML documents to this style sheet (implies --standalone)",
+                    ),
+            )
+            .arg(Arg::with_name("embed-css").long("embed-css").help(
+                "Embed styles for text attributes (and any --css file) in complete \
+                         HTML documents (implies --standalone)",
+            ))
+            .arg(
+                Arg::with_name("sentences")
+                    .long("sentences")
+                    .help("Reflow each paragraph of text output with one sentence per line"),
+            )
+            .arg(
+                Arg::with_name("toc")
+                    .long("toc")
+                    .help("Start HTML output with a table of contents linked to the headings"),
+            )
+            .arg(Arg::with_name("line-numbers").long("line-numbers").help(
+                "Prefix each text line with its input line number (e.g. '0123| '), or \
+                         give HTML paragraphs and headings a data-lines attribute",
+            ))
+            .arg(
+                Arg::with_name("line-comments")
+                    .long("line-comments")
+                    .help("Precede each HTML paragraph with a comment giving its input line range"),
+            )
+            .subcommand(
+                SubCommand::with_name(CMD_STATS)
+                    .about("Reports word, paragraph and page counts and other statistics only")
+                    .arg(
+                        Arg::with_name("inputs")
+                            .value_name("INPUT")
+                            .multiple(true)
+                            .help("Files to read after any given with --infile ('-' for stdin)"),
+                    ),
+            )
+            .subcommand(
+                SubCommand::with_name(CMD_GENERATE)
+                    .about("Writes a generated WordStar document (e.g. for benchmarks or demos)")
+                    .arg(
+                        Arg::with_name("size")
+                            .long("size")
+                            .takes_value(true)
+                            .value_name("BYTES")
+                            .validator(|s| match get_size(&s) {
+                                Some(_) => Ok(()),
+                                None => Err(format!("'{}' is not a number of bytes", s)),
+                            })
+                            .help("Minimum size of the document, e.g. 50k or 2M (default 1M)"),
+                    )
+                    .arg(
+                        Arg::with_name("density")
+                            .long("density")
+                            .takes_value(true)
+                            .value_name("RATIO")
+                            .validator(|s| match get_density(&s) {
+                                Some(_) => Ok(()),
+                                None => Err(format!("'{}' is not a ratio from 0 to 1", s)),
+                            })
+                            .help("Proportion of formatted words from 0 to 1 (default 0.1)"),
+                    )
+                    .arg(
+                        Arg::with_name("features")
+                            .long("features")
+                            .takes_value(true)
+                            .value_name("LIST")
+                            .validator(|s| s.parse::<Features>().map(|_| ()))
+                            .help(
+                                "Comma-separated features to use (default all): bold, double, \
+                                 underline, italic, strikethrough, superscript, subscript, \
+                                 overline, pitch, hyphens, dot-cmds",
+                            ),
+                    ),
+            )
+            .subcommand(
+                SubCommand::with_name(CMD_VERIFY_CORPUS)
+                    .about(
+                        "Converts each .ws file in a directory and compares the output with \
+                         the .expected file of the same name, failing if any differ",
+                    )
+                    .arg(
+                        Arg::with_name("dir")
+                            .value_name("DIR")
+                            .required(true)
+                            .help("Directory holding the .ws and .expected files"),
+                    ),
+            )
+            .subcommand(
+                SubCommand::with_name(CMD_BATCH)
+                    .about(
+                        "Converts each input to its own output file and ends with a table \
+                         summarising the outcome for every file",
+                    )
+                    .arg(
+                        Arg::with_name("out-dir")
+                            .long("out-dir")
+                            .takes_value(true)
+                            .value_name("DIR")
+                            .help("Write the output files to DIR instead of alongside the inputs"),
+                    )
+                    .arg(
+                        Arg::with_name("report")
+                            .long("report")
+                            .takes_value(true)
+                            .value_name("FILE")
+                            .help("Also write the summary to FILE (as JSON if .json, else CSV)"),
+                    )
+                    .arg(
+                        Arg::with_name("manifest")
+                            .long("manifest")
+                            .takes_value(true)
+                            .value_name("FILE")
+                            .help(
+                                "Write the paths, sizes and SHA-256 checksums of each input and \
+                                 output to FILE (as JSON if .json, else CSV)",
+                            ),
+                    )
+                    .arg(Arg::with_name("force").long("force").help(
+                        "Convert every input, even if its output exists and is newer than it",
+                    ))
+                    .arg(Arg::with_name("list").long("list").help(
+                        "List the output file planned for each input without converting anything",
+                    ))
+                    .arg(
+                        Arg::with_name("timeout-per-file")
+                            .long("timeout-per-file")
+                            .takes_value(true)
+                            .value_name("SECS")
+                            .validator(|s| match get_timeout(&s) {
+                                Some(_) => Ok(()),
+                                None => Err("expected a number of seconds".to_string()),
+                            })
+                            .help("Skip any file that takes longer than SECS to convert"),
+                    )
+                    .arg(
+                        Arg::with_name("max-output-size")
+                            .long("max-output-size")
+                            .takes_value(true)
+                            .value_name("SIZE")
+                            .validator(|s| match get_size(&s) {
+                                Some(_) => Ok(()),
+                                None => {
+                                    Err("expected a size in bytes (e.g. 512k or 10M)".to_string())
+                                }
+                            })
+                            .help("Skip any file whose output would be larger than SIZE"),
+                    )
+                    .arg(
+                        Arg::with_name("log-files").long("log-files").help(
+                            "Write the warnings for each input to a .log file beside its output",
+                        ),
+                    )
+                    .arg(
+                        Arg::with_name("recursive")
+                            .long("recursive")
+                            .short("r")
+                            .help(
+                            "Convert the files in any input directories and their subdirectories \
+                         (mirroring them under --out-dir)",
+                        ),
+                    )
+                    .arg(
+                        Arg::with_name("follow-symlinks")
+                            .long("follow-symlinks")
+                            .requires("recursive")
+                            .help(
+                                "Follow symbolic links when walking directories (else ignore them)",
+                            ),
+                    )
+                    .arg(
+                        Arg::with_name("skip-hidden")
+                            .long("skip-hidden")
+                            .requires("recursive")
+                            .help("Skip files and directories whose names start with '.'"),
+                    )
+                    .arg(
+                        Arg::with_name("exclude-path")
+                            .long("exclude-path")
+                            .takes_value(true)
+                            .multiple(true)
+                            .number_of_values(1)
+                            .value_name("PATTERN")
+                            .help(
+                                "Skip any input whose path or path component matches PATTERN \
+                                 (with * and ? wildcards)",
+                            ),
+                    )
+                    .arg(
+                        Arg::with_name("inputs")
+                            .value_name("INPUT")
+                            .multiple(true)
+                            .help("Files to convert after any given with --infile"),
+                    ),
+            )
+            .subcommand(
+                SubCommand::with_name(CMD_ASCIIFY)
+                    .about(
+                        "Writes the 7-bit ASCII intermediate text only, before any of the \
+                         filters are applied",
+                    )
+                    .arg(
+                        Arg::with_name("inputs")
+                            .value_name("INPUT")
+                            .multiple(true)
+                            .help("Files to read after any given with --infile ('-' for stdin)"),
+                    ),
+            )
+            .subcommand(
+                SubCommand::with_name(CMD_FILTERS)
+                    .about(
+                        "Applies only the filters to input that is already 7-bit ASCII text \
+                         (e.g. from the asciify subcommand)",
+                    )
+                    .arg(
+                        Arg::with_name("inputs")
+                            .value_name("INPUT")
+                            .multiple(true)
+                            .help("Files to read after any given with --infile ('-' for stdin)"),
+                    ),
+            )
+            .get_matches_from_safe(argv)
+            .unwrap_or_else(|e| match e.kind {
+                ErrorKind::HelpDisplayed | ErrorKind::VersionDisplayed => e.exit(),
+                _ => {
+                    eprintln!("{}", e.message);
+                    process::exit(ExitStatus::Fatal.code().into()) // Not "with warnings"
+                }
+            });
 
-        let infile = matches.value_of("infile").unwrap_or_default().to_string();
+        if let Some(textconv) = matches.value_of("textconv") {
+            return Self::textconv(textconv, no_color);
+        }
+
+        let (command, inputs) = match matches.subcommand() {
+            (CMD_STATS, Some(sub_matches)) => (Command::Stats, sub_matches.values_of("inputs")),
+            (CMD_ASCIIFY, Some(sub_matches)) => (Command::Asciify, sub_matches.values_of("inputs")),
+            (CMD_FILTERS, Some(sub_matches)) => (Command::Filters, sub_matches.values_of("inputs")),
+            (CMD_GENERATE, Some(sub_matches)) => {
+                let size = sub_matches.value_of("size").and_then(get_size);
+                let density = sub_matches.value_of("density").and_then(get_density);
+                let features = sub_matches.value_of("features").map(str::parse);
+                let command = Command::Generate {
+                    size: size.unwrap_or(DEFAULT_SIZE),
+                    density: density.unwrap_or(DEFAULT_DENSITY),
+                    features: features.and_then(Result::ok).unwrap_or_else(Features::all),
+                };
+                (command, None)
+            }
+            (CMD_VERIFY_CORPUS, Some(sub_matches)) => {
+                let dir = sub_matches.value_of("dir").unwrap_or_default().to_string();
+                (Command::VerifyCorpus { dir }, None)
+            }
+            (CMD_BATCH, Some(sub_matches)) => {
+                let command = Command::Batch(BatchSettings {
+                    out_dir: sub_matches.value_of("out-dir").map(str::to_string),
+                    report: sub_matches.value_of("report").map(str::to_string),
+                    manifest: sub_matches.value_of("manifest").map(str::to_string),
+                    log_files: sub_matches.is_present("log-files"),
+                    limits: Limits {
+                        timeout: sub_matches
+                            .value_of("timeout-per-file")
+                            .and_then(get_timeout),
+                        max_output: sub_matches
+                            .value_of("max-output-size")
+                            .and_then(get_size)
+                            .map(|size| size as u64),
+                    },
+                    force: sub_matches.is_present("force"),
+                    recursive: sub_matches.is_present("recursive"),
+                    list: sub_matches.is_present("list"),
+                    walk: WalkPolicy {
+                        follow_symlinks: sub_matches.is_present("follow-symlinks"),
+                        skip_hidden: sub_matches.is_present("skip-hidden"),
+                        exclude: sub_matches
+                            .values_of("exclude-path")
+                            .unwrap_or_default()
+                            .map(str::to_string)
+                            .collect(),
+                    },
+                });
+                (command, sub_matches.values_of("inputs"))
+            }
+            _ if matches.is_present("self-test") => {
+                (Command::SelfTest, matches.values_of("inputs"))
+            }
+            _ if matches.is_present("strip") => (Command::Strip, matches.values_of("inputs")),
+            _ => (Command::Convert, matches.values_of("inputs")),
+        };
+        let infiles = matches
+            .values_of("infile")
+            .unwrap_or_default()
+            .chain(inputs.unwrap_or_default())
+            .map(str::to_string)
+            .collect();
         let outfile = matches.value_of("outfile").unwrap_or_default().to_string();
         let log_str = matches.value_of("log-level").unwrap_or_default();
+        let log_format_str = matches.value_of("log-format").unwrap_or_default();
         let exclude_vec: Vec<&str> = matches.values_of("x-names").unwrap_or_default().collect();
+        let format_str = matches.value_of("output-format").unwrap_or_default();
+        let wrapper_vec: Vec<&str> = matches.values_of("wrapper").unwrap_or_default().collect();
+        let overline_str = matches.value_of("overline-style").unwrap_or_default();
+        let underline_str = matches.value_of("underline-style").unwrap_or_default();
+        let fraction_str = matches.value_of("fraction-style").unwrap_or_default();
+        let locale_str = matches.value_of("locale").unwrap_or_default();
+        let fraction_vec: Vec<&str> = matches.values_of("fraction").unwrap_or_default().collect();
+        let filler_str = matches.value_of("filler").unwrap_or_default();
+        let converted_str = matches.value_of("converted").unwrap_or_default();
+        let modified = matches
+            .is_present("reproducible")
+            .then(|| get_source_date(env::var(ENV_SOURCE_DATE).ok().as_deref()));
+        let input_format_str = matches.value_of("input-format").unwrap_or_default();
 
-        let log_level = get_log_level(&log_str);
-        let excludes = get_excludes(&exclude_vec);
+        let log_level = get_log_level(log_str);
+        let options = Options {
+            excludes: get_excludes(&exclude_vec),
+            filler: get_filler(filler_str),
+            converted: get_converted(converted_str),
+            input_format: get_input_format(input_format_str),
+            detect_columns: matches.is_present("detect-columns"),
+            outline: matches.is_present("outline"),
+            ruled_headings: get_ruled_headings(
+                matches.value_of("ruled-headings").unwrap_or_default(),
+            ),
+            blank_dot_cmds: matches.is_present("blank-dot-cmds"),
+            lenient_dot_cmds: matches.is_present("lenient-dot-cmds"),
+            mode: get_mode(matches.value_of("mode").unwrap_or_default()),
+            header_template: matches.value_of("header-template").map(str::to_string),
+            dot_cmd_suffix: get_dot_cmd_suffix(
+                matches.value_of("dot-cmd-suffix").unwrap_or_default(),
+            ),
+            wrappers: wrapper_vec.iter().filter_map(|s| get_wrapper(s)).collect(),
+            overline_style: get_overline_style(overline_str),
+            lenient_overline: matches.is_present("lenient-overline"),
+            max_output_line: matches
+                .value_of("max-output-line")
+                .and_then(|s| s.trim().parse().ok()),
+            long_line_markup: matches.is_present("long-line-markup"),
+            fraction_style: get_fraction_style(fraction_str),
+            locale: get_locale(locale_str),
+            symbols: get_symbols(matches.value_of("symbols").unwrap_or_default()),
+            fractions: fraction_vec
+                .iter()
+                .filter_map(|s| get_fraction(s))
+                .collect(),
+            special_rules: Vec::new(), // Read from any rules file by caller
+            dot_cmd_handlers: Vec::new(), // Read from any handlers file by caller
+            keep_unknown: matches.is_present("no-escape-unknown"),
+            preserve_controls: matches
+                .values_of("preserve-controls")
+                .unwrap_or_default()
+                .filter_map(|code| get_control_code(code.trim()))
+                .collect(),
+            profile: matches.is_present("profile"),
+            quiet: false,
+            compress: matches.is_present("compress"),
+            in_memory: matches.is_present("in-memory"),
+            charset: get_charset(matches.value_of("charset").unwrap_or_default()),
+            split: get_split(matches.value_of("split-output").unwrap_or_default()),
+            unknown_handler: None, // Only available through the library
+            render: RenderOptions {
+                format: match format_str {
+                    "" => get_format_from_path(&outfile),
+                    _ => get_format(format_str),
+                },
+                line_comments: matches.is_present("line-comments"),
+                line_numbers: matches.is_present("line-numbers"),
+                pitch_class: matches.value_of("pitch-class").map(str::to_string),
+                toc: matches.is_present("toc"),
+                sentences: matches.is_present("sentences"),
+                standalone: matches.is_present("standalone")
+                    || matches.is_present("css")
+                    || matches.is_present("embed-css"),
+                css: matches.value_of("css").map(str::to_string),
+                embed_css: matches.is_present("embed-css"),
+                underline_style: get_underline_style(underline_str),
+                page_break: matches
+                    .value_of("page-break")
+                    .map(get_page_break)
+                    .unwrap_or_default(),
+                modified,
+                language_detector: None, // Only available through the library
+                provenance: get_placement(matches.value_of("provenance").unwrap_or_default())
+                    .map(|placement| Provenance::new(placement, arguments, modified)),
+            },
+        };
 
         Self {
-            infile,
+            command,
+            infiles,
             outfile,
             log_level,
-            excludes,
+            log_format: get_log_format(log_format_str),
+            no_color,
+            rules: matches.value_of("rules").map(str::to_string),
+            dot_cmds: matches.value_of("dot-cmds").map(str::to_string),
+            log_file: matches.value_of("log-file").map(str::to_string),
+            textconv: false,
+            options,
         }
     }
 }
 
 // PRIVATE HELPER FUNCTIONS
 
+/// Returns the given list of arguments with the whitespace separated default
+/// options inserted after the program name, so that any later options on the
+/// command line take precedence
+///
+/// # Arguments
+///
+/// * `args` - List of command line arguments (starting with the program name)
+/// * `defaults` - Default options as text slice
+///
+/// # Examples
+/// ```
+/// let argv = with_defaults(vec!["wsconvert".into(), "-f".into()], "-l info");
+/// assert_eq!(argv.len(), 4);
+/// ```
+fn with_defaults<I>(args: I, defaults: &str) -> Vec<OsString>
+where
+    I: IntoIterator<Item = OsString>,
+{
+    let mut args = args.into_iter();
+    let mut argv: Vec<OsString> = args.next().into_iter().collect();
+    argv.extend(defaults.split_whitespace().map(OsString::from));
+    argv.extend(args);
+    argv
+}
+
 /// Returns `log::LevelFilter` enum value corresponding to input text slice
 /// or default of `log::LevelFilter::Error` if text slice is empty or not
 /// recognised
@@ -128,6 +1211,25 @@ fn get_log_level(log_str: &str) -> log::LevelFilter {
     }
 }
 
+/// Returns `LogFormat` enum value corresponding to input text slice or
+/// default of `LogFormat::Text` if text slice is empty or not recognised
+///
+/// # Arguments
+///
+/// * `log_format_str` - Desired log format as text slice
+///
+/// # Examples
+/// ```
+/// assert_eq!(get_log_format("JSON"), LogFormat::Json);
+/// ```
+fn get_log_format(log_format_str: &str) -> LogFormat {
+    match log_format_str.to_lowercase().as_str() {
+        LOG_FORMAT_TEXT => LogFormat::Text,
+        LOG_FORMAT_JSON => LogFormat::Json,
+        _ => LogFormat::Text, // Default setting
+    }
+}
+
 /// Returns `Excludes` struct corresponding to one or more exclusions
 /// specified in command line, or default of no exclusions (no flags
 /// set) if none are specified
@@ -138,43 +1240,1127 @@ fn get_log_level(log_str: &str) -> log::LevelFilter {
 ///
 /// # Examples
 /// ```
-/// assert_eq!(get_excludes(&vec!("specials")), Excludes::SPECIALS);
+/// assert_eq!(get_excludes(&["specials"]), Excludes::SPECIALS);
 /// ```
 fn get_excludes(exclude_strs: &[&str]) -> Excludes {
-    let mut excludes = Excludes::NONE;
+    let mut excludes = Excludes::none();
     for exclude_str in exclude_strs {
-        match exclude_str.to_lowercase().as_str() {
-            EXCLUDE_DOT_CMDS => excludes.insert(Excludes::DOT_CMDS),
-            EXCLUDE_RE_ALIGN => excludes.insert(Excludes::RE_ALIGN),
-            EXCLUDE_SPECIALS => excludes.insert(Excludes::SPECIALS),
-            EXCLUDE_OVERLINE => excludes.insert(Excludes::OVERLINE),
-            EXCLUDE_WRAPPERS => excludes.insert(Excludes::WRAPPERS),
-            EXCLUDE_CONTROLS => excludes.insert(Excludes::CONTROLS),
-            _ => {}
-        }
+        excludes.insert(exclude_str.parse().unwrap_or_default());
     }
     excludes
 }
 
-// Unit tests
-
-#[cfg(test)]
-mod tests {
-    use super::*;
+/// Returns `Filler` enum value corresponding to input text slice or default
+/// of `Filler::Keep` if text slice is empty or not recognised
+///
+/// # Arguments
+///
+/// * `filler_str` - Desired treatment of filler bytes as text slice
+///
+/// # Examples
+/// ```
+/// assert_eq!(get_filler("Strip"), Filler::Strip);
+/// ```
+fn get_filler(filler_str: &str) -> Filler {
+    match filler_str.to_lowercase().as_str() {
+        FILLER_KEEP => Filler::Keep,
+        FILLER_STRIP => Filler::Strip,
+        FILLER_FLAG => Filler::Flag,
+        _ => Filler::Keep, // Default setting
+    }
+}
 
-    #[test]
-    fn test_get_log_level() {
-        assert_eq!(get_log_level("info"), log::LevelFilter::Info);
-        assert_eq!(get_log_level("INFO"), log::LevelFilter::Info);
+/// Returns `Converted` enum value corresponding to input text slice or
+/// default of `Converted::Refuse` if text slice is empty or not recognised
+///
+/// # Arguments
+///
+/// * `converted_str` - Desired treatment of converted input as text slice
+///
+/// # Examples
+/// ```
+/// assert_eq!(get_converted("Pass"), Converted::Pass);
+/// ```
+fn get_converted(converted_str: &str) -> Converted {
+    match converted_str.to_lowercase().as_str() {
+        CONVERTED_REFUSE => Converted::Refuse,
+        CONVERTED_PASS => Converted::Pass,
+        CONVERTED_CONVERT => Converted::Convert,
+        _ => Converted::Refuse, // Default setting
     }
+}
 
-    #[test]
-    fn test_get_excludes() {
-        assert_eq!(get_excludes(&vec!("specials")), Excludes::SPECIALS);
-        assert_eq!(
-            get_excludes(&vec!("OverLINE", "WRAPPERS")),
-            Excludes::OVERLINE | Excludes::WRAPPERS
+/// Returns `InputFormat` enum value corresponding to input text slice or
+/// default of `InputFormat::WordStar` if text slice is empty or not recognised
+///
+/// # Arguments
+///
+/// * `input_format_str` - Desired input format as text slice
+///
+/// # Examples
+/// ```
+/// assert_eq!(get_input_format("NewWord"), InputFormat::NewWord);
+/// ```
+fn get_input_format(input_format_str: &str) -> InputFormat {
+    match input_format_str.to_lowercase().as_str() {
+        INPUT_FORMAT_WORDSTAR => InputFormat::WordStar,
+        INPUT_FORMAT_NEWWORD => InputFormat::NewWord,
+        _ => InputFormat::WordStar, // Default setting
+    }
+}
+
+/// Returns `Format` enum value corresponding to input text slice or default
+/// of `Format::Text` if text slice is empty or not recognised
+///
+/// # Arguments
+///
+/// * `format_str` - Desired output format as text slice
+///
+/// # Examples
+/// ```
+/// assert_eq!(get_format("HTML"), Format::Html);
+/// ```
+fn get_format(format_str: &str) -> Format {
+    match format_str.to_lowercase().as_str() {
+        FORMAT_TEXT => Format::Text,
+        FORMAT_HTML => Format::Html,
+        FORMAT_EPUB => Format::Epub,
+        FORMAT_DOCX => Format::Docx,
+        FORMAT_ODT => Format::Odt,
+        FORMAT_GROFF => Format::Groff,
+        FORMAT_PRINT => Format::Print,
+        FORMAT_JSON => Format::Json,
+        FORMAT_PANDOC => Format::Pandoc,
+        _ => Format::Text, // Default setting
+    }
+}
+
+/// Returns `OverlineStyle` enum value corresponding to input text slice or
+/// default of `OverlineStyle::Combining` if text slice is empty or not recognised
+///
+/// # Arguments
+///
+/// * `overline_str` - Desired overline style as text slice
+///
+/// # Examples
+/// ```
+/// assert_eq!(get_overline_style("Slash"), OverlineStyle::Slash);
+/// ```
+fn get_overline_style(overline_str: &str) -> OverlineStyle {
+    match overline_str.to_lowercase().as_str() {
+        OVERLINE_COMBINING => OverlineStyle::Combining,
+        OVERLINE_SLASH => OverlineStyle::Slash,
+        OVERLINE_TILDE => OverlineStyle::Tilde,
+        OVERLINE_STAR => OverlineStyle::Star,
+        _ => OverlineStyle::Combining, // Default setting
+    }
+}
+
+/// Returns `UnderlineStyle` enum value corresponding to input text slice or
+/// default of `UnderlineStyle::Combining` if text slice is empty or not recognised
+///
+/// # Arguments
+///
+/// * `underline_str` - Desired underline style as text slice
+///
+/// # Examples
+/// ```
+/// assert_eq!(get_underline_style("U-Tag"), UnderlineStyle::UTag);
+/// ```
+fn get_underline_style(underline_str: &str) -> UnderlineStyle {
+    match underline_str.to_lowercase().as_str() {
+        UNDERLINE_COMBINING => UnderlineStyle::Combining,
+        UNDERLINE_U_TAG => UnderlineStyle::UTag,
+        UNDERLINE_INS_TAG => UnderlineStyle::InsTag,
+        UNDERLINE_DOUBLE_UNDERSCORE => UnderlineStyle::DoubleUnderscore,
+        UNDERLINE_NONE => UnderlineStyle::None,
+        _ => UnderlineStyle::Combining, // Default setting
+    }
+}
+
+/// Returns `SuffixMode` enum value corresponding to input text slice or
+/// default of `SuffixMode::Text` if text slice is empty or not recognised
+///
+/// # Arguments
+///
+/// * `suffix_str` - Desired handling of dot command suffixes as text slice
+///
+/// # Examples
+/// ```
+/// assert_eq!(get_dot_cmd_suffix("Comment"), SuffixMode::Comment);
+/// ```
+fn get_dot_cmd_suffix(suffix_str: &str) -> SuffixMode {
+    match suffix_str.to_lowercase().as_str() {
+        SUFFIX_DROP => SuffixMode::Drop,
+        SUFFIX_TEXT => SuffixMode::Text,
+        SUFFIX_COMMENT => SuffixMode::Comment,
+        _ => SuffixMode::Text, // Default setting
+    }
+}
+
+/// Returns `Charset` enum value corresponding to input text slice or
+/// default of `Charset::Unicode` if text slice is empty or not recognised
+///
+/// # Arguments
+///
+/// * `charset_str` - Desired character set for the output as text slice
+///
+/// # Examples
+/// ```
+/// assert_eq!(get_charset("ASCII"), Charset::Ascii);
+/// ```
+fn get_charset(charset_str: &str) -> Charset {
+    match charset_str.to_lowercase().as_str() {
+        CHARSET_UNICODE => Charset::Unicode,
+        CHARSET_LATIN1 => Charset::Latin1,
+        CHARSET_ASCII => Charset::Ascii,
+        _ => Charset::Unicode, // Default setting
+    }
+}
+
+/// Returns `Split` enum value corresponding to input text slice or default
+/// of `Split::None` if text slice is empty or not recognised
+///
+/// # Arguments
+///
+/// * `split_str` - Desired points at which to split the output as text slice
+///
+/// # Examples
+/// ```
+/// assert_eq!(get_split("h1"), Split::H1);
+/// ```
+fn get_split(split_str: &str) -> Split {
+    match split_str.to_lowercase().as_str() {
+        SPLIT_PAGE => Split::Page,
+        SPLIT_H1 => Split::H1,
+        _ => Split::None, // Default setting
+    }
+}
+
+/// Returns `Some(Placement)` enum value corresponding to input text slice or
+/// `None` (for no provenance) if text slice is empty or not recognised
+///
+/// # Arguments
+///
+/// * `placement_str` - Desired place to record the provenance as text slice
+///
+/// # Examples
+/// ```
+/// assert_eq!(get_placement("sidecar"), Some(Placement::Sidecar));
+/// ```
+fn get_placement(placement_str: &str) -> Option<Placement> {
+    match placement_str.to_lowercase().as_str() {
+        PROVENANCE_EMBED => Some(Placement::Embed),
+        PROVENANCE_SIDECAR => Some(Placement::Sidecar),
+        _ => None,
+    }
+}
+
+/// Returns the fixed time (in seconds since the Unix epoch) to be recorded
+/// in reproducible output, given the value of the `SOURCE_DATE_EPOCH`
+/// environment variable (if set), or 0 if it is not set or not valid
+///
+/// # Arguments
+///
+/// * `source_date` - Value of `SOURCE_DATE_EPOCH` (if set)
+///
+/// # Examples
+/// ```
+/// assert_eq!(get_source_date(Some("1700000000")), 1_700_000_000);
+/// ```
+fn get_source_date(source_date: Option<&str>) -> u64 {
+    source_date
+        .and_then(|value| value.trim().parse().ok())
+        .unwrap_or(0)
+}
+
+/// Returns `PageBreakStyle` enum value corresponding to input text slice,
+/// which is used as custom text if it is not the name of a style
+///
+/// # Arguments
+///
+/// * `page_break_str` - Desired page break style (or text) as text slice
+///
+/// # Examples
+/// ```
+/// assert_eq!(get_page_break("Markdown"), PageBreakStyle::Markdown);
+/// ```
+fn get_page_break(page_break_str: &str) -> PageBreakStyle {
+    match page_break_str.to_lowercase().as_str() {
+        PAGE_BREAK_BARS => PageBreakStyle::Bars,
+        PAGE_BREAK_MARKDOWN => PageBreakStyle::Markdown,
+        PAGE_BREAK_HTML => PageBreakStyle::Html,
+        PAGE_BREAK_FORM_FEED => PageBreakStyle::FormFeed,
+        _ => PageBreakStyle::Custom(page_break_str.to_string()),
+    }
+}
+
+/// Returns `FractionStyle` enum value corresponding to input text slice or
+/// default of `FractionStyle::Unicode` if text slice is empty or not recognised
+///
+/// # Arguments
+///
+/// * `fraction_str` - Desired fraction style as text slice
+///
+/// # Examples
+/// ```
+/// assert_eq!(get_fraction_style("slash"), FractionStyle::Slash);
+/// ```
+fn get_fraction_style(fraction_str: &str) -> FractionStyle {
+    match fraction_str.to_lowercase().as_str() {
+        FRACTION_UNICODE => FractionStyle::Unicode,
+        FRACTION_SLASH => FractionStyle::Slash,
+        _ => FractionStyle::Unicode, // Default setting
+    }
+}
+
+/// Returns `Locale` enum value corresponding to input text slice or default
+/// of `Locale::None` if text slice is empty or not recognised
+///
+/// # Arguments
+///
+/// * `locale_str` - Desired locale as text slice
+///
+/// # Examples
+/// ```
+/// assert_eq!(get_locale("english"), Locale::English);
+/// ```
+fn get_locale(locale_str: &str) -> Locale {
+    match locale_str.to_lowercase().as_str() {
+        LOCALE_ENGLISH => Locale::English,
+        LOCALE_CONTINENTAL => Locale::Continental,
+        LOCALE_FRENCH => Locale::French,
+        LOCALE_GERMAN => Locale::German,
+        LOCALE_SPANISH => Locale::Spanish,
+        _ => Locale::None, // Default setting
+    }
+}
+
+/// Returns `RuledHeadings` enum value corresponding to input text slice or
+/// default of `RuledHeadings::None` if text slice is empty or not recognised
+///
+/// # Arguments
+///
+/// * `ruled_str` - Desired conversion of ruled headings as text slice
+///
+/// # Examples
+/// ```
+/// assert_eq!(get_ruled_headings("setext"), RuledHeadings::Setext);
+/// ```
+fn get_ruled_headings(ruled_str: &str) -> RuledHeadings {
+    match ruled_str.to_lowercase().as_str() {
+        RULED_SETEXT => RuledHeadings::Setext,
+        RULED_UNDERLINE => RuledHeadings::Underline,
+        _ => RuledHeadings::None, // Default setting
+    }
+}
+
+/// Returns `Mode` enum value corresponding to input text slice or default of
+/// `Mode::Permissive` if text slice is empty or not recognised
+///
+/// # Arguments
+///
+/// * `mode_str` - Desired handling of deviations from the format as text slice
+///
+/// # Examples
+/// ```
+/// assert_eq!(get_mode("strict"), Mode::Strict);
+/// ```
+fn get_mode(mode_str: &str) -> Mode {
+    match mode_str.to_lowercase().as_str() {
+        MODE_STRICT => Mode::Strict,
+        _ => Mode::Permissive, // Default setting
+    }
+}
+
+/// Returns `Symbols` enum value corresponding to input text slice or default
+/// of `Symbols::None` if text slice is empty or not recognised
+///
+/// # Arguments
+///
+/// * `symbols_str` - Desired conversion of signs and ordinals as text slice
+///
+/// # Examples
+/// ```
+/// assert_eq!(get_symbols("plain"), Symbols::Plain);
+/// ```
+fn get_symbols(symbols_str: &str) -> Symbols {
+    match symbols_str.to_lowercase().as_str() {
+        SYMBOLS_SUPERSCRIPT => Symbols::Superscript,
+        SYMBOLS_PLAIN => Symbols::Plain,
+        _ => Symbols::None, // Default setting
+    }
+}
+
+/// Returns `Some(size)` holding the number of bytes given by the input text
+/// slice (as a number with an optional suffix of "k" for KiB or "M" for
+/// MiB, in any case), or `None` if it is not a valid size
+///
+/// # Arguments
+///
+/// * `size_str` - Desired size of generated document as text slice
+///
+/// # Examples
+/// ```
+/// assert_eq!(get_size("50k"), Some(51200));
+/// ```
+fn get_size(size_str: &str) -> Option<usize> {
+    let size_str = size_str.trim().to_lowercase();
+    let (number, unit) = match size_str.strip_suffix('k') {
+        Some(number) => (number, 1024),
+        None => match size_str.strip_suffix('m') {
+            Some(number) => (number, 1024 * 1024),
+            None => (&size_str[..], 1),
+        },
+    };
+    number.parse::<usize>().ok()?.checked_mul(unit)
+}
+
+/// Returns `Some(timeout)` holding the length of time given by the input
+/// text slice (as a number of seconds, which may have a fractional part), or
+/// `None` if it is not a valid length of time
+///
+/// # Arguments
+///
+/// * `timeout_str` - Time allowed for each file as text slice
+///
+/// # Examples
+/// ```
+/// assert_eq!(get_timeout("1.5"), Some(Duration::from_millis(1500)));
+/// ```
+fn get_timeout(timeout_str: &str) -> Option<Duration> {
+    let secs = timeout_str.trim().parse::<f64>().ok()?;
+    Duration::try_from_secs_f64(secs).ok()
+}
+
+/// Returns `Some(density)` holding the proportion (from 0 to 1) given by the
+/// input text slice, or `None` if it is not a valid proportion
+///
+/// # Arguments
+///
+/// * `density_str` - Desired density of formatting as text slice
+///
+/// # Examples
+/// ```
+/// assert_eq!(get_density("0.25"), Some(0.25));
+/// ```
+fn get_density(density_str: &str) -> Option<f64> {
+    density_str
+        .trim()
+        .parse()
+        .ok()
+        .filter(|density| (0.0..=1.0).contains(density))
+}
+
+/// Returns `Some(style)` holding the set of text attributes corresponding to
+/// the input text slice, or `None` if it is not recognised
+///
+/// # Arguments
+///
+/// * `style_str` - Desired style as text slice
+///
+/// # Examples
+/// ```
+/// assert_eq!(get_style("Bold"), Some(Style::BOLD));
+/// ```
+fn get_style(style_str: &str) -> Option<Style> {
+    match style_str.to_lowercase().as_str() {
+        STYLE_NONE => Some(Style::empty()),
+        STYLE_BOLD => Some(Style::BOLD),
+        STYLE_ITALIC => Some(Style::ITALIC),
+        STYLE_UNDERLINE => Some(Style::UNDERLINE),
+        STYLE_STRIKETHROUGH => Some(Style::STRIKETHROUGH),
+        STYLE_OVERLINE => Some(Style::OVERLINE),
+        STYLE_SUPERSCRIPT => Some(Style::SUPERSCRIPT),
+        STYLE_SUBSCRIPT => Some(Style::SUBSCRIPT),
+        STYLE_ALTERNATE => Some(Style::ALTERNATE),
+        _ => None,
+    }
+}
+
+/// Returns `Some(code)` holding the ASCII control character given by the input
+/// text slice in either caret notation (e.g. "^Q") or hexadecimal (e.g. "0x11"
+/// or "11"), or `None` if it is not recognised
+///
+/// # Arguments
+///
+/// * `code_str` - Control character as text slice
+///
+/// # Examples
+/// ```
+/// assert_eq!(get_control_code("^q"), Some('\x11'));
+/// ```
+fn get_control_code(code_str: &str) -> Option<char> {
+    let code = if let Some(caret) = code_str.strip_prefix('^') {
+        let mut chars = caret.chars();
+        let c = chars.next()?.to_ascii_uppercase();
+        if chars.next().is_some() || !('@'..='_').contains(&c) {
+            return None;
+        }
+        (c as u8 - b'@') as char
+    } else {
+        let hex = code_str
+            .strip_prefix("0x")
+            .or_else(|| code_str.strip_prefix("0X"))
+            .unwrap_or(code_str);
+        u8::from_str_radix(hex, 16).ok()? as char
+    };
+    code.is_ascii_control().then_some(code)
+}
+
+/// Returns `Some(wrapper)` holding the additional wrapper given by the input
+/// text slice as a control code and a style separated by '=', or `None` if it
+/// is not recognised or is a standard WordStar wrapper character
+///
+/// # Arguments
+///
+/// * `wrapper_str` - Additional wrapper as text slice
+///
+/// # Examples
+/// ```
+/// assert!(get_wrapper("^Q=bold").is_some());
+/// ```
+fn get_wrapper(wrapper_str: &str) -> Option<CustomWrapper> {
+    let (code_str, style_str) = wrapper_str.split_once('=')?;
+    let code = get_control_code(code_str.trim())?;
+    let style = get_style(style_str.trim())?;
+    (!Wrappers::is_standard(code)).then_some(CustomWrapper { code, style })
+}
+
+/// Returns `Some(fraction)` holding the additional fraction given by the
+/// input text slice as a numerator and denominator separated by '/', then
+/// '=' and the replacement text, or `None` if it is not recognised
+///
+/// # Arguments
+///
+/// * `fraction_str` - Additional fraction as text slice
+///
+/// # Examples
+/// ```
+/// assert!(get_fraction("5/6=\u{215A}").is_some());
+/// ```
+fn get_fraction(fraction_str: &str) -> Option<CustomFraction> {
+    let (fraction, symbol) = fraction_str.split_once('=')?;
+    let (numerator, denominator) = fraction.split_once('/')?;
+    Some(CustomFraction {
+        numerator: numerator.trim().parse().ok()?,
+        denominator: denominator.trim().parse().ok()?,
+        symbol: symbol.to_string(),
+    })
+    .filter(|fraction| !fraction.symbol.is_empty())
+}
+
+/// Returns `Format` enum value corresponding to the extension of the given
+/// output file path (before any ".gz" extension for compressed output) or
+/// default of `Format::Text` if there is no extension or it is not recognised
+///
+/// A Markdown file (".md") is given `Format::Text`, as the text renderer
+/// writes Markdown.
+///
+/// # Arguments
+///
+/// * `path` - Output file path as text slice
+///
+/// # Examples
+/// ```
+/// assert_eq!(get_format_from_path("report.html"), Format::Html);
+/// assert_eq!(get_format_from_path("report.html.gz"), Format::Html);
+/// ```
+fn get_format_from_path(path: &str) -> Format {
+    let path = Path::new(path);
+    let path = match path.extension() {
+        Some(ext) if ext.eq_ignore_ascii_case(GZIP_EXTENSION) => path.with_extension(""),
+        _ => path.to_path_buf(),
+    };
+    let ext = path
+        .extension()
+        .and_then(|ext| ext.to_str())
+        .unwrap_or_default();
+    match ext.to_lowercase().as_str() {
+        "htm" | "html" | "xhtml" => Format::Html,
+        "epub" => Format::Epub,
+        "docx" => Format::Docx,
+        "odt" => Format::Odt,
+        "groff" | "man" | "roff" => Format::Groff,
+        "prn" => Format::Print,
+        "json" => Format::Json,
+        "md" | "markdown" | "txt" => Format::Text,
+        _ => Format::Text, // Default setting
+    }
+}
+
+// Unit tests
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(defaults: &str, args: &[&str]) -> Args {
+        let args = ["wsconvert"].iter().chain(args).map(OsString::from);
+        Args::parse_from(with_defaults(args, defaults), false)
+    }
+
+    #[test]
+    fn test_with_defaults() {
+        let args = vec![OsString::from("wsconvert"), OsString::from("a.ws")];
+        assert_eq!(
+            with_defaults(args.clone(), " -f  html "),
+            vec!["wsconvert", "-f", "html", "a.ws"]
         );
-        assert_eq!(get_excludes(&vec!("")), Excludes::NONE);
+        assert_eq!(with_defaults(args.clone(), ""), args);
+    }
+
+    #[test]
+    fn test_parse_defaults() {
+        let args = parse(
+            "-f html --detect-columns --outline -x specials",
+            &["-f", "odt", "a.ws"],
+        );
+        assert_eq!(args.options.render.format, Format::Odt);
+        assert!(args.options.detect_columns);
+        assert!(args.options.outline);
+        assert_eq!(args.options.excludes, Excludes::SPECIALS);
+        assert_eq!(args.infiles, vec!["a.ws"]);
+        let args = parse("-l debug -x specials", &["-x", "pitch"]);
+        assert_eq!(args.log_level, log::LevelFilter::Debug);
+        assert_eq!(args.options.excludes, Excludes::SPECIALS | Excludes::PITCH);
+        let args = parse("", &["-x", "pitch", "a.ws"]);
+        assert_eq!(args.options.excludes, Excludes::PITCH);
+        assert_eq!(args.infiles, vec!["a.ws"]);
+        let args = parse("", &["-x", "pitch,specials", "a.ws", "b.ws"]);
+        assert_eq!(args.options.excludes, Excludes::SPECIALS | Excludes::PITCH);
+        assert_eq!(args.infiles, vec!["a.ws", "b.ws"]);
+        assert!(!args.options.lenient_overline);
+        let args = parse("--lenient-overline", &["--lenient-dot-cmds", "a.ws"]);
+        assert!(args.options.lenient_overline);
+        assert!(args.options.lenient_dot_cmds);
+        assert_eq!(args.options.header_template, None);
+        let args = parse("", &["--header-template", "## {text}", "a.ws"]);
+        assert_eq!(args.options.header_template.as_deref(), Some("## {text}"));
+        let args = parse("", &["--header-template", "", "a.ws"]);
+        assert_eq!(args.options.header_template.as_deref(), Some(""));
+        assert_eq!(args.options.max_output_line, None);
+        let args = parse("--max-output-line 80", &["--long-line-markup", "a.ws"]);
+        assert_eq!(args.options.max_output_line, Some(80));
+        assert!(args.options.long_line_markup);
+        assert!(!args.options.render.toc);
+        let args = parse("", &["-f", "html", "--toc", "a.ws"]);
+        assert!(args.options.render.toc);
+        assert!(!args.options.render.sentences);
+        let args = parse("--sentences", &["a.ws"]);
+        assert!(args.options.render.sentences);
+        assert!(!args.options.render.line_numbers);
+        let args = parse("--line-numbers", &["a.ws"]);
+        assert!(args.options.render.line_numbers);
+        assert!(!args.options.render.standalone);
+        let args = parse("", &["--css", "s.css", "a.ws"]);
+        assert!(args.options.render.standalone);
+        assert_eq!(args.options.render.css.as_deref(), Some("s.css"));
+        assert!(!args.options.render.embed_css);
+        let args = parse("--embed-css", &["a.ws"]);
+        assert!(args.options.render.standalone);
+        assert!(args.options.render.embed_css);
+        assert_eq!(args.rules, None);
+        let args = parse("--rules site.rules", &["a.ws"]);
+        assert_eq!(args.rules.as_deref(), Some("site.rules"));
+        assert_eq!(args.dot_cmds, None);
+        let args = parse("--dot-cmds site.dotcmds", &["a.ws"]);
+        assert_eq!(args.dot_cmds.as_deref(), Some("site.dotcmds"));
+        assert_eq!(args.log_file, None);
+        let args = parse("--log-file out.log", &["a.ws"]);
+        assert_eq!(args.log_file.as_deref(), Some("out.log"));
+        assert!(!args.options.compress);
+        let args = parse("--compress -o out.txt", &["a.ws.gz"]);
+        assert!(args.options.compress);
+        assert!(!args.options.in_memory);
+        let args = parse("--in-memory", &["a.ws.gz"]);
+        assert!(args.options.in_memory);
+        assert_eq!(args.options.input_format, InputFormat::WordStar);
+        let args = parse("--input-format newword", &["a.ws"]);
+        assert_eq!(args.options.input_format, InputFormat::NewWord);
+    }
+
+    #[test]
+    fn test_parse_command() {
+        let args = parse("", &["a.ws"]);
+        assert_eq!(args.command, Command::Convert);
+        assert_eq!(args.infiles, vec!["a.ws"]);
+        let args = parse("--detect-columns", &["-i", "a.ws", "stats", "b.ws"]);
+        assert_eq!(args.command, Command::Stats);
+        assert_eq!(args.infiles, vec!["a.ws", "b.ws"]);
+        assert!(args.options.detect_columns);
+        let args = parse("", &["--self-test", "a.ws"]);
+        assert_eq!(args.command, Command::SelfTest);
+        assert_eq!(args.infiles, vec!["a.ws"]);
+        let args = parse("--compress", &["--strip", "-o", "out.txt", "a.ws", "b.ws"]);
+        assert_eq!(args.command, Command::Strip);
+        assert_eq!(args.infiles, vec!["a.ws", "b.ws"]);
+        assert!(args.options.compress);
+    }
+
+    #[test]
+    fn test_parse_inputs_named_like_commands() {
+        let args = parse("", &["stats.ws"]);
+        assert_eq!(args.command, Command::Convert);
+        assert_eq!(args.infiles, vec!["stats.ws"]);
+        let args = parse("-f html", &["batch.ws", "fil.ws"]);
+        assert_eq!(args.command, Command::Convert);
+        assert_eq!(args.infiles, vec!["batch.ws", "fil.ws"]);
+        let args = parse("", &["stats", "stats.ws"]);
+        assert_eq!(args.command, Command::Stats);
+        assert_eq!(args.infiles, vec!["stats.ws"]);
+    }
+
+    #[test]
+    fn test_parse_generate() {
+        let args = parse("", &["-o", "demo.ws", "generate"]);
+        assert_eq!(
+            args.command,
+            Command::Generate {
+                size: DEFAULT_SIZE,
+                density: DEFAULT_DENSITY,
+                features: Features::all()
+            }
+        );
+        assert_eq!(args.outfile, "demo.ws");
+        let args = parse(
+            "",
+            &[
+                "generate",
+                "--size",
+                "50k",
+                "--density",
+                "0.5",
+                "--features",
+                "bold,dotcmds",
+            ],
+        );
+        assert_eq!(
+            args.command,
+            Command::Generate {
+                size: 51200,
+                density: 0.5,
+                features: Features::BOLD | Features::DOT_CMDS
+            }
+        );
+        assert!(args.infiles.is_empty());
+    }
+
+    #[test]
+    fn test_parse_verify_corpus() {
+        let args = parse("-f html", &["verify-corpus", "corpus"]);
+        assert_eq!(
+            args.command,
+            Command::VerifyCorpus {
+                dir: "corpus".to_string()
+            }
+        );
+        assert_eq!(args.options.render.format, Format::Html);
+        assert!(args.infiles.is_empty());
+    }
+
+    #[test]
+    fn test_parse_batch() {
+        let args = parse(
+            "",
+            &["-i", "a.ws", "batch", "--out-dir", "out", "b.ws", "c.ws"],
+        );
+        assert_eq!(
+            args.command,
+            Command::Batch(BatchSettings {
+                out_dir: Some("out".to_string()),
+                ..Default::default()
+            })
+        );
+        assert_eq!(args.infiles, ["a.ws", "b.ws", "c.ws"]);
+        let args = parse(
+            "",
+            &[
+                "batch",
+                "--report",
+                "summary.json",
+                "--manifest",
+                "m.csv",
+                "--log-files",
+                "a.ws",
+            ],
+        );
+        assert_eq!(
+            args.command,
+            Command::Batch(BatchSettings {
+                report: Some("summary.json".to_string()),
+                manifest: Some("m.csv".to_string()),
+                log_files: true,
+                ..Default::default()
+            })
+        );
+        let args = parse(
+            "",
+            &[
+                "batch",
+                "--timeout-per-file",
+                "30",
+                "--max-output-size",
+                "10M",
+                "--force",
+                "a.ws",
+            ],
+        );
+        assert_eq!(
+            args.command,
+            Command::Batch(BatchSettings {
+                limits: Limits {
+                    timeout: Some(Duration::from_secs(30)),
+                    max_output: Some(10 * 1024 * 1024),
+                },
+                force: true,
+                ..Default::default()
+            })
+        );
+        let args = parse(
+            "",
+            &[
+                "batch",
+                "-r",
+                "--list",
+                "--follow-symlinks",
+                "--skip-hidden",
+                "--exclude-path",
+                "*.bak",
+                "--exclude-path",
+                "__MACOSX",
+                "archive",
+            ],
+        );
+        assert_eq!(
+            args.command,
+            Command::Batch(BatchSettings {
+                recursive: true,
+                walk: WalkPolicy {
+                    follow_symlinks: true,
+                    skip_hidden: true,
+                    exclude: vec!["*.bak".to_string(), "__MACOSX".to_string()],
+                },
+                list: true,
+                ..Default::default()
+            })
+        );
+        assert_eq!(args.infiles, ["archive"]);
+    }
+
+    #[test]
+    fn test_get_timeout() {
+        assert_eq!(get_timeout(" 30 "), Some(Duration::from_secs(30)));
+        assert_eq!(get_timeout("0.25"), Some(Duration::from_millis(250)));
+        assert_eq!(get_timeout("-1"), None);
+        assert_eq!(get_timeout("soon"), None);
+    }
+
+    #[test]
+    fn test_parse_asciify() {
+        let args = parse("", &["-o", "out.txt", "asciify", "a.ws", "-"]);
+        assert_eq!(args.command, Command::Asciify);
+        assert_eq!(args.infiles, ["a.ws", "-"]);
+        assert_eq!(args.outfile, "out.txt");
+    }
+
+    #[test]
+    fn test_parse_filters() {
+        let args = parse("", &["-o", "out.html", "filters", "a.txt"]);
+        assert_eq!(args.command, Command::Filters);
+        assert_eq!(args.infiles, ["a.txt"]);
+        assert_eq!(args.options.render.format, Format::Html);
+    }
+
+    #[test]
+    fn test_parse_preserve_controls() {
+        let args = parse("", &["a.ws"]);
+        assert!(args.options.preserve_controls.is_empty());
+        let args = parse("", &["--preserve-controls", "09,0C", "a.ws"]);
+        assert_eq!(args.options.preserve_controls, ['\t', '\x0C']);
+        assert_eq!(args.infiles, ["a.ws"]);
+        let args = parse("--preserve-controls ^L", &["--strip", "a.ws"]);
+        assert_eq!(args.options.preserve_controls, ['\x0C']);
+    }
+
+    #[test]
+    fn test_get_size_and_density() {
+        assert_eq!(get_size(" 100 "), Some(100));
+        assert_eq!(get_size("-1"), None);
+        assert_eq!(get_size("1K"), Some(1024));
+        assert_eq!(get_size("2m"), Some(2 * 1024 * 1024));
+        assert_eq!(get_size("k"), None);
+        assert_eq!(get_size("1.5k"), None);
+        assert_eq!(get_density("1"), Some(1.0));
+        assert_eq!(get_density("0"), Some(0.0));
+        assert_eq!(get_density("1.5"), None);
+        assert_eq!(get_density("-0.1"), None);
+        assert_eq!(get_density("dense"), None);
+    }
+
+    #[test]
+    fn test_parse_textconv() {
+        let args = parse("-f html -x specials --profile", &["--textconv", "a.ws"]);
+        assert_eq!(args.command, Command::Convert);
+        assert_eq!(args.infiles, vec!["a.ws"]);
+        assert_eq!(args.outfile, "");
+        assert_eq!(args.log_level, log::LevelFilter::Off);
+        assert_eq!(args.options.render.format, Format::Text);
+        assert_eq!(args.options.excludes, Excludes::none());
+        assert!(args.options.quiet);
+        assert!(!args.options.profile);
+        assert!(args.textconv);
+        assert!(!parse("", &["a.ws"]).textconv);
+    }
+
+    #[test]
+    fn test_get_log_level() {
+        assert_eq!(get_log_level("info"), log::LevelFilter::Info);
+        assert_eq!(get_log_level("INFO"), log::LevelFilter::Info);
+    }
+
+    #[test]
+    fn test_get_log_format() {
+        assert_eq!(get_log_format("json"), LogFormat::Json);
+        assert_eq!(get_log_format("JSON"), LogFormat::Json);
+        assert_eq!(get_log_format(""), LogFormat::Text);
+    }
+
+    #[test]
+    fn test_get_excludes() {
+        assert_eq!(get_excludes(&["specials"]), Excludes::SPECIALS);
+        assert_eq!(
+            get_excludes(&["OverLINE", "WRAPPERS"]),
+            Excludes::OVERLINE | Excludes::WRAPPERS
+        );
+        assert_eq!(get_excludes(&["pitch"]), Excludes::PITCH);
+        assert_eq!(get_excludes(&[""]), Excludes::none());
+    }
+
+    #[test]
+    fn test_get_filler() {
+        assert_eq!(get_filler("strip"), Filler::Strip);
+        assert_eq!(get_filler("FLAG"), Filler::Flag);
+        assert_eq!(get_filler(""), Filler::Keep);
+    }
+
+    #[test]
+    fn test_get_converted() {
+        assert_eq!(get_converted("pass"), Converted::Pass);
+        assert_eq!(get_converted("CONVERT"), Converted::Convert);
+        assert_eq!(get_converted(""), Converted::Refuse);
+    }
+
+    #[test]
+    fn test_get_input_format() {
+        assert_eq!(get_input_format("newword"), InputFormat::NewWord);
+        assert_eq!(get_input_format("WordStar"), InputFormat::WordStar);
+        assert_eq!(get_input_format(""), InputFormat::WordStar);
+    }
+
+    #[test]
+    fn test_get_format_from_path() {
+        assert_eq!(get_format_from_path("report.html"), Format::Html);
+        assert_eq!(get_format_from_path("dir.d/BOOK.EPUB"), Format::Epub);
+        assert_eq!(get_format_from_path("a.docx"), Format::Docx);
+        assert_eq!(get_format_from_path("a.odt"), Format::Odt);
+        assert_eq!(get_format_from_path("wsconvert.man"), Format::Groff);
+        assert_eq!(get_format_from_path("out.prn"), Format::Print);
+        assert_eq!(get_format_from_path("doc.JSON"), Format::Json);
+        assert_eq!(get_format_from_path("notes.md"), Format::Text);
+        assert_eq!(get_format_from_path("notes.MARKDOWN.gz"), Format::Text);
+        assert_eq!(get_format_from_path("dir.d/out"), Format::Text);
+        assert_eq!(get_format_from_path(""), Format::Text);
+        assert_eq!(get_format_from_path("x.html.gz"), Format::Html);
+        assert_eq!(get_format_from_path("x.JSON.GZ"), Format::Json);
+        assert_eq!(get_format_from_path("x.gz"), Format::Text);
+    }
+
+    #[test]
+    fn test_get_styles() {
+        assert_eq!(get_overline_style("tilde"), OverlineStyle::Tilde);
+        assert_eq!(get_overline_style("STAR"), OverlineStyle::Star);
+        assert_eq!(get_overline_style(""), OverlineStyle::Combining);
+        assert_eq!(get_underline_style("ins-tag"), UnderlineStyle::InsTag);
+        assert_eq!(
+            get_underline_style("Double-Underscore"),
+            UnderlineStyle::DoubleUnderscore
+        );
+        assert_eq!(get_underline_style("none"), UnderlineStyle::None);
+        assert_eq!(get_underline_style(""), UnderlineStyle::Combining);
+        assert_eq!(get_fraction_style("Slash"), FractionStyle::Slash);
+        assert_eq!(get_fraction_style(""), FractionStyle::Unicode);
+    }
+
+    #[test]
+    fn test_get_locale() {
+        assert_eq!(get_locale("English"), Locale::English);
+        assert_eq!(get_locale("continental"), Locale::Continental);
+        assert_eq!(get_locale("none"), Locale::None);
+        assert_eq!(get_locale("French"), Locale::French);
+        assert_eq!(get_locale("spanish"), Locale::Spanish);
+        assert_eq!(get_locale(""), Locale::None);
+    }
+
+    #[test]
+    fn test_get_ruled_headings() {
+        assert_eq!(get_ruled_headings("Setext"), RuledHeadings::Setext);
+        assert_eq!(get_ruled_headings("underline"), RuledHeadings::Underline);
+        assert_eq!(get_ruled_headings(""), RuledHeadings::None);
+        let args = parse("", &["--ruled-headings", "underline", "a.ws"]);
+        assert_eq!(args.options.ruled_headings, RuledHeadings::Underline);
+    }
+
+    #[test]
+    fn test_get_mode() {
+        assert_eq!(get_mode("Strict"), Mode::Strict);
+        assert_eq!(get_mode("permissive"), Mode::Permissive);
+        assert_eq!(get_mode(""), Mode::Permissive);
+        let args = parse("", &["--mode", "strict", "a.ws"]);
+        assert_eq!(args.options.mode, Mode::Strict);
+    }
+
+    #[test]
+    fn test_get_symbols() {
+        assert_eq!(get_symbols("Superscript"), Symbols::Superscript);
+        assert_eq!(get_symbols("plain"), Symbols::Plain);
+        assert_eq!(get_symbols(""), Symbols::None);
+        let args = parse("", &["--symbols", "plain", "a.ws"]);
+        assert_eq!(args.options.symbols, Symbols::Plain);
+    }
+
+    #[test]
+    fn test_get_wrapper() {
+        assert_eq!(
+            get_wrapper("^Q=bold"),
+            Some(CustomWrapper {
+                code: '\x11',
+                style: Style::BOLD
+            })
+        );
+        assert_eq!(
+            get_wrapper("0x12 = None"),
+            Some(CustomWrapper {
+                code: '\x12',
+                style: Style::empty()
+            })
+        );
+        assert_eq!(get_control_code("^["), Some('\x1B'));
+        assert_eq!(get_control_code("^a1"), None);
+        assert_eq!(get_control_code("0x41"), None);
+        assert_eq!(get_control_code("0c"), Some('\x0C'));
+        assert_eq!(get_control_code("7F"), Some('\x7F'));
+        assert_eq!(get_control_code("x9"), None);
+        assert_eq!(get_wrapper("^B=italic"), None); // Standard wrapper
+        assert_eq!(get_wrapper("^Q=blink"), None);
+        assert_eq!(get_wrapper("^Q"), None);
+    }
+
+    #[test]
+    fn test_get_dot_cmd_suffix() {
+        assert_eq!(get_dot_cmd_suffix("drop"), SuffixMode::Drop);
+        assert_eq!(get_dot_cmd_suffix("COMMENT"), SuffixMode::Comment);
+        assert_eq!(get_dot_cmd_suffix(""), SuffixMode::Text);
+        let args = parse("", &["--dot-cmd-suffix", "comment", "a.ws"]);
+        assert_eq!(args.options.dot_cmd_suffix, SuffixMode::Comment);
+        let args = parse("", &["a.ws"]);
+        assert_eq!(args.options.dot_cmd_suffix, SuffixMode::Text);
+    }
+
+    #[test]
+    fn test_get_charset() {
+        assert_eq!(get_charset("latin1"), Charset::Latin1);
+        assert_eq!(get_charset("ASCII"), Charset::Ascii);
+        assert_eq!(get_charset(""), Charset::Unicode);
+        let args = parse("", &["--charset", "ascii", "a.ws"]);
+        assert_eq!(args.options.charset, Charset::Ascii);
+        let args = parse("", &["a.ws"]);
+        assert_eq!(args.options.charset, Charset::Unicode);
+    }
+
+    #[test]
+    fn test_get_placement() {
+        assert_eq!(get_placement("Embed"), Some(Placement::Embed));
+        assert_eq!(get_placement("sidecar"), Some(Placement::Sidecar));
+        assert_eq!(get_placement(""), None);
+        let args = parse("", &["--provenance", "embed", "--reproducible", "a.ws"]);
+        let provenance = args.options.render.provenance.unwrap();
+        assert_eq!(provenance.placement, Placement::Embed);
+        assert_eq!(
+            provenance.arguments,
+            ["--provenance", "embed", "--reproducible", "a.ws"]
+        );
+        assert!(parse("", &["a.ws"]).options.render.provenance.is_none());
+    }
+
+    #[test]
+    fn test_get_split() {
+        assert_eq!(get_split("Page"), Split::Page);
+        assert_eq!(get_split("h1"), Split::H1);
+        assert_eq!(get_split(""), Split::None);
+        let args = parse("", &["--split-output", "h1", "-o", "book.html", "a.ws"]);
+        assert_eq!(args.options.split, Split::H1);
+        assert_eq!(args.outfile, "book.html");
+    }
+
+    #[test]
+    fn test_get_source_date() {
+        assert_eq!(get_source_date(Some("1700000000")), 1_700_000_000);
+        assert_eq!(get_source_date(Some("soon")), 0);
+        assert_eq!(get_source_date(None), 0);
+        let args = parse("", &["a.ws"]);
+        assert_eq!(args.options.render.modified, None);
+        let args = parse("--reproducible", &["a.ws"]);
+        assert!(args.options.render.modified.is_some());
+    }
+
+    #[test]
+    fn test_get_page_break() {
+        assert_eq!(get_page_break("markdown"), PageBreakStyle::Markdown);
+        assert_eq!(get_page_break("Form-Feed"), PageBreakStyle::FormFeed);
+        assert_eq!(
+            get_page_break("* * *"),
+            PageBreakStyle::Custom("* * *".to_string())
+        );
+        let args = parse("", &["--page-break", "html", "a.ws"]);
+        assert_eq!(args.options.render.page_break, PageBreakStyle::Html);
+    }
+
+    #[test]
+    fn test_get_fraction() {
+        assert_eq!(
+            get_fraction("5/6=\u{215A}"),
+            Some(CustomFraction {
+                numerator: 5,
+                denominator: 6,
+                symbol: "\u{215A}".to_string()
+            })
+        );
+        assert_eq!(
+            get_fraction(" 1 / 16 =1/16"),
+            Some(CustomFraction {
+                numerator: 1,
+                denominator: 16,
+                symbol: "1/16".to_string()
+            })
+        );
+        assert_eq!(get_fraction("5/6="), None);
+        assert_eq!(get_fraction("5-6=x"), None);
+        assert_eq!(get_fraction("a/6=x"), None);
+        assert_eq!(get_fraction("5/6"), None);
+    }
+
+    #[test]
+    fn test_get_format() {
+        assert_eq!(get_format("html"), Format::Html);
+        assert_eq!(get_format("Text"), Format::Text);
+        assert_eq!(get_format("EPUB"), Format::Epub);
+        assert_eq!(get_format("docx"), Format::Docx);
+        assert_eq!(get_format("Odt"), Format::Odt);
+        assert_eq!(get_format("groff"), Format::Groff);
+        assert_eq!(get_format("PRINT"), Format::Print);
+        assert_eq!(get_format("json"), Format::Json);
+        assert_eq!(get_format("Pandoc"), Format::Pandoc);
+        assert_eq!(get_format(""), Format::Text);
     }
 }
@@ -1,15 +1,246 @@
 //! Module to convert 8-bit input data into 7-bit ASCII characters
 
+use crate::uni_chars;
 use std::io::{self, Read, Write};
 
 const EOF_BYTE: u8 = 0x1A; // End of File (EOF) marker
 const ASCII_MASK: u8 = 0x7F; // Bit mask for 7-bit ASCII
 
+const NUL_BYTE: u8 = 0x00; // Filler found in unused parts of damaged files
+const ERASED_BYTE: u8 = 0xE5; // Filler left in erased CP/M disk sectors
+const MIN_ERASED_RUN: usize = 4; // Shorter runs may be 'e' ending words
+
 const CHUNK_SIZE: usize = 16 * 1024; // Moderate sized buffer
 
+// Signatures at the start of files in other formats that may be given as
+// input by mistake, with the names of the formats
+const FOREIGN_SIGNATURES: [(&[u8], &str); 10] = [
+    (b"%PDF-", "PDF"),
+    (b"PK\x03\x04", "zip archive (e.g. DOCX or ODT)"),
+    (b"\x1F\x8B", "gzip"),
+    (
+        b"\xD0\xCF\x11\xE0\xA1\xB1\x1A\xE1",
+        "Microsoft Office (e.g. Word .doc)",
+    ),
+    (b"{\\rtf", "RTF"),
+    (b"\x89PNG", "PNG image"),
+    (b"\xFF\xD8\xFF", "JPEG image"),
+    (b"GIF8", "GIF image"),
+    (b"\x7FELF", "ELF executable"),
+    (b"7z\xBC\xAF\x27\x1C", "7-Zip archive"),
+];
+
+/// Ways of dealing with runs of filler bytes (NUL or 0xE5) in files
+/// recovered from damaged disks
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub enum Filler {
+    #[default]
+    Keep, // Converted in the same way as any other bytes
+    Strip, // Removed from the output
+    Flag,  // Each run replaced by a Unicode replacement character
+}
+
+/// Ways of dealing with input that appears to be output from a previous
+/// conversion (see `is_converted()`), which would otherwise be garbled by
+/// being converted again
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub enum Converted {
+    #[default]
+    Refuse, // Conversion abandoned with an error
+    Pass,    // Copied through without conversion to 7-bit ASCII
+    Convert, // Converted in the same way as any other input
+}
+
+// "FILLER SCANNER" OBJECT
+
+/// Holds the run of possible filler bytes currently being scanned, together
+/// with the offset of the next byte from the start of the input
+#[derive(Debug)]
+struct FillerScanner {
+    filler: Filler,
+    offset: usize,
+    run_byte: u8,
+    run_len: usize,
+    runs: usize,
+}
+
+impl FillerScanner {
+    /// Returns a new `FillerScanner` object for the start of an input
+    fn new(filler: Filler) -> Self {
+        FillerScanner {
+            filler,
+            offset: 0,
+            run_byte: NUL_BYTE,
+            run_len: 0,
+            runs: 0,
+        }
+    }
+
+    /// Appends the converted bytes of the given chunk of 8-bit input to the
+    /// output, holding back any run of possible filler bytes at its end
+    /// until the next chunk (or `finish()`) shows where the run ends
+    ///
+    /// # Arguments
+    ///
+    /// * `chunk` - Byte (u8) slice of 8-bit input, up to any EOF marker
+    /// * `output` - Vector to which the converted bytes are appended
+    ///
+    fn scan(&mut self, chunk: &[u8], output: &mut Vec<u8>) {
+        for &byte in chunk {
+            if self.run_len > 0 && byte == self.run_byte {
+                self.run_len += 1;
+            } else {
+                self.finish(output);
+                if byte == NUL_BYTE || byte == ERASED_BYTE {
+                    self.run_byte = byte;
+                    self.run_len = 1;
+                } else {
+                    output.push(byte & ASCII_MASK);
+                }
+            }
+            self.offset += 1;
+        }
+    }
+
+    /// Appends the run of possible filler bytes being scanned (if any) to the
+    /// output, stripping or flagging it (and logging its offset) if it is
+    /// taken to be filler
+    ///
+    /// # Arguments
+    ///
+    /// * `output` - Vector to which the converted bytes are appended
+    ///
+    fn finish(&mut self, output: &mut Vec<u8>) {
+        if self.run_len == 0 {
+            return;
+        }
+        let start = self.offset - self.run_len;
+        if self.run_byte == NUL_BYTE || self.run_len >= MIN_ERASED_RUN {
+            log::warn!(
+                code = "filler-run";
+                "Filler of {} bytes of 0x{:02X} at offset {}",
+                self.run_len,
+                self.run_byte,
+                start
+            );
+            self.runs += 1;
+            if self.filler == Filler::Flag {
+                output.extend_from_slice(uni_chars::REPLACEMENT.as_bytes());
+            }
+        } else {
+            output.resize(output.len() + self.run_len, self.run_byte & ASCII_MASK);
+        }
+        self.run_len = 0;
+    }
+}
+
+// EXTERNAL PUBLIC FUNCTIONS
+
+/// Returns `true` if the given byte slice is already 7-bit ASCII without an
+/// End of File marker (or, unless runs of filler bytes are kept, any NUL
+/// filler bytes), so that `convert_file()` would leave it unchanged,
+/// otherwise `false`
+///
+/// # Arguments
+///
+/// * `buf` - Byte (u8) slice of 8-bit input characters
+/// * `filler` - Treatment of runs of filler bytes
+///
+/// # Examples
+/// ```
+/// assert!(asciify::is_clean(b"Plain text\n", Filler::Keep));
+/// assert!(!asciify::is_clean(b"Bold\xC5", Filler::Keep));
+/// ```
+pub fn is_clean(buf: &[u8], filler: Filler) -> bool {
+    buf.iter().all(|&byte| {
+        byte <= ASCII_MASK && byte != EOF_BYTE && (filler == Filler::Keep || byte != NUL_BYTE)
+    })
+}
+
+/// Returns `true` if the given start of an input looks like output from a
+/// previous conversion, i.e. it is valid UTF-8 (apart from a character cut
+/// off at the end), contains combining underline or overline marks and has
+/// no control characters other than tabs and line endings, otherwise `false`
+///
+/// # Arguments
+///
+/// * `buf` - Byte (u8) slice from the start of an input
+///
+/// # Examples
+/// ```
+/// assert!(asciify::is_converted("Bo\u{0332}ld\n".as_bytes()));
+/// assert!(!asciify::is_converted(b"Bo\x13ld\x13\n"));
+/// ```
+pub fn is_converted(buf: &[u8]) -> bool {
+    let text = match std::str::from_utf8(buf) {
+        Ok(text) => text,
+        Err(e) if e.error_len().is_none() => std::str::from_utf8(&buf[..e.valid_up_to()]).unwrap(),
+        Err(_) => return false,
+    };
+    text.contains([uni_chars::COMB_UNDERLINE, uni_chars::COMB_OVERLINE])
+        && !text
+            .chars()
+            .any(|c| c.is_ascii_control() && !matches!(c, '\t' | '\n' | '\r'))
+}
+
+/// Returns `Some(name)` holding the name of the format of the given start of
+/// an input if it has the signature of a known format that is not WordStar
+/// text (e.g. PDF or a zip archive), otherwise `None`
+///
+/// # Arguments
+///
+/// * `buf` - Byte (u8) slice from the start of an input
+///
+/// # Examples
+/// ```
+/// assert_eq!(asciify::detect_foreign(b"%PDF-1.4\n"), Some("PDF"));
+/// assert_eq!(asciify::detect_foreign(b"Bo\x13ld\x13\n"), None);
+/// ```
+pub fn detect_foreign(buf: &[u8]) -> Option<&'static str> {
+    FOREIGN_SIGNATURES
+        .iter()
+        .find(|(signature, _)| buf.starts_with(signature))
+        .map(|(_, name)| *name)
+}
+
+/// Returns `Ok(true)` if all of the bytes from the given input are already
+/// clean (as for `is_clean()`), `Ok(false)` if not, or a `std::io::Error`
+/// type on failure
+///
+/// The input is read only as far as the first byte that is not clean.
+///
+/// # Arguments
+///
+/// * `input` - Source of bytes that implements `Read` trait
+/// * `filler` - Treatment of runs of filler bytes
+///
+/// # Examples
+/// ```
+/// assert!(asciify::is_clean_file(&mut &b"Plain text\n"[..], Filler::Keep).unwrap());
+/// ```
+pub fn is_clean_file(input: &mut dyn Read, filler: Filler) -> io::Result<bool> {
+    let mut buffer = [0; CHUNK_SIZE];
+    loop {
+        let num_read = input.read(&mut buffer)?;
+        if num_read == 0 {
+            return Ok(true);
+        }
+        if !is_clean(&buffer[..num_read], filler) {
+            return Ok(false);
+        }
+    }
+}
+
 /// Converts a stream of 8-bit input bytes into a stream of output bytes in
 /// 7-bit ASCII format, using `convert_chunk()` to transform data in chunks
-/// and detect an End of File marker
+/// and detect an End of File marker, with runs of filler bytes dealt with
+/// as given by `filler`
+///
+/// A run of filler is one or more NUL bytes, or four or more 0xE5 bytes
+/// (as a single 0xE5 byte is an 'e' with the top bit set to mark the end
+/// of a word).  The length and offset of each run are logged as warnings.
+/// If runs are flagged then the output is no longer 7-bit ASCII, as each
+/// run is replaced by a Unicode replacement character in UTF-8 form.
 ///
 /// Returns `()` on success or a `std::io::Error` type on failure
 ///
@@ -17,18 +248,25 @@ const CHUNK_SIZE: usize = 16 * 1024; // Moderate sized buffer
 ///
 /// * `input` - Source of bytes that implements `Read` trait
 /// * `output` - Destination for bytes that implements `Write` trait
+/// * `filler` - Treatment of runs of filler bytes
 ///
 /// # Examples
 /// ```
 /// use std::io;
-/// use asciify::convert_file;
+/// use asciify::{convert_file, Filler};
 ///
 /// let mut input = io::stdin();
 /// let mut output = io::stdout();
-/// convert_file(&mut input, &mut output).unwrap();
+/// convert_file(&mut input, &mut output, Filler::Keep).unwrap();
 /// ```
-pub fn convert_file(input: &mut dyn Read, output: &mut dyn Write) -> io::Result<()> {
+pub fn convert_file(
+    input: &mut dyn Read,
+    output: &mut dyn Write,
+    filler: Filler,
+) -> io::Result<()> {
     let mut buffer = [0; CHUNK_SIZE];
+    let mut scanner = FillerScanner::new(filler);
+    let mut converted = Vec::new();
     let mut total_input = 0;
     let mut total_output = 0;
 
@@ -40,19 +278,34 @@ pub fn convert_file(input: &mut dyn Read, output: &mut dyn Write) -> io::Result<
         total_input += num_read;
         log::info!("Read {} bytes", num_read);
 
-        let conv = convert_chunk(&mut buffer[..num_read]);
-        let num_conv = conv.len();
-        total_output += num_conv;
+        let num_conv = if filler == Filler::Keep {
+            let conv = convert_chunk(&mut buffer[..num_read]);
+            output.write_all(conv)?;
+            total_output += conv.len();
+            conv.len()
+        } else {
+            let chunk = &buffer[..num_read];
+            let end = chunk.iter().position(|&b| b == EOF_BYTE);
+            scanner.scan(&chunk[..end.unwrap_or(num_read)], &mut converted);
+            output.write_all(&converted)?;
+            total_output += converted.len();
+            converted.clear();
+            end.unwrap_or(num_read)
+        };
         log::info!("Converted {} bytes", num_conv);
 
-        output.write_all(&buffer[..num_conv])?;
-
         if num_conv < num_read {
             break; // EOF encountered
         }
     }
+    scanner.finish(&mut converted);
+    output.write_all(&converted)?;
+    total_output += converted.len();
     log::info!("Total input bytes: {}", total_input);
     log::info!("Total output bytes: {}", total_output);
+    if scanner.runs > 0 {
+        log::warn!(code = "filler-runs"; "Total runs of filler: {}", scanner.runs);
+    }
     Ok(())
 }
 
@@ -67,6 +320,9 @@ pub fn convert_file(input: &mut dyn Read, output: &mut dyn Write) -> io::Result<
 /// means that an End of File (EOF) marker was encountered, causing the
 /// EOF and subsequent characters to be excluded from the return slice
 ///
+/// A chunk that is already clean (i.e. has no bytes with the top bit set
+/// and no EOF marker) is returned unchanged without being modified.
+///
 /// # Arguments
 ///
 /// * `buf` - Mutable byte (u8) slice of 8-bit input characters
@@ -79,6 +335,9 @@ pub fn convert_file(input: &mut dyn Read, output: &mut dyn Write) -> io::Result<
 /// assert_eq!(convert_chunk(&mut buf[..5]), [ 0x41, 0x42, 0x43 ]);
 /// ```
 pub fn convert_chunk(buf: &mut [u8]) -> &[u8] {
+    if is_clean(buf, Filler::Keep) {
+        return buf; // Fast path for clean chunk
+    }
     let mut count = 0;
     for byte in &mut buf[..] {
         if *byte == EOF_BYTE {
@@ -102,6 +361,51 @@ mod tests {
         assert_eq!(convert_chunk(&mut buf), [0x7E, 0x7F, 0x00, 0x01, 0x02]);
     }
 
+    #[test]
+    fn test_convert_chunk_clean() {
+        let mut buf = *b"Clean\tASCII\r\n\x7F";
+        assert_eq!(convert_chunk(&mut buf), b"Clean\tASCII\r\n\x7F");
+        let mut buf = *b"Clean\x1A";
+        assert_eq!(convert_chunk(&mut buf), b"Clean");
+    }
+
+    #[test]
+    fn test_is_converted() {
+        assert!(is_converted("T\u{0332}i\u{0305}\r\n".as_bytes()));
+        assert!(is_converted(&"U\u{0332}\u{0332}".as_bytes()[..4])); // Cut off
+        assert!(!is_converted(b"Plain text\n"));
+        assert!(!is_converted("U\u{0332}\x13".as_bytes()));
+        assert!(!is_converted(b"U\xCC\xB2\xE5 "));
+    }
+
+    #[test]
+    fn test_detect_foreign() {
+        assert_eq!(detect_foreign(b"%PDF-1.7\r\n"), Some("PDF"));
+        assert_eq!(
+            detect_foreign(b"PK\x03\x04\x14\x00"),
+            Some("zip archive (e.g. DOCX or ODT)")
+        );
+        assert_eq!(detect_foreign(b"{\\rtf1\\ansi"), Some("RTF"));
+        assert_eq!(detect_foreign(b"\x1F\x8B\x08\x00"), Some("gzip"));
+        assert_eq!(detect_foreign(b"\xFF\xD8\xFF\xE0"), Some("JPEG image"));
+        assert_eq!(detect_foreign(b""), None);
+        assert_eq!(detect_foreign(b"%PD"), None);
+        assert_eq!(detect_foreign(b".pa\r\nHe\xEClo\r\n"), None);
+    }
+
+    #[test]
+    fn test_is_clean() {
+        assert!(is_clean(b"", Filler::Strip));
+        assert!(is_clean(b"a\x00b\x7F", Filler::Keep));
+        assert!(!is_clean(b"a\x00b", Filler::Flag));
+        assert!(!is_clean(b"a\x1Ab", Filler::Keep));
+        assert!(!is_clean(b"a\xE5", Filler::Keep));
+        let mut clean = vec![b'a'; CHUNK_SIZE + 1];
+        assert!(is_clean_file(&mut &clean[..], Filler::Keep).unwrap());
+        clean.push(0x80);
+        assert!(!is_clean_file(&mut &clean[..], Filler::Keep).unwrap());
+    }
+
     #[test]
     fn test_convert_chunk_slice() {
         let mut buf = [0x41, 0xC2, 0x43, 0xC4, 0x45];
@@ -111,7 +415,7 @@ mod tests {
     #[test]
     fn test_convert_chunk_eof_first() {
         let mut buf = [EOF_BYTE, 0xC2, 0x43, 0xC4, 0x45];
-        assert_eq!(convert_chunk(&mut buf), []);
+        assert_eq!(convert_chunk(&mut buf), b"");
     }
 
     #[test]
@@ -126,10 +430,41 @@ mod tests {
         assert_eq!(convert_chunk(&mut buf), [0x41, 0x42, 0x43, 0x44]);
     }
 
+    fn convert_with(input: &[u8], filler: Filler) -> Vec<u8> {
+        let mut output = Vec::new();
+        convert_file(&mut &input[..], &mut output, filler).unwrap();
+        output
+    }
+
+    #[test]
+    fn test_convert_file() {
+        let input = b"ab\0\0c\xE5\xE5\xE5\xE5\xE5d\xE5 \0";
+        assert_eq!(convert_with(input, Filler::Keep), b"ab\0\0ceeeeede \0");
+        assert_eq!(convert_with(input, Filler::Strip), b"abcde ");
+        assert_eq!(
+            convert_with(input, Filler::Flag),
+            "ab\u{FFFD}c\u{FFFD}de \u{FFFD}".as_bytes()
+        );
+        assert_eq!(convert_with(b"\xE5\xE5\xE5\x1A\0", Filler::Strip), b"eee");
+    }
+
+    #[test]
+    fn test_filler_scanner_chunks() {
+        let mut scanner = FillerScanner::new(Filler::Strip);
+        let mut output = Vec::new();
+        scanner.scan(b"a\xE5\xE5", &mut output);
+        assert_eq!(output, b"a");
+        scanner.scan(b"\xE5\xE5b", &mut output);
+        scanner.finish(&mut output);
+        assert_eq!(output, b"ab");
+        assert_eq!(scanner.runs, 1);
+        assert_eq!(scanner.offset, 6);
+    }
+
     #[test]
     fn test_convert_chunk_empty() {
         let mut buf = [];
         println!("{:?}", buf);
-        assert_eq!(convert_chunk(&mut buf), []);
+        assert_eq!(convert_chunk(&mut buf), b"");
     }
 }
@@ -0,0 +1,556 @@
+//! Module to collect the outcome of converting each file in a batch and
+//! report them together as a table, CSV or JSON, along with a manifest of
+//! the files converted (with their sizes and SHA-256 checksums)
+
+use crate::walk::WalkPolicy;
+use crate::ws_events::Summary;
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use std::error::Error;
+use std::fmt;
+use std::fs::File;
+use std::io::{self, Read, Write};
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+const CSV_HEADER: &str = "file,output,lines,replacements,warnings,controls,score,status";
+const MANIFEST_HEADER: &str = "input,output,input_size,output_size,input_sha256,output_sha256";
+
+/// Holds the outcome of converting a single file in a batch
+#[derive(Debug, Serialize)]
+pub struct FileReport {
+    pub input: String,
+    pub output: String,
+    #[serde(flatten)]
+    pub summary: Summary,
+    pub error: Option<String>, // Reason for failure, if the conversion failed
+    pub skipped: bool,         // Whether the conversion was stopped by a limit
+    pub up_to_date: bool,      // Whether the output was newer than the input
+}
+
+impl FileReport {
+    /// Returns the status of the conversion as text ("ok" or the reason for
+    /// failure)
+    fn status(&self) -> String {
+        match &self.error {
+            Some(error) if self.skipped => format!("skipped: {}", error),
+            Some(error) => format!("error: {}", error),
+            None if self.up_to_date => "up to date".to_string(),
+            None => "ok".to_string(),
+        }
+    }
+}
+
+/// Holds the limits placed on the conversion of each file in a batch, so
+/// that a single pathological file cannot hang the batch or fill the disk
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct Limits {
+    pub timeout: Option<Duration>, // Longest time allowed for each file
+    pub max_output: Option<u64>,   // Largest output allowed for each file (in bytes)
+}
+
+/// Holds the settings for a batch conversion, apart from the `Options` for
+/// converting each file
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct BatchSettings {
+    pub out_dir: Option<String>, // Directory for output files (else beside inputs)
+    pub report: Option<String>,  // Path to report file
+    pub manifest: Option<String>, // Path to manifest file
+    pub log_files: bool,         // Whether to write a log file beside each output
+    pub limits: Limits,          // Limits on the conversion of each file
+    pub force: bool,             // Whether to convert files with up to date outputs
+    pub recursive: bool,         // Whether to convert the files in any input directories
+    pub walk: WalkPolicy,        // Policy for walking input directories
+    pub list: bool,              // Whether to list the planned outputs instead of converting
+}
+
+/// Error given when the conversion of a file is stopped by one of its
+/// `Limits`
+#[derive(Debug)]
+struct LimitExceeded(String);
+
+/// Display trait implementation for LimitExceeded, giving the limit that
+/// was exceeded
+impl fmt::Display for LimitExceeded {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl Error for LimitExceeded {}
+
+/// Source of bytes that fails with a `LimitExceeded` error once the time
+/// allowed by the `Limits` for converting a file has passed
+pub struct TimedReader<R: Read> {
+    inner: R,
+    start: Instant,
+    timeout: Option<Duration>,
+}
+
+/// Destination for bytes that fails with a `LimitExceeded` error once the
+/// output allowed by the `Limits` for a file has been written
+pub struct LimitedWriter<W: Write> {
+    inner: W,
+    written: u64,
+    max_output: Option<u64>,
+}
+
+/// Holds the outcomes of converting each file in a batch, in the order in
+/// which they were converted
+#[derive(Debug, Default, Serialize)]
+pub struct BatchReport {
+    pub files: Vec<FileReport>,
+}
+
+/// Display trait implementation for BatchReport, giving a table with a row
+/// for each file followed by a row of totals
+impl fmt::Display for BatchReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let width = self
+            .files
+            .iter()
+            .map(|file| file.input.chars().count())
+            .fold(4, usize::max);
+        writeln!(
+            f,
+            "{:<w$}  {:>8}  {:>8}  {:>8}  {:>8}  {:>5}  Status",
+            "File",
+            "Lines",
+            "Replaced",
+            "Warnings",
+            "Controls",
+            "Score",
+            w = width
+        )?;
+        for file in &self.files {
+            let s = &file.summary;
+            writeln!(
+                f,
+                "{:<w$}  {:>8}  {:>8}  {:>8}  {:>8}  {:>4}%  {}",
+                file.input,
+                s.lines,
+                s.replacements,
+                s.warnings,
+                s.controls,
+                s.score,
+                file.status(),
+                w = width
+            )?;
+        }
+        write!(f, "Batch: {} converted", self.files.len() - self.failed())?;
+        if self.up_to_date() > 0 {
+            write!(f, " ({} up to date)", self.up_to_date())?;
+        }
+        write!(f, ", {} failed", self.failed())?;
+        if self.skipped() > 0 {
+            write!(f, " ({} skipped)", self.skipped())?;
+        }
+        write!(f, " of {} file(s)", self.files.len())
+    }
+}
+
+/// Holds the sizes and SHA-256 checksums of an input file and the output
+/// file converted from it
+#[derive(Debug, Serialize)]
+pub struct ManifestEntry {
+    pub input: String,
+    pub output: String,
+    pub input_size: u64,
+    pub output_size: u64,
+    pub input_sha256: String,
+    pub output_sha256: String,
+}
+
+/// Holds a manifest entry for each file converted in a batch, in the order
+/// in which they were converted
+#[derive(Debug, Default, Serialize)]
+pub struct Manifest {
+    pub files: Vec<ManifestEntry>,
+}
+
+// PRIVATE HELPER FUNCTIONS
+
+/// Returns the size of the given file and its SHA-256 checksum (as lower
+/// case hex), or a `std::io::Error` type if it cannot be read
+///
+/// # Arguments
+///
+/// * `path` - Path to file
+///
+pub(crate) fn checksum(path: &Path) -> io::Result<(u64, String)> {
+    let mut hasher = Sha256::new();
+    let size = io::copy(&mut File::open(path)?, &mut hasher)?;
+    Ok((size, format!("{:x}", hasher.finalize())))
+}
+
+/// Returns the given text as a CSV field, quoted if it contains a comma,
+/// double quote or line break
+///
+/// # Arguments
+///
+/// * `text` - Text of field
+///
+/// # Examples
+/// ```
+/// assert_eq!(csv_field("a,b"), "\"a,b\"");
+/// ```
+fn csv_field(text: &str) -> String {
+    if text.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", text.replace('"', "\"\""))
+    } else {
+        text.to_string()
+    }
+}
+
+// "LIMITS" OBJECT
+
+impl Limits {
+    /// Returns `true` if the given error was caused by exceeding one of the
+    /// limits (i.e. the file was skipped), otherwise `false`
+    ///
+    /// # Arguments
+    ///
+    /// * `e` - Error from the conversion of a file
+    ///
+    pub fn is_exceeded(e: &io::Error) -> bool {
+        e.get_ref().is_some_and(|inner| inner.is::<LimitExceeded>())
+    }
+
+    /// Returns the given source of bytes wrapped to fail once the time
+    /// allowed for converting a file (from the given start time) has passed
+    pub fn timed<R: Read>(&self, inner: R, start: Instant) -> TimedReader<R> {
+        TimedReader {
+            inner,
+            start,
+            timeout: self.timeout,
+        }
+    }
+
+    /// Returns the given destination for bytes wrapped to fail once the
+    /// output allowed for a file has been written
+    pub fn limited<W: Write>(&self, inner: W) -> LimitedWriter<W> {
+        LimitedWriter {
+            inner,
+            written: 0,
+            max_output: self.max_output,
+        }
+    }
+}
+
+impl<R: Read> Read for TimedReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if let Some(timeout) = self.timeout {
+            if self.start.elapsed() > timeout {
+                let message = format!("timed out after {} s", timeout.as_secs_f64());
+                return Err(io::Error::other(LimitExceeded(message)));
+            }
+        }
+        self.inner.read(buf)
+    }
+}
+
+impl<W: Write> LimitedWriter<W> {
+    /// Returns the destination wrapped by this object
+    pub fn into_inner(self) -> W {
+        self.inner
+    }
+}
+
+impl<W: Write> Write for LimitedWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if let Some(max_output) = self.max_output {
+            if self.written + buf.len() as u64 > max_output {
+                let message = format!("output larger than {} bytes", max_output);
+                return Err(io::Error::other(LimitExceeded(message)));
+            }
+        }
+        let count = self.inner.write(buf)?;
+        self.written += count as u64;
+        Ok(count)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+// "BATCH REPORT" OBJECT
+
+impl BatchReport {
+    /// Returns a new `BatchReport` object with no files
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Adds the outcome of converting a file to the report
+    pub fn push(&mut self, file: FileReport) {
+        self.files.push(file);
+    }
+
+    /// Returns the number of files whose conversion failed
+    pub fn failed(&self) -> usize {
+        self.files
+            .iter()
+            .filter(|file| file.error.is_some())
+            .count()
+    }
+
+    /// Returns the number of files that were not converted again because
+    /// their outputs were up to date (which are also counted as converted)
+    pub fn up_to_date(&self) -> usize {
+        self.files.iter().filter(|file| file.up_to_date).count()
+    }
+
+    /// Returns the number of files whose conversion was stopped by one of
+    /// the `Limits` (which are also counted as failed)
+    pub fn skipped(&self) -> usize {
+        self.files.iter().filter(|file| file.skipped).count()
+    }
+
+    /// Returns the report as CSV text with a header row and a row per file
+    pub fn to_csv(&self) -> String {
+        let mut csv = format!("{}\n", CSV_HEADER);
+        for file in &self.files {
+            let s = &file.summary;
+            csv.push_str(&format!(
+                "{},{},{},{},{},{},{},{}\n",
+                csv_field(&file.input),
+                csv_field(&file.output),
+                s.lines,
+                s.replacements,
+                s.warnings,
+                s.controls,
+                s.score,
+                csv_field(&file.status())
+            ));
+        }
+        csv
+    }
+
+    /// Returns the report as pretty-printed JSON text, or a `std::io::Error`
+    /// type on failure
+    pub fn to_json(&self) -> io::Result<String> {
+        Ok(serde_json::to_string_pretty(self)?)
+    }
+}
+
+// "MANIFEST" OBJECT
+
+impl ManifestEntry {
+    /// Returns a new `ManifestEntry` object for the given input and output
+    /// files, or a `std::io::Error` type if either cannot be read
+    ///
+    /// # Arguments
+    ///
+    /// * `input` - Path to input file
+    /// * `output` - Path to output file
+    ///
+    /// # Examples
+    /// ```
+    /// let entry = ManifestEntry::new("one.ws", "one.txt").unwrap();
+    /// ```
+    pub fn new(input: &str, output: &str) -> io::Result<Self> {
+        let (input_size, input_sha256) = checksum(Path::new(input))?;
+        let (output_size, output_sha256) = checksum(Path::new(output))?;
+        Ok(ManifestEntry {
+            input: input.to_string(),
+            output: output.to_string(),
+            input_size,
+            output_size,
+            input_sha256,
+            output_sha256,
+        })
+    }
+}
+
+impl Manifest {
+    /// Returns a new `Manifest` object with no files
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Adds the entry for a converted file to the manifest
+    pub fn push(&mut self, entry: ManifestEntry) {
+        self.files.push(entry);
+    }
+
+    /// Returns the manifest as CSV text with a header row and a row per file
+    pub fn to_csv(&self) -> String {
+        let mut csv = format!("{}\n", MANIFEST_HEADER);
+        for entry in &self.files {
+            csv.push_str(&format!(
+                "{},{},{},{},{},{}\n",
+                csv_field(&entry.input),
+                csv_field(&entry.output),
+                entry.input_size,
+                entry.output_size,
+                entry.input_sha256,
+                entry.output_sha256
+            ));
+        }
+        csv
+    }
+
+    /// Returns the manifest as pretty-printed JSON text, or a
+    /// `std::io::Error` type on failure
+    pub fn to_json(&self) -> io::Result<String> {
+        Ok(serde_json::to_string_pretty(self)?)
+    }
+}
+
+// Unit tests
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn report() -> BatchReport {
+        let mut report = BatchReport::new();
+        report.push(FileReport {
+            input: "one.ws".to_string(),
+            output: "one.txt".to_string(),
+            summary: Summary {
+                lines: 12,
+                replacements: 3,
+                warnings: 1,
+                controls: 0,
+                score: 91,
+            },
+            error: None,
+            skipped: false,
+            up_to_date: false,
+        });
+        report.push(FileReport {
+            input: "a,b.ws".to_string(),
+            output: String::new(),
+            summary: Summary::default(),
+            error: Some("File exists".to_string()),
+            skipped: false,
+            up_to_date: false,
+        });
+        report
+    }
+
+    #[test]
+    fn test_csv_field() {
+        assert_eq!(csv_field("abc"), "abc");
+        assert_eq!(csv_field("a,b"), "\"a,b\"");
+        assert_eq!(csv_field("a\"b"), "\"a\"\"b\"");
+    }
+
+    #[test]
+    fn test_display() {
+        assert_eq!(
+            report().to_string(),
+            "File       Lines  Replaced  Warnings  Controls  Score  Status\n\
+             one.ws        12         3         1         0    91%  ok\n\
+             a,b.ws         0         0         0         0     0%  error: File exists\n\
+             Batch: 1 converted, 1 failed of 2 file(s)"
+        );
+    }
+
+    #[test]
+    fn test_skipped() {
+        let mut report = report();
+        report.push(FileReport {
+            input: "big.ws".to_string(),
+            output: "big.txt".to_string(),
+            summary: Summary::default(),
+            error: Some("timed out after 5 s".to_string()),
+            skipped: true,
+            up_to_date: false,
+        });
+        assert_eq!(report.failed(), 2);
+        assert_eq!(report.skipped(), 1);
+        assert!(report.to_csv().ends_with(",skipped: timed out after 5 s\n"));
+        assert!(report
+            .to_string()
+            .ends_with("Batch: 1 converted, 2 failed (1 skipped) of 3 file(s)"));
+    }
+
+    #[test]
+    fn test_up_to_date() {
+        let mut report = report();
+        report.push(FileReport {
+            input: "old.ws".to_string(),
+            output: "old.txt".to_string(),
+            summary: Summary::default(),
+            error: None,
+            skipped: false,
+            up_to_date: true,
+        });
+        assert_eq!(report.up_to_date(), 1);
+        assert!(report.to_csv().ends_with(",up to date\n"));
+        assert!(report
+            .to_string()
+            .ends_with("Batch: 2 converted (1 up to date), 1 failed of 3 file(s)"));
+    }
+
+    #[test]
+    fn test_limits() {
+        let limits = Limits {
+            timeout: Some(Duration::ZERO),
+            max_output: Some(4),
+        };
+        let mut reader = limits.timed(&b"abc"[..], Instant::now());
+        std::thread::sleep(Duration::from_millis(1));
+        let e = reader.read(&mut [0; 4]).unwrap_err();
+        assert!(Limits::is_exceeded(&e));
+        let mut writer = limits.limited(Vec::new());
+        writer.write_all(b"abcd").unwrap();
+        let e = writer.write_all(b"e").unwrap_err();
+        assert!(Limits::is_exceeded(&e));
+        assert_eq!(e.to_string(), "output larger than 4 bytes");
+        assert_eq!(writer.into_inner(), b"abcd");
+        assert!(!Limits::is_exceeded(&io::Error::other("File exists")));
+        let mut reader = Limits::default().timed(&b"abc"[..], Instant::now());
+        assert_eq!(reader.read(&mut [0; 4]).unwrap(), 3);
+    }
+
+    #[test]
+    fn test_to_csv() {
+        assert_eq!(
+            report().to_csv(),
+            "file,output,lines,replacements,warnings,controls,score,status\n\
+             one.ws,one.txt,12,3,1,0,91,ok\n\
+             \"a,b.ws\",,0,0,0,0,0,error: File exists\n"
+        );
+    }
+
+    #[test]
+    fn test_to_json() {
+        let json: serde_json::Value = serde_json::from_str(&report().to_json().unwrap()).unwrap();
+        assert_eq!(json["files"][0]["input"], "one.ws");
+        assert_eq!(json["files"][0]["replacements"], 3);
+        assert!(json["files"][0]["error"].is_null());
+        assert_eq!(json["files"][1]["error"], "File exists");
+    }
+
+    #[test]
+    fn test_manifest() {
+        let dir = tempfile::tempdir().unwrap();
+        let input = dir.path().join("in,1.ws");
+        let output = dir.path().join("out.txt");
+        std::fs::write(&input, "abc").unwrap();
+        std::fs::write(&output, "").unwrap();
+        let (input, output) = (input.to_str().unwrap(), output.to_str().unwrap());
+        let mut manifest = Manifest::new();
+        manifest.push(ManifestEntry::new(input, output).unwrap());
+        let entry = &manifest.files[0];
+        assert_eq!((entry.input_size, entry.output_size), (3, 0));
+        assert_eq!(
+            entry.input_sha256,
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        );
+        assert_eq!(
+            entry.output_sha256,
+            "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855"
+        );
+        let csv = manifest.to_csv();
+        assert!(csv.starts_with(MANIFEST_HEADER));
+        assert!(csv.contains(&format!("\"{}\",{},3,0,ba7816bf", input, output)));
+        let json: serde_json::Value = serde_json::from_str(&manifest.to_json().unwrap()).unwrap();
+        assert_eq!(json["files"][0]["output_size"], 0);
+        assert!(ManifestEntry::new(input, "missing.txt").is_err());
+    }
+}
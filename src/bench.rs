@@ -0,0 +1,462 @@
+//! Module of deterministic entry points for measuring the performance of the
+//! conversion (e.g. from Criterion benchmarks), including a generator of
+//! large WordStar documents that can also be used as demonstration data
+
+use crate::asciify::{self, Filler};
+use crate::ws_align;
+use crate::ws_chars;
+use crate::ws_filters::{self, Options};
+use crate::ws_wrappers::Wrappers;
+use bitflags::bitflags;
+use std::fmt;
+use std::io;
+use std::str::FromStr;
+
+const SEED: u64 = 0x5753_434F_4E56_4552; // Fixed seed ("WSCONVER") for repeatable output
+
+const LINE_WIDTH: usize = 65; // Characters per line before a soft return
+const SOFT_RETURN: &[u8] = b"\x8D\n"; // Line break within a paragraph
+const HARD_RETURN: &[u8] = b"\r\n"; // Line break at the end of a paragraph
+const EOF_BYTE: u8 = 0x1A; // End of File (EOF) marker
+
+// Words from which the text of generated documents is formed
+const WORDS: [&str; 24] = [
+    "the",
+    "bus",
+    "data",
+    "logic",
+    "analyser",
+    "was",
+    "used",
+    "to",
+    "examine",
+    "signals",
+    "on",
+    "each",
+    "line",
+    "and",
+    "a",
+    "computer",
+    "with",
+    "an",
+    "interface",
+    "measured",
+    "voltage",
+    "in",
+    "several",
+    "experiments",
+];
+
+// Holds a set of flags to specify the WordStar features in generated documents
+bitflags! {
+    #[derive(Default)]
+    pub struct Features: u16 {
+        const BOLD = (1 << 0);
+        const DOUBLE = (1 << 1);
+        const UNDERLINE = (1 << 2);
+        const ITALIC = (1 << 3);
+        const STRIKETHROUGH = (1 << 4);
+        const SUPERSCRIPT = (1 << 5);
+        const SUBSCRIPT = (1 << 6);
+        const OVERLINE = (1 << 7);
+        const PITCH = (1 << 8);
+        const HYPHENS = (1 << 9);
+        const DOT_CMDS = (1 << 10);
+    }
+}
+
+// Names of the features that can be generated
+const FEATURE_NAMES: [(Features, &str); 11] = [
+    (Features::BOLD, "bold"),
+    (Features::DOUBLE, "double"),
+    (Features::UNDERLINE, "underline"),
+    (Features::ITALIC, "italic"),
+    (Features::STRIKETHROUGH, "strikethrough"),
+    (Features::SUPERSCRIPT, "superscript"),
+    (Features::SUBSCRIPT, "subscript"),
+    (Features::OVERLINE, "overline"),
+    (Features::PITCH, "pitch"),
+    (Features::HYPHENS, "hyphens"),
+    (Features::DOT_CMDS, "dot-cmds"),
+];
+
+// Wrapper characters placed around words for each of the wrapper features
+const WRAPPERS: [(Features, char); 7] = [
+    (Features::BOLD, ws_chars::BOLD),
+    (Features::DOUBLE, ws_chars::DOUBLE),
+    (Features::UNDERLINE, ws_chars::UNDERLINE),
+    (Features::ITALIC, ws_chars::ITALIC),
+    (Features::STRIKETHROUGH, ws_chars::STRIKETHROUGH),
+    (Features::SUPERSCRIPT, ws_chars::SUPERSCRIPT),
+    (Features::SUBSCRIPT, ws_chars::SUBSCRIPT),
+];
+
+/// FromStr trait implementation for Features, accepting a comma-separated
+/// list of feature names (in any case, with "dotcmds" also accepted for
+/// "dot-cmds") or "all" or "none"
+impl FromStr for Features {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut features = Features::empty();
+        for name in s.split(',').map(str::trim).filter(|name| !name.is_empty()) {
+            let name = name.to_lowercase().replace("dotcmds", "dot-cmds");
+            match FEATURE_NAMES.iter().find(|(_, n)| *n == name) {
+                Some((flag, _)) => features.insert(*flag),
+                None if name == "all" => features = Features::all(),
+                None if name == "none" => {}
+                None => return Err(format!("Unknown feature name: {}", name)),
+            }
+        }
+        Ok(features)
+    }
+}
+
+/// Display trait implementation for Features, giving a comma-separated list
+/// of the feature names (or "none")
+impl fmt::Display for Features {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let names: Vec<&str> = FEATURE_NAMES
+            .iter()
+            .filter(|(flag, _)| self.contains(*flag))
+            .map(|(_, name)| *name)
+            .collect();
+        if names.is_empty() {
+            write!(f, "none")
+        } else {
+            write!(f, "{}", names.join(","))
+        }
+    }
+}
+
+// "RANDOM" OBJECT
+
+/// Holds the state of a small pseudo-random number generator (xorshift64*),
+/// so that generated documents are the same on every run and platform
+#[derive(Debug)]
+struct Random(u64);
+
+impl Random {
+    /// Returns the next pseudo-random number
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 >> 12;
+        self.0 ^= self.0 << 25;
+        self.0 ^= self.0 >> 27;
+        self.0.wrapping_mul(0x2545_F491_4F6C_DD1D)
+    }
+
+    /// Returns a pseudo-random number from 0 up to (but excluding) `n`
+    fn below(&mut self, n: usize) -> usize {
+        (self.next() % n as u64) as usize
+    }
+
+    /// Returns `true` with the given probability (from 0.0 to 1.0)
+    fn chance(&mut self, probability: f64) -> bool {
+        (self.next() >> 11) as f64 / (1u64 << 53) as f64 <= probability
+    }
+}
+
+// PRIVATE HELPER FUNCTIONS
+
+/// Returns the lines of the given text with the given per-line function
+/// applied to each, keeping any lines for which it returns `None`
+///
+/// # Arguments
+///
+/// * `text` - Slice of text holding lines separated by line feeds
+/// * `f` - Function that returns `Some(replacement)` if a line is changed
+///
+fn map_lines<F>(text: &str, mut f: F) -> String
+where
+    F: FnMut(&str) -> Option<String>,
+{
+    let mut result = String::with_capacity(text.len() * 2);
+    for line in text.lines() {
+        match f(line) {
+            Some(replacement) => result.push_str(&replacement),
+            None => result.push_str(line),
+        }
+        result.push('\n');
+    }
+    result
+}
+
+/// Appends the given word to the document, formatted with one of the given
+/// word-level features chosen at random (if any)
+///
+/// # Arguments
+///
+/// * `doc` - Vector to which the formatted word is appended
+/// * `word` - Slice of text for the word
+/// * `features` - Set of features from which to choose
+/// * `random` - Source of pseudo-random numbers
+///
+fn push_formatted(doc: &mut Vec<u8>, word: &str, features: Features, random: &mut Random) {
+    let choices: Vec<(Features, &str)> = FEATURE_NAMES
+        .iter()
+        .filter(|(flag, _)| features.contains(*flag) && *flag != Features::DOT_CMDS)
+        .copied()
+        .collect();
+    if choices.is_empty() {
+        doc.extend_from_slice(word.as_bytes());
+        return;
+    }
+    let (feature, _) = choices[random.below(choices.len())];
+    let len = word.len();
+    match feature {
+        Features::OVERLINE => {
+            doc.extend_from_slice(word.as_bytes());
+            doc.resize(doc.len() + len, ws_chars::OVERPRINT as u8);
+            doc.push(ws_chars::SUPERSCRIPT as u8);
+            doc.resize(doc.len() + len, ws_chars::UNDERSCORE as u8);
+            doc.push(ws_chars::SUPERSCRIPT as u8);
+        }
+        Features::PITCH => {
+            doc.push(ws_chars::ALTERNATE_PITCH as u8);
+            doc.extend_from_slice(word.as_bytes());
+            doc.push(ws_chars::NORMAL_PITCH as u8);
+        }
+        Features::HYPHENS if len >= 4 => {
+            let (head, tail) = word.split_at(len / 2);
+            doc.extend_from_slice(head.as_bytes());
+            doc.push(ws_chars::INACTIVE_SOFT_HYPHEN as u8);
+            doc.extend_from_slice(tail.as_bytes());
+        }
+        _ => match WRAPPERS.iter().find(|(flag, _)| *flag == feature) {
+            Some((_, wrapper)) => {
+                let padding: &[u8] = if random.chance(0.25) { b" " } else { b"" };
+                doc.push(*wrapper as u8);
+                doc.extend_from_slice(padding);
+                doc.extend_from_slice(word.as_bytes());
+                doc.extend_from_slice(padding);
+                doc.push(*wrapper as u8);
+            }
+            None => doc.extend_from_slice(word.as_bytes()),
+        },
+    }
+}
+
+// EXTERNAL PUBLIC FUNCTIONS
+
+/// Returns a generated WordStar document of (at least) the given size in
+/// bytes with all of the features that can be generated, which is the same
+/// for the same arguments on every run
+///
+/// # Arguments
+///
+/// * `size` - Minimum size of the document in bytes
+/// * `density` - Proportion of formatted words (from 0.0 to 1.0)
+///
+/// # Examples
+/// ```
+/// let doc = bench::generate_document(64 * 1024, 0.1);
+/// assert_eq!(doc, bench::generate_document(64 * 1024, 0.1));
+/// ```
+pub fn generate_document(size: usize, density: f64) -> Vec<u8> {
+    generate_features(size, density, Features::all())
+}
+
+/// Returns a generated WordStar document of (at least) the given size in
+/// bytes using the given features, which is the same for the same arguments
+/// on every run
+///
+/// The document consists of sections of paragraphs of words, with soft
+/// returns (with the top bit set) between lines.  The proportion of words
+/// formatted with one of the word-level features (i.e. wrappers, overlines,
+/// pitch changes or soft hyphens) is given by `density`.  Wrappers sometimes
+/// have spaces inside them, to be re-aligned.  Each section starts with a
+/// heading (in bold if selected) and, if dot commands are selected, the
+/// document starts with a header and margin setting and each new section
+/// starts on a new page.
+///
+/// # Arguments
+///
+/// * `size` - Minimum size of the document in bytes
+/// * `density` - Proportion of formatted words (from 0.0 to 1.0)
+/// * `features` - Set of features to be used
+///
+/// # Examples
+/// ```
+/// let doc = bench::generate_features(50 * 1024, 0.2, Features::BOLD | Features::DOT_CMDS);
+/// ```
+pub fn generate_features(size: usize, density: f64, features: Features) -> Vec<u8> {
+    let mut random = Random(SEED);
+    let mut doc = Vec::with_capacity(size + LINE_WIDTH * 4);
+    let dot_cmds = features.contains(Features::DOT_CMDS);
+    let heading_wrapper = if features.contains(Features::BOLD) {
+        "\x02"
+    } else {
+        ""
+    };
+    if dot_cmds && size > 0 {
+        doc.extend_from_slice(b".he Generated document\r\n.mt 3\r\n");
+    }
+    let mut paragraphs = 0;
+    while doc.len() < size {
+        if paragraphs % 8 == 0 {
+            if paragraphs > 0 && dot_cmds {
+                doc.extend_from_slice(b".pa\r\n");
+            }
+            let number = paragraphs / 8 + 1;
+            let heading = format!("{0}SECTION {1}{0}\r\n\r\n", heading_wrapper, number);
+            doc.extend(heading.bytes());
+        }
+        let mut line_len = 0;
+        for _ in 0..40 + random.below(60) {
+            let word = WORDS[random.below(WORDS.len())];
+            if line_len + word.len() >= LINE_WIDTH {
+                doc.extend_from_slice(SOFT_RETURN);
+                line_len = 0;
+            } else if line_len > 0 {
+                doc.push(b' ');
+                line_len += 1;
+            }
+            if random.chance(density) {
+                push_formatted(&mut doc, word, features, &mut random);
+            } else {
+                doc.extend_from_slice(word.as_bytes());
+            }
+            line_len += word.len();
+        }
+        doc.extend_from_slice(b".");
+        doc.extend_from_slice(HARD_RETURN);
+        doc.extend_from_slice(HARD_RETURN);
+        paragraphs += 1;
+    }
+    doc.push(EOF_BYTE);
+    doc
+}
+
+/// Returns the 7-bit ASCII conversion of the given 8-bit document (up to any
+/// End of File marker), as produced by the first stage of the conversion
+///
+/// # Arguments
+///
+/// * `doc` - Byte (u8) slice of 8-bit input
+///
+/// # Examples
+/// ```
+/// assert_eq!(bench::asciify(b"ab\xE3\x1Ad"), "abc");
+/// ```
+pub fn asciify(doc: &[u8]) -> String {
+    let mut output = Vec::with_capacity(doc.len());
+    asciify::convert_file(&mut &doc[..], &mut output, Filler::Keep)
+        .expect("Writing to a vector cannot fail");
+    String::from_utf8(output).expect("7-bit ASCII is valid UTF-8")
+}
+
+/// Returns the given 7-bit ASCII text with spaces re-aligned outside pairs of
+/// wrapper characters on each line, as by the alignment stage of the filters
+///
+/// # Arguments
+///
+/// * `text` - Slice of text holding lines separated by line feeds
+///
+/// # Examples
+/// ```
+/// assert_eq!(bench::align("a\x13 b \x13c\n"), "a \x13b\x13 c\n");
+/// ```
+pub fn align(text: &str) -> String {
+    map_lines(text, |line| ws_align::process(line, &[]))
+}
+
+/// Returns the given 7-bit ASCII text with the text attributes of wrapper
+/// characters mapped to Unicode characters, as by the wrappers stage of the
+/// filters (with the attributes carried over from line to line)
+///
+/// # Arguments
+///
+/// * `text` - Slice of text holding lines separated by line feeds
+///
+/// # Examples
+/// ```
+/// assert_eq!(bench::wrappers("\x02C\x02\n"), "\u{1D402}\n");
+/// ```
+pub fn wrappers(text: &str) -> String {
+    let mut wrappers = Wrappers::new();
+    map_lines(text, |line| wrappers.process(line))
+}
+
+/// Returns the complete conversion of the given 8-bit document with the
+/// given settings, or a `std::io::Error` type on failure
+///
+/// # Arguments
+///
+/// * `doc` - Byte (u8) slice of 8-bit input
+/// * `options` - Settings for filter exclusions and output rendering
+///
+/// # Examples
+/// ```
+/// let options = Options { quiet: true, ..Default::default() };
+/// let output = bench::convert(&bench::generate_document(1024, 0.1), &options).unwrap();
+/// ```
+pub fn convert(doc: &[u8], options: &Options) -> io::Result<Vec<u8>> {
+    let text = asciify(doc);
+    let mut output = Vec::with_capacity(text.len() * 2);
+    ws_filters::transform_file(&mut text.as_bytes(), &mut output, options)?;
+    Ok(output)
+}
+
+// Unit tests
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_generate_document() {
+        let doc = generate_document(4096, 0.2);
+        assert!(doc.len() >= 4096);
+        assert_eq!(doc.last(), Some(&EOF_BYTE));
+        assert_eq!(doc, generate_document(4096, 0.2));
+        let plain = generate_document(4096, 0.0);
+        assert!(!plain.contains(&(ws_chars::UNDERLINE as u8)));
+        assert!(doc.contains(&(ws_chars::UNDERLINE as u8)));
+        assert_eq!(generate_document(0, 0.5), [EOF_BYTE]);
+    }
+
+    #[test]
+    fn test_features() {
+        assert_eq!(
+            "Bold, dotcmds".parse(),
+            Ok(Features::BOLD | Features::DOT_CMDS)
+        );
+        assert_eq!("all".parse(), Ok(Features::all()));
+        assert_eq!("".parse(), Ok(Features::empty()));
+        assert!("blink".parse::<Features>().is_err());
+        assert_eq!(Features::empty().to_string(), "none");
+        assert_eq!(Features::all().to_string().parse(), Ok(Features::all()));
+
+        let doc = generate_features(4096, 1.0, Features::OVERLINE);
+        let text = asciify(&doc);
+        assert!(text.starts_with("SECTION 1\r\n"));
+        assert!(text.contains("\x08\x14_"));
+        assert!(!text.contains(".pa") && !text.contains('\x02'));
+        let doc = generate_features(4096, 1.0, Features::PITCH | Features::HYPHENS);
+        assert!(doc.contains(&(ws_chars::ALTERNATE_PITCH as u8)));
+        assert!(doc.contains(&(ws_chars::INACTIVE_SOFT_HYPHEN as u8)));
+        let doc = generate_features(4096, 1.0, Features::DOT_CMDS);
+        assert!(asciify(&doc).contains("\r\n.pa\r\nSECTION 2"));
+    }
+
+    #[test]
+    fn test_filters() {
+        let text = asciify(&generate_document(2048, 0.5));
+        assert!(text.is_ascii());
+        assert!(text.starts_with(".he Generated document\r\n.mt 3\r\n\x02SECTION 1\x02\r\n"));
+        assert_eq!(align("a\x13 b \x13c\n"), "a \x13b\x13 c\n");
+        assert_eq!(wrappers("\x13a\nb\x13\nc\n"), "a\u{332}\nb\u{332}\nc\n");
+    }
+
+    #[test]
+    fn test_convert() {
+        let options = Options {
+            quiet: true,
+            ..Default::default()
+        };
+        let output = convert(&generate_document(2048, 0.3), &options).unwrap();
+        let text = String::from_utf8(output).unwrap();
+        assert!(text.contains("\u{1D412}\u{1D404}\u{1D402}"));
+        assert!(!text.contains('\x13'));
+    }
+}
@@ -0,0 +1,431 @@
+//! Module to maintain sets of counters for characters (by default only the
+//! ASCII control characters) as histograms
+
+use crate::ws_control;
+use bitflags::bitflags;
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+
+// Holds a set of flags for the classes of character to be counted
+bitflags! {
+    pub struct CharClasses: u8 {
+        const CONTROL = (1 << 0); // ASCII control characters
+        const PRINTABLE = (1 << 1); // ASCII graphic characters
+        const WHITESPACE = (1 << 2); // ASCII space
+        const HIGH = (1 << 3); // Characters beyond 7-bit ASCII
+    }
+}
+
+impl CharClasses {
+    /// Returns the class of the given character
+    ///
+    /// # Arguments
+    ///
+    /// * `ch` - Character to be classified
+    ///
+    /// # Examples
+    /// ```
+    /// assert_eq!(CharClasses::of('\x02'), CharClasses::CONTROL);
+    /// ```
+    pub fn of(ch: char) -> Self {
+        match ch {
+            ' ' => CharClasses::WHITESPACE,
+            c if c.is_ascii_control() => CharClasses::CONTROL,
+            c if c.is_ascii() => CharClasses::PRINTABLE,
+            _ => CharClasses::HIGH,
+        }
+    }
+}
+
+// Names of the character classes, as used in `class_summary()`
+const CLASS_NAMES: [(CharClasses, &str); 4] = [
+    (CharClasses::CONTROL, "control"),
+    (CharClasses::PRINTABLE, "printable"),
+    (CharClasses::WHITESPACE, "whitespace"),
+    (CharClasses::HIGH, "high"),
+];
+
+/// Holds a name tag for a set of counters, the classes of character that are
+/// counted, a 'used' marker and a binary tree of counts for the characters
+#[derive(Debug)]
+pub struct CharHistogram {
+    tag: String,
+    classes: CharClasses,
+    used: bool,
+    counts: BTreeMap<char, i32>,
+}
+
+/// Display trait implementation for CharHistogram, starting with the name tag,
+/// then listing each active counter as a hex ASCII key and a decimal value
+impl fmt::Display for CharHistogram {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: ", self.tag)?;
+        if !self.used {
+            write!(f, "Skipped")?;
+        } else {
+            let mut previous = false;
+            for (key, value) in &self.counts {
+                if previous {
+                    write!(f, ", ")?;
+                }
+                write!(f, "[{:02X}]={}", *key as u32, *value)?;
+                previous = true;
+            }
+            if previous {
+                write!(f, " => {} char(s), {} type(s)", self.total(), self.bins())?;
+            } else {
+                write!(f, "None")?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl CharHistogram {
+    /// Returns a set of counters for ASCII control characters (as defined by
+    /// `char::is_ascii_control()`) with the given name tag
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - String containing name tag (moved into `CharHistogram` struct)
+    ///
+    /// # Examples
+    /// ```
+    /// let mut counts = CharHistogram::new("name".to_string());
+    /// ```
+    pub fn new(tag: String) -> CharHistogram {
+        CharHistogram::with_classes(tag, CharClasses::CONTROL)
+    }
+
+    /// Returns a set of counters for the given classes of character with the
+    /// given name tag
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - String containing name tag (moved into `CharHistogram` struct)
+    /// * `classes` - Classes of character to be counted
+    ///
+    /// # Examples
+    /// ```
+    /// let mut counts = CharHistogram::with_classes("name".to_string(), CharClasses::all());
+    /// ```
+    pub fn with_classes(tag: String, classes: CharClasses) -> CharHistogram {
+        CharHistogram {
+            tag,
+            classes,
+            used: false,
+            counts: BTreeMap::new(),
+        }
+    }
+
+    /// Attempts to increment (by one) the count for the given character
+    ///
+    /// If the given character is not in one of the classes being counted then
+    /// no action is taken
+    ///
+    /// # Arguments
+    ///
+    /// * `ch` - Character (char) specifying the count to be incremented
+    ///
+    /// # Examples
+    /// ```
+    /// let mut counts = CharHistogram::new("name".to_string());
+    /// counts.up('\x06');
+    /// ```
+    pub fn up(&mut self, ch: char) {
+        if self.classes.contains(CharClasses::of(ch)) {
+            let counter = self.counts.entry(ch).or_insert(0);
+            *counter += 1;
+        }
+        self.used = true;
+    }
+
+    /// Attempts to return the current count for the given character
+    ///
+    /// Returns `Some(u32)` if a count has been established for the given
+    /// character or `None` if not
+    ///
+    /// # Arguments
+    ///
+    /// * `ch` - Character (char) specifying the count to be returned
+    ///
+    /// # Examples
+    /// ```
+    /// let mut counts = CharHistogram::new("name".to_string());
+    /// counts.up('\x07');
+    /// assert_eq!(counts.get('\x07'), Some(1));
+    /// assert_eq!(counts.get('\x08'), None);
+    /// ```
+    pub fn get(&self, ch: char) -> Option<i32> {
+        self.counts.get(&ch).copied()
+    }
+
+    /// Scans text slice and increments counts for each character found in it
+    /// that is in one of the classes being counted
+    ///
+    /// # Arguments
+    ///
+    /// * `s` - Slice of text to be scanned
+    ///
+    /// # Examples
+    /// ```
+    /// let mut counts = CharHistogram::new("name".to_string());
+    /// counts.scan("ABC\x14DEF");
+    /// assert_eq!(counts.get('\x14'), Some(1));
+    /// ```
+    pub fn scan(&mut self, s: &str) {
+        for ch in s.chars() {
+            self.up(ch);
+        }
+        self.used = true;
+    }
+
+    /// Returns number of different bins that have been created
+    ///
+    /// # Examples
+    /// ```
+    /// let mut counts = CharHistogram::new("name".to_string());
+    /// counts.scan("A\x14BC\x14DE\x15F");
+    /// assert_eq!(counts.bins(), 2);
+    /// ```
+    pub fn bins(&self) -> usize {
+        self.counts.len()
+    }
+
+    /// Returns total count from all bins
+    ///
+    /// # Examples
+    /// ```
+    /// let mut counts = CharHistogram::new("name".to_string());
+    /// counts.scan("A\x14BC\x14DE\x15F");
+    /// assert_eq!(counts.total(), 3);
+    /// ```
+    pub fn total(&self) -> i32 {
+        self.counts.values().sum()
+    }
+
+    /// Returns `true` if any text has been counted (i.e. the stage was not
+    /// skipped), otherwise `false`
+    pub fn is_used(&self) -> bool {
+        self.used
+    }
+
+    /// Returns the count at the given percentile (from 0 to 100) of the
+    /// counts for the characters found, using the nearest-rank method, or
+    /// `None` if no characters have been counted
+    ///
+    /// # Arguments
+    ///
+    /// * `percent` - Percentile to be returned
+    ///
+    /// # Examples
+    /// ```
+    /// let mut counts = CharHistogram::new("name".to_string());
+    /// counts.scan("A\x14BC\x14DE\x15F");
+    /// assert_eq!(counts.percentile(50.0), Some(1));
+    /// ```
+    pub fn percentile(&self, percent: f64) -> Option<i32> {
+        let mut values: Vec<i32> = self.counts.values().copied().collect();
+        values.sort_unstable();
+        let rank = (percent.clamp(0.0, 100.0) / 100.0 * values.len() as f64).ceil() as usize;
+        values.get(rank.max(1) - 1).copied()
+    }
+
+    /// Returns a summary of the counts, starting with the name tag and then
+    /// giving the total for each class of character being counted and the
+    /// counts at the 50th, 90th and 99th percentiles (see `percentile()`)
+    ///
+    /// # Examples
+    /// ```
+    /// let mut counts = CharHistogram::with_classes("name".to_string(), CharClasses::all());
+    /// counts.scan("ab a\x02");
+    /// assert!(counts.class_summary().starts_with("name: control=1, printable=3"));
+    /// ```
+    pub fn class_summary(&self) -> String {
+        let classes = CLASS_NAMES
+            .iter()
+            .filter(|(class, _)| self.classes.contains(*class))
+            .map(|(class, name)| {
+                let total: i32 = self
+                    .counts
+                    .iter()
+                    .filter(|(key, _)| CharClasses::of(**key) == *class)
+                    .map(|(_, value)| value)
+                    .sum();
+                format!("{}={}", name, total)
+            });
+        let percentiles = [50.0, 90.0, 99.0].iter().map(|&percent| {
+            let value = self.percentile(percent).unwrap_or(0);
+            format!("p{}={}", percent, value)
+        });
+        let parts: Vec<String> = classes.chain(percentiles).collect();
+        format!("{}: {}", self.tag, parts.join(", "))
+    }
+
+    /// Returns a description of the changes from the given previous set of
+    /// counters (e.g. for the stage before), starting with the name tag and
+    /// then listing the characters consumed and added with their counts
+    ///
+    /// # Arguments
+    ///
+    /// * `previous` - Set of counters to compare against
+    ///
+    /// # Examples
+    /// ```
+    /// let mut before = CharHistogram::new("before".to_string());
+    /// before.scan("A\x14B\x14");
+    /// let mut after = CharHistogram::new("after".to_string());
+    /// after.scan("AB");
+    /// assert_eq!(after.changes_from(&before), "after: consumed 2\u{D7}^T");
+    /// ```
+    pub fn changes_from(&self, previous: &CharHistogram) -> String {
+        if !self.used {
+            return format!("{}: Skipped", self.tag);
+        }
+        let mut consumed = Vec::new();
+        let mut added = Vec::new();
+        let keys = previous.counts.keys().chain(self.counts.keys());
+        for key in keys.collect::<BTreeSet<_>>() {
+            let delta = self.get(*key).unwrap_or(0) - previous.get(*key).unwrap_or(0);
+            let name = ws_control::get_escaped(*key).unwrap_or_else(|| key.to_string());
+            match delta {
+                d if d < 0 => consumed.push(format!("{}\u{D7}{}", -d, name)),
+                d if d > 0 => added.push(format!("{}\u{D7}{}", d, name)),
+                _ => {}
+            }
+        }
+        let mut parts = Vec::new();
+        if !consumed.is_empty() {
+            parts.push(format!("consumed {}", consumed.join(", ")));
+        }
+        if !added.is_empty() {
+            parts.push(format!("added {}", added.join(", ")));
+        }
+        if parts.is_empty() {
+            format!("{}: No change", self.tag)
+        } else {
+            format!("{}: {}", self.tag, parts.join("; "))
+        }
+    }
+}
+
+// Unit tests
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_simple() {
+        let mut counts = CharHistogram::new("Counts".to_string());
+        counts.up('\x03');
+        assert_eq!(counts.get('\x03'), Some(1));
+        assert_eq!(counts.get('\x02'), None);
+        assert_eq!(counts.get('\x20'), None);
+    }
+
+    #[test]
+    fn test_multi() {
+        let mut counts = CharHistogram::new("Counts".to_string());
+        counts.up('\x04');
+        counts.up('\x04');
+        counts.up('\x04');
+        assert_eq!(counts.get('\x04'), Some(3));
+    }
+
+    #[test]
+    fn test_display() {
+        let mut counts = CharHistogram::new("Counts".to_string());
+        counts.up('\x03');
+        counts.up('\x08');
+        counts.up('\x19');
+        counts.up('\x7F');
+        counts.up('\x7F');
+        counts.up('\x07');
+        assert_eq!(
+            format!("{}", counts),
+            "Counts: [03]=1, [07]=1, [08]=1, [19]=1, [7F]=2 => 6 char(s), 5 type(s)"
+        );
+    }
+
+    #[test]
+    fn test_scan() {
+        let mut counts = CharHistogram::new("name".to_string());
+        counts.scan("a'\x07bc\x14de'\x07f");
+        assert_eq!(counts.get('\x07'), Some(2));
+        assert_eq!(counts.get('\x14'), Some(1));
+    }
+
+    #[test]
+    fn test_bins() {
+        let mut counts = CharHistogram::new("name".to_string());
+        counts.scan("A\x14BC\x14DE\x15F");
+        assert_eq!(counts.bins(), 2);
+    }
+
+    #[test]
+    fn test_changes_from() {
+        let mut before = CharHistogram::new("Before".to_string());
+        before.scan("A\x14B\x14C\x02D\x02E\x13");
+        let mut after = CharHistogram::new("After".to_string());
+        after.scan("AB\x13C\x02D\x02E\x08");
+        assert_eq!(
+            after.changes_from(&before),
+            "After: consumed 2\u{D7}^T; added 1\u{D7}^H"
+        );
+        assert_eq!(before.changes_from(&before), "Before: No change");
+        let skipped = CharHistogram::new("Skipped".to_string());
+        assert_eq!(skipped.changes_from(&before), "Skipped: Skipped");
+        assert!(!skipped.is_used());
+    }
+
+    #[test]
+    fn test_classes() {
+        assert_eq!(CharClasses::of('\x7F'), CharClasses::CONTROL);
+        assert_eq!(CharClasses::of('a'), CharClasses::PRINTABLE);
+        assert_eq!(CharClasses::of(' '), CharClasses::WHITESPACE);
+        assert_eq!(CharClasses::of('\u{E9}'), CharClasses::HIGH);
+        let mut counts = CharHistogram::with_classes("name".to_string(), CharClasses::all());
+        counts.scan("ab a\x02\u{E9}");
+        assert_eq!(counts.get('a'), Some(2));
+        assert_eq!(counts.get(' '), Some(1));
+        assert_eq!(counts.get('\u{E9}'), Some(1));
+        assert_eq!(counts.total(), 6);
+        let mut counts = CharHistogram::with_classes("name".to_string(), CharClasses::HIGH);
+        counts.scan("ab\x02\u{E9}");
+        assert_eq!(counts.bins(), 1);
+    }
+
+    #[test]
+    fn test_percentile() {
+        let mut counts = CharHistogram::with_classes("name".to_string(), CharClasses::all());
+        assert_eq!(counts.percentile(50.0), None);
+        counts.scan("abbcccdddd");
+        assert_eq!(counts.percentile(0.0), Some(1));
+        assert_eq!(counts.percentile(50.0), Some(2));
+        assert_eq!(counts.percentile(75.0), Some(3));
+        assert_eq!(counts.percentile(100.0), Some(4));
+    }
+
+    #[test]
+    fn test_class_summary() {
+        let mut counts = CharHistogram::with_classes("All".to_string(), CharClasses::all());
+        counts.scan("ab a\x02\u{E9}");
+        assert_eq!(
+            counts.class_summary(),
+            "All: control=1, printable=3, whitespace=1, high=1, p50=1, p90=2, p99=2"
+        );
+        let counts = CharHistogram::new("Controls".to_string());
+        assert_eq!(
+            counts.class_summary(),
+            "Controls: control=0, p50=0, p90=0, p99=0"
+        );
+    }
+
+    #[test]
+    fn test_total() {
+        let mut counts = CharHistogram::new("name".to_string());
+        counts.scan("A\x14BC\x14DE\x15F");
+        assert_eq!(counts.total(), 3);
+    }
+}
@@ -0,0 +1,576 @@
+//! Module to provide a builder-style interface for converting WordStar text
+//! from library code, without the need to fill in an `Options` struct
+
+use crate::asciify::{self, Filler};
+use crate::render::{Format, LanguageDetector, PageBreakStyle};
+use crate::sink::{OutputSink, Split};
+use crate::transliterate::Charset;
+use crate::ws_control::ControlHandler;
+use crate::ws_dot_cmd::{DotCmdHandler, InputFormat, SuffixMode};
+use crate::ws_events::ConversionStats;
+use crate::ws_filters::{self, Excludes, Mode, Options};
+use crate::ws_heading::RuledHeadings;
+use crate::ws_overline::OverlineStyle;
+use crate::ws_special::{CustomFraction, FractionStyle, Locale, SpecialRule, Symbols};
+use crate::ws_wrappers::{CustomWrapper, UnderlineStyle};
+use std::io::{self, Read, Write};
+
+/// Filters that can be excluded from the conversion (in pipeline order)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Filter {
+    DotCmds,
+    Escapes,
+    Pitch,
+    ReAlign,
+    Specials,
+    Overline,
+    Overprint,
+    Wrappers,
+    Controls,
+}
+
+/// From trait implementation to give the `Excludes` flag for a `Filter`
+impl From<Filter> for Excludes {
+    fn from(filter: Filter) -> Self {
+        match filter {
+            Filter::DotCmds => Excludes::DOT_CMDS,
+            Filter::Escapes => Excludes::ESCAPES,
+            Filter::Pitch => Excludes::PITCH,
+            Filter::ReAlign => Excludes::RE_ALIGN,
+            Filter::Specials => Excludes::SPECIALS,
+            Filter::Overline => Excludes::OVERLINE,
+            Filter::Overprint => Excludes::OVERPRINT,
+            Filter::Wrappers => Excludes::WRAPPERS,
+            Filter::Controls => Excludes::CONTROLS,
+        }
+    }
+}
+
+// "CONVERTER BUILDER" OBJECT
+
+/// Collects the settings for a `Converter`, starting from the defaults
+/// (all filters enabled and plain text output, with the statistics on the
+/// conversion returned rather than written to `stderr`)
+#[derive(Debug, Clone)]
+pub struct ConverterBuilder {
+    options: Options,
+}
+
+/// Default trait implementation for ConverterBuilder, giving the default
+/// `Options` apart from leaving the statistics out of `stderr`
+impl Default for ConverterBuilder {
+    fn default() -> Self {
+        ConverterBuilder {
+            options: Options {
+                quiet: true,
+                ..Default::default()
+            },
+        }
+    }
+}
+
+impl ConverterBuilder {
+    /// Excludes the given filter from the conversion
+    pub fn exclude(mut self, filter: Filter) -> Self {
+        self.options.excludes.insert(filter.into());
+        self
+    }
+
+    /// Sets the output format (with `Format::Text` for Markdown, which the
+    /// text renderer writes)
+    pub fn output(mut self, format: Format) -> Self {
+        self.options.render.format = format;
+        self
+    }
+
+    /// Sets how runs of filler bytes (NUL or 0xE5) in the input are treated
+    pub fn filler(mut self, filler: Filler) -> Self {
+        self.options.filler = filler;
+        self
+    }
+
+    /// Sets the word processor that created the input (WordStar by default)
+    pub fn input_format(mut self, format: InputFormat) -> Self {
+        self.options.input_format = format;
+        self
+    }
+
+    /// Sets whether text laid out in side-by-side columns is re-flowed
+    pub fn detect_columns(mut self, detect: bool) -> Self {
+        self.options.detect_columns = detect;
+        self
+    }
+
+    /// Sets whether lines starting with outline numbers (e.g. "1.", "1.1" or
+    /// "1.1.1") are taken to be headings nested by the depth of numbering
+    pub fn outline(mut self, outline: bool) -> Self {
+        self.options.outline = outline;
+        self
+    }
+
+    /// Sets how a line ruled off by a line of '=', '-' or '_' characters is
+    /// converted into a heading (left unchanged by default)
+    pub fn ruled_headings(mut self, ruled: RuledHeadings) -> Self {
+        self.options.ruled_headings = ruled;
+        self
+    }
+
+    /// Sets whether removed dot command lines are left as blank lines
+    pub fn blank_dot_cmds(mut self, blank: bool) -> Self {
+        self.options.blank_dot_cmds = blank;
+        self
+    }
+
+    /// Sets whether damaged dot commands (e.g. with a stray control
+    /// character or space inside) are repaired rather than left as text
+    pub fn lenient_dot_cmds(mut self, lenient: bool) -> Self {
+        self.options.lenient_dot_cmds = lenient;
+        self
+    }
+
+    /// Sets whether deviations from the WordStar format fail the conversion
+    /// (`Mode::Strict`) rather than being recovered (`Mode::Permissive`, the
+    /// default), in which case lenient repairs are not made
+    pub fn mode(mut self, mode: Mode) -> Self {
+        self.options.mode = mode;
+        self
+    }
+
+    /// Sets the template for header and footer text (e.g. "> {text}"), in
+    /// which "{text}" is replaced by the text, or `None` to wrap the text in
+    /// underline characters (an empty template removes the text)
+    pub fn header_template(mut self, template: Option<&str>) -> Self {
+        self.options.header_template = template.map(str::to_string);
+        self
+    }
+
+    /// Sets how any suffix (i.e. a trailing comment or continuation text)
+    /// after the text of a dot command is kept
+    pub fn dot_cmd_suffix(mut self, mode: SuffixMode) -> Self {
+        self.options.dot_cmd_suffix = mode;
+        self
+    }
+
+    /// Sets the character set (Unicode, Latin-1 or ASCII) into which text
+    /// output is transliterated
+    pub fn charset(mut self, charset: Charset) -> Self {
+        self.options.charset = charset;
+        self
+    }
+
+    /// Sets the points (page breaks or top-level headings) at which the
+    /// output of `Converter::convert_split()` is split into parts
+    pub fn split(mut self, split: Split) -> Self {
+        self.options.split = split;
+        self
+    }
+
+    /// Adds a custom wrapper for a control character
+    pub fn wrapper(mut self, wrapper: CustomWrapper) -> Self {
+        self.options.wrappers.push(wrapper);
+        self
+    }
+
+    /// Sets the style used to render overlined text
+    pub fn overline_style(mut self, style: OverlineStyle) -> Self {
+        self.options.overline_style = style;
+        self
+    }
+
+    /// Sets the style used to render underlined text
+    pub fn underline_style(mut self, style: UnderlineStyle) -> Self {
+        self.options.render.underline_style = style;
+        self
+    }
+
+    /// Sets the representation of page breaks in plain text output
+    pub fn page_break(mut self, style: PageBreakStyle) -> Self {
+        self.options.render.page_break = style;
+        self
+    }
+
+    /// Sets the modification time (in seconds since the Unix epoch) recorded
+    /// in output formats that record one (e.g. EPUB), instead of the current
+    /// time, so that the output is the same for the same input and settings
+    pub fn modified(mut self, secs: Option<u64>) -> Self {
+        self.options.render.modified = secs;
+        self
+    }
+
+    /// Sets whether overline sequences with counts that differ by one are
+    /// accepted
+    pub fn lenient_overline(mut self, lenient: bool) -> Self {
+        self.options.lenient_overline = lenient;
+        self
+    }
+
+    /// Sets the maximum length (in characters) of an output line in text or
+    /// print output, beyond which a warning is logged
+    pub fn max_output_line(mut self, max: usize) -> Self {
+        self.options.max_output_line = Some(max);
+        self
+    }
+
+    /// Sets whether lines longer than the maximum output line length have
+    /// their line attributes marked up rather than given by combining
+    /// characters
+    pub fn long_line_markup(mut self, markup: bool) -> Self {
+        self.options.long_line_markup = markup;
+        self
+    }
+
+    /// Sets the style used to render fractions
+    pub fn fraction_style(mut self, style: FractionStyle) -> Self {
+        self.options.fraction_style = style;
+        self
+    }
+
+    /// Sets the conventions for numbers and units used to tidy up
+    /// temperatures and ordinal numbers, and for typed accents and
+    /// guillemets (none by default)
+    pub fn locale(mut self, locale: Locale) -> Self {
+        self.options.locale = locale;
+        self
+    }
+
+    /// Sets how trade mark, copyright and registered signs and superscripted
+    /// ordinal suffixes are converted (left unchanged by default)
+    pub fn symbols(mut self, symbols: Symbols) -> Self {
+        self.options.symbols = symbols;
+        self
+    }
+
+    /// Adds a custom fraction and the text that replaces it
+    pub fn fraction(mut self, fraction: CustomFraction) -> Self {
+        self.options.fractions.push(fraction);
+        self
+    }
+
+    /// Adds a custom special sequence and its replacement (e.g. from
+    /// `ws_special::read_rules()`)
+    pub fn special_rule(mut self, rule: SpecialRule) -> Self {
+        self.options.special_rules.push(rule);
+        self
+    }
+
+    /// Adds a handler that replaces the standard action for a dot command
+    /// (e.g. from `ws_dot_cmd::read_handlers()`), taking precedence over any
+    /// handler for the same command added before it
+    pub fn dot_cmd_handler(mut self, handler: DotCmdHandler) -> Self {
+        self.options.dot_cmd_handlers.push(handler);
+        self
+    }
+
+    /// Sets whether unknown control characters are kept rather than escaped
+    pub fn keep_unknown(mut self, keep: bool) -> Self {
+        self.options.keep_unknown = keep;
+        self
+    }
+
+    /// Adds a control character (e.g. a tab or form feed) to be passed
+    /// through untouched instead of being mapped, escaped or stripped
+    pub fn preserve_control(mut self, c: char) -> Self {
+        self.options.preserve_controls.push(c);
+        self
+    }
+
+    /// Sets whether the statistics on the conversion are left out of `stderr`
+    pub fn quiet(mut self, quiet: bool) -> Self {
+        self.options.quiet = quiet;
+        self
+    }
+
+    /// Sets a function to be called with each unrecognised control character
+    /// and its line number, returning `Some(replacement)` to replace it
+    pub fn on_unknown(mut self, handler: ControlHandler) -> Self {
+        self.options.unknown_handler = Some(handler);
+        self
+    }
+
+    /// Sets a function to be called with the text of each paragraph,
+    /// returning `Some(code)` for the language in which it is written, which
+    /// is recorded as a `lang` attribute in HTML (and EPUB) output and as
+    /// metadata in JSON output
+    pub fn detect_language(mut self, detector: LanguageDetector) -> Self {
+        self.options.render.language_detector = Some(detector);
+        self
+    }
+
+    /// Returns a `Converter` object with the collected settings
+    pub fn build(self) -> Converter {
+        Converter {
+            options: self.options,
+        }
+    }
+}
+
+// "CONVERTER" OBJECT
+
+/// Converts WordStar documents with a fixed set of options
+#[derive(Debug, Default, Clone)]
+pub struct Converter {
+    options: Options,
+}
+
+impl Converter {
+    /// Returns a new `ConverterBuilder` object with the default settings
+    ///
+    /// # Examples
+    /// ```
+    /// let converter = Converter::builder()
+    ///     .exclude(Filter::DotCmds)
+    ///     .output(Format::Html)
+    ///     .build();
+    /// ```
+    pub fn builder() -> ConverterBuilder {
+        Default::default()
+    }
+
+    /// Returns the `Options` used by the converter
+    pub fn options(&self) -> &Options {
+        &self.options
+    }
+
+    /// Converts a WordStar document from the given input to the given output
+    ///
+    /// Returns the statistics on the conversion (see `ConversionStats`) on
+    /// success or a `std::io::Error` type on failure
+    ///
+    /// # Arguments
+    ///
+    /// * `input` - Source of bytes that implements `Read` trait
+    /// * `output` - Destination for bytes that implements `Write` trait
+    ///
+    /// # Examples
+    /// ```
+    /// let mut input = io::stdin();
+    /// let mut output = io::stdout();
+    /// let stats = Converter::builder().build().convert(&mut input, &mut output).unwrap();
+    /// println!("{} lines converted", stats.lines);
+    /// ```
+    pub fn convert(
+        &self,
+        input: &mut dyn Read,
+        output: &mut dyn Write,
+    ) -> io::Result<ConversionStats> {
+        let mut original = Vec::new();
+        input.read_to_end(&mut original)?;
+        if asciify::is_clean(&original, self.options.filler) {
+            return ws_filters::transform_file(&mut &original[..], output, &self.options);
+        }
+        let mut intermediate = Vec::new();
+        asciify::convert_file(&mut &original[..], &mut intermediate, self.options.filler)?;
+        ws_filters::transform_file(&mut &intermediate[..], output, &self.options)
+    }
+
+    /// Converts a WordStar document from the given input in the same way as
+    /// `convert()`, but splits the output into parts as set by `split()`,
+    /// each written to the destination given by the `OutputSink`
+    ///
+    /// Returns the statistics on the conversion (see `ConversionStats`) on
+    /// success or a `std::io::Error` type on failure
+    ///
+    /// # Arguments
+    ///
+    /// * `input` - Source of bytes that implements `Read` trait
+    /// * `sink` - Destinations for the parts of the output
+    ///
+    /// # Examples
+    /// ```
+    /// let converter = Converter::builder().split(Split::Page).build();
+    /// let mut sink = MemorySink::new();
+    /// converter.convert_split(&mut "one\n.pa\ntwo\n".as_bytes(), &mut sink).unwrap();
+    /// assert_eq!(sink.parts.len(), 2);
+    /// ```
+    pub fn convert_split(
+        &self,
+        input: &mut dyn Read,
+        sink: &mut dyn OutputSink,
+    ) -> io::Result<ConversionStats> {
+        let mut original = Vec::new();
+        input.read_to_end(&mut original)?;
+        if asciify::is_clean(&original, self.options.filler) {
+            return ws_filters::transform_split(&mut &original[..], sink, &self.options);
+        }
+        let mut intermediate = Vec::new();
+        asciify::convert_file(&mut &original[..], &mut intermediate, self.options.filler)?;
+        ws_filters::transform_split(&mut &intermediate[..], sink, &self.options)
+    }
+
+    /// Returns the conversion of the given WordStar text, or a
+    /// `std::io::Error` type on failure (e.g. if the output format is not
+    /// a text format)
+    ///
+    /// # Arguments
+    ///
+    /// * `s` - WordStar text to be converted
+    ///
+    /// # Examples
+    /// ```
+    /// let converter = Converter::builder().build();
+    /// assert_eq!(converter.convert_str("a\x13b\x13c\n").unwrap(), "ab\u{332}c\n");
+    /// ```
+    pub fn convert_str(&self, s: &str) -> io::Result<String> {
+        let mut output = Vec::new();
+        self.convert(&mut s.as_bytes(), &mut output)?;
+        String::from_utf8(output).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+    }
+}
+
+// Unit tests
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sink::MemorySink;
+
+    #[test]
+    fn test_builder() {
+        let converter = Converter::builder()
+            .exclude(Filter::DotCmds)
+            .exclude(Filter::Overline)
+            .output(Format::Html)
+            .keep_unknown(true)
+            .preserve_control('\t')
+            .modified(Some(0))
+            .fraction(CustomFraction {
+                numerator: 5,
+                denominator: 6,
+                symbol: "\u{215A}".to_string(),
+            })
+            .detect_language(|_| None)
+            .build();
+        let options = converter.options();
+        assert_eq!(options.fractions.len(), 1);
+        assert_eq!(options.excludes, Excludes::DOT_CMDS | Excludes::OVERLINE);
+        assert_eq!(options.render.format, Format::Html);
+        assert!(options.keep_unknown);
+        assert_eq!(options.preserve_controls, ['\t']);
+        assert_eq!(options.render.modified, Some(0));
+        assert!(!options.detect_columns);
+        assert!(options.render.language_detector.is_some());
+    }
+
+    #[test]
+    fn test_convert_stats() {
+        let converter = Converter::builder().build();
+        assert!(converter.options().quiet);
+        let mut output = Vec::new();
+        let stats = converter
+            .convert(
+                &mut &b"one\n.mt 3\n.pa\na\x13b\x13c\x1B\x01\n"[..],
+                &mut output,
+            )
+            .unwrap();
+        assert_eq!(stats.lines, 4);
+        assert_eq!(stats.dot_cmds_removed, 1);
+        assert_eq!(stats.escapes_removed, 1);
+        assert!(stats.replacements.contains(&("Wrappers", 1)));
+        assert_eq!(stats.summary().lines, 4);
+    }
+
+    #[test]
+    fn test_convert_split() {
+        let input = "1. One\ntext\n\n1.1 Scope\n\n2. Two\nmore\n.pa\nend\n";
+        let converter = Converter::builder()
+            .output(Format::Html)
+            .outline(true)
+            .split(Split::H1)
+            .build();
+        let mut sink = MemorySink::new();
+        converter
+            .convert_split(&mut input.as_bytes(), &mut sink)
+            .unwrap();
+        assert_eq!(sink.parts.len(), 2);
+        let second = String::from_utf8(sink.parts.remove(1)).unwrap();
+        assert!(second.starts_with("<h1"));
+        let converter = Converter::builder().split(Split::Page).build();
+        let mut sink = MemorySink::new();
+        converter
+            .convert_split(&mut input.as_bytes(), &mut sink)
+            .unwrap();
+        assert_eq!(
+            sink.parts,
+            [
+                b"1. One\ntext\n\n1.1 Scope\n\n2. Two\nmore\n".to_vec(),
+                b"end\n".to_vec()
+            ]
+        );
+        let mut output = Vec::new();
+        converter
+            .convert(&mut input.as_bytes(), &mut output)
+            .unwrap();
+        assert!(output.starts_with(&sink.parts[0]) && output.ends_with(b"\nend\n"));
+    }
+
+    #[test]
+    fn test_convert_str() {
+        let converter = Converter::builder().build();
+        assert_eq!(
+            converter.convert_str("a\x13b\x13c\n").unwrap(),
+            "ab\u{332}c\n"
+        );
+        assert_eq!(
+            converter.convert_str("one\n.mt 3\ntwo\n").unwrap(),
+            "one\ntwo\n"
+        );
+        let converter = Converter::builder().exclude(Filter::DotCmds).build();
+        assert_eq!(converter.convert_str(".mt 3\n").unwrap(), ".mt 3\n");
+        let converter = Converter::builder()
+            .on_unknown(|c, _| (c == '\x05').then(|| "*".to_string()))
+            .build();
+        assert_eq!(converter.convert_str("a\x05b\x03\n").unwrap(), "a*b^C\n");
+        let converter = Converter::builder()
+            .input_format(InputFormat::NewWord)
+            .build();
+        assert_eq!(converter.convert_str("a\x1Db\x1D\n").unwrap(), "a^]b^]\n");
+        let converter = Converter::builder()
+            .output(Format::Html)
+            .outline(true)
+            .build();
+        assert_eq!(
+            converter
+                .convert_str("1. Aims\n\n1.1 Scope\ntext\n")
+                .unwrap(),
+            "<h1 id=\"1-aims\">1. Aims</h1>\n<h2 id=\"1-1-scope\">1.1 Scope</h2>\n<p>text</p>\n"
+        );
+        let converter = Converter::builder().lenient_dot_cmds(true).build();
+        assert_eq!(
+            converter
+                .convert_str("one\n.\x02mt 3\n.o p\ntwo\n")
+                .unwrap(),
+            "one\ntwo\n"
+        );
+        let converter = Converter::builder()
+            .header_template(Some("> {text}"))
+            .build();
+        assert_eq!(
+            converter.convert_str(".he Title\ntext\n").unwrap(),
+            "> Title\ntext\n"
+        );
+        let converter = Converter::builder().build();
+        assert_eq!(
+            converter
+                .convert_str(".he Title .. draft\n.lm 10 Dear Sir\n")
+                .unwrap(),
+            "T\u{332}i\u{332}t\u{332}l\u{332}e\u{332}\ndraft\nDear Sir\n"
+        );
+        let converter = Converter::builder()
+            .dot_cmd_suffix(SuffixMode::Drop)
+            .build();
+        assert_eq!(
+            converter.convert_str(".lm 10 Dear Sir\ntext\n").unwrap(),
+            "text\n"
+        );
+        let converter = Converter::builder()
+            .max_output_line(6)
+            .long_line_markup(true)
+            .build();
+        assert_eq!(
+            converter.convert_str("\x13ab\x13\n\x13abcd\x13\n").unwrap(),
+            "a\u{332}b\u{332}\n__abcd__\n"
+        );
+        let converter = Converter::builder().output(Format::Docx).build();
+        assert!(converter.convert_str("text\n").is_err());
+    }
+}
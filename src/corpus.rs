@@ -0,0 +1,237 @@
+//! Module to verify the conversion of a corpus of WordStar files against
+//! the expected outputs held alongside them
+
+use crate::asciify;
+use crate::ws_filters::{self, Options};
+use std::fmt;
+use std::fs::{self, File};
+use std::io;
+use std::path::{Path, PathBuf};
+
+const INPUT_EXTENSION: &str = "ws"; // Extension of input files (in any case)
+const EXPECTED_EXTENSION: &str = "expected"; // Extension of expected outputs
+
+/// Outcomes of comparing the conversion of a file with its expected output
+#[derive(Debug, PartialEq)]
+pub enum Outcome {
+    Passed,  // Conversion matches the expected output
+    Missing, // No expected output to compare against
+    Failed {
+        lines: usize,     // Number of lines that differ
+        first: usize,     // Line number (from 1) of the first difference
+        expected: String, // Expected text of the first line that differs
+        actual: String,   // Converted text of the first line that differs
+    },
+}
+
+/// Holds the name of a file in the corpus and the outcome of its comparison
+#[derive(Debug)]
+pub struct FileResult {
+    pub name: String,
+    pub outcome: Outcome,
+}
+
+/// Display trait implementation for FileResult, giving the outcome and the
+/// name of the file, followed by a summary of any differences
+impl fmt::Display for FileResult {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.outcome {
+            Outcome::Passed => write!(f, "PASSED  {}", self.name),
+            Outcome::Missing => write!(
+                f,
+                "MISSING {} (no .{} output)",
+                self.name, EXPECTED_EXTENSION
+            ),
+            Outcome::Failed {
+                lines,
+                first,
+                expected,
+                actual,
+            } => write!(
+                f,
+                "FAILED  {} ({} line(s) differ, first at line {})\n  -{}\n  +{}",
+                self.name, lines, first, expected, actual
+            ),
+        }
+    }
+}
+
+// PRIVATE HELPER FUNCTIONS
+
+/// Returns the paths of the WordStar files (with a ".ws" extension in any
+/// case) in the given directory, sorted by name
+///
+/// # Arguments
+///
+/// * `dir` - Path to directory holding the corpus
+///
+fn input_files(dir: &Path) -> io::Result<Vec<PathBuf>> {
+    let mut paths = Vec::new();
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        let is_input = path
+            .extension()
+            .is_some_and(|ext| ext.eq_ignore_ascii_case(INPUT_EXTENSION));
+        if is_input && path.is_file() {
+            paths.push(path);
+        }
+    }
+    paths.sort();
+    Ok(paths)
+}
+
+/// Returns the conversion of the given WordStar file with the given
+/// settings (without reporting any statistics) as text
+///
+/// # Arguments
+///
+/// * `path` - Path to WordStar file
+/// * `options` - Settings for filter exclusions and output rendering
+///
+fn convert(path: &Path, options: &Options) -> io::Result<String> {
+    let mut intermediate = Vec::new();
+    asciify::convert_file(&mut File::open(path)?, &mut intermediate, options.filler)?;
+    let mut output = Vec::new();
+    let options = Options {
+        quiet: true,
+        ..options.clone()
+    };
+    ws_filters::transform_file(&mut &intermediate[..], &mut output, &options)?;
+    Ok(String::from_utf8_lossy(&output).into_owned())
+}
+
+/// Returns the outcome of comparing the given converted text line by line
+/// with the given expected text
+///
+/// # Arguments
+///
+/// * `expected` - Slice of expected text
+/// * `actual` - Slice of converted text
+///
+/// # Examples
+/// ```
+/// assert_eq!(compare("a\nb\n", "a\nb\n"), Outcome::Passed);
+/// ```
+pub(crate) fn compare(expected: &str, actual: &str) -> Outcome {
+    let expected_lines: Vec<&str> = expected.lines().collect();
+    let actual_lines: Vec<&str> = actual.lines().collect();
+    let count = expected_lines.len().max(actual_lines.len());
+    let mut lines = 0;
+    let mut first = None;
+    for i in 0..count {
+        let pair = (expected_lines.get(i), actual_lines.get(i));
+        if pair.0 != pair.1 {
+            lines += 1;
+            first.get_or_insert((i, pair));
+        }
+    }
+    match first {
+        Some((i, (expected, actual))) => Outcome::Failed {
+            lines,
+            first: i + 1,
+            expected: expected.map_or("<end of file>", |s| s).to_string(),
+            actual: actual.map_or("<end of file>", |s| s).to_string(),
+        },
+        None if expected != actual => Outcome::Failed {
+            lines: 0,
+            first: count,
+            expected: "<line endings differ>".to_string(),
+            actual: "<line endings differ>".to_string(),
+        },
+        None => Outcome::Passed,
+    }
+}
+
+// EXTERNAL PUBLIC FUNCTIONS
+
+/// Returns the result of converting each of the WordStar files (with a
+/// ".ws" extension) in the given directory with the given settings and
+/// comparing the output with that held in the file of the same name with
+/// an ".expected" extension, or a `std::io::Error` type on failure
+///
+/// # Arguments
+///
+/// * `dir` - Path to directory holding the corpus
+/// * `options` - Settings for filter exclusions and output rendering
+///
+/// # Examples
+/// ```
+/// let results = corpus::verify(Path::new("corpus"), &Options::default()).unwrap();
+/// ```
+pub fn verify(dir: &Path, options: &Options) -> io::Result<Vec<FileResult>> {
+    let mut results = Vec::new();
+    for path in input_files(dir)? {
+        let name = path.file_name().unwrap_or_default().to_string_lossy();
+        let actual = convert(&path, options)?;
+        let outcome = match fs::read(path.with_extension(EXPECTED_EXTENSION)) {
+            Ok(expected) => compare(&String::from_utf8_lossy(&expected), &actual),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Outcome::Missing,
+            Err(e) => return Err(e),
+        };
+        results.push(FileResult {
+            name: name.into_owned(),
+            outcome,
+        });
+    }
+    Ok(results)
+}
+
+// Unit tests
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_compare() {
+        assert_eq!(compare("", ""), Outcome::Passed);
+        assert_eq!(
+            compare("a\nb\nc\n", "a\nB\nc\nd\n"),
+            Outcome::Failed {
+                lines: 2,
+                first: 2,
+                expected: "b".to_string(),
+                actual: "B".to_string()
+            }
+        );
+        assert_eq!(
+            compare("a\n", "a\nb\n"),
+            Outcome::Failed {
+                lines: 1,
+                first: 2,
+                expected: "<end of file>".to_string(),
+                actual: "b".to_string()
+            }
+        );
+        assert!(matches!(
+            compare("a\n", "a"),
+            Outcome::Failed { lines: 0, .. }
+        ));
+    }
+
+    #[test]
+    fn test_verify() {
+        let dir = tempfile::tempdir().unwrap();
+        let write = |name: &str, contents: &[u8]| fs::write(dir.path().join(name), contents);
+        write("one.ws", b"a\x13b\x13\n").unwrap();
+        write("one.expected", "ab\u{332}\n".as_bytes()).unwrap();
+        write("TWO.WS", b"c\n").unwrap();
+        write("TWO.expected", b"d\n").unwrap();
+        write("three.ws", b"e\n").unwrap();
+        write("notes.txt", b"f\n").unwrap();
+        let results = verify(dir.path(), &Options::default()).unwrap();
+        let names: Vec<&str> = results.iter().map(|r| r.name.as_str()).collect();
+        assert_eq!(names, ["TWO.WS", "one.ws", "three.ws"]);
+        assert!(matches!(
+            results[0].outcome,
+            Outcome::Failed { first: 1, .. }
+        ));
+        assert_eq!(results[1].outcome, Outcome::Passed);
+        assert_eq!(results[2].outcome, Outcome::Missing);
+        assert_eq!(
+            results[0].to_string(),
+            "FAILED  TWO.WS (1 line(s) differ, first at line 1)\n  -d\n  +c"
+        );
+        assert_eq!(results[1].to_string(), "PASSED  one.ws");
+    }
+}
@@ -0,0 +1,201 @@
+//! Module to write log records to an interactive `stderr` as humane
+//! diagnostics, with each warning about a line of input followed by an echo
+//! of that line and a marker under its offending span
+
+// The echoed line is the 7-bit intermediate text on which the filters act,
+// with each control character shown in caret notation (e.g. "^B" for bold),
+// so that the marker can point at toggles and controls that would otherwise
+// be invisible.  Where the line has no control characters, any dot command
+// at its start is marked instead.  Colors are written only if enabled for
+// the logger (e.g. not if NO_COLOR is set).
+
+use crate::log_file;
+use crate::ws_dot_cmd;
+use env_logger::fmt::{Color, Formatter};
+use log::{Level, Record};
+use std::io::{self, Write};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+
+const GUTTER: &str = " | "; // Separator between line number and echoed line
+const MARKER: char = '^'; // Character used to mark the offending span
+
+static ENABLED: AtomicBool = AtomicBool::new(false); // Whether lines are kept
+static SOURCE: Mutex<Option<(usize, String)>> = Mutex::new(None); // Current line
+
+// PRIVATE HELPER FUNCTIONS
+
+/// Returns `(echo, spans)` holding the given line with each control
+/// character shown in caret notation and the (start, end) character
+/// positions of the offending spans within the echo
+///
+/// # Arguments
+///
+/// * `s` - Line of input
+///
+/// # Examples
+/// ```
+/// assert_eq!(echo_line("a\x02b"), ("a^Bb".to_string(), vec![(1, 3)]));
+/// ```
+fn echo_line(s: &str) -> (String, Vec<(usize, usize)>) {
+    let mut echo = String::with_capacity(s.len() + 8);
+    let mut spans = Vec::new();
+    for c in s.chars() {
+        let start = echo.chars().count();
+        match c {
+            '\x00'..='\x1F' => {
+                echo.push('^');
+                echo.push((c as u8 + b'@') as char);
+                spans.push((start, start + 2));
+            }
+            '\x7F' => {
+                echo.push_str("^?");
+                spans.push((start, start + 2));
+            }
+            _ => echo.push(c),
+        }
+    }
+    if spans.is_empty() {
+        if let Some((cmd, _)) = ws_dot_cmd::split_dot_cmd(s) {
+            spans.push((0, 1 + cmd.chars().count()));
+        }
+    }
+    (echo, spans)
+}
+
+/// Returns new String holding the markers under the given spans
+///
+/// # Arguments
+///
+/// * `spans` - (start, end) character positions of spans, in order
+///
+/// # Examples
+/// ```
+/// assert_eq!(marker_line(&[(1, 3), (5, 6)]), " ^^  ^");
+/// ```
+fn marker_line(spans: &[(usize, usize)]) -> String {
+    let mut result = String::new();
+    for &(start, end) in spans {
+        let len = result.chars().count();
+        result.extend(std::iter::repeat_n(' ', start.saturating_sub(len)));
+        result.extend(std::iter::repeat_n(MARKER, end - start));
+    }
+    result
+}
+
+/// Returns the color in which the given log level is written
+///
+/// # Arguments
+///
+/// * `level` - Log level
+///
+fn level_color(level: Level) -> Color {
+    match level {
+        Level::Error => Color::Red,
+        Level::Warn => Color::Yellow,
+        Level::Info => Color::Green,
+        Level::Debug => Color::Blue,
+        Level::Trace => Color::Cyan,
+    }
+}
+
+// EXTERNAL PUBLIC FUNCTIONS
+
+/// Starts keeping each line of input given to `set_source()`, so that it
+/// can be echoed by `format()`
+pub fn enable() {
+    ENABLED.store(true, Ordering::Relaxed);
+}
+
+/// Keeps the given line of input (if enabled) for echoing by `format()`
+/// with any warning about it
+///
+/// # Arguments
+///
+/// * `line_no` - Number of the line in the input
+/// * `line` - Text of the line
+///
+/// # Examples
+/// ```
+/// diagnostics::set_source(3, "Some \x02bold text");
+/// ```
+pub fn set_source(line_no: usize, line: &str) {
+    if ENABLED.load(Ordering::Relaxed) {
+        *SOURCE.lock().unwrap() = Some((line_no, line.to_string()));
+    }
+}
+
+/// Writes the given log record to the given `env_logger` formatter as a
+/// colored diagnostic, followed (if the record is about the line kept by
+/// `set_source()`) by an echo of the line with its offending spans marked
+///
+/// Returns `()` on success or a `std::io::Error` type on failure
+///
+/// # Arguments
+///
+/// * `buf` - Formatter for the log record
+/// * `record` - Log record to be written
+///
+/// # Examples
+/// ```
+/// env_logger::builder().format(diagnostics::format).init();
+/// ```
+pub fn format(buf: &mut Formatter, record: &Record) -> io::Result<()> {
+    let mut level_style = buf.style();
+    level_style
+        .set_color(level_color(record.level()))
+        .set_bold(true);
+    let mut gutter_style = buf.style();
+    gutter_style.set_color(Color::Blue).set_bold(true);
+    let message = record.args().to_string();
+    writeln!(
+        buf,
+        "{}: {}",
+        level_style.value(record.level().as_str().to_lowercase()),
+        message
+    )?;
+
+    let source = SOURCE.lock().unwrap();
+    let (line_no, line) = match (log_file::split_line_no(&message), source.as_ref()) {
+        ((Some(line_no), _), Some((source_no, line))) if line_no == *source_no => (line_no, line),
+        _ => return Ok(()),
+    };
+    let (echo, spans) = echo_line(line);
+    let number = line_no.to_string();
+    let gutter = format!("{:width$}{}", "", GUTTER, width = number.len());
+    writeln!(buf, "{}{}", gutter_style.value(number + GUTTER), echo)?;
+    if !spans.is_empty() {
+        writeln!(
+            buf,
+            "{}{}",
+            gutter_style.value(gutter),
+            level_style.value(marker_line(&spans))
+        )?;
+    }
+    Ok(())
+}
+
+// Unit tests
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_echo_line() {
+        assert_eq!(echo_line("plain"), ("plain".to_string(), vec![]));
+        assert_eq!(echo_line("a\x02b"), ("a^Bb".to_string(), vec![(1, 3)]));
+        assert_eq!(
+            echo_line("\x13x\x7F"),
+            ("^Sx^?".to_string(), vec![(0, 2), (3, 5)])
+        );
+        assert_eq!(echo_line(".zz odd"), (".zz odd".to_string(), vec![(0, 3)]));
+    }
+
+    #[test]
+    fn test_marker_line() {
+        assert_eq!(marker_line(&[]), "");
+        assert_eq!(marker_line(&[(1, 3), (5, 6)]), " ^^  ^");
+        assert_eq!(marker_line(&[(0, 3)]), "^^^");
+    }
+}
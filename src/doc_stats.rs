@@ -0,0 +1,149 @@
+//! Module to maintain counts of words, characters, paragraphs and pages in
+//! the converted text of a document
+
+use std::fmt;
+
+// Range of Unicode combining marks (e.g. those added for underlining), which
+// are not counted as characters in their own right
+const COMBINING_MARKS: std::ops::RangeInclusive<char> = '\u{0300}'..='\u{036F}';
+
+/// Holds a name tag for a set of counters, a 'used' marker, whether the last
+/// line scanned was part of a paragraph and the counts so far
+#[derive(Debug)]
+pub struct DocStats {
+    tag: String,
+    used: bool,
+    in_paragraph: bool,
+    words: usize,
+    chars: usize,
+    paragraphs: usize,
+    page_breaks: usize,
+}
+
+/// Display trait implementation for DocStats, starting with the name tag,
+/// then giving each of the counts
+impl fmt::Display for DocStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: ", self.tag)?;
+        if !self.used {
+            write!(f, "None")
+        } else {
+            write!(
+                f,
+                "{} word(s), {} char(s), {} paragraph(s), {} page(s)",
+                self.words,
+                self.chars,
+                self.paragraphs,
+                self.pages()
+            )
+        }
+    }
+}
+
+impl DocStats {
+    /// Returns a set of counters with the given name tag
+    ///
+    /// # Arguments
+    ///
+    /// * `tag` - String containing name tag (moved into `DocStats` struct)
+    ///
+    /// # Examples
+    /// ```
+    /// let mut stats = DocStats::new("name".to_string());
+    /// ```
+    pub fn new(tag: String) -> DocStats {
+        DocStats {
+            tag,
+            used: false,
+            in_paragraph: false,
+            words: 0,
+            chars: 0,
+            paragraphs: 0,
+            page_breaks: 0,
+        }
+    }
+
+    /// Adds the words and characters of the given line of converted text to
+    /// the counts, starting a new paragraph if it follows a blank line
+    ///
+    /// Control characters and combining marks are not counted as characters.
+    ///
+    /// # Arguments
+    ///
+    /// * `line` - Slice of text for the line
+    ///
+    /// # Examples
+    /// ```
+    /// let mut stats = DocStats::new("name".to_string());
+    /// stats.scan("Two words");
+    /// ```
+    pub fn scan(&mut self, line: &str) {
+        let words = line.split_whitespace().count();
+        if words == 0 {
+            self.in_paragraph = false;
+        } else if !self.in_paragraph {
+            self.in_paragraph = true;
+            self.paragraphs += 1;
+        }
+        self.words += words;
+        self.chars += line
+            .chars()
+            .filter(|c| !c.is_control() && !COMBINING_MARKS.contains(c))
+            .count();
+        self.used = true;
+    }
+
+    /// Adds a page break to the counts
+    pub fn page_break(&mut self) {
+        self.page_breaks += 1;
+        self.used = true;
+    }
+
+    /// Returns the count of words
+    #[allow(dead_code)]
+    pub fn words(&self) -> usize {
+        self.words
+    }
+
+    /// Returns the count of pages (one more than the number of page breaks)
+    pub fn pages(&self) -> usize {
+        if self.used {
+            self.page_breaks + 1
+        } else {
+            0
+        }
+    }
+}
+
+// Unit tests
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_scan() {
+        let mut stats = DocStats::new("Document".to_string());
+        assert_eq!(stats.pages(), 0);
+        stats.scan("One two");
+        stats.scan("three\u{332}.");
+        stats.scan("  ");
+        stats.page_break();
+        stats.scan("\tfour\x0C");
+        assert_eq!(stats.words(), 4);
+        assert_eq!(stats.chars, 19);
+        assert_eq!(stats.paragraphs, 2);
+        assert_eq!(stats.pages(), 2);
+    }
+
+    #[test]
+    fn test_display() {
+        let mut stats = DocStats::new("Document".to_string());
+        assert_eq!(format!("{}", stats), "Document: None");
+        stats.scan("a b");
+        assert_eq!(
+            format!("{}", stats),
+            "Document: 2 word(s), 3 char(s), 1 paragraph(s), 1 page(s)"
+        );
+    }
+}
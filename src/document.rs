@@ -0,0 +1,402 @@
+//! Module to hold a converted WordStar document as a structure of blocks
+
+// The document is built from the events given by `ws_events::Parser`, so
+// the filters have already been applied to the text in each span.  Blank
+// lines separate paragraphs and are not held in the document.
+
+use crate::asciify;
+use crate::provenance::Provenance;
+use crate::render::LanguageDetector;
+use crate::ws_dot_cmd;
+use crate::ws_events::{Event, Parser, Tag};
+use crate::ws_filters::Options;
+use crate::ws_heading::Heading;
+use crate::ws_lines;
+use crate::ws_wrappers::{join_spans, Span, Style};
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::io::{self, Read};
+
+/// Holds a line of text as a series of styled spans
+pub type Line = Vec<Span>;
+
+/// Holds a single block of a document
+#[derive(Debug, PartialEq, Clone, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Block {
+    Paragraph(Vec<Line>),
+    Heading(Heading, Line),
+    PageBreak,
+}
+
+/// Holds a change of line spacing (set by a `.ls` dot command), which
+/// applies from the block with the given index onwards
+#[derive(Debug, PartialEq, Clone, Serialize)]
+pub struct SpacingChange {
+    pub block: usize,
+    pub spacing: usize,
+}
+
+/// Holds a document as a series of blocks, together with each change of
+/// its line spacing and its starting page number (if set by `.ls` and `.pn`
+/// dot commands), the language detected for each paragraph (by the index of
+/// its block) and the provenance of the conversion (if recorded)
+#[derive(Debug, Default, PartialEq, Clone, Serialize)]
+pub struct Document {
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub line_spacing: Vec<SpacingChange>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub start_page: Option<usize>,
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub languages: BTreeMap<usize, String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub provenance: Option<Provenance>,
+    pub blocks: Vec<Block>,
+}
+
+// PRIVATE HELPER FUNCTIONS
+
+/// Returns `true` if the given line has no text other than white space,
+/// otherwise `false`
+///
+/// # Arguments
+///
+/// * `line` - Line to be checked
+///
+fn is_blank(line: &[Span]) -> bool {
+    line.iter().all(|span| span.text.trim().is_empty())
+}
+
+impl Document {
+    /// Returns a new empty `Document` object
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Records the language found by the given detector (if any) for each
+    /// paragraph, keyed by the index of its block, with the lines of the
+    /// paragraph joined by spaces
+    ///
+    /// # Arguments
+    ///
+    /// * `detector` - Function that returns the language of a text slice
+    ///
+    /// # Examples
+    /// ```
+    /// document.detect_languages(|text| text.contains("le ").then(|| "fr".to_string()));
+    /// ```
+    pub fn detect_languages(&mut self, detector: LanguageDetector) {
+        self.languages = self
+            .blocks
+            .iter()
+            .enumerate()
+            .filter_map(|(index, block)| match block {
+                Block::Paragraph(lines) => {
+                    let lines: Vec<String> = lines.iter().map(|line| join_spans(line)).collect();
+                    detector(lines.join(" ").trim()).map(|lang| (index, lang))
+                }
+                _ => None,
+            })
+            .collect();
+    }
+
+    /// Returns a `Document` object built from the given events, or a
+    /// `std::io::Error` type if an event could not be read
+    ///
+    /// Dot commands are not acted upon (apart from recording the line
+    /// spacing and starting page number), so they are left to the filters.
+    ///
+    /// # Arguments
+    ///
+    /// * `events` - Iterator over events (e.g. a `ws_events::Parser`)
+    ///
+    /// # Examples
+    /// ```
+    /// let options = Options::default();
+    /// let parser = Parser::new(io::stdin().lock().lines(), &options, true);
+    /// let document = Document::from_events(parser).unwrap();
+    /// ```
+    pub fn from_events<E>(events: E) -> io::Result<Document>
+    where
+        E: Iterator<Item = io::Result<Event>>,
+    {
+        let mut builder = DocumentBuilder::new();
+        let mut heading = None;
+        let mut style = Style::empty();
+        let mut line = Vec::new();
+
+        for event in events {
+            match event? {
+                Event::Start(Tag::Line(_)) => line = Vec::new(),
+                Event::Start(Tag::Heading(detected)) => heading = Some(detected),
+                Event::Start(Tag::Style(flag)) => style.insert(flag),
+                Event::End(Tag::Style(flag)) => style.remove(flag),
+                Event::Text(text) => line.push(Span { style, text }),
+                Event::DotCommand { cmd, text, .. } => {
+                    if let Some(spacing) = ws_dot_cmd::line_spacing(&cmd, &text) {
+                        builder.set_line_spacing(spacing);
+                    }
+                    if let Some(number) = ws_dot_cmd::page_number(&cmd, &text) {
+                        builder.set_start_page(number);
+                    }
+                }
+                Event::End(Tag::Heading(_)) | Event::Comment { .. } => {}
+                Event::End(Tag::Line(_)) => {
+                    let line = std::mem::take(&mut line);
+                    match heading.take() {
+                        Some(heading) => builder.push_heading(heading, line),
+                        None => builder.push_line(line),
+                    }
+                }
+                Event::PageBreak(_) => builder.push_page_break(),
+            }
+        }
+        Ok(builder.finish())
+    }
+
+    /// Returns a `Document` object built from a WordStar document in the
+    /// given input, or a `std::io::Error` type on failure
+    ///
+    /// # Arguments
+    ///
+    /// * `input` - Source of bytes that implements `Read` trait
+    /// * `options` - Settings for filter exclusions
+    ///
+    /// # Examples
+    /// ```
+    /// let document = Document::parse(&mut io::stdin(), &Options::default()).unwrap();
+    /// ```
+    pub fn parse(input: &mut dyn Read, options: &Options) -> io::Result<Document> {
+        let mut intermediate = Vec::new();
+        asciify::convert_file(input, &mut intermediate, options.filler)?;
+        Document::from_events(Parser::new(
+            ws_lines::lines(&intermediate[..]),
+            options,
+            true,
+        ))
+    }
+}
+
+// "DOCUMENT BUILDER" OBJECT
+
+/// Holds a document under construction, together with the lines of the
+/// paragraph that is being collected
+#[derive(Debug, Default)]
+pub struct DocumentBuilder {
+    document: Document,
+    paragraph: Vec<Line>,
+}
+
+impl DocumentBuilder {
+    /// Returns a new `DocumentBuilder` object for an empty document
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Adds a line to the current paragraph, or ends the paragraph if the
+    /// line is blank
+    ///
+    /// # Arguments
+    ///
+    /// * `line` - Line to be added
+    ///
+    pub fn push_line(&mut self, line: Line) {
+        if is_blank(&line) {
+            self.end_paragraph();
+        } else {
+            self.paragraph.push(line);
+        }
+    }
+
+    /// Ends the current paragraph and adds a heading block
+    ///
+    /// # Arguments
+    ///
+    /// * `heading` - Level and plain text title of the heading
+    /// * `line` - Line holding the heading
+    ///
+    pub fn push_heading(&mut self, heading: Heading, line: Line) {
+        self.end_paragraph();
+        self.document.blocks.push(Block::Heading(heading, line));
+    }
+
+    /// Ends the current paragraph and adds a page break block
+    pub fn push_page_break(&mut self) {
+        self.end_paragraph();
+        self.document.blocks.push(Block::PageBreak);
+    }
+
+    /// Records a change of line spacing from the block now being collected
+    /// (or the next block if none is), replacing any other change for the
+    /// same block and ignoring a change to the spacing already in force
+    ///
+    /// # Arguments
+    ///
+    /// * `spacing` - Number of lines for each line of text (e.g. 2 for
+    ///   double spacing)
+    ///
+    pub fn set_line_spacing(&mut self, spacing: usize) {
+        let block = self.document.blocks.len();
+        let changes = &mut self.document.line_spacing;
+        if changes.last().is_some_and(|last| last.block == block) {
+            changes.pop();
+        }
+        if changes.last().is_none_or(|last| last.spacing != spacing) {
+            changes.push(SpacingChange { block, spacing });
+        }
+    }
+
+    /// Records the number of the first page of the document (if not already
+    /// set, so that the number set by the first `.pn` dot command is kept)
+    ///
+    /// # Arguments
+    ///
+    /// * `number` - Number of the first page
+    ///
+    pub fn set_start_page(&mut self, number: usize) {
+        self.document.start_page.get_or_insert(number);
+    }
+
+    /// Returns the completed document, ending the current paragraph
+    pub fn finish(mut self) -> Document {
+        self.end_paragraph();
+        self.document
+    }
+
+    /// Moves the lines of the current paragraph (if any) into a new block
+    fn end_paragraph(&mut self) {
+        if !self.paragraph.is_empty() {
+            let lines = std::mem::take(&mut self.paragraph);
+            self.document.blocks.push(Block::Paragraph(lines));
+        }
+    }
+}
+
+// Unit tests
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn span(style: Style, text: &str) -> Span {
+        Span {
+            style,
+            text: text.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_is_blank() {
+        assert!(is_blank(&[]));
+        assert!(is_blank(&[span(Style::BOLD, "  ")]));
+        assert!(!is_blank(&[span(Style::empty(), " a ")]));
+    }
+
+    #[test]
+    fn test_parse() {
+        let input = ".ls 2\n.pn 3\none \x02two\x02\nthree\n\n.pa\n.pn 9\n.ls 1\nfour\n";
+        let document = Document::parse(&mut input.as_bytes(), &Options::default()).unwrap();
+        assert_eq!(
+            document.line_spacing,
+            [
+                SpacingChange {
+                    block: 0,
+                    spacing: 2
+                },
+                SpacingChange {
+                    block: 2,
+                    spacing: 1
+                },
+            ]
+        );
+        assert_eq!(document.start_page, Some(3));
+        assert_eq!(
+            document.blocks,
+            vec![
+                Block::Paragraph(vec![
+                    vec![span(Style::empty(), "one "), span(Style::BOLD, "two")],
+                    vec![span(Style::empty(), "three")],
+                ]),
+                Block::PageBreak,
+                Block::Paragraph(vec![vec![span(Style::empty(), "four")]]),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_builder() {
+        let mut builder = DocumentBuilder::new();
+        builder.push_line(vec![span(Style::empty(), "a")]);
+        builder.push_line(vec![span(Style::empty(), " ")]);
+        builder.push_line(vec![span(Style::ITALIC, "b")]);
+        builder.set_line_spacing(2);
+        builder.push_page_break();
+        builder.set_line_spacing(3);
+        builder.set_line_spacing(2);
+        let document = builder.finish();
+        assert_eq!(
+            document.line_spacing,
+            [SpacingChange {
+                block: 1,
+                spacing: 2
+            }]
+        );
+        assert_eq!(
+            document.blocks,
+            vec![
+                Block::Paragraph(vec![vec![span(Style::empty(), "a")]]),
+                Block::Paragraph(vec![vec![span(Style::ITALIC, "b")]]),
+                Block::PageBreak,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_detect_languages() {
+        let input = "le chat \x02noir\x02\nest ici\n\n.pa\nthe cat\n";
+        let mut document = Document::parse(&mut input.as_bytes(), &Options::default()).unwrap();
+        document
+            .detect_languages(|text| (text == "le chat noir est ici").then(|| "fr".to_string()));
+        assert_eq!(document.languages, BTreeMap::from([(0, "fr".to_string())]));
+        assert!(serde_json::to_string(&document)
+            .unwrap()
+            .starts_with(r#"{"languages":{"0":"fr"},"blocks":"#));
+    }
+
+    #[test]
+    fn test_serialize() {
+        let mut document = Document {
+            line_spacing: Vec::new(),
+            start_page: None,
+            languages: BTreeMap::new(),
+            provenance: None,
+            blocks: vec![
+                Block::Paragraph(vec![vec![span(Style::BOLD | Style::ITALIC, "a")]]),
+                Block::PageBreak,
+            ],
+        };
+        assert_eq!(
+            serde_json::to_string(&document).unwrap(),
+            r#"{"blocks":[{"paragraph":[[{"style":["bold","italic"],"text":"a"}]]},"page_break"]}"#
+        );
+        document.line_spacing = vec![SpacingChange {
+            block: 1,
+            spacing: 2,
+        }];
+        document.start_page = Some(5);
+        assert!(serde_json::to_string(&document)
+            .unwrap()
+            .starts_with(r#"{"line_spacing":[{"block":1,"spacing":2}],"start_page":5,"blocks":"#));
+    }
+
+    #[test]
+    fn test_heading() {
+        let input = "\x02Introduction\x02\ntext\n";
+        let document = Document::parse(&mut input.as_bytes(), &Options::default()).unwrap();
+        assert!(matches!(document.blocks[0], Block::Heading(_, _)));
+        assert_eq!(
+            document.blocks[1],
+            Block::Paragraph(vec![vec![span(Style::empty(), "text")]])
+        );
+    }
+}
@@ -0,0 +1,194 @@
+//! Module to maintain a census of the WordStar dot commands encountered
+
+use crate::ws_dot_cmd::{self, InputFormat};
+use std::collections::BTreeMap;
+use std::fmt;
+
+/// Holds a name tag for a census, a 'used' marker, the input format against
+/// which commands are checked and a binary tree of counts for each (lower
+/// case) two character dot command
+#[derive(Debug)]
+pub struct DotCmdCount {
+    tag: String,
+    used: bool,
+    format: InputFormat,
+    counts: BTreeMap<String, i32>,
+}
+
+/// Display trait implementation for DotCmdCount, starting with the name tag,
+/// then listing each command (marked with '?' if not a known WordStar dot
+/// command for the input format) and its decimal count
+impl fmt::Display for DotCmdCount {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: ", self.tag)?;
+        if !self.used {
+            write!(f, "Skipped")?;
+        } else if self.counts.is_empty() {
+            write!(f, "None")?;
+        } else {
+            let mut previous = false;
+            for (key, value) in &self.counts {
+                if previous {
+                    write!(f, ", ")?;
+                }
+                let marker = if ws_dot_cmd::is_known(key, self.format) {
+                    ""
+                } else {
+                    "?"
+                };
+                write!(f, ".{}{}={}", key, marker, *value)?;
+                previous = true;
+            }
+            write!(
+                f,
+                " => {} command(s), {} type(s), {} unknown",
+                self.total(),
+                self.counts.len(),
+                self.unknown()
+            )?;
+        }
+        Ok(())
+    }
+}
+
+impl DotCmdCount {
+    /// Returns a census with the given name tag
+    ///
+    /// # Arguments
+    ///
+    /// * `tag` - String containing name tag (moved into `DotCmdCount` struct)
+    ///
+    /// # Examples
+    /// ```
+    /// let mut census = DotCmdCount::new("name".to_string());
+    /// ```
+    pub fn new(tag: String) -> DotCmdCount {
+        DotCmdCount {
+            tag,
+            used: false,
+            format: InputFormat::default(),
+            counts: BTreeMap::new(),
+        }
+    }
+
+    /// Returns this census checking commands against the dot commands
+    /// documented for the given input format (rather than for WordStar)
+    ///
+    /// # Arguments
+    ///
+    /// * `format` - Word processor that created the file
+    ///
+    /// # Examples
+    /// ```
+    /// let census = DotCmdCount::new("name".to_string()).with_format(InputFormat::NewWord);
+    /// ```
+    pub fn with_format(mut self, format: InputFormat) -> Self {
+        self.format = format;
+        self
+    }
+
+    /// Marks this census as used, even if no dot commands are counted
+    pub fn activate(&mut self) {
+        self.used = true;
+    }
+
+    /// Increments (by one) the count for the given dot command
+    ///
+    /// # Arguments
+    ///
+    /// * `cmd` - Slice of text holding the (lower case) command
+    ///
+    /// # Examples
+    /// ```
+    /// let mut census = DotCmdCount::new("name".to_string());
+    /// census.up("pa");
+    /// ```
+    pub fn up(&mut self, cmd: &str) {
+        let counter = self.counts.entry(cmd.to_string()).or_insert(0);
+        *counter += 1;
+        self.used = true;
+    }
+
+    /// Attempts to return the current count for the given dot command
+    ///
+    /// Returns `Some(i32)` if a count has been established for the given
+    /// command or `None` if not
+    ///
+    /// # Arguments
+    ///
+    /// * `cmd` - Slice of text holding the (lower case) command
+    ///
+    /// # Examples
+    /// ```
+    /// let mut census = DotCmdCount::new("name".to_string());
+    /// census.up("he");
+    /// assert_eq!(census.get("he"), Some(1));
+    /// ```
+    #[allow(dead_code)]
+    pub fn get(&self, cmd: &str) -> Option<i32> {
+        self.counts.get(cmd).copied()
+    }
+
+    /// Returns total count of all dot commands
+    pub fn total(&self) -> i32 {
+        self.counts.values().sum()
+    }
+
+    /// Returns total count of dot commands that are not known commands for the
+    /// input format
+    pub fn unknown(&self) -> i32 {
+        self.counts
+            .iter()
+            .filter(|(key, _)| !ws_dot_cmd::is_known(key, self.format))
+            .map(|(_, value)| value)
+            .sum()
+    }
+}
+
+// Unit tests
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_counts() {
+        let mut census = DotCmdCount::new("Census".to_string());
+        census.up("pa");
+        census.up("pa");
+        census.up("zz");
+        assert_eq!(census.get("pa"), Some(2));
+        assert_eq!(census.get("he"), None);
+        assert_eq!(census.total(), 3);
+        assert_eq!(census.unknown(), 1);
+    }
+
+    #[test]
+    fn test_with_format() {
+        let mut census = DotCmdCount::new("Census".to_string());
+        census.up("df");
+        assert_eq!(census.unknown(), 1);
+        census = census.with_format(InputFormat::NewWord);
+        assert_eq!(census.unknown(), 0);
+        assert_eq!(
+            format!("{}", census),
+            "Census: .df=1 => 1 command(s), 1 type(s), 0 unknown"
+        );
+    }
+
+    #[test]
+    fn test_display() {
+        let mut census = DotCmdCount::new("Census".to_string());
+        assert_eq!(format!("{}", census), "Census: Skipped");
+        census.activate();
+        assert_eq!(format!("{}", census), "Census: None");
+        census.up("mt");
+        census.up("he");
+        census.up("qq");
+        census.up("he");
+        assert_eq!(
+            format!("{}", census),
+            "Census: .he=2, .mt=1, .qq?=1 => 4 command(s), 3 type(s), 1 unknown"
+        );
+    }
+}
@@ -0,0 +1,148 @@
+//! Module to classify the outcome of a run of the `wsconvert` command line
+//! utility as an exit status, so that scripts can branch on its quality
+
+// The exit codes are:
+//
+//     0  Clean: every file was converted without any warnings
+//     1  Warnings: every file was converted, but problems were found in the
+//        conversion of some (whatever the log level)
+//     2  Failed: some files were not converted (or did not pass a check)
+//     3  Fatal: the run could not be completed
+//
+// A run that fails for some files (e.g. in a batch) returns an error made by
+// `files_failed()`, which is told apart from any other (fatal) error.
+
+use std::error::Error;
+use std::fmt;
+use std::io;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+static WARNINGS: AtomicUsize = AtomicUsize::new(0); // Problems found in conversions
+
+/// Error given when some (but not necessarily all) files were not converted
+/// or did not pass a check, while the run itself was completed
+#[derive(Debug)]
+struct FilesFailed(String);
+
+/// Display trait implementation for FilesFailed, giving the number of files
+/// that failed
+impl fmt::Display for FilesFailed {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl Error for FilesFailed {}
+
+/// Holds the outcome of a run, from best to worst
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum ExitStatus {
+    Clean,
+    Warnings,
+    Failed,
+    Fatal,
+}
+
+impl ExitStatus {
+    /// Returns the exit status for the given result of a run, during which
+    /// the given number of warnings were logged
+    ///
+    /// # Arguments
+    ///
+    /// * `result` - Result of the run
+    /// * `warnings` - Number of warnings (or errors) logged during the run
+    ///
+    /// # Examples
+    /// ```
+    /// assert_eq!(ExitStatus::from_result(&Ok(()), 2), ExitStatus::Warnings);
+    /// ```
+    pub fn from_result(result: &io::Result<()>, warnings: usize) -> Self {
+        match result {
+            Ok(()) if warnings == 0 => Self::Clean,
+            Ok(()) => Self::Warnings,
+            Err(e) if is_files_failed(e) => Self::Failed,
+            Err(_) => Self::Fatal,
+        }
+    }
+
+    /// Returns the exit code for the exit status
+    pub fn code(self) -> u8 {
+        match self {
+            Self::Clean => 0,
+            Self::Warnings => 1,
+            Self::Failed => 2,
+            Self::Fatal => 3,
+        }
+    }
+}
+
+// EXTERNAL PUBLIC FUNCTIONS
+
+/// Returns a new `std::io::Error` with the given message for a run that was
+/// completed but in which some files failed, giving an exit status of
+/// `ExitStatus::Failed` rather than `ExitStatus::Fatal`
+///
+/// # Arguments
+///
+/// * `message` - Description of the files that failed
+///
+/// # Examples
+/// ```
+/// let e = exit_status::files_failed("2 of 5 file(s) not converted".to_string());
+/// ```
+pub fn files_failed(message: String) -> io::Error {
+    io::Error::other(FilesFailed(message))
+}
+
+/// Adds the given number of problems found in a conversion (see
+/// `ConversionStats::warnings`) to the count for the run, so that the exit
+/// status does not depend on which warnings the log level shows
+///
+/// # Arguments
+///
+/// * `count` - Number of problems found
+///
+/// # Examples
+/// ```
+/// let stats = ws_filters::transform_file(&mut input, &mut output, &options)?;
+/// exit_status::add_warnings(stats.warnings);
+/// ```
+pub fn add_warnings(count: usize) {
+    WARNINGS.fetch_add(count, Ordering::Relaxed);
+}
+
+/// Returns the number of problems found in the conversions of the run so
+/// far (see `add_warnings()`)
+pub fn warnings() -> usize {
+    WARNINGS.load(Ordering::Relaxed)
+}
+
+/// Returns `true` if the given error was made by `files_failed()`, otherwise
+/// `false`
+///
+/// # Arguments
+///
+/// * `e` - Error to be checked
+///
+pub fn is_files_failed(e: &io::Error) -> bool {
+    e.get_ref().is_some_and(|inner| inner.is::<FilesFailed>())
+}
+
+// Unit tests
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_from_result() {
+        assert_eq!(ExitStatus::from_result(&Ok(()), 0), ExitStatus::Clean);
+        assert_eq!(ExitStatus::from_result(&Ok(()), 3), ExitStatus::Warnings);
+        let e = files_failed("1 of 2 file(s) not converted".to_string());
+        assert_eq!(e.to_string(), "1 of 2 file(s) not converted");
+        assert_eq!(ExitStatus::from_result(&Err(e), 0), ExitStatus::Failed);
+        let fatal = Err(io::Error::other("no such file"));
+        assert_eq!(ExitStatus::from_result(&fatal, 0), ExitStatus::Fatal);
+        assert_eq!(ExitStatus::Fatal.code(), 3);
+    }
+}
@@ -0,0 +1,70 @@
+//! Library of WordStar conversion functions used by the `wsconvert` command
+//! line utility, which can also be called directly (e.g. to convert text
+//! that is already held in memory)
+
+mod asciify;
+mod batch;
+pub mod bench;
+pub mod char_histogram;
+mod converter;
+mod corpus;
+pub mod diagnostics;
+mod doc_stats;
+pub mod document;
+mod dot_cmd_count;
+pub mod exit_status;
+pub mod log_file;
+mod profile;
+pub mod provenance;
+mod quality;
+pub mod render;
+mod render_docx;
+mod render_epub;
+mod render_groff;
+mod render_html;
+mod render_json;
+mod render_odt;
+mod render_pandoc;
+mod render_print;
+mod self_test;
+pub mod sink;
+pub mod strip;
+mod style_count;
+pub mod transliterate;
+mod uni_chars;
+mod walk;
+mod ws_align;
+mod ws_chars;
+mod ws_columns;
+mod ws_control;
+pub mod ws_dot_cmd;
+mod ws_escape;
+pub mod ws_events;
+pub mod ws_file;
+pub mod ws_filters;
+mod ws_heading;
+mod ws_lines;
+mod ws_mappings;
+pub mod ws_overline;
+mod ws_overprint;
+mod ws_pitch;
+pub mod ws_special;
+mod ws_string;
+mod ws_windows;
+pub mod ws_wrappers;
+
+pub use crate::asciify::{Converted, Filler};
+pub use crate::batch::{BatchSettings, Limits};
+pub use crate::char_histogram::{CharClasses, CharHistogram};
+pub use crate::converter::{Converter, ConverterBuilder, Filter};
+pub use crate::quality::Quality;
+pub use crate::render::{Format, LanguageDetector};
+pub use crate::sink::{OutputSink, Split};
+pub use crate::walk::WalkPolicy;
+pub use crate::ws_control::ControlHandler;
+pub use crate::ws_dot_cmd::InputFormat;
+pub use crate::ws_events::ConversionStats;
+pub use crate::ws_filters::{
+    convert_line, transform_file, transform_split, ConvertState, Excludes, Mode, Options,
+};
+pub use crate::ws_heading::RuledHeadings;
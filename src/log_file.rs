@@ -0,0 +1,400 @@
+//! Module to copy warnings (and errors) logged during a conversion to a log
+//! file, e.g. alongside the output file, as well as to the usual logger, and
+//! to format log records as JSON lines (e.g. for log pipelines)
+
+use log::kv::Key;
+use log::{Level, Log, Metadata, Record, SetLoggerError};
+use serde::Serialize;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+use std::sync::Mutex;
+
+const FILE_LEVEL: Level = Level::Warn; // Least serious level written to file
+
+static SINK: Mutex<Option<BufWriter<File>>> = Mutex::new(None); // Open log file
+static INPUT_SINK: Mutex<Option<BufWriter<File>>> = Mutex::new(None); // Log file for one input
+static INPUT: Mutex<Option<String>> = Mutex::new(None); // Name of input being converted
+
+const LINE_PREFIX: &str = "Line "; // Start of messages about a numbered line
+const CRATE_PREFIX: &str = "wsconvert::"; // Start of targets within this crate
+const CODE_KEY: &str = "code"; // Key of the code given for a kind of message
+const PLURAL_SUFFIX: &str = "(s)"; // Ending of words that may be plural
+
+/// Format of each log record written to `stderr` and to the log file
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum LogFormat {
+    #[default]
+    Text,
+    Json,
+}
+
+/// Holds the fields of a log record written as a JSON line
+#[derive(Debug, Serialize)]
+struct JsonRecord<'a> {
+    level: &'a str,
+    file: Option<String>, // Name of input being converted (if any)
+    line: Option<usize>,  // Line number (if the message is about a line)
+    filter: &'a str,      // Module that logged the record (e.g. "quality")
+    code: String,         // Code given for the kind of message (or made from it)
+    message: &'a str,     // Message without any line number
+}
+
+// "TEE LOGGER" OBJECT
+
+/// Holds the usual (`stderr`) logger, to which every log record that it
+/// accepts is passed before any warning (or error) is written to the log
+/// file and to the log file for the current input (if open)
+struct TeeLogger {
+    inner: env_logger::Logger,
+    format: LogFormat,
+}
+
+impl Log for TeeLogger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        self.inner.enabled(metadata) || metadata.level() <= FILE_LEVEL
+    }
+
+    fn log(&self, record: &Record) {
+        if self.inner.matches(record) {
+            self.inner.log(record);
+        }
+        if record.level() <= FILE_LEVEL {
+            for sink in [&SINK, &INPUT_SINK] {
+                if let Some(writer) = sink.lock().unwrap().as_mut() {
+                    let _ = match self.format {
+                        LogFormat::Text => writeln!(
+                            writer,
+                            "[{:<5} {}] {}",
+                            record.level(),
+                            record.target(),
+                            record.args()
+                        ),
+                        LogFormat::Json => writeln!(writer, "{}", to_json(record)),
+                    };
+                }
+            }
+        }
+    }
+
+    fn flush(&self) {
+        self.inner.flush();
+        for sink in [&SINK, &INPUT_SINK] {
+            if let Some(writer) = sink.lock().unwrap().as_mut() {
+                let _ = writer.flush();
+            }
+        }
+    }
+}
+
+// PRIVATE HELPER FUNCTIONS
+
+/// Returns `(line, rest)` holding the line number at the start of the given
+/// message (as "Line n: ...") and the rest of the message, or `(None, s)` if
+/// the message does not start with a line number
+///
+/// # Arguments
+///
+/// * `s` - Message to be split
+///
+/// # Examples
+/// ```
+/// assert_eq!(split_line_no("Line 3: unknown dot command"), (Some(3), "unknown dot command"));
+/// ```
+pub(crate) fn split_line_no(s: &str) -> (Option<usize>, &str) {
+    let split = s.strip_prefix(LINE_PREFIX).and_then(|rest| {
+        let end = rest.find(|c: char| !c.is_ascii_digit())?;
+        let line_no = rest[..end].parse().ok()?;
+        let rest = rest[end..].strip_prefix(':').unwrap_or(&rest[end..]);
+        Some((Some(line_no), rest.trim_start()))
+    });
+    split.unwrap_or((None, s))
+}
+
+/// Returns new String holding a code for the given message (without any
+/// line number) that has not been given one by its caller, made from its
+/// words in lower case joined by hyphens, leaving out any numbers, any "(s)"
+/// endings and any quoted text so that messages of the same kind share the
+/// same code
+///
+/// A quote only opens quoted text at the start of a word and only closes it
+/// at the end of one, so that an apostrophe (e.g. in a filename) is kept.
+///
+/// # Arguments
+///
+/// * `s` - Message (without any line number)
+///
+/// # Examples
+/// ```
+/// assert_eq!(message_code("2 unmatched toggle(s)"), "unmatched-toggle");
+/// ```
+fn message_code(s: &str) -> String {
+    let chars: Vec<char> = s.replace(PLURAL_SUFFIX, "").chars().collect();
+    let is_word = |i: Option<usize>| {
+        i.and_then(|i| chars.get(i))
+            .is_some_and(|c| c.is_alphanumeric())
+    };
+    let mut unquoted = String::with_capacity(s.len());
+    let mut quote = None; // Closing quote of any quoted text
+    for (i, &c) in chars.iter().enumerate() {
+        match quote {
+            Some(close) if c == close && !is_word(Some(i + 1)) => quote = None,
+            Some(_) => {}
+            None if (c == '"' || c == '\'') && !is_word(i.checked_sub(1)) => quote = Some(c),
+            None => unquoted.push(c),
+        }
+    }
+    let words = unquoted
+        .split(|c: char| !c.is_ascii_alphanumeric())
+        .filter(|word| !word.is_empty() && word.chars().all(|c| c.is_ascii_alphabetic()));
+    let words: Vec<String> = words.map(str::to_ascii_lowercase).collect();
+    words.join("-")
+}
+
+// EXTERNAL PUBLIC FUNCTIONS
+
+/// Returns new String holding the given log record as a single line of JSON
+/// with fields for its level, the input being converted (see `set_input()`),
+/// the line number (if any), the module that logged it (as "filter"), a
+/// stable code for the kind of message and the message itself
+///
+/// The code is the value given for the "code" key of the record (e.g.
+/// `log::warn!(code = "unknown-dot-command"; ...)`), so that it does not
+/// change if the message is reworded, or else is made from the message (see
+/// `message_code()`).
+///
+/// # Arguments
+///
+/// * `record` - Log record to be formatted
+///
+/// # Examples
+/// ```
+/// builder.format(|buf, record| writeln!(buf, "{}", log_file::to_json(record)));
+/// ```
+pub fn to_json(record: &Record) -> String {
+    let message = record.args().to_string();
+    let (line, message) = split_line_no(&message);
+    let target = record.target();
+    let json = JsonRecord {
+        level: record.level().as_str(),
+        file: INPUT.lock().unwrap().clone(),
+        line,
+        filter: target.strip_prefix(CRATE_PREFIX).unwrap_or(target),
+        code: record
+            .key_values()
+            .get(Key::from_str(CODE_KEY))
+            .map_or_else(|| message_code(message), |code| code.to_string()),
+        message,
+    };
+    serde_json::to_string(&json).unwrap_or_default()
+}
+
+/// Sets the name of the input being converted, for the "file" field of log
+/// records written as JSON (see `to_json()`)
+///
+/// # Arguments
+///
+/// * `name` - Name of input (or `None` if no input is being converted)
+///
+/// # Examples
+/// ```
+/// log_file::set_input(Some("letter.ws"));
+/// ```
+pub fn set_input(name: Option<&str>) {
+    *INPUT.lock().unwrap() = name.map(str::to_string);
+}
+
+/// Installs the given logger (e.g. from `env_logger::Builder::build()`) as
+/// the global logger, with warnings (and errors) also written to the log
+/// file in the given format while one is open
+///
+/// Returns `()` on success or a `log::SetLoggerError` type if a global
+/// logger has already been installed
+///
+/// # Arguments
+///
+/// * `inner` - Logger for all records that it accepts (e.g. to `stderr`)
+/// * `format` - Format of the records written to the log file
+///
+/// # Examples
+/// ```
+/// log_file::init(env_logger::builder().build(), LogFormat::Text).unwrap();
+/// ```
+pub fn init(inner: env_logger::Logger, format: LogFormat) -> Result<(), SetLoggerError> {
+    let max_level = inner.filter().max(FILE_LEVEL.to_level_filter());
+    log::set_boxed_logger(Box::new(TeeLogger { inner, format }))?;
+    log::set_max_level(max_level);
+    Ok(())
+}
+
+/// Creates (or truncates) the log file at the given path and writes any
+/// warnings (or errors) logged from now on to it, closing any log file that
+/// was already open
+///
+/// Returns `()` on success or a `std::io::Error` type on failure
+///
+/// # Arguments
+///
+/// * `path` - Path to log file
+///
+/// # Examples
+/// ```
+/// log_file::open(Path::new("output.log")).unwrap();
+/// ```
+pub fn open(path: &Path) -> io::Result<()> {
+    let writer = BufWriter::new(File::create(path)?);
+    if let Some(mut previous) = SINK.lock().unwrap().replace(writer) {
+        previous.flush()?;
+    }
+    Ok(())
+}
+
+/// Flushes and closes the log file (if open)
+///
+/// Returns `()` on success or a `std::io::Error` type on failure
+pub fn close() -> io::Result<()> {
+    match SINK.lock().unwrap().take() {
+        Some(mut writer) => writer.flush(),
+        None => Ok(()),
+    }
+}
+
+/// Creates (or truncates) the log file for the current input at the given
+/// path (e.g. beside its output in a batch) and writes any warnings (or
+/// errors) logged from now on to it as well as to the log file opened by
+/// `open()`, closing any log file for a previous input
+///
+/// Returns `()` on success or a `std::io::Error` type on failure
+///
+/// # Arguments
+///
+/// * `path` - Path to log file for the input
+///
+/// # Examples
+/// ```
+/// log_file::open_input(Path::new("letter.log")).unwrap();
+/// ```
+pub fn open_input(path: &Path) -> io::Result<()> {
+    let writer = BufWriter::new(File::create(path)?);
+    if let Some(mut previous) = INPUT_SINK.lock().unwrap().replace(writer) {
+        previous.flush()?;
+    }
+    Ok(())
+}
+
+/// Flushes and closes the log file for the current input (if open), leaving
+/// any log file opened by `open()` open
+///
+/// Returns `()` on success or a `std::io::Error` type on failure
+pub fn close_input() -> io::Result<()> {
+    match INPUT_SINK.lock().unwrap().take() {
+        Some(mut writer) => writer.flush(),
+        None => Ok(()),
+    }
+}
+
+// Unit tests
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use log::LevelFilter;
+
+    #[test]
+    fn test_open_and_close() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("test.log");
+        let logger = TeeLogger {
+            inner: env_logger::builder().filter_level(LevelFilter::Off).build(),
+            format: LogFormat::Text,
+        };
+        let record = |level, message| {
+            logger.log(
+                &Record::builder()
+                    .level(level)
+                    .target("test")
+                    .args(format_args!("{}", message))
+                    .build(),
+            )
+        };
+        let input_path = dir.path().join("input.log");
+        open(&path).unwrap();
+        record(Level::Warn, "Line 3: unknown dot command");
+        record(Level::Info, "Read 16 bytes");
+        open_input(&input_path).unwrap();
+        record(Level::Warn, "For input");
+        close_input().unwrap();
+        record(Level::Warn, "After input");
+        close().unwrap();
+        record(Level::Warn, "After close");
+        assert_eq!(
+            std::fs::read_to_string(&path).unwrap(),
+            "[WARN  test] Line 3: unknown dot command\n[WARN  test] For input\n\
+             [WARN  test] After input\n"
+        );
+        assert_eq!(
+            std::fs::read_to_string(&input_path).unwrap(),
+            "[WARN  test] For input\n"
+        );
+        assert!(logger.enabled(&Metadata::builder().level(Level::Error).build()));
+        assert!(!logger.enabled(&Metadata::builder().level(Level::Info).build()));
+    }
+
+    #[test]
+    fn test_split_line_no() {
+        assert_eq!(
+            split_line_no("Line 3: unknown dot command"),
+            (Some(3), "unknown dot command")
+        );
+        assert_eq!(
+            split_line_no("Line 12 longer than 80 bytes split at byte 80"),
+            (Some(12), "longer than 80 bytes split at byte 80")
+        );
+        assert_eq!(split_line_no("Lines split: 2"), (None, "Lines split: 2"));
+    }
+
+    #[test]
+    fn test_message_code() {
+        assert_eq!(message_code("2 unmatched toggle(s)"), "unmatched-toggle");
+        assert_eq!(
+            message_code("repaired dot command \".p a\""),
+            "repaired-dot-command"
+        );
+        assert_eq!(
+            message_code("Skipped 'a.ws': output too large"),
+            "skipped-output-too-large"
+        );
+        assert_eq!(
+            message_code("Filler of 5 bytes of 0xE5"),
+            "filler-of-bytes-of"
+        );
+        assert_eq!(
+            message_code("Skipped 'O'Brien.ws': directory already visited"),
+            "skipped-directory-already-visited"
+        );
+    }
+
+    #[test]
+    fn test_to_json() {
+        let record = |message, code: &[(&str, &str)]| {
+            to_json(
+                &Record::builder()
+                    .level(Level::Warn)
+                    .target("wsconvert::quality")
+                    .args(format_args!("{}", message))
+                    .key_values(&code)
+                    .build(),
+            )
+        };
+        let message = "Line 7: 2 unmatched toggle(s)";
+        let json: serde_json::Value =
+            serde_json::from_str(&record(message, &[("code", "toggle")])).unwrap();
+        assert_eq!(json["level"], "WARN");
+        assert_eq!(json["line"], 7);
+        assert_eq!(json["filter"], "quality");
+        assert_eq!(json["code"], "toggle");
+        assert_eq!(json["message"], "2 unmatched toggle(s)");
+        let json: serde_json::Value = serde_json::from_str(&record(message, &[])).unwrap();
+        assert_eq!(json["code"], "unmatched-toggle");
+    }
+}
@@ -1,34 +1,126 @@
 //! Main module of WordStar conversion command line utility
 
 mod args;
-mod asciify;
-mod control_count;
-mod uni_chars;
-mod ws_align;
-mod ws_chars;
-mod ws_control;
-mod ws_dot_cmd;
-mod ws_file;
-mod ws_filters;
-mod ws_mappings;
-mod ws_overline;
-mod ws_special;
-mod ws_string;
-mod ws_wrappers;
-
-use crate::args::Args;
-use std::io;
-
-/// Reads command line parameters, sets up logging and then calls
-/// `ws_file::process()` with any supplied parameters
-///
-fn main() -> io::Result<()> {
+
+use crate::args::{Args, Command};
+use std::io::{self, IsTerminal, Write};
+use std::path::Path;
+use std::process::ExitCode;
+use wsconvert::exit_status::ExitStatus;
+use wsconvert::log_file::LogFormat;
+use wsconvert::{diagnostics, exit_status, log_file, ws_dot_cmd, ws_file, ws_special};
+
+/// Reads command line parameters, sets up logging (with warnings also written
+/// to any log file), reads any rules file for additional special sequences
+/// and any handlers file for dot commands, and then calls
+/// `ws_file::process()` (or `ws_file::report()` for the "stats" subcommand,
+/// `ws_file::generate()` for the "generate" subcommand,
+/// `ws_file::verify_corpus()` for the "verify-corpus" subcommand,
+/// `ws_file::batch()` for the "batch" subcommand, `ws_file::asciify()` for
+/// the "asciify" subcommand, `ws_file::filters()` for the "filters"
+/// subcommand, `ws_file::self_test()` for the `--self-test` option or
+/// `ws_file::strip()` for the `--strip` option) with any supplied
+/// parameters, before exiting with a status that reflects the outcome (see
+/// `run_status()`)
+///
+fn main() -> ExitCode {
     let args = Args::parse();
+    let textconv = args.textconv;
+    let result = run(args);
+    if let Err(e) = &result {
+        eprintln!("Error: {}", e);
+    }
+    ExitCode::from(run_status(&result, textconv).code())
+}
+
+/// Returns the exit status for the given result of a run (see `ExitStatus`),
+/// counting the problems found in its conversions whatever the log level,
+/// but none at all for the `--textconv` option
+///
+/// # Arguments
+///
+/// * `result` - Result of the run
+/// * `textconv` - Flag for the `--textconv` option
+///
+fn run_status(result: &io::Result<()>, textconv: bool) -> ExitStatus {
+    // Git takes any non-zero status from a textconv driver as a failure
+    let warnings = if textconv { 0 } else { exit_status::warnings() };
+    ExitStatus::from_result(result, warnings)
+}
+
+/// Runs the utility with the given command line parameters as described for
+/// `main()`
+///
+/// Returns `()` on success or a `std::io::Error` type on failure
+///
+/// # Arguments
+///
+/// * `args` - Processed command line parameters
+///
+fn run(mut args: Args) -> io::Result<()> {
+    let mut builder = env_logger::builder();
+    builder.format_timestamp(None).filter_level(args.log_level);
+    if args.no_color {
+        builder.write_style(env_logger::WriteStyle::Never);
+    }
+    if args.log_format == LogFormat::Json {
+        builder.format(|buf, record| writeln!(buf, "{}", log_file::to_json(record)));
+    } else if io::stderr().is_terminal() {
+        diagnostics::enable();
+        builder.format(diagnostics::format);
+    }
+    log_file::init(builder.build(), args.log_format).map_err(io::Error::other)?;
+    if let Some(log) = &args.log_file {
+        log_file::open(Path::new(log))?;
+    }
+
+    if let Some(rules) = &args.rules {
+        args.options.special_rules = ws_special::read_rules(Path::new(rules))?;
+    }
+    if let Some(dot_cmds) = &args.dot_cmds {
+        args.options.dot_cmd_handlers = ws_dot_cmd::read_handlers(Path::new(dot_cmds))?;
+    }
+
+    let result = match args.command {
+        Command::Convert => ws_file::process(&args.infiles, &args.outfile, &args.options),
+        Command::Stats => ws_file::report(&args.infiles, &args.options),
+        Command::Generate {
+            size,
+            density,
+            features,
+        } => ws_file::generate(&args.outfile, size, density, features),
+        Command::VerifyCorpus { dir } => ws_file::verify_corpus(&dir, &args.options),
+        Command::Batch(settings) => ws_file::batch(&args.infiles, &settings, &args.options),
+        Command::Asciify => ws_file::asciify(&args.infiles, &args.outfile, &args.options),
+        Command::Filters => ws_file::filters(&args.infiles, &args.outfile, &args.options),
+        Command::SelfTest => ws_file::self_test(&args.infiles, &args.options),
+        Command::Strip => ws_file::strip(&args.infiles, &args.outfile, &args.options),
+    };
+    log_file::close()?;
+    result
+}
+
+// Unit tests
 
-    env_logger::builder()
-        .format_timestamp(None)
-        .filter_level(args.log_level)
-        .init();
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use wsconvert::ws_filters::Options;
 
-    ws_file::process(&args.infile, &args.outfile, Some(args.excludes))
+    #[test]
+    fn test_run_status() {
+        let dir = tempfile::tempdir().unwrap();
+        let infile = dir.path().join("doc.ws");
+        std::fs::write(&infile, ".zz unknown\ntext\n").unwrap();
+        let infiles = [infile.to_string_lossy().into_owned()];
+        let outfile = dir.path().join("doc.txt");
+        let options = Options {
+            quiet: true,
+            ..Default::default()
+        };
+        let result = ws_file::process(&infiles, &outfile.to_string_lossy(), &options);
+        assert!(result.is_ok());
+        assert_eq!(run_status(&result, false), ExitStatus::Warnings); // No logger
+        assert_eq!(run_status(&result, true), ExitStatus::Clean);
+    }
 }
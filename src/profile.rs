@@ -0,0 +1,170 @@
+//! Module to measure the time taken and lines changed by each pipeline stage
+
+use std::fmt;
+use std::time::{Duration, Instant};
+
+/// Holds the measurements for a single pipeline stage
+#[derive(Debug)]
+struct Stage {
+    name: &'static str,
+    time: Duration,
+    lines: u32,
+    changed: u32,
+}
+
+/// Holds the measurements for each pipeline stage, in the order in which the
+/// stages were first recorded
+#[derive(Debug, Default)]
+pub struct Profile {
+    stages: Vec<Stage>,
+}
+
+/// Display trait implementation for Profile, giving a table with a row for
+/// each stage (time, share of the total time, lines processed and lines
+/// changed) followed by a row of totals
+impl fmt::Display for Profile {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let total = self.total_time();
+        writeln!(
+            f,
+            "{:<10} {:>10} {:>6} {:>8} {:>8}",
+            "Stage", "Time (ms)", "Share", "Lines", "Changed"
+        )?;
+        for stage in &self.stages {
+            let share = if total.is_zero() {
+                0.0
+            } else {
+                stage.time.as_secs_f64() * 100.0 / total.as_secs_f64()
+            };
+            writeln!(
+                f,
+                "{:<10} {:>10.3} {:>5.1}% {:>8} {:>8}",
+                stage.name,
+                stage.time.as_secs_f64() * 1000.0,
+                share,
+                stage.lines,
+                stage.changed
+            )?;
+        }
+        write!(f, "{:<10} {:>10.3}", "Total", total.as_secs_f64() * 1000.0)
+    }
+}
+
+impl Profile {
+    /// Returns a new `Profile` object with no stages recorded
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Adds the time since the given start time to the named stage and
+    /// counts one line processed (and changed if `changed` is `true`)
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - Name of the stage
+    /// * `start` - Time at which the stage started processing the line
+    /// * `changed` - Flag indicating whether the stage changed the line
+    ///
+    /// # Examples
+    /// ```
+    /// let mut profile = Profile::new();
+    /// let start = Instant::now();
+    /// profile.record("Stage", start, true);
+    /// ```
+    pub fn record(&mut self, name: &'static str, start: Instant, changed: bool) {
+        self.add(name, start.elapsed(), changed);
+    }
+
+    /// Adds the given time to the named stage and counts one line processed
+    /// (and changed if `changed` is `true`)
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - Name of the stage
+    /// * `time` - Time taken by the stage to process the line
+    /// * `changed` - Flag indicating whether the stage changed the line
+    ///
+    fn add(&mut self, name: &'static str, time: Duration, changed: bool) {
+        let index = match self.stages.iter().position(|stage| stage.name == name) {
+            Some(index) => index,
+            None => {
+                self.stages.push(Stage {
+                    name,
+                    time: Duration::default(),
+                    lines: 0,
+                    changed: 0,
+                });
+                self.stages.len() - 1
+            }
+        };
+        let stage = &mut self.stages[index];
+        stage.time += time;
+        stage.lines += 1;
+        stage.changed += changed as u32;
+    }
+
+    /// Returns the total time recorded for all stages
+    pub fn total_time(&self) -> Duration {
+        self.stages.iter().map(|stage| stage.time).sum()
+    }
+
+    /// Returns the name of each stage that changed any lines, with the count
+    /// of lines that it changed, in the order in which the stages were first
+    /// recorded
+    pub fn changes(&self) -> Vec<(&'static str, u32)> {
+        self.stages
+            .iter()
+            .filter(|stage| stage.changed > 0)
+            .map(|stage| (stage.name, stage.changed))
+            .collect()
+    }
+
+    /// Returns the total count of lines changed by all stages (so a line
+    /// changed by several stages is counted once for each)
+    pub fn total_changed(&self) -> u32 {
+        self.stages.iter().map(|stage| stage.changed).sum()
+    }
+}
+
+// Unit tests
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_add() {
+        let mut profile = Profile::new();
+        profile.add("One", Duration::from_millis(3), true);
+        profile.add("Two", Duration::from_millis(1), false);
+        profile.add("One", Duration::from_millis(4), false);
+        assert_eq!(profile.stages.len(), 2);
+        assert_eq!(profile.stages[0].lines, 2);
+        assert_eq!(profile.stages[0].changed, 1);
+        assert_eq!(profile.total_time(), Duration::from_millis(8));
+        assert_eq!(profile.total_changed(), 1);
+        assert_eq!(profile.changes(), vec![("One", 1)]);
+    }
+
+    #[test]
+    fn test_display() {
+        let mut profile = Profile::new();
+        profile.add("One", Duration::from_millis(3), true);
+        profile.add("Two", Duration::from_millis(1), false);
+        assert_eq!(
+            format!("{}", profile),
+            "Stage       Time (ms)  Share    Lines  Changed\n\
+             One             3.000  75.0%        1        1\n\
+             Two             1.000  25.0%        1        0\n\
+             Total           4.000"
+        );
+    }
+
+    #[test]
+    fn test_record() {
+        let mut profile = Profile::new();
+        profile.record("Stage", Instant::now(), false);
+        assert_eq!(profile.stages[0].changed, 0);
+        assert_eq!(format!("{}", Profile::new()).lines().count(), 2);
+    }
+}
@@ -0,0 +1,220 @@
+//! Module to record the provenance of a conversion (the tool and options
+//! used, the checksums of the inputs and the date) for archival records
+
+// An archive needs to document how each of its derived files was made, so
+// the provenance can be embedded in the output where the format allows it
+// (as `<meta>` elements in a complete HTML document, a comment at the end of
+// text or an HTML fragment, or a field of the JSON document model) or else
+// written as JSON to a sidecar file beside the output.
+
+use crate::batch;
+use crate::render_epub;
+use serde::Serialize;
+use std::io;
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+const STDIN_NAME: &str = "-"; // Input filename that selects `stdin`
+const SIDECAR_SUFFIX: &str = ".provenance.json"; // Added to output filename for sidecar
+
+/// Places where the provenance of a conversion is recorded
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub enum Placement {
+    #[default]
+    Embed, // In the output, if its format allows
+    Sidecar, // In a JSON file beside the output
+}
+
+/// Holds the name of an input and its SHA-256 checksum (as lower case hex),
+/// which is `None` for `stdin`
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct InputDigest {
+    pub name: String,
+    pub sha256: Option<String>,
+}
+
+/// Holds the provenance of a conversion
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Provenance {
+    #[serde(skip)]
+    pub placement: Placement,
+    pub tool: String,
+    pub version: String,
+    pub arguments: Vec<String>,
+    pub date: String,
+    pub inputs: Vec<InputDigest>,
+}
+
+impl Provenance {
+    /// Returns a new `Provenance` object for this version of the tool with
+    /// the given command line arguments (or other description of the options
+    /// used), dated at the given time (e.g. for reproducible output) or else
+    /// at the current time, and with no inputs
+    ///
+    /// # Arguments
+    ///
+    /// * `placement` - Place where the provenance is to be recorded
+    /// * `arguments` - Arguments (options and inputs) given to the tool
+    /// * `date` - Optional time of the conversion (in seconds since the Unix
+    ///   epoch)
+    ///
+    /// # Examples
+    /// ```
+    /// let args = vec!["-f".to_string(), "html".to_string()];
+    /// let provenance = Provenance::new(Placement::Embed, args, Some(0));
+    /// assert_eq!(provenance.date, "1970-01-01T00:00:00Z");
+    /// ```
+    pub fn new(placement: Placement, arguments: Vec<String>, date: Option<u64>) -> Self {
+        let secs = date.unwrap_or_else(|| {
+            SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map_or(0, |d| d.as_secs())
+        });
+        Provenance {
+            placement,
+            tool: env!("CARGO_PKG_NAME").to_string(),
+            version: env!("CARGO_PKG_VERSION").to_string(),
+            arguments,
+            date: render_epub::utc_timestamp(secs),
+            inputs: Vec::new(),
+        }
+    }
+
+    /// Adds the given input files with their checksums, or returns a
+    /// `std::io::Error` type if one of them cannot be read
+    ///
+    /// An input filename of "-" (or no input files at all) is recorded as
+    /// "-" for `stdin`, without a checksum.
+    ///
+    /// # Arguments
+    ///
+    /// * `infiles` - Paths to input files (or none for `stdin`)
+    ///
+    pub fn add_inputs(&mut self, infiles: &[String]) -> io::Result<()> {
+        let stdin = [STDIN_NAME.to_string()];
+        let infiles = if infiles.is_empty() { &stdin } else { infiles };
+        for infile in infiles {
+            let sha256 = match infile.as_str() {
+                STDIN_NAME => None,
+                _ => Some(batch::checksum(Path::new(infile))?.1),
+            };
+            self.inputs.push(InputDigest {
+                name: infile.clone(),
+                sha256,
+            });
+        }
+        Ok(())
+    }
+
+    /// Returns the provenance as pretty-printed JSON (e.g. for a sidecar)
+    pub fn to_json(&self) -> io::Result<String> {
+        Ok(serde_json::to_string_pretty(self)?)
+    }
+
+    /// Returns the name/content pairs of the `<meta>` elements that record
+    /// the provenance in the head of an HTML document (not yet escaped)
+    pub fn meta(&self) -> Vec<(&'static str, String)> {
+        let mut meta = vec![
+            ("generator", format!("{} {}", self.tool, self.version)),
+            ("dcterms.created", self.date.clone()),
+            ("wsconvert-arguments", self.arguments.join(" ")),
+        ];
+        for input in &self.inputs {
+            let content = match &input.sha256 {
+                Some(digest) => format!("{} sha256:{}", input.name, digest),
+                None => input.name.clone(),
+            };
+            meta.push(("wsconvert-input", content));
+        }
+        meta
+    }
+
+    /// Returns the provenance as the text of a comment, one item to a line,
+    /// which contains no "--" sequence so that it can be written as an HTML
+    /// (or Markdown) comment
+    pub fn comment(&self) -> String {
+        self.meta()
+            .iter()
+            .map(|(name, content)| format!("{}: {}\n", name, content.replace("--", "- -")))
+            .collect()
+    }
+}
+
+// EXTERNAL PUBLIC FUNCTIONS
+
+/// Returns the name of the sidecar file for the provenance of the given
+/// output file
+///
+/// # Arguments
+///
+/// * `outfile` - Path to output file
+///
+/// # Examples
+/// ```
+/// assert_eq!(sidecar_name("book.html"), "book.html.provenance.json");
+/// ```
+pub fn sidecar_name(outfile: &str) -> String {
+    format!("{}{}", outfile, SIDECAR_SUFFIX)
+}
+
+// Unit tests
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample() -> Provenance {
+        let args = vec!["--format".to_string(), "html".to_string()];
+        Provenance::new(Placement::Embed, args, Some(86_400))
+    }
+
+    #[test]
+    fn test_new() {
+        let provenance = sample();
+        assert_eq!(provenance.tool, "wsconvert");
+        assert_eq!(provenance.version, env!("CARGO_PKG_VERSION"));
+        assert_eq!(provenance.date, "1970-01-02T00:00:00Z");
+        assert!(provenance.inputs.is_empty());
+    }
+
+    #[test]
+    fn test_add_inputs() {
+        let dir = tempfile::tempdir().unwrap();
+        let input = dir.path().join("in.ws");
+        std::fs::write(&input, "abc").unwrap();
+        let mut provenance = sample();
+        let infiles = [input.to_str().unwrap().to_string(), "-".to_string()];
+        provenance.add_inputs(&infiles).unwrap();
+        assert_eq!(
+            provenance.inputs[0].sha256.as_deref(),
+            Some("ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad")
+        );
+        assert_eq!(provenance.inputs[1].sha256, None);
+        assert!(provenance.add_inputs(&["missing.ws".to_string()]).is_err());
+        let mut provenance = sample();
+        provenance.add_inputs(&[]).unwrap();
+        assert_eq!(provenance.inputs[0].name, "-");
+    }
+
+    #[test]
+    fn test_comment() {
+        let mut provenance = sample();
+        provenance.add_inputs(&[]).unwrap();
+        assert_eq!(
+            provenance.comment(),
+            format!(
+                "generator: wsconvert {}\ndcterms.created: 1970-01-02T00:00:00Z\n\
+                 wsconvert-arguments: - -format html\nwsconvert-input: -\n",
+                env!("CARGO_PKG_VERSION")
+            )
+        );
+    }
+
+    #[test]
+    fn test_to_json() {
+        let json: serde_json::Value = serde_json::from_str(&sample().to_json().unwrap()).unwrap();
+        assert_eq!(json["arguments"][1], "html");
+        assert!(json.get("placement").is_none());
+        assert_eq!(sidecar_name("a.txt"), "a.txt.provenance.json");
+    }
+}
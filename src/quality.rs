@@ -0,0 +1,272 @@
+//! Module to maintain a summary of the problems found in a conversion and a
+//! quality score derived from them
+
+use crate::ws_wrappers::Style;
+use std::collections::HashSet;
+use std::fmt;
+
+/// Holds counts of each kind of problem found in a conversion, the number of
+/// lines, the set of lines with problems, the
+/// wrapper attributes already counted as left unmatched and whether problems
+/// are logged as errors (in strict mode) rather than warnings
+#[derive(Debug, Default)]
+pub struct Quality {
+    strict: bool,
+    unmatched_toggles: usize,
+    unconverted_controls: usize,
+    unknown_dot_cmds: usize,
+    lossy_fallbacks: usize,
+    lines: usize,
+    problem_lines: HashSet<usize>,
+    leaked: Style,
+}
+
+/// Display trait implementation for Quality, giving the score followed by
+/// the count of each kind of problem
+impl fmt::Display for Quality {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Quality: {}% => {} unmatched toggle(s), {} unconverted control(s), \
+             {} unknown dot command(s), {} lossy fallback(s) in {} of {} line(s)",
+            self.score(),
+            self.unmatched_toggles,
+            self.unconverted_controls,
+            self.unknown_dot_cmds,
+            self.lossy_fallbacks,
+            self.problem_lines.len(),
+            self.lines
+        )
+    }
+}
+
+impl Quality {
+    /// Returns a new `Quality` object with no problems counted
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Returns the `Quality` object with each problem logged as an error if
+    /// `strict` is `true`, rather than as a warning
+    pub fn with_strict(mut self, strict: bool) -> Self {
+        self.strict = strict;
+        self
+    }
+
+    /// Logs the given description of a problem in the given line with the
+    /// given code for its kind (e.g. for JSON log records), as an error in
+    /// strict mode or otherwise as a warning
+    ///
+    /// # Arguments
+    ///
+    /// * `line_no` - Number of the line in the input
+    /// * `code` - Stable code for the kind of problem
+    /// * `description` - Description of the problem
+    ///
+    fn log(&self, line_no: usize, code: &str, description: fmt::Arguments) {
+        if self.strict {
+            log::error!(code = code; "Line {}: {}", line_no, description);
+        } else {
+            log::warn!(code = code; "Line {}: {}", line_no, description);
+        }
+    }
+
+    /// Counts the given line (numbered from 1) as converted
+    pub fn line(&mut self, line_no: usize) {
+        self.lines = self.lines.max(line_no);
+    }
+
+    /// Counts the given line (numbered from 1) as having a problem, unless it
+    /// has been counted already
+    ///
+    /// # Arguments
+    ///
+    /// * `line_no` - Number of the line in the input
+    ///
+    fn problem(&mut self, line_no: usize) {
+        self.line(line_no);
+        self.problem_lines.insert(line_no);
+    }
+
+    /// Counts the text attributes in the given style that have been left
+    /// switched on (e.g. at the end of a paragraph) as unmatched toggles,
+    /// unless they have already been counted
+    ///
+    /// # Arguments
+    ///
+    /// * `style` - Set of text attributes left switched on
+    /// * `line_no` - Number of the line in the input
+    ///
+    /// # Examples
+    /// ```
+    /// let mut quality = Quality::new();
+    /// quality.unmatched(Style::BOLD, 12);
+    /// ```
+    pub fn unmatched(&mut self, style: Style, line_no: usize) {
+        let added = (style - self.leaked).bits().count_ones() as usize;
+        if added > 0 {
+            self.log(
+                line_no,
+                "unmatched-toggle",
+                format_args!("{} unmatched toggle(s)", added),
+            );
+            self.unmatched_toggles += added;
+            self.problem(line_no);
+        }
+        self.leaked = style;
+    }
+
+    /// Counts the text attributes in the given style that are still switched
+    /// on at the end of the document as unmatched toggles (against the last
+    /// line), unless they have already been counted
+    pub fn finish(&mut self, style: Style) {
+        self.unmatched(style, self.lines);
+    }
+
+    /// Adds the given number of control characters left unconverted in the
+    /// given line
+    pub fn unconverted(&mut self, count: usize, line_no: usize) {
+        if count > 0 {
+            self.log(
+                line_no,
+                "unconverted-control",
+                format_args!("{} unconverted control(s)", count),
+            );
+            self.unconverted_controls += count;
+            self.problem(line_no);
+        }
+    }
+
+    /// Adds a dot command in the given line that is not a known WordStar
+    /// dot command
+    pub fn unknown_dot_cmd(&mut self, line_no: usize) {
+        self.log(
+            line_no,
+            "unknown-dot-command",
+            format_args!("unknown dot command"),
+        );
+        self.unknown_dot_cmds += 1;
+        self.problem(line_no);
+    }
+
+    /// Adds a damaged dot command in the given line that was not repaired
+    /// (e.g. with a control character before the dot), which is counted as
+    /// an unknown dot command
+    pub fn malformed_dot_cmd(&mut self, line_no: usize) {
+        self.log(
+            line_no,
+            "malformed-dot-command",
+            format_args!("malformed dot command"),
+        );
+        self.unknown_dot_cmds += 1;
+        self.problem(line_no);
+    }
+
+    /// Adds the given number of characters in the given line whose text
+    /// attributes could not be represented
+    pub fn lossy(&mut self, count: usize, line_no: usize) {
+        if count > 0 {
+            self.log(
+                line_no,
+                "lossy-fallback",
+                format_args!("{} lossy fallback(s)", count),
+            );
+            self.lossy_fallbacks += count;
+            self.problem(line_no);
+        }
+    }
+
+    /// Returns the number of lines converted
+    pub fn lines(&self) -> usize {
+        self.lines
+    }
+
+    /// Returns the total count of problems of all kinds
+    pub fn problems(&self) -> usize {
+        self.unmatched_toggles
+            + self.unconverted_controls
+            + self.unknown_dot_cmds
+            + self.lossy_fallbacks
+    }
+
+    /// Returns the quality score as the percentage (rounded down) of lines
+    /// without problems, or 100 if there are no lines
+    pub fn score(&self) -> usize {
+        (self.lines.saturating_sub(self.problem_lines.len()) * 100)
+            .checked_div(self.lines)
+            .unwrap_or(100)
+    }
+}
+
+// Unit tests
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_score() {
+        let mut quality = Quality::new();
+        assert_eq!(quality.score(), 100);
+        quality.unconverted(0, 1);
+        quality.line(1);
+        quality.unconverted(2, 2);
+        quality.lossy(1, 2);
+        quality.line(2);
+        quality.unknown_dot_cmd(3);
+        assert_eq!(quality.problems(), 4);
+        assert_eq!(quality.lines(), 3);
+        assert_eq!(quality.score(), 33);
+    }
+
+    #[test]
+    fn test_score_repeated_lines() {
+        let mut quality = Quality::new();
+        quality.line(4);
+        quality.unconverted(1, 1);
+        quality.unconverted(1, 3);
+        quality.unconverted(1, 1);
+        quality.unconverted(1, 3);
+        quality.lossy(1, 1);
+        assert_eq!(quality.problems(), 5);
+        assert_eq!(quality.score(), 50);
+        assert!(quality.to_string().ends_with("in 2 of 4 line(s)"));
+    }
+
+    #[test]
+    fn test_unmatched() {
+        let mut quality = Quality::new();
+        quality.unmatched(Style::BOLD | Style::ITALIC, 1);
+        quality.unmatched(Style::BOLD | Style::ITALIC, 2);
+        quality.unmatched(Style::BOLD, 3);
+        quality.unmatched(Style::BOLD | Style::ITALIC, 4);
+        quality.unmatched(Style::empty(), 5);
+        quality.line(5);
+        assert_eq!(quality.problems(), 3);
+        assert_eq!(quality.score(), 60);
+        quality.finish(Style::SUBSCRIPT);
+        assert_eq!(quality.problems(), 4);
+        assert_eq!(quality.score(), 40);
+    }
+
+    #[test]
+    fn test_malformed() {
+        let mut quality = Quality::new().with_strict(true);
+        quality.malformed_dot_cmd(2);
+        quality.unknown_dot_cmd(2);
+        assert_eq!(quality.problems(), 2);
+        assert_eq!(quality.score(), 50);
+    }
+
+    #[test]
+    fn test_display() {
+        let mut quality = Quality::new();
+        quality.unmatched(Style::UNDERLINE, 1);
+        quality.line(2);
+        assert_eq!(
+            format!("{}", quality),
+            "Quality: 50% => 1 unmatched toggle(s), 0 unconverted control(s), \
+             0 unknown dot command(s), 0 lossy fallback(s) in 1 of 2 line(s)"
+        );
+    }
+}
@@ -0,0 +1,812 @@
+//! Module to render converted lines of text into the chosen output format
+
+use crate::provenance::{Placement, Provenance};
+use crate::render_docx::DocxRenderer;
+use crate::render_epub::EpubRenderer;
+use crate::render_groff::GroffRenderer;
+use crate::render_html::{ContentsRenderer, DocumentRenderer, HtmlRenderer};
+use crate::render_json::JsonRenderer;
+use crate::render_odt::OdtRenderer;
+use crate::render_print::PrintRenderer;
+use crate::ws_dot_cmd;
+use crate::ws_heading::Heading;
+use crate::ws_wrappers::{self, Span, UnderlineStyle};
+use std::io::{self, Write};
+
+/// Output formats that can be produced from the converted text
+///
+/// There is no separate Markdown format: `Text` output is also the Markdown
+/// output, as its page breaks ("---"), comments and any setext rulings for
+/// headings are written in Markdown syntax.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub enum Format {
+    #[default]
+    Text,
+    Html,
+    Epub,
+    Docx,
+    Odt,
+    Groff,
+    Print,
+    Json,
+    Pandoc,
+}
+
+impl Format {
+    /// Returns the usual file extension for output in this format (e.g. for
+    /// naming the output files of a batch conversion)
+    pub fn extension(self) -> &'static str {
+        match self {
+            Format::Text => "txt",
+            Format::Html => "html",
+            Format::Epub => "epub",
+            Format::Docx => "docx",
+            Format::Odt => "odt",
+            Format::Groff => "groff",
+            Format::Print => "prn",
+            Format::Json | Format::Pandoc => "json",
+        }
+    }
+
+    /// Returns `true` if output in this format is text (rather than an
+    /// archive of files), otherwise `false`
+    pub fn is_text(self) -> bool {
+        !matches!(self, Format::Epub | Format::Docx | Format::Odt)
+    }
+
+    /// Returns `true` if the provenance of a conversion can be embedded in
+    /// output in this format, otherwise `false`
+    pub fn embeds_provenance(self) -> bool {
+        matches!(self, Format::Text | Format::Html | Format::Json)
+    }
+
+    /// Returns `true` if the given control character (e.g. one preserved
+    /// by `--preserve-controls`) can be written in output in this format,
+    /// otherwise `false` (as it is shown in its escaped '^' form instead)
+    ///
+    /// XML does not allow most control characters, but DOCX and ODT output
+    /// write tabs and form feeds as tab stops and page breaks.
+    pub fn writes_control(self, c: char) -> bool {
+        match self {
+            Format::Html | Format::Epub => matches!(c, '\t' | '\n' | '\r'),
+            Format::Docx | Format::Odt => matches!(c, '\t' | '\n' | '\r' | '\x0C'),
+            _ => true,
+        }
+    }
+}
+
+/// Representations of a page break in plain text output (other formats use
+/// their own form of page break)
+#[derive(Debug, Default, Clone, PartialEq)]
+pub enum PageBreakStyle {
+    #[default]
+    Bars, // Line of horizontal bar characters
+    Markdown,       // Markdown thematic break ("---")
+    Html,           // HTML horizontal rule ("<hr />")
+    FormFeed,       // Form feed control character
+    Custom(String), // Given text
+}
+
+impl PageBreakStyle {
+    /// Returns the line of text that represents a page break in this style
+    pub fn text(&self) -> String {
+        match self {
+            PageBreakStyle::Bars => ws_dot_cmd::page_break_text(),
+            PageBreakStyle::Markdown => "---".to_string(),
+            PageBreakStyle::Html => "<hr />".to_string(),
+            PageBreakStyle::FormFeed => "\x0C".to_string(),
+            PageBreakStyle::Custom(text) => text.clone(),
+        }
+    }
+}
+
+/// Type for a function that is given the text of a paragraph and returns the
+/// code of the language in which it is written (e.g. "en" or "fr"), if this
+/// can be detected, for recording as a `lang` attribute or as metadata
+pub type LanguageDetector = fn(&str) -> Option<String>;
+
+/// Settings that affect how converted lines are rendered
+#[derive(Debug, Default, Clone)]
+pub struct RenderOptions {
+    pub format: Format,
+    pub line_comments: bool,
+    pub line_numbers: bool,
+    pub pitch_class: Option<String>,
+    pub toc: bool,
+    pub sentences: bool,
+    pub standalone: bool,
+    pub css: Option<String>,
+    pub embed_css: bool,
+    pub underline_style: UnderlineStyle,
+    pub page_break: PageBreakStyle,
+    pub modified: Option<u64>,
+    pub language_detector: Option<LanguageDetector>,
+    pub provenance: Option<Provenance>,
+}
+
+/// Common interface for objects that write converted lines in a given format
+///
+/// Each line is supplied after all of the filters have been applied, along
+/// with its (1-based) line number in the original input so that renderers
+/// can refer back to the source if required.
+pub trait Renderer {
+    /// Writes any preamble needed before the first line
+    fn begin(&mut self, _output: &mut dyn Write) -> io::Result<()> {
+        Ok(())
+    }
+
+    /// Writes (or buffers) a single converted line
+    fn line(&mut self, output: &mut dyn Write, line: &str, line_no: usize) -> io::Result<()>;
+
+    /// Returns `true` if this renderer needs lines to be supplied as styled
+    /// spans via `styled_line()` rather than with text attributes already
+    /// mapped to Unicode characters, otherwise `false`
+    fn wants_spans(&self) -> bool {
+        false
+    }
+
+    /// Writes (or buffers) a single converted line supplied as styled spans,
+    /// by default ignoring the styles and treating it as an ordinary line
+    fn styled_line(
+        &mut self,
+        output: &mut dyn Write,
+        spans: &[Span],
+        line_no: usize,
+    ) -> io::Result<()> {
+        self.line(output, &ws_wrappers::join_spans(spans), line_no)
+    }
+
+    /// Writes a converted line that has been detected as a heading, by
+    /// default treating it as an ordinary line
+    fn heading(
+        &mut self,
+        output: &mut dyn Write,
+        line: &str,
+        _heading: &Heading,
+        line_no: usize,
+    ) -> io::Result<()> {
+        self.line(output, line, line_no)
+    }
+
+    /// Offers a dot command (given as a lower case command and the text that
+    /// follows it) to the renderer before it is processed by the filters
+    ///
+    /// Returns `true` if the renderer has consumed the dot command, in which
+    /// case its line is removed from the output, or `false` (the default) to
+    /// leave it to the filters.
+    fn dot_command(
+        &mut self,
+        _output: &mut dyn Write,
+        _cmd: &str,
+        _text: &str,
+        _line_no: usize,
+    ) -> io::Result<bool> {
+        Ok(false)
+    }
+
+    /// Writes a comment (e.g. a suffix kept from a dot command) that is not
+    /// part of the text, by default leaving it out for formats without them
+    fn comment(&mut self, _output: &mut dyn Write, _text: &str, _line_no: usize) -> io::Result<()> {
+        Ok(())
+    }
+
+    /// Writes a page break, by default as a line of horizontal bars
+    fn page_break(&mut self, output: &mut dyn Write, line_no: usize) -> io::Result<()> {
+        self.line(output, &ws_dot_cmd::page_break_text(), line_no)
+    }
+
+    /// Writes any buffered content and postamble after the last line
+    fn end(&mut self, _output: &mut dyn Write) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+// "TEXT" RENDERER
+
+/// Renderer that writes each converted line unchanged as plain text,
+/// optionally prefixed with its line number in the original input, and
+/// followed by blank lines for line spacing set by `.ls` (e.g. one for
+/// double spacing)
+#[derive(Default, Debug)]
+pub struct TextRenderer {
+    line_numbers: bool,
+    spacing: usize,
+    page_break: PageBreakStyle,
+}
+
+impl TextRenderer {
+    /// Creates a new `TextRenderer` object, with each line prefixed by its
+    /// input line number (e.g. "0123| ") if `line_numbers` is `true`
+    pub fn new(line_numbers: bool) -> Self {
+        TextRenderer {
+            line_numbers,
+            spacing: 1,
+            ..Default::default()
+        }
+    }
+
+    /// Sets the representation of each page break
+    pub fn with_page_break(mut self, page_break: PageBreakStyle) -> Self {
+        self.page_break = page_break;
+        self
+    }
+}
+
+impl Renderer for TextRenderer {
+    fn line(&mut self, output: &mut dyn Write, line: &str, line_no: usize) -> io::Result<()> {
+        let blanks = self.spacing.saturating_sub(1);
+        for text in std::iter::once(line).chain(std::iter::repeat_n("", blanks)) {
+            if self.line_numbers {
+                writeln!(output, "{}", numbered(text, line_no))?;
+            } else {
+                writeln!(output, "{}", text)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn dot_command(
+        &mut self,
+        _output: &mut dyn Write,
+        cmd: &str,
+        text: &str,
+        _line_no: usize,
+    ) -> io::Result<bool> {
+        match ws_dot_cmd::line_spacing(cmd, text) {
+            Some(spacing) => {
+                self.spacing = spacing;
+                Ok(true)
+            }
+            None => Ok(false),
+        }
+    }
+
+    fn page_break(&mut self, output: &mut dyn Write, line_no: usize) -> io::Result<()> {
+        let text = self.page_break.text();
+        self.line(output, &text, line_no)
+    }
+}
+
+// "SENTENCE" RENDERER
+
+/// Renderer that reflows each paragraph of plain text so that every
+/// sentence starts on a new line, which keeps the differences between
+/// revisions of a document small when it is held under version control
+///
+/// The indentation of the first line of a paragraph (beyond any left margin
+/// set by a `.po` dot command) is kept, while headings and page breaks are
+/// written unchanged on lines of their own.  If line
+/// numbers are enabled, each sentence is prefixed with the input line number
+/// of the start of its paragraph.
+#[derive(Default, Debug)]
+pub struct SentenceRenderer {
+    line_numbers: bool,
+    page_break: PageBreakStyle,
+    page_offset: usize,
+    indent: String,
+    words: Vec<String>,
+    first_line_no: usize,
+}
+
+impl SentenceRenderer {
+    /// Creates a new `SentenceRenderer` object, with each line prefixed by an
+    /// input line number (e.g. "0123| ") if `line_numbers` is `true`
+    pub fn new(line_numbers: bool) -> Self {
+        SentenceRenderer {
+            line_numbers,
+            ..Default::default()
+        }
+    }
+
+    /// Sets the representation of each page break
+    pub fn with_page_break(mut self, page_break: PageBreakStyle) -> Self {
+        self.page_break = page_break;
+        self
+    }
+
+    /// Writes the given line, prefixed with the given input line number if
+    /// line numbers are enabled
+    fn write_line(&self, output: &mut dyn Write, line: &str, line_no: usize) -> io::Result<()> {
+        if self.line_numbers {
+            writeln!(output, "{}", numbered(line, line_no))
+        } else {
+            writeln!(output, "{}", line)
+        }
+    }
+
+    /// Writes the words held for the current paragraph (if any) with one
+    /// sentence per line and then clears them
+    ///
+    /// # Arguments
+    ///
+    /// * `output` - Destination for bytes that implements `Write` trait
+    ///
+    fn flush_paragraph(&mut self, output: &mut dyn Write) -> io::Result<()> {
+        for sentence in split_sentences(&self.words) {
+            let line = format!("{}{}", self.indent, sentence);
+            self.write_line(output, &line, self.first_line_no)?;
+        }
+        self.words.clear();
+        Ok(())
+    }
+}
+
+impl Renderer for SentenceRenderer {
+    fn line(&mut self, output: &mut dyn Write, line: &str, line_no: usize) -> io::Result<()> {
+        if line.trim().is_empty() {
+            self.flush_paragraph(output)?;
+            return self.write_line(output, line, line_no);
+        }
+        if self.words.is_empty() {
+            let line = ws_dot_cmd::strip_page_offset(line, self.page_offset);
+            let text = line.trim_start();
+            self.indent = line[..line.len() - text.len()].to_string();
+            self.first_line_no = line_no;
+        }
+        self.words
+            .extend(line.split_whitespace().map(str::to_string));
+        Ok(())
+    }
+
+    fn dot_command(
+        &mut self,
+        _output: &mut dyn Write,
+        cmd: &str,
+        text: &str,
+        _line_no: usize,
+    ) -> io::Result<bool> {
+        if let Some(offset) = ws_dot_cmd::page_offset(cmd, text) {
+            self.page_offset = offset;
+        }
+        Ok(false) // Leave the dot command to the filters
+    }
+
+    fn heading(
+        &mut self,
+        output: &mut dyn Write,
+        line: &str,
+        _heading: &Heading,
+        line_no: usize,
+    ) -> io::Result<()> {
+        self.flush_paragraph(output)?;
+        self.write_line(output, line, line_no)
+    }
+
+    fn page_break(&mut self, output: &mut dyn Write, line_no: usize) -> io::Result<()> {
+        self.flush_paragraph(output)?;
+        self.write_line(output, &self.page_break.text(), line_no)
+    }
+
+    fn end(&mut self, output: &mut dyn Write) -> io::Result<()> {
+        self.flush_paragraph(output)
+    }
+}
+
+// PRIVATE HELPER FUNCTIONS
+
+/// Returns the given line prefixed with the given input line number (padded
+/// to at least four digits) and a bar, for proofreading against the input
+///
+/// # Arguments
+///
+/// * `line` - Converted line of text
+/// * `line_no` - Line number of the line in the original input
+///
+/// # Examples
+/// ```
+/// assert_eq!(numbered("text", 123), "0123| text");
+/// ```
+fn numbered(line: &str, line_no: usize) -> String {
+    if line.is_empty() {
+        format!("{:04}|", line_no)
+    } else {
+        format!("{:04}| {}", line_no, line)
+    }
+}
+
+/// Returns `true` if the given word ends a sentence, i.e. ends in a full
+/// stop, question mark or exclamation mark (possibly followed by closing
+/// quotes or brackets), otherwise `false`
+fn ends_sentence(word: &str) -> bool {
+    word.trim_end_matches(['"', '\'', ')', ']', '\u{201D}', '\u{2019}'])
+        .ends_with(['.', '?', '!'])
+}
+
+/// Returns `true` if the given word can start a sentence, i.e. starts with
+/// an upper case letter or a digit (possibly after opening quotes or
+/// brackets), otherwise `false`
+///
+/// This prevents a break after abbreviations such as "e.g." when followed
+/// by a lower case word.
+fn starts_sentence(word: &str) -> bool {
+    word.trim_start_matches(['"', '\'', '(', '[', '\u{201C}', '\u{2018}'])
+        .chars()
+        .next()
+        .is_some_and(|c| c.is_uppercase() || c.is_ascii_digit())
+}
+
+/// Returns the given words of a paragraph joined into sentences, with each
+/// sentence ending after a word that ends a sentence and is followed by a
+/// word that can start one
+///
+/// # Arguments
+///
+/// * `words` - Slice of the words of a paragraph in order
+///
+/// # Examples
+/// ```
+/// let words = vec!["One.".to_string(), "Two".to_string()];
+/// assert_eq!(split_sentences(&words), vec!["One.", "Two"]);
+/// ```
+fn split_sentences(words: &[String]) -> Vec<String> {
+    let mut sentences = Vec::new();
+    let mut sentence = String::new();
+    for (i, word) in words.iter().enumerate() {
+        if !sentence.is_empty() {
+            sentence.push(' ');
+        }
+        sentence.push_str(word);
+        let next = words.get(i + 1);
+        if next.is_none() || (ends_sentence(word) && next.is_some_and(|w| starts_sentence(w))) {
+            sentences.push(std::mem::take(&mut sentence));
+        }
+    }
+    sentences
+}
+
+/// Returns the provenance given in the options if it is to be embedded in
+/// the output, otherwise `None`
+///
+/// # Arguments
+///
+/// * `options` - Settings for the renderer to be created
+///
+fn embedded_provenance(options: &RenderOptions) -> Option<Provenance> {
+    options
+        .provenance
+        .clone()
+        .filter(|provenance| provenance.placement == Placement::Embed)
+}
+
+/// Returns a boxed `Renderer` object for the format given in the options,
+/// without any provenance comment
+///
+/// # Arguments
+///
+/// * `options` - Settings for the renderer to be created
+///
+fn format_renderer(options: &RenderOptions) -> Box<dyn Renderer> {
+    match options.format {
+        Format::Text if options.sentences => Box::new(
+            SentenceRenderer::new(options.line_numbers).with_page_break(options.page_break.clone()),
+        ),
+        Format::Text => Box::new(
+            TextRenderer::new(options.line_numbers).with_page_break(options.page_break.clone()),
+        ),
+        Format::Html => {
+            let html = HtmlRenderer::new(options.line_comments)
+                .with_line_numbers(options.line_numbers)
+                .with_language_detector(options.language_detector)
+                .with_pitch_class(options.pitch_class.clone())
+                .with_classes(options.embed_css || options.css.is_some())
+                .with_underline_style(options.underline_style);
+            let (css, embed_css) = (options.css.clone(), options.embed_css);
+            match (options.toc, options.standalone) {
+                (false, false) => Box::new(html),
+                (true, false) => Box::new(ContentsRenderer::new(html)),
+                (false, true) => Box::new(
+                    DocumentRenderer::new(html, css, embed_css)
+                        .with_provenance(embedded_provenance(options)),
+                ),
+                (true, true) => Box::new(
+                    DocumentRenderer::new(ContentsRenderer::new(html), css, embed_css)
+                        .with_provenance(embedded_provenance(options)),
+                ),
+            }
+        }
+        Format::Epub => Box::new(
+            EpubRenderer::new(options.line_comments)
+                .with_line_numbers(options.line_numbers)
+                .with_pitch_class(options.pitch_class.clone())
+                .with_language_detector(options.language_detector)
+                .with_modified(options.modified),
+        ),
+        Format::Docx => Box::new(DocxRenderer::new()),
+        Format::Odt => Box::new(OdtRenderer::new()),
+        Format::Groff => Box::new(GroffRenderer::new()),
+        Format::Print => Box::new(PrintRenderer::new()),
+        Format::Json => Box::new(
+            JsonRenderer::new()
+                .with_language_detector(options.language_detector)
+                .with_provenance(embedded_provenance(options)),
+        ),
+        Format::Pandoc => Box::new(JsonRenderer::pandoc()),
+    }
+}
+
+// "PROVENANCE" RENDERER
+
+/// Renderer that wraps another renderer, adding the provenance of the
+/// conversion as a comment after the end of its output (for text, where it
+/// is read as a Markdown comment, and HTML fragments)
+pub struct ProvenanceRenderer {
+    inner: Box<dyn Renderer>,
+    comment: String,
+}
+
+impl ProvenanceRenderer {
+    /// Creates a new `ProvenanceRenderer` object for the given renderer,
+    /// adding the text of the given comment (see `Provenance::comment()`)
+    pub fn new(inner: Box<dyn Renderer>, comment: String) -> Self {
+        ProvenanceRenderer { inner, comment }
+    }
+}
+
+impl Renderer for ProvenanceRenderer {
+    fn begin(&mut self, output: &mut dyn Write) -> io::Result<()> {
+        self.inner.begin(output)
+    }
+
+    fn line(&mut self, output: &mut dyn Write, line: &str, line_no: usize) -> io::Result<()> {
+        self.inner.line(output, line, line_no)
+    }
+
+    fn wants_spans(&self) -> bool {
+        self.inner.wants_spans()
+    }
+
+    fn styled_line(
+        &mut self,
+        output: &mut dyn Write,
+        spans: &[Span],
+        line_no: usize,
+    ) -> io::Result<()> {
+        self.inner.styled_line(output, spans, line_no)
+    }
+
+    fn heading(
+        &mut self,
+        output: &mut dyn Write,
+        line: &str,
+        heading: &Heading,
+        line_no: usize,
+    ) -> io::Result<()> {
+        self.inner.heading(output, line, heading, line_no)
+    }
+
+    fn dot_command(
+        &mut self,
+        output: &mut dyn Write,
+        cmd: &str,
+        text: &str,
+        line_no: usize,
+    ) -> io::Result<bool> {
+        self.inner.dot_command(output, cmd, text, line_no)
+    }
+
+    fn comment(&mut self, output: &mut dyn Write, text: &str, line_no: usize) -> io::Result<()> {
+        self.inner.comment(output, text, line_no)
+    }
+
+    fn page_break(&mut self, output: &mut dyn Write, line_no: usize) -> io::Result<()> {
+        self.inner.page_break(output, line_no)
+    }
+
+    fn end(&mut self, output: &mut dyn Write) -> io::Result<()> {
+        self.inner.end(output)?;
+        write!(output, "<!--\n{}-->\n", self.comment)
+    }
+}
+
+// EXTERNAL PUBLIC FUNCTIONS
+
+/// Returns a boxed `Renderer` object for the format given in the options,
+/// with the provenance of the conversion (if given) embedded in the output
+/// where the format allows
+///
+/// # Arguments
+///
+/// * `options` - Settings for the renderer to be created
+///
+/// # Examples
+/// ```
+/// let mut renderer = render::new_renderer(&RenderOptions::default());
+/// ```
+pub fn new_renderer(options: &RenderOptions) -> Box<dyn Renderer> {
+    let renderer = format_renderer(options);
+    let as_comment = match options.format {
+        Format::Text => true,
+        Format::Html => !options.standalone,
+        _ => false,
+    };
+    match embedded_provenance(options) {
+        Some(provenance) if as_comment => {
+            Box::new(ProvenanceRenderer::new(renderer, provenance.comment()))
+        }
+        _ => renderer,
+    }
+}
+
+// Unit tests
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_text_renderer() {
+        let mut out = Vec::new();
+        let mut r = new_renderer(&RenderOptions::default());
+        r.begin(&mut out).unwrap();
+        r.line(&mut out, "abc <d>", 1).unwrap();
+        r.line(&mut out, "", 2).unwrap();
+        r.end(&mut out).unwrap();
+        assert_eq!(String::from_utf8(out).unwrap(), "abc <d>\n\n");
+    }
+
+    #[test]
+    fn test_line_spacing() {
+        let mut out = Vec::new();
+        let mut r = new_renderer(&RenderOptions::default());
+        r.line(&mut out, "a", 1).unwrap();
+        assert!(r.dot_command(&mut out, "ls", " 2", 2).unwrap());
+        r.line(&mut out, "b", 3).unwrap();
+        r.line(&mut out, "c", 4).unwrap();
+        assert!(!r.dot_command(&mut out, "ls", " x", 5).unwrap());
+        assert_eq!(String::from_utf8(out).unwrap(), "a\nb\n\nc\n\n");
+    }
+
+    #[test]
+    fn test_page_break() {
+        assert_eq!(PageBreakStyle::Bars.text().chars().count(), 39);
+        assert_eq!(PageBreakStyle::FormFeed.text(), "\x0C");
+        let mut out = Vec::new();
+        let mut r = new_renderer(&RenderOptions {
+            page_break: PageBreakStyle::Markdown,
+            ..Default::default()
+        });
+        r.line(&mut out, "a", 1).unwrap();
+        r.page_break(&mut out, 2).unwrap();
+        let mut r = new_renderer(&RenderOptions {
+            sentences: true,
+            page_break: PageBreakStyle::Custom("* * *".to_string()),
+            ..Default::default()
+        });
+        r.line(&mut out, "b.", 3).unwrap();
+        r.page_break(&mut out, 4).unwrap();
+        assert_eq!(String::from_utf8(out).unwrap(), "a\n---\nb.\n* * *\n");
+    }
+
+    #[test]
+    fn test_sentence_page_offset() {
+        let mut out = Vec::new();
+        let mut r = new_renderer(&RenderOptions {
+            sentences: true,
+            ..Default::default()
+        });
+        assert!(!r.dot_command(&mut out, "po", " 4", 1).unwrap());
+        r.line(&mut out, "      Indented.  Text", 2).unwrap();
+        r.end(&mut out).unwrap();
+        assert_eq!(String::from_utf8(out).unwrap(), "  Indented.\n  Text\n");
+    }
+
+    #[test]
+    fn test_numbered() {
+        assert_eq!(numbered("text", 123), "0123| text");
+        assert_eq!(numbered("", 7), "0007|");
+        assert_eq!(numbered("x", 12345), "12345| x");
+        let mut out = Vec::new();
+        let mut r = new_renderer(&RenderOptions {
+            line_numbers: true,
+            ..Default::default()
+        });
+        r.line(&mut out, "abc", 1).unwrap();
+        r.line(&mut out, "", 3).unwrap();
+        r.end(&mut out).unwrap();
+        assert_eq!(String::from_utf8(out).unwrap(), "0001| abc\n0003|\n");
+    }
+
+    #[test]
+    fn test_extension() {
+        assert_eq!(Format::Text.extension(), "txt");
+        assert_eq!(Format::Print.extension(), "prn");
+        assert_eq!(Format::Pandoc.extension(), "json");
+    }
+
+    #[test]
+    fn test_split_sentences() {
+        let words: Vec<String> = "One, e.g. two.  \"Three?\" he said (Five!) 6."
+            .split_whitespace()
+            .map(str::to_string)
+            .collect();
+        assert_eq!(
+            split_sentences(&words),
+            vec!["One, e.g. two.", "\"Three?\" he said (Five!)", "6."]
+        );
+        assert!(split_sentences(&[]).is_empty());
+    }
+
+    #[test]
+    fn test_sentence_renderer() {
+        let heading = Heading {
+            level: 1,
+            title: "Title".to_string(),
+        };
+        let mut out = Vec::new();
+        let mut r = new_renderer(&RenderOptions {
+            sentences: true,
+            ..Default::default()
+        });
+        r.heading(&mut out, "TITLE", &heading, 1).unwrap();
+        r.line(&mut out, "  One line.  Two", 2).unwrap();
+        r.line(&mut out, "lines.", 3).unwrap();
+        r.line(&mut out, "", 4).unwrap();
+        r.line(&mut out, "Last. One", 5).unwrap();
+        r.end(&mut out).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "TITLE\n  One line.\n  Two lines.\n\nLast.\nOne\n"
+        );
+        let mut out = Vec::new();
+        let mut r = new_renderer(&RenderOptions {
+            sentences: true,
+            line_numbers: true,
+            ..Default::default()
+        });
+        r.line(&mut out, "One. Two", 2).unwrap();
+        r.line(&mut out, "lines.", 3).unwrap();
+        r.end(&mut out).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "0002| One.\n0002| Two lines.\n"
+        );
+    }
+
+    #[test]
+    fn test_provenance() {
+        let provenance = Provenance::new(Placement::Embed, vec!["a--b".to_string()], Some(0));
+        let render = |format, standalone| {
+            let mut out = Vec::new();
+            let mut r = new_renderer(&RenderOptions {
+                format,
+                standalone,
+                provenance: Some(provenance.clone()),
+                ..Default::default()
+            });
+            r.begin(&mut out).unwrap();
+            r.line(&mut out, "a", 1).unwrap();
+            r.end(&mut out).unwrap();
+            String::from_utf8(out).unwrap()
+        };
+        let version = env!("CARGO_PKG_VERSION");
+        assert_eq!(
+            render(Format::Text, false),
+            format!(
+                "a\n<!--\ngenerator: wsconvert {}\n\
+                 dcterms.created: 1970-01-01T00:00:00Z\nwsconvert-arguments: a- -b\n-->\n",
+                version
+            )
+        );
+        assert!(render(Format::Html, false).starts_with("<p>a</p>\n<!--\ngenerator: "));
+        let html = render(Format::Html, true);
+        assert!(html.contains(&format!(
+            "<meta name=\"generator\" content=\"wsconvert {}\" />\n",
+            version
+        )));
+        assert!(!html.contains("<!--"));
+        let json: serde_json::Value = serde_json::from_str(&render(Format::Json, false)).unwrap();
+        assert_eq!(json["provenance"]["arguments"][0], "a--b");
+        let sidecar = RenderOptions {
+            provenance: Some(Provenance {
+                placement: Placement::Sidecar,
+                ..provenance.clone()
+            }),
+            ..Default::default()
+        };
+        let mut out = Vec::new();
+        let mut r = new_renderer(&sidecar);
+        r.line(&mut out, "a", 1).unwrap();
+        r.end(&mut out).unwrap();
+        assert_eq!(out, b"a\n");
+    }
+}
@@ -0,0 +1,388 @@
+//! Module to render converted lines of text as an Office Open XML (DOCX) file
+
+// A DOCX file is a zip archive of WordprocessingML parts.  Only the minimum
+// set of parts is written: content types, package relationships, the main
+// document, and a styles part defining a fixed-pitch default font (so that
+// WordStar column layouts survive) plus the two heading styles used here.
+//
+// Lines within a paragraph are kept as separate lines using line breaks
+// rather than being reflowed, as many WordStar documents contain tables
+// laid out with spaces that reflowing would destroy.
+
+use crate::render::Renderer;
+use crate::render_html;
+use crate::ws_heading::Heading;
+use crate::ws_wrappers::{Span, Style};
+use std::io::{self, Cursor, Write};
+use zip::write::FileOptions;
+use zip::{CompressionMethod, ZipWriter};
+
+const CONTENT_TYPES_XML: &str = r#"<?xml version="1.0" encoding="UTF-8" standalone="yes"?>
+<Types xmlns="http://schemas.openxmlformats.org/package/2006/content-types">
+<Default Extension="rels" ContentType="application/vnd.openxmlformats-package.relationships+xml"/>
+<Default Extension="xml" ContentType="application/xml"/>
+<Override PartName="/word/document.xml" ContentType="application/vnd.openxmlformats-officedocument.wordprocessingml.document.main+xml"/>
+<Override PartName="/word/styles.xml" ContentType="application/vnd.openxmlformats-officedocument.wordprocessingml.styles+xml"/>
+</Types>
+"#;
+
+const PACKAGE_RELS_XML: &str = r#"<?xml version="1.0" encoding="UTF-8" standalone="yes"?>
+<Relationships xmlns="http://schemas.openxmlformats.org/package/2006/relationships">
+<Relationship Id="rId1" Type="http://schemas.openxmlformats.org/officeDocument/2006/relationships/officeDocument" Target="word/document.xml"/>
+</Relationships>
+"#;
+
+const DOCUMENT_RELS_XML: &str = r#"<?xml version="1.0" encoding="UTF-8" standalone="yes"?>
+<Relationships xmlns="http://schemas.openxmlformats.org/package/2006/relationships">
+<Relationship Id="rId1" Type="http://schemas.openxmlformats.org/officeDocument/2006/relationships/styles" Target="styles.xml"/>
+</Relationships>
+"#;
+
+const STYLES_XML: &str = r#"<?xml version="1.0" encoding="UTF-8" standalone="yes"?>
+<w:styles xmlns:w="http://schemas.openxmlformats.org/wordprocessingml/2006/main">
+<w:docDefaults>
+<w:rPrDefault><w:rPr><w:rFonts w:ascii="Courier New" w:hAnsi="Courier New" w:cs="Courier New"/><w:sz w:val="20"/></w:rPr></w:rPrDefault>
+</w:docDefaults>
+<w:style w:type="paragraph" w:default="1" w:styleId="Normal"><w:name w:val="Normal"/></w:style>
+<w:style w:type="paragraph" w:styleId="Heading1"><w:name w:val="heading 1"/><w:basedOn w:val="Normal"/><w:next w:val="Normal"/><w:pPr><w:keepNext/><w:outlineLvl w:val="0"/></w:pPr><w:rPr><w:b/><w:sz w:val="28"/></w:rPr></w:style>
+<w:style w:type="paragraph" w:styleId="Heading2"><w:name w:val="heading 2"/><w:basedOn w:val="Normal"/><w:next w:val="Normal"/><w:pPr><w:keepNext/><w:outlineLvl w:val="1"/></w:pPr><w:rPr><w:b/><w:sz w:val="24"/></w:rPr></w:style>
+</w:styles>
+"#;
+
+const DOCUMENT_START: &str = r#"<?xml version="1.0" encoding="UTF-8" standalone="yes"?>
+<w:document xmlns:w="http://schemas.openxmlformats.org/wordprocessingml/2006/main">
+<w:body>
+"#;
+
+const DOCUMENT_END: &str = "</w:body>\n</w:document>\n";
+
+// PRIVATE HELPER FUNCTIONS
+
+/// Returns the WordprocessingML run properties for the given set of text
+/// attributes, or an empty string if there are none
+///
+/// # Arguments
+///
+/// * `style` - Set of text attributes to be represented
+///
+/// # Examples
+/// ```
+/// assert_eq!(run_properties(Style::BOLD), "<w:rPr><w:b/></w:rPr>");
+/// ```
+fn run_properties(style: Style) -> String {
+    let mut props = String::new();
+    if style.contains(Style::BOLD) {
+        props.push_str("<w:b/>");
+    }
+    if style.contains(Style::ITALIC) {
+        props.push_str("<w:i/>");
+    }
+    if style.contains(Style::STRIKETHROUGH) {
+        props.push_str("<w:strike/>");
+    }
+    if style.contains(Style::ALTERNATE) {
+        props.push_str("<w:w w:val=\"83\"/>"); // Condense 10 pitch to 12 pitch
+    }
+    if style.contains(Style::UNDERLINE) {
+        props.push_str("<w:u w:val=\"single\"/>");
+    }
+    if style.contains(Style::SUPERSCRIPT) {
+        props.push_str("<w:vertAlign w:val=\"superscript\"/>");
+    } else if style.contains(Style::SUBSCRIPT) {
+        props.push_str("<w:vertAlign w:val=\"subscript\"/>");
+    }
+    if props.is_empty() {
+        props
+    } else {
+        format!("<w:rPr>{}</w:rPr>", props)
+    }
+}
+
+/// Returns the WordprocessingML run content for the given text, with any
+/// (preserved) tabs and form feeds written as tab stops and page breaks
+///
+/// # Arguments
+///
+/// * `s` - Slice of text to be represented
+///
+/// # Examples
+/// ```
+/// assert_eq!(
+///     run_content("a\tb"),
+///     "<w:t xml:space=\"preserve\">a</w:t><w:tab/><w:t xml:space=\"preserve\">b</w:t>"
+/// );
+/// ```
+fn run_content(s: &str) -> String {
+    let text = |t: &str| {
+        format!(
+            "<w:t xml:space=\"preserve\">{}</w:t>",
+            render_html::escape(t)
+        )
+    };
+    let mut content = String::new();
+    let mut rest = s;
+    while let Some(index) = rest.find(['\t', '\x0C']) {
+        if index > 0 {
+            content.push_str(&text(&rest[..index]));
+        }
+        content.push_str(match &rest[index..index + 1] {
+            "\t" => "<w:tab/>",
+            _ => "<w:br w:type=\"page\"/>",
+        });
+        rest = &rest[index + 1..];
+    }
+    if !rest.is_empty() || content.is_empty() {
+        content.push_str(&text(rest));
+    }
+    content
+}
+
+/// Returns a WordprocessingML run holding the text of the given span with its
+/// text attributes
+///
+/// # Arguments
+///
+/// * `span` - Span of text to be represented
+///
+fn run(span: &Span) -> String {
+    format!(
+        "<w:r>{}{}</w:r>",
+        run_properties(span.style),
+        run_content(&span.text)
+    )
+}
+
+// "DOCX" RENDERER
+
+/// Renderer that groups consecutive non-blank lines into WordprocessingML
+/// paragraphs and then packages them into a DOCX archive when the end of
+/// the input is reached
+#[derive(Default, Debug)]
+pub struct DocxRenderer {
+    body: String,
+    lines: Vec<Vec<Span>>,
+}
+
+impl DocxRenderer {
+    /// Creates a new `DocxRenderer` object with an empty document
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Adds any lines held for the current paragraph to the document as a
+    /// `<w:p>` element and then clears them
+    fn flush_paragraph(&mut self) {
+        if self.lines.is_empty() {
+            return;
+        }
+        self.body.push_str("<w:p>");
+        for (index, line) in self.lines.iter().enumerate() {
+            if index > 0 {
+                self.body.push_str("<w:r><w:br/></w:r>");
+            }
+            for span in line {
+                self.body.push_str(&run(span));
+            }
+        }
+        self.body.push_str("</w:p>\n");
+        self.lines.clear();
+    }
+
+    /// Returns the bytes of a zip archive containing the complete document
+    fn package(&self) -> io::Result<Vec<u8>> {
+        let deflated = FileOptions::default().compression_method(CompressionMethod::Deflated);
+        let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+
+        zip.start_file("[Content_Types].xml", deflated)?;
+        zip.write_all(CONTENT_TYPES_XML.as_bytes())?;
+        zip.start_file("_rels/.rels", deflated)?;
+        zip.write_all(PACKAGE_RELS_XML.as_bytes())?;
+        zip.start_file("word/_rels/document.xml.rels", deflated)?;
+        zip.write_all(DOCUMENT_RELS_XML.as_bytes())?;
+        zip.start_file("word/styles.xml", deflated)?;
+        zip.write_all(STYLES_XML.as_bytes())?;
+        zip.start_file("word/document.xml", deflated)?;
+        zip.write_all(DOCUMENT_START.as_bytes())?;
+        zip.write_all(self.body.as_bytes())?;
+        zip.write_all(DOCUMENT_END.as_bytes())?;
+        Ok(zip.finish()?.into_inner())
+    }
+}
+
+impl Renderer for DocxRenderer {
+    fn wants_spans(&self) -> bool {
+        true
+    }
+
+    fn line(&mut self, output: &mut dyn Write, line: &str, line_no: usize) -> io::Result<()> {
+        let span = Span {
+            style: Style::empty(),
+            text: line.to_string(),
+        };
+        self.styled_line(output, &[span], line_no)
+    }
+
+    fn styled_line(
+        &mut self,
+        _output: &mut dyn Write,
+        spans: &[Span],
+        _line_no: usize,
+    ) -> io::Result<()> {
+        if spans.iter().all(|span| span.text.trim().is_empty()) {
+            self.flush_paragraph();
+        } else {
+            self.lines.push(spans.to_vec());
+        }
+        Ok(())
+    }
+
+    fn heading(
+        &mut self,
+        _output: &mut dyn Write,
+        _line: &str,
+        heading: &Heading,
+        _line_no: usize,
+    ) -> io::Result<()> {
+        self.flush_paragraph();
+        self.body.push_str(&format!(
+            "<w:p><w:pPr><w:pStyle w:val=\"Heading{}\"/></w:pPr><w:r>{}</w:r></w:p>\n",
+            heading.level.min(2),
+            run_content(&heading.title)
+        ));
+        Ok(())
+    }
+
+    fn page_break(&mut self, _output: &mut dyn Write, _line_no: usize) -> io::Result<()> {
+        self.flush_paragraph();
+        self.body
+            .push_str("<w:p><w:r><w:br w:type=\"page\"/></w:r></w:p>\n");
+        Ok(())
+    }
+
+    fn end(&mut self, output: &mut dyn Write) -> io::Result<()> {
+        self.flush_paragraph();
+        output.write_all(&self.package()?)
+    }
+}
+
+// Unit tests
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Read;
+
+    fn span(style: Style, text: &str) -> Span {
+        Span {
+            style,
+            text: text.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_run_properties() {
+        assert_eq!(run_properties(Style::BOLD), "<w:rPr><w:b/></w:rPr>");
+        assert_eq!(
+            run_properties(Style::ITALIC | Style::UNDERLINE),
+            "<w:rPr><w:i/><w:u w:val=\"single\"/></w:rPr>"
+        );
+        assert_eq!(
+            run_properties(Style::SUBSCRIPT | Style::STRIKETHROUGH),
+            "<w:rPr><w:strike/><w:vertAlign w:val=\"subscript\"/></w:rPr>"
+        );
+        assert_eq!(
+            run_properties(Style::ALTERNATE),
+            "<w:rPr><w:w w:val=\"83\"/></w:rPr>"
+        );
+        assert_eq!(run_properties(Style::empty()), "");
+    }
+
+    #[test]
+    fn test_run_content() {
+        assert_eq!(
+            run_content("a&b"),
+            "<w:t xml:space=\"preserve\">a&amp;b</w:t>"
+        );
+        assert_eq!(
+            run_content("\ta\x0C"),
+            "<w:tab/><w:t xml:space=\"preserve\">a</w:t><w:br w:type=\"page\"/>"
+        );
+        assert_eq!(run_content(""), "<w:t xml:space=\"preserve\"></w:t>");
+    }
+
+    #[test]
+    fn test_paragraphs() {
+        let mut out = Vec::new();
+        let mut r = DocxRenderer::new();
+        r.styled_line(
+            &mut out,
+            &[span(Style::empty(), "a "), span(Style::BOLD, "b")],
+            1,
+        )
+        .unwrap();
+        r.line(&mut out, "c<d", 2).unwrap();
+        r.line(&mut out, " ", 3).unwrap();
+        r.page_break(&mut out, 4).unwrap();
+        r.flush_paragraph();
+        assert_eq!(
+            r.body,
+            "<w:p><w:r><w:t xml:space=\"preserve\">a </w:t></w:r>\
+             <w:r><w:rPr><w:b/></w:rPr><w:t xml:space=\"preserve\">b</w:t></w:r>\
+             <w:r><w:br/></w:r>\
+             <w:r><w:t xml:space=\"preserve\">c&lt;d</w:t></w:r></w:p>\n\
+             <w:p><w:r><w:br w:type=\"page\"/></w:r></w:p>\n"
+        );
+    }
+
+    #[test]
+    fn test_package() {
+        let mut out = Vec::new();
+        let mut r = DocxRenderer::new();
+        let heading = Heading {
+            level: 1,
+            title: "TITLE".to_string(),
+        };
+        r.heading(&mut out, "TITLE", &heading, 1).unwrap();
+        r.line(&mut out, "text", 2).unwrap();
+        r.end(&mut out).unwrap();
+
+        let mut archive = zip::ZipArchive::new(Cursor::new(out)).unwrap();
+        let mut document = String::new();
+        archive
+            .by_name("word/document.xml")
+            .unwrap()
+            .read_to_string(&mut document)
+            .unwrap();
+        assert!(document.contains("<w:pStyle w:val=\"Heading1\"/>"));
+        assert!(document.contains(">text</w:t>"));
+        assert!(archive.by_name("[Content_Types].xml").is_ok());
+    }
+
+    #[test]
+    fn test_controls() {
+        let mut out = Vec::new();
+        let mut r = DocxRenderer::new();
+        let heading = Heading {
+            level: 1,
+            title: "A\tB".to_string(),
+        };
+        r.heading(&mut out, "A\tB", &heading, 1).unwrap();
+        r.styled_line(&mut out, &[span(Style::BOLD, "\x0Ca\tb\x03c")], 2)
+            .unwrap();
+        r.end(&mut out).unwrap();
+
+        let mut archive = zip::ZipArchive::new(Cursor::new(out)).unwrap();
+        for index in 0..archive.len() {
+            let mut part = String::new();
+            let mut file = archive.by_index(index).unwrap();
+            file.read_to_string(&mut part).unwrap();
+            assert_eq!(render_html::check_xml(&part), Ok(()), "{}", file.name());
+            if file.name() == "word/document.xml" {
+                assert!(part.contains(
+                    "<w:br w:type=\"page\"/><w:t xml:space=\"preserve\">a</w:t><w:tab/>\
+                     <w:t xml:space=\"preserve\">b^Cc</w:t>"
+                ));
+            }
+        }
+    }
+}
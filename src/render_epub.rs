@@ -0,0 +1,421 @@
+//! Module to render converted lines of text as an EPUB e-book
+
+// An EPUB file is a zip archive holding an uncompressed `mimetype` entry
+// (which must come first), a container file pointing to the package
+// document, and the XHTML content itself.  The document is split into
+// chapters at each page break and each level 1 heading.
+
+use crate::render::{LanguageDetector, Renderer};
+use crate::render_html::{self, HtmlRenderer};
+use crate::ws_heading::Heading;
+use crate::ws_wrappers::Span;
+use std::io::{self, Cursor, Write};
+use std::time::{SystemTime, UNIX_EPOCH};
+use zip::write::FileOptions;
+use zip::{CompressionMethod, ZipWriter};
+
+const MIMETYPE: &str = "application/epub+zip";
+
+const CONTAINER_XML: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<container version="1.0" xmlns="urn:oasis:names:tc:opendocument:xmlns:container">
+  <rootfiles>
+    <rootfile full-path="OEBPS/content.opf" media-type="application/oebps-package+xml"/>
+  </rootfiles>
+</container>
+"#;
+
+const DEFAULT_TITLE: &str = "Untitled";
+
+/// Holds the title (if known) and XHTML body content of a single chapter
+#[derive(Default, Debug)]
+struct Chapter {
+    title: Option<String>,
+    body: Vec<u8>,
+}
+
+// PRIVATE HELPER FUNCTIONS
+
+/// Returns a UTC timestamp in the "CCYY-MM-DDThh:mm:ssZ" form required for
+/// EPUB modification dates, given a number of seconds since the Unix epoch
+///
+/// # Arguments
+///
+/// * `secs` - Number of seconds since 1970-01-01T00:00:00Z
+///
+/// # Examples
+/// ```
+/// assert_eq!(utc_timestamp(0), "1970-01-01T00:00:00Z");
+/// ```
+pub(crate) fn utc_timestamp(secs: u64) -> String {
+    // Converts days to a civil (proleptic Gregorian) date, using the
+    // well-known algorithm based on 400-year eras of 146,097 days
+    let days = (secs / 86_400) as i64 + 719_468;
+    let era = days.div_euclid(146_097);
+    let doe = days.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+    let rem = secs % 86_400;
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z",
+        year,
+        month,
+        day,
+        rem / 3600,
+        (rem / 60) % 60,
+        rem % 60
+    )
+}
+
+/// Returns a 64-bit FNV-1a hash of the given bytes, used to form a stable
+/// identifier for the book from its content
+///
+/// # Arguments
+///
+/// * `bytes` - Slice of bytes to be hashed
+///
+fn fnv_hash(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf2_9ce4_8422_2325, |hash, b| {
+        (hash ^ *b as u64).wrapping_mul(0x0000_0100_0000_01B3)
+    })
+}
+
+/// Returns the file name used for the chapter with the given (1-based) number
+fn chapter_file(number: usize) -> String {
+    format!("chapter{}.xhtml", number)
+}
+
+/// Returns the title shown for the chapter with the given (1-based) number
+fn chapter_title(chapter: &Chapter, number: usize) -> String {
+    chapter
+        .title
+        .clone()
+        .unwrap_or_else(|| format!("Section {}", number))
+}
+
+/// Returns a complete XHTML document wrapping the given title and body
+fn xhtml_document(title: &str, body: &str) -> String {
+    format!(
+        r#"<?xml version="1.0" encoding="UTF-8"?>
+<!DOCTYPE html>
+<html xmlns="http://www.w3.org/1999/xhtml" xmlns:epub="http://www.idpf.org/2007/ops">
+<head>
+<title>{}</title>
+</head>
+<body>
+{}</body>
+</html>
+"#,
+        render_html::escape(title),
+        body
+    )
+}
+
+// "EPUB" RENDERER
+
+/// Renderer that collects HTML paragraphs into chapters and then packages
+/// them into an EPUB archive when the end of the input is reached
+#[derive(Default, Debug)]
+pub struct EpubRenderer {
+    html: HtmlRenderer,
+    chapters: Vec<Chapter>,
+    current: Chapter,
+    modified: Option<u64>,
+}
+
+impl EpubRenderer {
+    /// Creates a new `EpubRenderer` object, with source line range comments
+    /// enabled in the XHTML content if `line_comments` is `true`
+    pub fn new(line_comments: bool) -> Self {
+        EpubRenderer {
+            html: HtmlRenderer::new(line_comments),
+            ..Default::default()
+        }
+    }
+
+    /// Returns this `EpubRenderer` object with the input line numbers of each
+    /// paragraph and heading given as `data-lines` attributes if
+    /// `line_numbers` is `true`
+    pub fn with_line_numbers(mut self, line_numbers: bool) -> Self {
+        self.html = self.html.with_line_numbers(line_numbers);
+        self
+    }
+
+    /// Returns this `EpubRenderer` object with alternate pitch text marked
+    /// up as a `<span>` with the given CSS class (if any) instead of `<code>`
+    pub fn with_pitch_class(mut self, pitch_class: Option<String>) -> Self {
+        self.html = self.html.with_pitch_class(pitch_class);
+        self
+    }
+
+    /// Returns this `EpubRenderer` object with each paragraph given a `lang`
+    /// attribute for the language found by the given detector (if any)
+    pub fn with_language_detector(mut self, detector: Option<LanguageDetector>) -> Self {
+        self.html = self.html.with_language_detector(detector);
+        self
+    }
+
+    /// Returns this `EpubRenderer` object with the given modification time
+    /// (in seconds since the Unix epoch) recorded in the package document
+    /// instead of the current time (e.g. for reproducible output)
+    pub fn with_modified(mut self, modified: Option<u64>) -> Self {
+        self.modified = modified;
+        self
+    }
+
+    /// Completes the current chapter (if it has any content) and starts a new
+    /// one with the given title (if any)
+    ///
+    /// # Arguments
+    ///
+    /// * `title` - Optional title for the new chapter
+    ///
+    fn new_chapter(&mut self, title: Option<&str>) -> io::Result<()> {
+        self.html.end(&mut self.current.body)?;
+        if !self.current.body.is_empty() {
+            let chapter = std::mem::take(&mut self.current);
+            self.chapters.push(chapter);
+        }
+        if let Some(title) = title {
+            self.current.title = Some(title.to_string());
+        }
+        Ok(())
+    }
+
+    /// Returns the title of the whole book, taken from the first chapter
+    /// with a title (if any)
+    fn book_title(&self) -> &str {
+        self.chapters
+            .iter()
+            .find_map(|chapter| chapter.title.as_deref())
+            .unwrap_or(DEFAULT_TITLE)
+    }
+
+    /// Returns the package document listing the metadata, files and reading
+    /// order of the book
+    fn package_document(&self) -> String {
+        let content: Vec<u8> = self.chapters.iter().flat_map(|c| c.body.clone()).collect();
+        let modified = self.modified.unwrap_or_else(|| {
+            SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|d| d.as_secs())
+                .unwrap_or(0)
+        });
+
+        let mut manifest = String::new();
+        let mut spine = String::new();
+        for number in 1..=self.chapters.len() {
+            manifest.push_str(&format!(
+                "    <item id=\"chapter{0}\" href=\"{1}\" media-type=\"application/xhtml+xml\"/>\n",
+                number,
+                chapter_file(number)
+            ));
+            spine.push_str(&format!("    <itemref idref=\"chapter{}\"/>\n", number));
+        }
+        format!(
+            r#"<?xml version="1.0" encoding="UTF-8"?>
+<package xmlns="http://www.idpf.org/2007/opf" version="3.0" unique-identifier="uid">
+  <metadata xmlns:dc="http://purl.org/dc/elements/1.1/">
+    <dc:identifier id="uid">urn:wsconvert:{:016x}</dc:identifier>
+    <dc:title>{}</dc:title>
+    <dc:language>en</dc:language>
+    <meta property="dcterms:modified">{}</meta>
+  </metadata>
+  <manifest>
+    <item id="nav" href="nav.xhtml" media-type="application/xhtml+xml" properties="nav"/>
+{}  </manifest>
+  <spine>
+{}  </spine>
+</package>
+"#,
+            fnv_hash(&content),
+            render_html::escape(self.book_title()),
+            utc_timestamp(modified),
+            manifest,
+            spine
+        )
+    }
+
+    /// Returns the navigation document that acts as the table of contents
+    fn nav_document(&self) -> String {
+        let mut body =
+            String::from("<nav epub:type=\"toc\" id=\"toc\">\n<h1>Contents</h1>\n<ol>\n");
+        for (index, chapter) in self.chapters.iter().enumerate() {
+            body.push_str(&format!(
+                "<li><a href=\"{}\">{}</a></li>\n",
+                chapter_file(index + 1),
+                render_html::escape(&chapter_title(chapter, index + 1))
+            ));
+        }
+        body.push_str("</ol>\n</nav>\n");
+        xhtml_document("Contents", &body)
+    }
+
+    /// Returns the bytes of a zip archive containing the complete book
+    fn package(&self) -> io::Result<Vec<u8>> {
+        let stored = FileOptions::default().compression_method(CompressionMethod::Stored);
+        let deflated = FileOptions::default().compression_method(CompressionMethod::Deflated);
+        let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+
+        zip.start_file("mimetype", stored)?;
+        zip.write_all(MIMETYPE.as_bytes())?;
+        zip.start_file("META-INF/container.xml", deflated)?;
+        zip.write_all(CONTAINER_XML.as_bytes())?;
+        zip.start_file("OEBPS/content.opf", deflated)?;
+        zip.write_all(self.package_document().as_bytes())?;
+        zip.start_file("OEBPS/nav.xhtml", deflated)?;
+        zip.write_all(self.nav_document().as_bytes())?;
+        for (index, chapter) in self.chapters.iter().enumerate() {
+            let title = chapter_title(chapter, index + 1);
+            let body = String::from_utf8_lossy(&chapter.body);
+            zip.start_file(format!("OEBPS/{}", chapter_file(index + 1)), deflated)?;
+            zip.write_all(xhtml_document(&title, &body).as_bytes())?;
+        }
+        Ok(zip.finish()?.into_inner())
+    }
+}
+
+impl Renderer for EpubRenderer {
+    fn line(&mut self, _output: &mut dyn Write, line: &str, line_no: usize) -> io::Result<()> {
+        self.html.line(&mut self.current.body, line, line_no)
+    }
+
+    fn wants_spans(&self) -> bool {
+        self.html.wants_spans()
+    }
+
+    fn styled_line(
+        &mut self,
+        _output: &mut dyn Write,
+        spans: &[Span],
+        line_no: usize,
+    ) -> io::Result<()> {
+        self.html
+            .styled_line(&mut self.current.body, spans, line_no)
+    }
+
+    fn heading(
+        &mut self,
+        _output: &mut dyn Write,
+        line: &str,
+        heading: &Heading,
+        line_no: usize,
+    ) -> io::Result<()> {
+        if heading.level == 1 {
+            self.new_chapter(Some(&heading.title))?;
+        }
+        self.html
+            .heading(&mut self.current.body, line, heading, line_no)
+    }
+
+    fn comment(&mut self, _output: &mut dyn Write, text: &str, line_no: usize) -> io::Result<()> {
+        self.html.comment(&mut self.current.body, text, line_no)
+    }
+
+    fn page_break(&mut self, _output: &mut dyn Write, _line_no: usize) -> io::Result<()> {
+        self.new_chapter(None)
+    }
+
+    fn end(&mut self, output: &mut dyn Write) -> io::Result<()> {
+        self.new_chapter(None)?;
+        if self.chapters.is_empty() {
+            self.chapters.push(Chapter::default()); // Spine must not be empty
+        }
+        output.write_all(&self.package()?)
+    }
+}
+
+// Unit tests
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Read;
+
+    #[test]
+    fn test_utc_timestamp() {
+        assert_eq!(utc_timestamp(0), "1970-01-01T00:00:00Z");
+        assert_eq!(utc_timestamp(951_782_400), "2000-02-29T00:00:00Z");
+        assert_eq!(utc_timestamp(1_614_643_199), "2021-03-01T23:59:59Z");
+    }
+
+    #[test]
+    fn test_chapters() {
+        let mut out = Vec::new();
+        let mut r = EpubRenderer::new(false);
+        let heading = Heading {
+            level: 1,
+            title: "ONE".to_string(),
+        };
+        r.line(&mut out, "preface", 1).unwrap();
+        r.heading(&mut out, "ONE", &heading, 2).unwrap();
+        r.line(&mut out, "text", 3).unwrap();
+        r.page_break(&mut out, 4).unwrap();
+        r.page_break(&mut out, 5).unwrap();
+        r.line(&mut out, "more", 6).unwrap();
+        r.end(&mut out).unwrap();
+
+        assert_eq!(r.chapters.len(), 3);
+        assert_eq!(r.chapters[0].title, None);
+        assert_eq!(r.chapters[1].title, Some("ONE".to_string()));
+        assert_eq!(r.book_title(), "ONE");
+
+        let mut archive = zip::ZipArchive::new(Cursor::new(out)).unwrap();
+        assert_eq!(archive.by_index(0).unwrap().name(), "mimetype");
+        let mut chapter = String::new();
+        archive
+            .by_name("OEBPS/chapter2.xhtml")
+            .unwrap()
+            .read_to_string(&mut chapter)
+            .unwrap();
+        assert!(chapter.contains("<h1 id=\"one\">ONE</h1>\n<p>text</p>"));
+    }
+
+    #[test]
+    fn test_controls() {
+        let mut out = Vec::new();
+        let mut r = EpubRenderer::new(false);
+        let heading = Heading {
+            level: 1,
+            title: "A\x0CB".to_string(),
+        };
+        r.heading(&mut out, "A\x0CB", &heading, 1).unwrap();
+        r.line(&mut out, "a\tb\x0Cc\x03", 2).unwrap();
+        r.end(&mut out).unwrap();
+
+        let mut archive = zip::ZipArchive::new(Cursor::new(out)).unwrap();
+        for index in 1..archive.len() {
+            let mut part = String::new();
+            let mut file = archive.by_index(index).unwrap();
+            file.read_to_string(&mut part).unwrap();
+            assert_eq!(render_html::check_xml(&part), Ok(()), "{}", file.name());
+        }
+    }
+
+    #[test]
+    fn test_modified() {
+        let render = || {
+            let mut out = Vec::new();
+            let mut r = EpubRenderer::new(false).with_modified(Some(0));
+            r.line(&mut out, "text", 1).unwrap();
+            r.end(&mut out).unwrap();
+            assert!(r
+                .package_document()
+                .contains("dcterms:modified\">1970-01-01T00:00:00Z<"));
+            out
+        };
+        assert_eq!(render(), render());
+    }
+
+    #[test]
+    fn test_null() {
+        let mut out = Vec::new();
+        let mut r = EpubRenderer::new(false);
+        r.end(&mut out).unwrap();
+        assert_eq!(r.chapters.len(), 1);
+        assert_eq!(r.book_title(), DEFAULT_TITLE);
+    }
+}
@@ -0,0 +1,330 @@
+//! Module to render converted lines of text as troff/groff source (man macros)
+
+// Paragraphs are normally left for groff to fill, but a paragraph in which
+// any continuation line is indented is assumed to be a table or other fixed
+// layout and is written in no-fill mode to preserve its columns.  Any left
+// margin set by a `.po` (page offset) dot command is removed from each line
+// first, so that only deliberate indentation is taken into account.
+
+use crate::render::Renderer;
+use crate::ws_dot_cmd;
+use crate::ws_heading::Heading;
+use crate::ws_wrappers::{Span, Style};
+use std::io::{self, Write};
+
+const DEFAULT_TITLE: &str = "UNTITLED";
+
+// PRIVATE HELPER FUNCTIONS
+
+/// Returns new String formed from given text slice with backslashes escaped
+/// so that they are not interpreted by groff
+///
+/// # Arguments
+///
+/// * `s` - Slice of text to be escaped
+///
+/// # Examples
+/// ```
+/// assert_eq!(escape("a\\b"), "a\\eb");
+/// ```
+fn escape(s: &str) -> String {
+    s.replace('\\', "\\e")
+}
+
+/// Returns the groff font escape for the given set of text attributes
+///
+/// Underlining is shown in italics, following the usual man page convention.
+///
+/// # Arguments
+///
+/// * `style` - Set of text attributes to be represented
+///
+/// # Examples
+/// ```
+/// assert_eq!(font(Style::BOLD | Style::ITALIC), "\\f(BI");
+/// ```
+fn font(style: Style) -> &'static str {
+    let bold = style.contains(Style::BOLD);
+    let italic = style.intersects(Style::ITALIC | Style::UNDERLINE);
+    match (bold, italic) {
+        (true, true) => "\\f(BI",
+        (true, false) => "\\fB",
+        (false, true) => "\\fI",
+        (false, false) => "\\fR",
+    }
+}
+
+/// Returns a line of groff input holding the given spans, with font changes
+/// for text attributes and a protective `\&` if the line would otherwise be
+/// taken as a request
+///
+/// # Arguments
+///
+/// * `spans` - Slice of spans making up the line
+///
+fn groff_line(spans: &[Span]) -> String {
+    let mut result = String::new();
+    for span in spans {
+        let raised = span.style.contains(Style::SUPERSCRIPT);
+        let lowered = !raised && span.style.contains(Style::SUBSCRIPT);
+        let font = font(span.style);
+        if font != "\\fR" {
+            result.push_str(font);
+        }
+        if raised {
+            result.push_str("\\u");
+        } else if lowered {
+            result.push_str("\\d");
+        }
+        result.push_str(&escape(&span.text));
+        if raised {
+            result.push_str("\\d");
+        } else if lowered {
+            result.push_str("\\u");
+        }
+        if font != "\\fR" {
+            result.push_str("\\fR");
+        }
+    }
+    if result.starts_with('.') || result.starts_with('\'') {
+        result.insert_str(0, "\\&");
+    }
+    result
+}
+
+/// Returns a copy of the given spans with leading whitespace removed from
+/// the first span(s), so that groff does not treat the line as a break
+fn trim_spans_start(spans: &[Span]) -> Vec<Span> {
+    let mut trimmed = Vec::with_capacity(spans.len());
+    let mut at_start = true;
+    for span in spans {
+        let text = if at_start {
+            span.text.trim_start()
+        } else {
+            &span.text
+        };
+        if !text.is_empty() {
+            at_start = false;
+            trimmed.push(Span {
+                style: span.style,
+                text: text.to_string(),
+            });
+        }
+    }
+    trimmed
+}
+
+// "GROFF" RENDERER
+
+/// Renderer that writes paragraphs and headings using the groff man macros,
+/// preceded by a `.TH` title line taken from the first heading
+#[derive(Default, Debug)]
+pub struct GroffRenderer {
+    title: Option<String>,
+    body: String,
+    lines: Vec<Vec<Span>>,
+    page_offset: usize,
+}
+
+impl GroffRenderer {
+    /// Creates a new `GroffRenderer` object with an empty document
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Adds any lines held for the current paragraph to the document after
+    /// a `.PP` macro and then clears them
+    fn flush_paragraph(&mut self) {
+        if self.lines.is_empty() {
+            return;
+        }
+        let fixed = self.lines.iter().skip(1).any(|line| {
+            line.first()
+                .is_some_and(|span| span.text.starts_with(char::is_whitespace))
+        });
+        self.body.push_str(".PP\n");
+        if fixed {
+            self.body.push_str(".nf\n");
+        }
+        for line in &self.lines {
+            let text = if fixed {
+                groff_line(line)
+            } else {
+                groff_line(&trim_spans_start(line))
+            };
+            self.body.push_str(text.trim_end());
+            self.body.push('\n');
+        }
+        if fixed {
+            self.body.push_str(".fi\n");
+        }
+        self.lines.clear();
+    }
+}
+
+impl Renderer for GroffRenderer {
+    fn wants_spans(&self) -> bool {
+        true
+    }
+
+    fn line(&mut self, output: &mut dyn Write, line: &str, line_no: usize) -> io::Result<()> {
+        let span = Span {
+            style: Style::empty(),
+            text: line.to_string(),
+        };
+        self.styled_line(output, &[span], line_no)
+    }
+
+    fn styled_line(
+        &mut self,
+        _output: &mut dyn Write,
+        spans: &[Span],
+        _line_no: usize,
+    ) -> io::Result<()> {
+        if spans.iter().all(|span| span.text.trim().is_empty()) {
+            self.flush_paragraph();
+        } else {
+            let mut spans = spans.to_vec();
+            if let Some(first) = spans.first_mut() {
+                first.text =
+                    ws_dot_cmd::strip_page_offset(&first.text, self.page_offset).to_string();
+            }
+            self.lines.push(spans);
+        }
+        Ok(())
+    }
+
+    fn dot_command(
+        &mut self,
+        _output: &mut dyn Write,
+        cmd: &str,
+        text: &str,
+        _line_no: usize,
+    ) -> io::Result<bool> {
+        if let Some(offset) = ws_dot_cmd::page_offset(cmd, text) {
+            self.page_offset = offset;
+        }
+        Ok(false) // Leave the dot command to the filters
+    }
+
+    fn heading(
+        &mut self,
+        _output: &mut dyn Write,
+        _line: &str,
+        heading: &Heading,
+        _line_no: usize,
+    ) -> io::Result<()> {
+        self.flush_paragraph();
+        if self.title.is_none() {
+            self.title = Some(heading.title.clone());
+        }
+        let macro_name = if heading.level == 1 { ".SH" } else { ".SS" };
+        let title = escape(&heading.title).replace('"', "\\(dq");
+        self.body
+            .push_str(&format!("{} \"{}\"\n", macro_name, title));
+        Ok(())
+    }
+
+    fn comment(&mut self, _output: &mut dyn Write, text: &str, _line_no: usize) -> io::Result<()> {
+        self.flush_paragraph();
+        self.body.push_str(&format!(".\\\" {}\n", text));
+        Ok(())
+    }
+
+    fn page_break(&mut self, _output: &mut dyn Write, _line_no: usize) -> io::Result<()> {
+        self.flush_paragraph();
+        self.body.push_str(".bp\n");
+        Ok(())
+    }
+
+    fn end(&mut self, output: &mut dyn Write) -> io::Result<()> {
+        self.flush_paragraph();
+        let title = self.title.as_deref().unwrap_or(DEFAULT_TITLE);
+        writeln!(output, ".TH \"{}\" 1", escape(title).replace('"', "\\(dq"))?;
+        output.write_all(self.body.as_bytes())
+    }
+}
+
+// Unit tests
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn span(style: Style, text: &str) -> Span {
+        Span {
+            style,
+            text: text.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_font() {
+        assert_eq!(font(Style::BOLD), "\\fB");
+        assert_eq!(font(Style::UNDERLINE), "\\fI");
+        assert_eq!(font(Style::BOLD | Style::ITALIC), "\\f(BI");
+        assert_eq!(font(Style::STRIKETHROUGH), "\\fR");
+    }
+
+    #[test]
+    fn test_groff_line() {
+        assert_eq!(
+            groff_line(&[span(Style::empty(), "a "), span(Style::BOLD, "b\\c")]),
+            "a \\fBb\\ec\\fR"
+        );
+        assert_eq!(
+            groff_line(&[span(Style::empty(), "x"), span(Style::SUPERSCRIPT, "2")]),
+            "x\\u2\\d"
+        );
+        assert_eq!(groff_line(&[span(Style::empty(), ".he")]), "\\&.he");
+        assert_eq!(groff_line(&[]), "");
+    }
+
+    #[test]
+    fn test_trim_spans_start() {
+        assert_eq!(
+            trim_spans_start(&[span(Style::empty(), "  "), span(Style::BOLD, " a ")]),
+            vec![span(Style::BOLD, "a ")]
+        );
+    }
+
+    #[test]
+    fn test_page_offset() {
+        let mut out = Vec::new();
+        let mut r = GroffRenderer::new();
+        assert!(!r.dot_command(&mut out, "po", " 4", 1).unwrap());
+        r.line(&mut out, "    Margin", 2).unwrap();
+        r.line(&mut out, "    only", 3).unwrap();
+        r.line(&mut out, "", 4).unwrap();
+        r.line(&mut out, "    Table", 5).unwrap();
+        r.line(&mut out, "      1  2", 6).unwrap();
+        r.end(&mut out).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            ".TH \"UNTITLED\" 1\n.PP\nMargin\nonly\n.PP\n.nf\nTable\n  1  2\n.fi\n"
+        );
+    }
+
+    #[test]
+    fn test_document() {
+        let mut out = Vec::new();
+        let mut r = GroffRenderer::new();
+        let heading = Heading {
+            level: 1,
+            title: "NAME".to_string(),
+        };
+        r.heading(&mut out, "NAME", &heading, 1).unwrap();
+        r.line(&mut out, "     filled ", 2).unwrap();
+        r.line(&mut out, "text", 3).unwrap();
+        r.line(&mut out, "", 4).unwrap();
+        r.line(&mut out, "  col  1", 5).unwrap();
+        r.line(&mut out, "  col  2", 6).unwrap();
+        r.page_break(&mut out, 7).unwrap();
+        r.end(&mut out).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            ".TH \"NAME\" 1\n.SH \"NAME\"\n.PP\nfilled\ntext\n\
+             .PP\n.nf\n  col  1\n  col  2\n.fi\n.bp\n"
+        );
+    }
+}
@@ -0,0 +1,1049 @@
+//! Module to render converted lines of text as HTML paragraphs
+
+use crate::provenance::Provenance;
+use crate::render::{LanguageDetector, Renderer};
+use crate::ws_control;
+use crate::ws_dot_cmd;
+use crate::ws_heading::{Heading, HeadingIds};
+use crate::ws_wrappers::{self, Span, Style, UnderlineStyle, Wrappers, STYLE_NAMES};
+use std::fs;
+use std::io::{self, Write};
+
+const DEFAULT_TITLE: &str = "Untitled";
+
+// Style sheet embedded in a complete document, with a class for each of the
+// text attributes (other than alternate pitch, which has its own markup)
+const ATTRIBUTE_CSS: &str = "\
+.ws-bold { font-weight: bold; }
+.ws-italic { font-style: italic; }
+.ws-underline { text-decoration: underline; }
+.ws-strikethrough { text-decoration: line-through; }
+.ws-overline { text-decoration: overline; }
+.ws-superscript { vertical-align: super; font-size: smaller; }
+.ws-subscript { vertical-align: sub; font-size: smaller; }
+";
+
+// EXTERNAL PUBLIC FUNCTIONS
+
+/// Returns new String formed from given text slice with characters that are
+/// significant in HTML (or XML) replaced by their corresponding entities
+///
+/// Characters that XML does not allow at all (e.g. control characters kept
+/// by `--no-escape-unknown`) are replaced by their escaped '^' form, or by
+/// U+FFFD if they have none, so that the XML parts of EPUB, DOCX and ODT
+/// output stay well-formed.
+///
+/// # Arguments
+///
+/// * `s` - Slice of text to be escaped
+///
+/// # Examples
+/// ```
+/// assert_eq!(escape("a<b & c>"), "a&lt;b &amp; c&gt;");
+/// assert_eq!(escape("a\x03b"), "a^Cb");
+/// ```
+pub fn escape(s: &str) -> String {
+    let mut result = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '&' => result.push_str("&amp;"),
+            '<' => result.push_str("&lt;"),
+            '>' => result.push_str("&gt;"),
+            '"' => result.push_str("&quot;"),
+            _ => push_char(&mut result, c),
+        }
+    }
+    result
+}
+
+/// Returns a copy of the given text that can be the body of an HTML (or XML)
+/// comment, with a space put between any two consecutive hyphens (and after
+/// a final hyphen) and any control characters not allowed in XML replaced
+/// as for `escape()`
+///
+/// # Arguments
+///
+/// * `s` - Slice of text to be put in a comment
+///
+/// # Examples
+/// ```
+/// assert_eq!(comment_text("a---b-"), "a- - -b- ");
+/// ```
+fn comment_text(s: &str) -> String {
+    let mut result = String::with_capacity(s.len());
+    for c in s.chars() {
+        if c == '-' && result.ends_with('-') {
+            result.push(' ');
+        }
+        push_char(&mut result, c);
+    }
+    if result.ends_with('-') {
+        result.push(' ');
+    }
+    result
+}
+
+/// Appends the given character to the given string, or its "escaped" form
+/// (see `ws_control::get_escaped()`) or U+FFFD if it is not allowed in XML
+///
+/// # Arguments
+///
+/// * `result` - String to be appended to
+/// * `c` - Character to be appended
+///
+fn push_char(result: &mut String, c: char) {
+    match c {
+        '\t' | '\n' | '\r' => result.push(c),
+        '\x00'..='\x1F' | '\u{FFFE}' | '\u{FFFF}' => match ws_control::get_escaped(c) {
+            Some(escaped) => result.push_str(&escaped),
+            None => result.push(char::REPLACEMENT_CHARACTER),
+        },
+        _ => result.push(c),
+    }
+}
+
+/// Checks that the given text is a well-formed XML document (apart from
+/// the names it uses), so that the XML parts written by the renderers can
+/// be tested without an XML parser
+///
+/// Returns `()` if the text is well-formed or a description of the first
+/// problem found
+///
+/// # Arguments
+///
+/// * `xml` - Text of the XML document to be checked
+///
+#[cfg(test)]
+pub(crate) fn check_xml(xml: &str) -> Result<(), String> {
+    let is_char = |c: char| matches!(c, '\t' | '\n' | '\r' | ' '..='\u{FFFD}' | '\u{10000}'..);
+    if let Some(c) = xml.chars().find(|&c| !is_char(c)) {
+        return Err(format!("invalid character {:?}", c));
+    }
+    let check_refs = |text: &str| {
+        for entity in text.split('&').skip(1) {
+            let name = entity.split(';').next().unwrap_or_default();
+            let known = matches!(name, "amp" | "lt" | "gt" | "quot" | "apos")
+                || (name.starts_with('#') && name.len() > 1);
+            if !known || !entity.contains(';') {
+                return Err(format!("invalid reference &{}", name));
+            }
+        }
+        Ok(())
+    };
+    let mut open: Vec<&str> = Vec::new();
+    let mut roots = 0;
+    let mut rest = xml;
+    while let Some(start) = rest.find('<') {
+        if !rest[..start].trim().is_empty() && open.is_empty() {
+            return Err("text outside the root element".to_string());
+        }
+        check_refs(&rest[..start])?;
+        rest = &rest[start..];
+        let (end, is_element) = if rest.starts_with("<?") {
+            ("?>", false)
+        } else if rest.starts_with("<!--") {
+            ("-->", false)
+        } else {
+            (">", !rest.starts_with("<!")) // Not a document type declaration
+        };
+        let len = rest.find(end).ok_or("unterminated markup")? + end.len();
+        let markup = &rest[..len];
+        rest = &rest[len..];
+        if let Some(body) = markup.strip_prefix("<!--") {
+            let body = &body[..body.len() - 3];
+            if body.contains("--") || body.ends_with('-') {
+                return Err("'--' in comment".to_string());
+            }
+        }
+        if !is_element {
+            continue;
+        }
+        let inner = &markup[1..len - 1];
+        if let Some(name) = inner.strip_prefix('/') {
+            if open.pop() != Some(name.trim()) {
+                return Err(format!("mismatched end tag </{}>", name));
+            }
+            continue;
+        }
+        let (inner, empty) = match inner.strip_suffix('/') {
+            Some(inner) => (inner, true),
+            None => (inner, false),
+        };
+        let name = inner.split_whitespace().next().ok_or("empty tag")?;
+        let mut attrs = inner[name.len()..].trim();
+        while !attrs.is_empty() {
+            let (_, value) = attrs.split_once('=').ok_or("attribute without value")?;
+            let value = value.trim_start();
+            let quote = value.chars().next().filter(|&q| q == '"' || q == '\'');
+            let quote = quote.ok_or("unquoted attribute value")?;
+            let (text, after) = value[1..].split_once(quote).ok_or("unterminated value")?;
+            if text.contains('<') {
+                return Err("'<' in attribute value".to_string());
+            }
+            check_refs(text)?;
+            attrs = after.trim_start();
+        }
+        if open.is_empty() {
+            roots += 1;
+        }
+        if !empty {
+            open.push(name);
+        }
+    }
+    match (open.last(), roots, rest.trim().is_empty()) {
+        (Some(name), _, _) => Err(format!("unclosed element <{}>", name)),
+        (_, 1, true) => Ok(()),
+        (_, 1, false) => Err("text outside the root element".to_string()),
+        _ => Err(format!("{} root elements", roots)),
+    }
+}
+
+// "HTML" RENDERER
+
+/// Renderer that groups consecutive non-blank lines into HTML paragraphs,
+/// optionally preceding each paragraph with a comment that records the
+/// range of input lines from which it was converted
+///
+/// Text at alternate pitch is marked up as `<code>` (or as a `<span>` with
+/// a given CSS class), while other text attributes are mapped to Unicode
+/// characters as for plain text.  Each heading is given a unique `id`
+/// attribute formed from its title, so that it can be linked to.  Each
+/// paragraph and heading can also be given a `data-lines` attribute with the
+/// input line (or range of lines) from which it was converted, and each
+/// paragraph a `lang` attribute for the language in which it is written.
+#[derive(Default, Debug)]
+pub struct HtmlRenderer {
+    line_comments: bool,
+    line_numbers: bool,
+    pitch_class: Option<String>,
+    classes: bool,
+    underline_style: UnderlineStyle,
+    language_detector: Option<LanguageDetector>,
+    ids: HeadingIds,
+    lines: Vec<String>,
+    text: Vec<String>,
+    first_line_no: usize,
+    last_line_no: usize,
+}
+
+impl HtmlRenderer {
+    /// Creates a new `HtmlRenderer` object, with source line range comments
+    /// enabled if `line_comments` is `true`
+    pub fn new(line_comments: bool) -> Self {
+        HtmlRenderer {
+            line_comments,
+            ..Default::default()
+        }
+    }
+
+    /// Returns this `HtmlRenderer` object with the input line numbers of each
+    /// paragraph and heading given as `data-lines` attributes (e.g.
+    /// `<p data-lines="3-5">`) if `line_numbers` is `true`
+    pub fn with_line_numbers(mut self, line_numbers: bool) -> Self {
+        self.line_numbers = line_numbers;
+        self
+    }
+
+    /// Returns the `data-lines` attribute (preceded by a space) for the given
+    /// range of input lines if line numbers are enabled, otherwise ""
+    ///
+    /// # Arguments
+    ///
+    /// * `first_line_no` - Line number of the first line in the input
+    /// * `last_line_no` - Line number of the last line in the input
+    ///
+    fn lines_attr(&self, first_line_no: usize, last_line_no: usize) -> String {
+        match (self.line_numbers, first_line_no == last_line_no) {
+            (false, _) => String::new(),
+            (true, true) => format!(" data-lines=\"{}\"", first_line_no),
+            (true, false) => format!(" data-lines=\"{}-{}\"", first_line_no, last_line_no),
+        }
+    }
+
+    /// Returns this `HtmlRenderer` object with each paragraph given a `lang`
+    /// attribute (e.g. `<p lang="fr">`) for the language found in its text
+    /// by the given detector (if any)
+    pub fn with_language_detector(mut self, detector: Option<LanguageDetector>) -> Self {
+        self.language_detector = detector;
+        self
+    }
+
+    /// Returns the `lang` attribute (preceded by a space) for the language
+    /// of the current paragraph if a detector is set and finds one,
+    /// otherwise ""
+    fn lang_attr(&self) -> String {
+        self.language_detector
+            .and_then(|detect| detect(&self.text.join(" ")))
+            .map_or_else(String::new, |lang| format!(" lang=\"{}\"", escape(&lang)))
+    }
+
+    /// Returns this `HtmlRenderer` object with alternate pitch text marked
+    /// up as a `<span>` with the given CSS class (if any) instead of `<code>`
+    pub fn with_pitch_class(mut self, pitch_class: Option<String>) -> Self {
+        self.pitch_class = pitch_class;
+        self
+    }
+
+    /// Returns this `HtmlRenderer` object with text attributes marked up as
+    /// nested `<span>` elements with a `ws-` class for each attribute (e.g.
+    /// `ws-bold`) if `classes` is `true`, instead of mapping them to Unicode
+    pub fn with_classes(mut self, classes: bool) -> Self {
+        self.classes = classes;
+        self
+    }
+
+    /// Returns this `HtmlRenderer` object with runs of underlined text marked
+    /// up in the given style (e.g. as `<u>` elements)
+    pub fn with_underline_style(mut self, style: UnderlineStyle) -> Self {
+        self.underline_style = style;
+        self
+    }
+
+    /// Returns the given span as HTML, with its text attributes (other than
+    /// alternate pitch) either mapped to Unicode characters or marked up
+    /// with classes
+    fn span_html(&self, span: &Span) -> String {
+        if !self.classes {
+            return escape(&Wrappers::map_span(span));
+        }
+        let flags: Vec<&str> = STYLE_NAMES
+            .iter()
+            .filter(|(flag, _)| *flag != Style::ALTERNATE && span.style.contains(*flag))
+            .map(|(_, name)| *name)
+            .collect();
+        let mut html = String::new();
+        for name in &flags {
+            html.push_str(&format!("<span class=\"ws-{}\">", name));
+        }
+        html.push_str(&escape(&span.text));
+        html.push_str(&"</span>".repeat(flags.len()));
+        html
+    }
+
+    /// Returns the opening and closing tags used to mark up alternate pitch text
+    fn pitch_tags(&self) -> (String, &'static str) {
+        match &self.pitch_class {
+            Some(class) => (format!("<span class=\"{}\">", escape(class)), "</span>"),
+            None => ("<code>".to_string(), "</code>"),
+        }
+    }
+
+    /// Holds the given line of HTML as part of the current paragraph, along
+    /// with its plain text if a language detector is set
+    ///
+    /// # Arguments
+    ///
+    /// * `html` - Line of text already escaped or marked up as HTML
+    /// * `text` - Line of plain text (for language detection)
+    /// * `line_no` - Line number of the line in the original input
+    ///
+    fn push_line(&mut self, html: String, text: &str, line_no: usize) {
+        if self.lines.is_empty() {
+            self.first_line_no = line_no;
+        }
+        self.last_line_no = line_no;
+        self.lines.push(html);
+        if self.language_detector.is_some() {
+            self.text.push(text.trim().to_string());
+        }
+    }
+
+    /// Writes any lines held for the current paragraph as a `<p>` element
+    /// (preceded by a line range comment if enabled) and then clears them
+    ///
+    /// # Arguments
+    ///
+    /// * `output` - Destination for bytes that implements `Write` trait
+    ///
+    fn flush_paragraph(&mut self, output: &mut dyn Write) -> io::Result<()> {
+        if self.lines.is_empty() {
+            return Ok(());
+        }
+        if self.line_comments {
+            if self.first_line_no == self.last_line_no {
+                writeln!(output, "<!-- line {} -->", self.first_line_no)?;
+            } else {
+                writeln!(
+                    output,
+                    "<!-- lines {}-{} -->",
+                    self.first_line_no, self.last_line_no
+                )?;
+            }
+        }
+        writeln!(
+            output,
+            "<p{}{}>{}</p>",
+            self.lines_attr(self.first_line_no, self.last_line_no),
+            self.lang_attr(),
+            self.lines.join("\n")
+        )?;
+        self.lines.clear();
+        self.text.clear();
+        Ok(())
+    }
+}
+
+impl Renderer for HtmlRenderer {
+    fn line(&mut self, output: &mut dyn Write, line: &str, line_no: usize) -> io::Result<()> {
+        if line.trim().is_empty() {
+            return self.flush_paragraph(output);
+        }
+        self.push_line(escape(line), line, line_no);
+        Ok(())
+    }
+
+    fn wants_spans(&self) -> bool {
+        true
+    }
+
+    fn styled_line(
+        &mut self,
+        output: &mut dyn Write,
+        spans: &[Span],
+        line_no: usize,
+    ) -> io::Result<()> {
+        if spans.iter().all(|span| span.text.trim().is_empty()) {
+            return self.flush_paragraph(output);
+        }
+        let (open, close) = self.pitch_tags();
+        let html = ws_wrappers::map_spans_with(spans, self.underline_style, |span| {
+            let text = self.span_html(span);
+            if span.style.contains(Style::ALTERNATE) {
+                format!("{}{}{}", open, text, close)
+            } else {
+                text
+            }
+        });
+        let text = match self.language_detector {
+            Some(_) => ws_wrappers::join_spans(spans),
+            None => String::new(),
+        };
+        self.push_line(html, &text, line_no);
+        Ok(())
+    }
+
+    fn heading(
+        &mut self,
+        output: &mut dyn Write,
+        _line: &str,
+        heading: &Heading,
+        line_no: usize,
+    ) -> io::Result<()> {
+        self.flush_paragraph(output)?;
+        if self.line_comments {
+            writeln!(output, "<!-- line {} -->", line_no)?;
+        }
+        writeln!(
+            output,
+            "<h{0} id=\"{1}\"{2}>{3}</h{0}>",
+            heading.level,
+            escape(&self.ids.id(heading)),
+            self.lines_attr(line_no, line_no),
+            escape(&heading.title)
+        )
+    }
+
+    fn comment(&mut self, output: &mut dyn Write, text: &str, _line_no: usize) -> io::Result<()> {
+        self.flush_paragraph(output)?;
+        writeln!(output, "<!-- {} -->", comment_text(text))
+    }
+
+    fn page_break(&mut self, output: &mut dyn Write, _line_no: usize) -> io::Result<()> {
+        self.flush_paragraph(output)?;
+        writeln!(output, "<hr />")
+    }
+
+    fn end(&mut self, output: &mut dyn Write) -> io::Result<()> {
+        self.flush_paragraph(output)
+    }
+}
+
+// "CONTENTS" RENDERER
+
+/// Renderer that wraps an `HtmlRenderer`, holding back its output until the
+/// end so that a table of contents (as nested lists of links to each of the
+/// headings) can be written before it
+///
+/// The links use the same identifiers as the `id` attributes given to the
+/// headings by the `HtmlRenderer`.
+#[derive(Debug)]
+pub struct ContentsRenderer {
+    html: HtmlRenderer,
+    ids: HeadingIds,
+    entries: Vec<(usize, String, String)>,
+    body: Vec<u8>,
+}
+
+impl ContentsRenderer {
+    /// Creates a new `ContentsRenderer` object for the given `HtmlRenderer`
+    pub fn new(html: HtmlRenderer) -> Self {
+        ContentsRenderer {
+            html,
+            ids: HeadingIds::new(),
+            entries: Vec::new(),
+            body: Vec::new(),
+        }
+    }
+
+    /// Returns the table of contents for the headings so far as HTML
+    fn contents(&self) -> String {
+        let mut html = String::from("<nav class=\"toc\">\n");
+        let mut levels: Vec<usize> = Vec::new();
+        for (level, id, title) in &self.entries {
+            while levels.last().is_some_and(|open| open > level) {
+                html.push_str("</li>\n</ul>\n");
+                levels.pop();
+            }
+            if levels.last() == Some(level) {
+                html.push_str("</li>\n");
+            } else {
+                html.push_str(if levels.is_empty() {
+                    "<ul>\n"
+                } else {
+                    "\n<ul>\n"
+                });
+                levels.push(*level);
+            }
+            html.push_str(&format!(
+                "<li><a href=\"#{}\">{}</a>",
+                escape(id),
+                escape(title)
+            ));
+        }
+        while levels.pop().is_some() {
+            html.push_str("</li>\n</ul>\n");
+        }
+        html.push_str("</nav>\n");
+        html
+    }
+}
+
+impl Renderer for ContentsRenderer {
+    fn begin(&mut self, output: &mut dyn Write) -> io::Result<()> {
+        self.html.begin(output)
+    }
+
+    fn line(&mut self, _output: &mut dyn Write, line: &str, line_no: usize) -> io::Result<()> {
+        self.html.line(&mut self.body, line, line_no)
+    }
+
+    fn wants_spans(&self) -> bool {
+        self.html.wants_spans()
+    }
+
+    fn styled_line(
+        &mut self,
+        _output: &mut dyn Write,
+        spans: &[Span],
+        line_no: usize,
+    ) -> io::Result<()> {
+        self.html.styled_line(&mut self.body, spans, line_no)
+    }
+
+    fn heading(
+        &mut self,
+        _output: &mut dyn Write,
+        line: &str,
+        heading: &Heading,
+        line_no: usize,
+    ) -> io::Result<()> {
+        let id = self.ids.id(heading);
+        self.entries
+            .push((heading.level as usize, id, heading.title.clone()));
+        self.html.heading(&mut self.body, line, heading, line_no)
+    }
+
+    fn dot_command(
+        &mut self,
+        _output: &mut dyn Write,
+        cmd: &str,
+        text: &str,
+        line_no: usize,
+    ) -> io::Result<bool> {
+        self.html.dot_command(&mut self.body, cmd, text, line_no)
+    }
+
+    fn comment(&mut self, _output: &mut dyn Write, text: &str, line_no: usize) -> io::Result<()> {
+        self.html.comment(&mut self.body, text, line_no)
+    }
+
+    fn page_break(&mut self, _output: &mut dyn Write, line_no: usize) -> io::Result<()> {
+        self.html.page_break(&mut self.body, line_no)
+    }
+
+    fn end(&mut self, output: &mut dyn Write) -> io::Result<()> {
+        self.html.end(&mut self.body)?;
+        if !self.entries.is_empty() {
+            output.write_all(self.contents().as_bytes())?;
+        }
+        output.write_all(&self.body)
+    }
+}
+
+// "DOCUMENT" RENDERER
+
+/// Renderer that wraps another HTML renderer, holding back its output until
+/// the end so that it can be written as the body of a complete document
+///
+/// The document is titled after the first heading (if any) and can link to
+/// a style sheet or embed one with classes for the text attributes, to
+/// which the contents of a given style sheet file are added.  The line
+/// spacing and starting page number set by the first `.ls` and `.pn` dot
+/// commands (if any) are recorded in `<meta>` elements, as is the provenance
+/// of the conversion (if given).
+#[derive(Debug)]
+pub struct DocumentRenderer<R: Renderer> {
+    inner: R,
+    css: Option<String>,
+    embed_css: bool,
+    title: Option<String>,
+    line_spacing: Option<usize>,
+    start_page: Option<usize>,
+    provenance: Option<Provenance>,
+    style: String,
+    body: Vec<u8>,
+}
+
+impl<R: Renderer> DocumentRenderer<R> {
+    /// Creates a new `DocumentRenderer` object for the given renderer
+    ///
+    /// # Arguments
+    ///
+    /// * `inner` - Renderer for the body of the document
+    /// * `css` - Optional path (or URL) of a style sheet
+    /// * `embed_css` - Embed style sheets in the document if `true`, rather
+    ///   than linking to them
+    ///
+    /// # Examples
+    /// ```
+    /// let r = DocumentRenderer::new(HtmlRenderer::new(false), None, true);
+    /// ```
+    pub fn new(inner: R, css: Option<String>, embed_css: bool) -> Self {
+        DocumentRenderer {
+            inner,
+            css,
+            embed_css,
+            title: None,
+            line_spacing: None,
+            start_page: None,
+            provenance: None,
+            style: String::new(),
+            body: Vec::new(),
+        }
+    }
+
+    /// Returns this `DocumentRenderer` object with the given provenance of
+    /// the conversion (if any) recorded in `<meta>` elements
+    pub fn with_provenance(mut self, provenance: Option<Provenance>) -> Self {
+        self.provenance = provenance;
+        self
+    }
+
+    /// Returns the `<head>` element of the document
+    fn head(&self) -> String {
+        let mut head = format!(
+            "<head>\n<meta charset=\"utf-8\" />\n<title>{}</title>\n",
+            escape(self.title.as_deref().unwrap_or(DEFAULT_TITLE))
+        );
+        if let Some(spacing) = self.line_spacing {
+            head.push_str(&format!(
+                "<meta name=\"line-spacing\" content=\"{}\" />\n",
+                spacing
+            ));
+        }
+        if let Some(number) = self.start_page {
+            head.push_str(&format!(
+                "<meta name=\"start-page\" content=\"{}\" />\n",
+                number
+            ));
+        }
+        if let Some(provenance) = &self.provenance {
+            for (name, content) in provenance.meta() {
+                head.push_str(&format!(
+                    "<meta name=\"{}\" content=\"{}\" />\n",
+                    name,
+                    escape(&content)
+                ));
+            }
+        }
+        if self.embed_css {
+            head.push_str(&format!("<style>\n{}</style>\n", self.style));
+        } else if let Some(css) = &self.css {
+            head.push_str(&format!(
+                "<link rel=\"stylesheet\" href=\"{}\" />\n",
+                escape(css)
+            ));
+        }
+        head.push_str("</head>\n");
+        head
+    }
+}
+
+impl<R: Renderer> Renderer for DocumentRenderer<R> {
+    fn begin(&mut self, _output: &mut dyn Write) -> io::Result<()> {
+        if self.embed_css {
+            self.style = ATTRIBUTE_CSS.to_string();
+            if let Some(css) = &self.css {
+                let text = fs::read_to_string(css)?;
+                self.style.push_str(&text);
+                if !text.ends_with('\n') {
+                    self.style.push('\n');
+                }
+            }
+        }
+        self.inner.begin(&mut self.body)
+    }
+
+    fn line(&mut self, _output: &mut dyn Write, line: &str, line_no: usize) -> io::Result<()> {
+        self.inner.line(&mut self.body, line, line_no)
+    }
+
+    fn wants_spans(&self) -> bool {
+        self.inner.wants_spans()
+    }
+
+    fn styled_line(
+        &mut self,
+        _output: &mut dyn Write,
+        spans: &[Span],
+        line_no: usize,
+    ) -> io::Result<()> {
+        self.inner.styled_line(&mut self.body, spans, line_no)
+    }
+
+    fn heading(
+        &mut self,
+        _output: &mut dyn Write,
+        line: &str,
+        heading: &Heading,
+        line_no: usize,
+    ) -> io::Result<()> {
+        if self.title.is_none() {
+            self.title = Some(heading.title.clone());
+        }
+        self.inner.heading(&mut self.body, line, heading, line_no)
+    }
+
+    fn dot_command(
+        &mut self,
+        _output: &mut dyn Write,
+        cmd: &str,
+        text: &str,
+        line_no: usize,
+    ) -> io::Result<bool> {
+        if let Some(spacing) = ws_dot_cmd::line_spacing(cmd, text) {
+            self.line_spacing.get_or_insert(spacing);
+        }
+        if let Some(number) = ws_dot_cmd::page_number(cmd, text) {
+            self.start_page.get_or_insert(number);
+        }
+        self.inner.dot_command(&mut self.body, cmd, text, line_no)
+    }
+
+    fn comment(&mut self, _output: &mut dyn Write, text: &str, line_no: usize) -> io::Result<()> {
+        self.inner.comment(&mut self.body, text, line_no)
+    }
+
+    fn page_break(&mut self, _output: &mut dyn Write, line_no: usize) -> io::Result<()> {
+        self.inner.page_break(&mut self.body, line_no)
+    }
+
+    fn end(&mut self, output: &mut dyn Write) -> io::Result<()> {
+        self.inner.end(&mut self.body)?;
+        writeln!(output, "<!DOCTYPE html>\n<html>\n{}<body>", self.head())?;
+        output.write_all(&self.body)?;
+        writeln!(output, "</body>\n</html>")
+    }
+}
+
+// Unit tests
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn render(r: &mut HtmlRenderer, lines: &[(&str, usize)]) -> String {
+        let mut out = Vec::new();
+        for (line, line_no) in lines {
+            r.line(&mut out, line, *line_no).unwrap();
+        }
+        r.end(&mut out).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn test_escape() {
+        assert_eq!(escape("a<b & c>"), "a&lt;b &amp; c&gt;");
+        assert_eq!(escape("\"q\""), "&quot;q&quot;");
+        assert_eq!(escape("a\tb\x03c\x0C"), "a\tb^Cc^L");
+        assert_eq!(escape("\u{FFFF}\x7F"), "\u{FFFD}\x7F");
+        let xml = format!(
+            "<?xml version=\"1.0\"?>\n<a b=\"{}\">{}</a>",
+            escape("\""),
+            escape("\x0C&")
+        );
+        assert_eq!(check_xml(&xml), Ok(()));
+        assert!(check_xml("<a>\x0C</a>").is_err());
+        assert!(check_xml("<a><b></a></b>").is_err());
+        assert!(check_xml("<a>&</a>").is_err());
+        assert_eq!(escape(""), "");
+    }
+
+    #[test]
+    fn test_paragraphs() {
+        let mut r = HtmlRenderer::new(false);
+        assert_eq!(
+            render(&mut r, &[("ab", 1), ("cd", 2), ("  ", 3), ("e&f", 4)]),
+            "<p>ab\ncd</p>\n<p>e&amp;f</p>\n"
+        );
+    }
+
+    #[test]
+    fn test_language_detector() {
+        let mut r = HtmlRenderer::new(false)
+            .with_line_numbers(true)
+            .with_language_detector(Some(|text| {
+                text.starts_with("le ").then(|| "fr".to_string())
+            }));
+        assert_eq!(
+            render(
+                &mut r,
+                &[("le chat", 1), ("  noir", 2), ("", 3), ("cat", 4)]
+            ),
+            "<p data-lines=\"1-2\" lang=\"fr\">le chat\n  noir</p>\n<p data-lines=\"4\">cat</p>\n"
+        );
+    }
+
+    #[test]
+    fn test_line_comments() {
+        let mut r = HtmlRenderer::new(true);
+        assert_eq!(
+            render(&mut r, &[("ab", 3), ("cd", 5), ("", 6), ("ef", 7)]),
+            "<!-- lines 3-5 -->\n<p>ab\ncd</p>\n<!-- line 7 -->\n<p>ef</p>\n"
+        );
+    }
+
+    #[test]
+    fn test_line_numbers() {
+        let mut r = HtmlRenderer::new(false).with_line_numbers(true);
+        assert_eq!(
+            render(&mut r, &[("ab", 3), ("cd", 5), ("", 6), ("ef", 7)]),
+            "<p data-lines=\"3-5\">ab\ncd</p>\n<p data-lines=\"7\">ef</p>\n"
+        );
+        let mut out = Vec::new();
+        let heading = Heading {
+            level: 1,
+            title: "Title".to_string(),
+        };
+        r.heading(&mut out, "", &heading, 9).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "<h1 id=\"title\" data-lines=\"9\">Title</h1>\n"
+        );
+    }
+
+    #[test]
+    fn test_heading_and_page_break() {
+        let mut out = Vec::new();
+        let mut r = HtmlRenderer::new(false);
+        let heading = Heading {
+            level: 2,
+            title: "A & B".to_string(),
+        };
+        r.line(&mut out, "ab", 1).unwrap();
+        r.heading(&mut out, "A\u{0332}", &heading, 2).unwrap();
+        r.page_break(&mut out, 3).unwrap();
+        r.end(&mut out).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "<p>ab</p>\n<h2 id=\"a-b\">A &amp; B</h2>\n<hr />\n"
+        );
+    }
+
+    #[test]
+    fn test_comment() {
+        let mut out = Vec::new();
+        let mut r = HtmlRenderer::new(false);
+        r.line(&mut out, "ab", 1).unwrap();
+        r.comment(&mut out, "draft -- check", 2).unwrap();
+        r.end(&mut out).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "<p>ab</p>\n<!-- draft - - check -->\n"
+        );
+        let mut out = Vec::new();
+        r.comment(&mut out, "see---here\x01x-", 3).unwrap();
+        let comment = String::from_utf8(out).unwrap();
+        assert_eq!(comment, "<!-- see- - -here^Ax-  -->\n");
+        assert_eq!(check_xml(&format!("<a>{}</a>", comment)), Ok(()));
+        assert!(check_xml("<a><!-- a--b --></a>").is_err());
+    }
+
+    #[test]
+    fn test_contents() {
+        let heading = |level, title: &str| Heading {
+            level,
+            title: title.to_string(),
+        };
+        let mut out = Vec::new();
+        let mut r = ContentsRenderer::new(HtmlRenderer::new(false));
+        r.line(&mut out, "ab", 1).unwrap();
+        r.heading(&mut out, "", &heading(1, "One"), 2).unwrap();
+        r.heading(&mut out, "", &heading(2, "A"), 3).unwrap();
+        r.heading(&mut out, "", &heading(2, "B"), 4).unwrap();
+        r.heading(&mut out, "", &heading(1, "One"), 5).unwrap();
+        assert!(out.is_empty());
+        r.end(&mut out).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "<nav class=\"toc\">\n<ul>\n\
+             <li><a href=\"#one\">One</a>\n<ul>\n\
+             <li><a href=\"#a\">A</a></li>\n\
+             <li><a href=\"#b\">B</a></li>\n</ul>\n</li>\n\
+             <li><a href=\"#one-2\">One</a></li>\n</ul>\n</nav>\n\
+             <p>ab</p>\n<h1 id=\"one\">One</h1>\n<h2 id=\"a\">A</h2>\n\
+             <h2 id=\"b\">B</h2>\n<h1 id=\"one-2\">One</h1>\n"
+        );
+    }
+
+    #[test]
+    fn test_contents_none() {
+        let mut out = Vec::new();
+        let mut r = ContentsRenderer::new(HtmlRenderer::new(false));
+        r.line(&mut out, "ab", 1).unwrap();
+        r.end(&mut out).unwrap();
+        assert_eq!(String::from_utf8(out).unwrap(), "<p>ab</p>\n");
+    }
+
+    #[test]
+    fn test_pitch() {
+        let span = |style, text: &str| Span {
+            style,
+            text: text.to_string(),
+        };
+        let spans = [
+            span(Style::BOLD, "C"),
+            span(Style::ALTERNATE, "a<b"),
+            span(Style::empty(), " "),
+        ];
+        let mut out = Vec::new();
+        let mut r = HtmlRenderer::new(false);
+        r.styled_line(&mut out, &spans, 1).unwrap();
+        r.end(&mut out).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "<p>\u{1D402}<code>a&lt;b</code> </p>\n"
+        );
+        let mut out = Vec::new();
+        let mut r = HtmlRenderer::new(false).with_pitch_class(Some("elite".to_string()));
+        r.styled_line(&mut out, &spans[1..], 1).unwrap();
+        r.end(&mut out).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "<p><span class=\"elite\">a&lt;b</span> </p>\n"
+        );
+    }
+
+    #[test]
+    fn test_classes() {
+        let spans = [
+            Span {
+                style: Style::BOLD | Style::UNDERLINE | Style::ALTERNATE,
+                text: "a<".to_string(),
+            },
+            Span {
+                style: Style::empty(),
+                text: "b".to_string(),
+            },
+        ];
+        let mut out = Vec::new();
+        let mut r = HtmlRenderer::new(false).with_classes(true);
+        r.styled_line(&mut out, &spans, 1).unwrap();
+        r.end(&mut out).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "<p><code><span class=\"ws-bold\"><span class=\"ws-underline\">a&lt;\
+             </span></span></code>b</p>\n"
+        );
+    }
+
+    #[test]
+    fn test_underline_style() {
+        let spans = [
+            Span {
+                style: Style::UNDERLINE,
+                text: "a<".to_string(),
+            },
+            Span {
+                style: Style::UNDERLINE | Style::ALTERNATE,
+                text: "b".to_string(),
+            },
+        ];
+        let mut out = Vec::new();
+        let mut r = HtmlRenderer::new(false).with_underline_style(UnderlineStyle::UTag);
+        r.styled_line(&mut out, &spans, 1).unwrap();
+        r.end(&mut out).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "<p><u>a&lt;<code>b</code></u></p>\n"
+        );
+    }
+
+    #[test]
+    fn test_document() {
+        let heading = Heading {
+            level: 1,
+            title: "A & B".to_string(),
+        };
+        let mut out = Vec::new();
+        let mut r =
+            DocumentRenderer::new(HtmlRenderer::new(false), Some("s.css".to_string()), false);
+        r.begin(&mut out).unwrap();
+        r.line(&mut out, "ab", 1).unwrap();
+        r.heading(&mut out, "", &heading, 2).unwrap();
+        assert!(out.is_empty());
+        r.end(&mut out).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\" />\n\
+             <title>A &amp; B</title>\n<link rel=\"stylesheet\" href=\"s.css\" />\n\
+             </head>\n<body>\n<p>ab</p>\n<h1 id=\"a-b\">A &amp; B</h1>\n</body>\n</html>\n"
+        );
+        let mut out = Vec::new();
+        let mut r = DocumentRenderer::new(HtmlRenderer::new(false), None, false);
+        assert!(!r.dot_command(&mut out, "ls", " 2", 1).unwrap());
+        assert!(!r.dot_command(&mut out, "pn", " 4", 1).unwrap());
+        r.end(&mut out).unwrap();
+        assert!(String::from_utf8(out).unwrap().contains(
+            "<title>Untitled</title>\n<meta name=\"line-spacing\" content=\"2\" />\n\
+             <meta name=\"start-page\" content=\"4\" />\n"
+        ));
+    }
+
+    #[test]
+    fn test_document_embed_css() {
+        let mut css = tempfile::NamedTempFile::new().unwrap();
+        write!(css, "p {{ margin: 0; }}").unwrap();
+        let path = css.path().to_str().unwrap().to_string();
+        let mut out = Vec::new();
+        let mut r = DocumentRenderer::new(HtmlRenderer::new(false), Some(path), true);
+        r.begin(&mut out).unwrap();
+        r.end(&mut out).unwrap();
+        let html = String::from_utf8(out).unwrap();
+        assert!(html.contains("<title>Untitled</title>\n<style>\n.ws-bold {"));
+        assert!(html.contains("font-size: smaller; }\np { margin: 0; }\n</style>\n</head>"));
+        assert!(!html.contains("<link"));
+        let mut r = DocumentRenderer::new(
+            HtmlRenderer::new(false),
+            Some("/nonexistent/s.css".to_string()),
+            true,
+        );
+        assert!(r.begin(&mut Vec::new()).is_err());
+    }
+
+    #[test]
+    fn test_null() {
+        let mut r = HtmlRenderer::new(true);
+        assert_eq!(render(&mut r, &[("", 1), ("", 2)]), "");
+    }
+}
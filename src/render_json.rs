@@ -0,0 +1,185 @@
+//! Module to render converted lines of text as a JSON document model
+
+// The lines are collected into a `document::Document` (paragraphs, headings
+// and page breaks made up of styled spans), which is written out as JSON
+// at the end so that other tools can consume the structure directly.  The
+// document can be written either in its own form or as Pandoc's JSON AST.
+
+use crate::document::{DocumentBuilder, Line};
+use crate::provenance::Provenance;
+use crate::render::{LanguageDetector, Renderer};
+use crate::render_pandoc;
+use crate::ws_dot_cmd;
+use crate::ws_heading::Heading;
+use crate::ws_wrappers::{Span, Style};
+use std::io::{self, Write};
+
+// PRIVATE HELPER FUNCTIONS
+
+/// Returns a line made up of a single unstyled span holding the given text
+///
+/// # Arguments
+///
+/// * `s` - Slice of text for the line
+///
+fn plain_line(s: &str) -> Line {
+    vec![Span {
+        style: Style::empty(),
+        text: s.to_string(),
+    }]
+}
+
+// "JSON" RENDERER
+
+/// Renderer that collects lines into a document model and writes it as JSON
+#[derive(Debug, Default)]
+pub struct JsonRenderer {
+    builder: DocumentBuilder,
+    pandoc: bool,
+    language_detector: Option<LanguageDetector>,
+    provenance: Option<Provenance>,
+}
+
+impl JsonRenderer {
+    /// Returns a new `JsonRenderer` object that writes the document model
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Returns a new `JsonRenderer` object that writes Pandoc's JSON AST
+    pub fn pandoc() -> Self {
+        JsonRenderer {
+            pandoc: true,
+            ..Default::default()
+        }
+    }
+
+    /// Returns this `JsonRenderer` object with the language found by the
+    /// given detector (if any) recorded for each paragraph of the document
+    pub fn with_language_detector(mut self, detector: Option<LanguageDetector>) -> Self {
+        self.language_detector = detector;
+        self
+    }
+
+    /// Returns this `JsonRenderer` object with the given provenance of the
+    /// conversion (if any) recorded in the document model
+    pub fn with_provenance(mut self, provenance: Option<Provenance>) -> Self {
+        self.provenance = provenance;
+        self
+    }
+}
+
+impl Renderer for JsonRenderer {
+    fn line(&mut self, _output: &mut dyn Write, line: &str, _line_no: usize) -> io::Result<()> {
+        self.builder.push_line(plain_line(line));
+        Ok(())
+    }
+
+    fn wants_spans(&self) -> bool {
+        true
+    }
+
+    fn styled_line(
+        &mut self,
+        _output: &mut dyn Write,
+        spans: &[Span],
+        _line_no: usize,
+    ) -> io::Result<()> {
+        self.builder.push_line(spans.to_vec());
+        Ok(())
+    }
+
+    fn heading(
+        &mut self,
+        _output: &mut dyn Write,
+        line: &str,
+        heading: &Heading,
+        _line_no: usize,
+    ) -> io::Result<()> {
+        self.builder.push_heading(heading.clone(), plain_line(line));
+        Ok(())
+    }
+
+    fn dot_command(
+        &mut self,
+        _output: &mut dyn Write,
+        cmd: &str,
+        text: &str,
+        _line_no: usize,
+    ) -> io::Result<bool> {
+        if let Some(spacing) = ws_dot_cmd::line_spacing(cmd, text) {
+            self.builder.set_line_spacing(spacing);
+            Ok(true)
+        } else if let Some(number) = ws_dot_cmd::page_number(cmd, text) {
+            self.builder.set_start_page(number);
+            Ok(true)
+        } else {
+            Ok(false)
+        }
+    }
+
+    fn page_break(&mut self, _output: &mut dyn Write, _line_no: usize) -> io::Result<()> {
+        self.builder.push_page_break();
+        Ok(())
+    }
+
+    fn end(&mut self, output: &mut dyn Write) -> io::Result<()> {
+        let mut document = std::mem::take(&mut self.builder).finish();
+        if let Some(detector) = self.language_detector {
+            document.detect_languages(detector);
+        }
+        document.provenance = self.provenance.take();
+        if self.pandoc {
+            serde_json::to_writer(&mut *output, &render_pandoc::to_pandoc(&document))?;
+        } else {
+            serde_json::to_writer_pretty(&mut *output, &document)?;
+        }
+        writeln!(output)
+    }
+}
+
+// Unit tests
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_json() {
+        let mut r = JsonRenderer::new();
+        let mut out = Vec::new();
+        let bold = Span {
+            style: Style::BOLD,
+            text: "b".to_string(),
+        };
+        assert!(r.dot_command(&mut out, "ls", " 2", 1).unwrap());
+        assert!(r.dot_command(&mut out, "pn", " 3", 1).unwrap());
+        r.styled_line(&mut out, &[bold], 1).unwrap();
+        r.line(&mut out, "", 2).unwrap();
+        r.page_break(&mut out, 3).unwrap();
+        r.end(&mut out).unwrap();
+        let json: serde_json::Value = serde_json::from_slice(&out).unwrap();
+        assert_eq!(
+            json,
+            serde_json::json!({
+                "line_spacing": [{ "block": 0, "spacing": 2 }],
+                "start_page": 3,
+                "blocks": [
+                    { "paragraph": [[{ "style": ["bold"], "text": "b" }]] },
+                    "page_break"
+                ]
+            })
+        );
+    }
+
+    #[test]
+    fn test_pandoc() {
+        let mut r = JsonRenderer::pandoc();
+        let mut out = Vec::new();
+        r.line(&mut out, "a b", 1).unwrap();
+        r.end(&mut out).unwrap();
+        let json: serde_json::Value = serde_json::from_slice(&out).unwrap();
+        assert_eq!(json["blocks"][0]["t"], "Para");
+        assert_eq!(json["blocks"][0]["c"].as_array().unwrap().len(), 3);
+    }
+}
@@ -0,0 +1,452 @@
+//! Module to render converted lines of text as an OpenDocument Text (ODT) file
+
+// An ODT file is a zip archive holding an uncompressed `mimetype` entry
+// (which must come first), a manifest, and the content and styles parts.
+// Text attributes are represented by automatic text styles named after the
+// bits of the corresponding `Style` value, so each combination used in the
+// document needs only one style definition.
+//
+// OpenDocument collapses runs of spaces (and drops leading spaces) unless
+// they are written as `<text:s/>` elements, so spaces are encoded carefully
+// to preserve the column layouts found in many WordStar documents.
+
+use crate::render::Renderer;
+use crate::render_html;
+use crate::ws_heading::Heading;
+use crate::ws_wrappers::{Span, Style};
+use std::collections::BTreeSet;
+use std::io::{self, Cursor, Write};
+use zip::write::FileOptions;
+use zip::{CompressionMethod, ZipWriter};
+
+const MIMETYPE: &str = "application/vnd.oasis.opendocument.text";
+
+const MANIFEST_XML: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<manifest:manifest xmlns:manifest="urn:oasis:names:tc:opendocument:xmlns:manifest:1.0" manifest:version="1.2">
+<manifest:file-entry manifest:full-path="/" manifest:version="1.2" manifest:media-type="application/vnd.oasis.opendocument.text"/>
+<manifest:file-entry manifest:full-path="content.xml" manifest:media-type="text/xml"/>
+<manifest:file-entry manifest:full-path="styles.xml" manifest:media-type="text/xml"/>
+</manifest:manifest>
+"#;
+
+const STYLES_XML: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<office:document-styles xmlns:office="urn:oasis:names:tc:opendocument:xmlns:office:1.0" xmlns:style="urn:oasis:names:tc:opendocument:xmlns:style:1.0" xmlns:fo="urn:oasis:names:tc:opendocument:xmlns:xsl-fo-compatible:1.0" office:version="1.2">
+<office:styles>
+<style:default-style style:family="paragraph">
+<style:text-properties style:font-name="Courier New" fo:font-family="'Courier New'" style:font-family-generic="modern" style:font-pitch="fixed" fo:font-size="10pt"/>
+</style:default-style>
+</office:styles>
+</office:document-styles>
+"#;
+
+const CONTENT_START: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<office:document-content xmlns:office="urn:oasis:names:tc:opendocument:xmlns:office:1.0" xmlns:style="urn:oasis:names:tc:opendocument:xmlns:style:1.0" xmlns:text="urn:oasis:names:tc:opendocument:xmlns:text:1.0" xmlns:fo="urn:oasis:names:tc:opendocument:xmlns:xsl-fo-compatible:1.0" office:version="1.2">
+<office:automatic-styles>
+<style:style style:name="PageBreak" style:family="paragraph"><style:paragraph-properties fo:break-before="page"/></style:style>
+"#;
+
+const CONTENT_MIDDLE: &str = "</office:automatic-styles>\n<office:body>\n<office:text>\n";
+
+const CONTENT_END: &str = "</office:text>\n</office:body>\n</office:document-content>\n";
+
+// PRIVATE HELPER FUNCTIONS
+
+/// Returns the name of the automatic text style for the given attributes
+fn style_name(style: Style) -> String {
+    format!("T{}", style.bits())
+}
+
+/// Returns the definition of the automatic text style for the given set of
+/// text attributes
+///
+/// # Arguments
+///
+/// * `style` - Set of text attributes to be represented
+///
+/// # Examples
+/// ```
+/// assert!(style_definition(Style::BOLD).contains("fo:font-weight=\"bold\""));
+/// ```
+fn style_definition(style: Style) -> String {
+    let mut props = String::new();
+    if style.contains(Style::BOLD) {
+        props.push_str(" fo:font-weight=\"bold\"");
+    }
+    if style.contains(Style::ITALIC) {
+        props.push_str(" fo:font-style=\"italic\"");
+    }
+    if style.contains(Style::UNDERLINE) {
+        props.push_str(" style:text-underline-style=\"solid\" style:text-underline-width=\"auto\" style:text-underline-color=\"font-color\"");
+    }
+    if style.contains(Style::OVERLINE) {
+        props.push_str(" style:text-overline-style=\"solid\" style:text-overline-width=\"auto\" style:text-overline-color=\"font-color\"");
+    }
+    if style.contains(Style::STRIKETHROUGH) {
+        props.push_str(" style:text-line-through-style=\"solid\"");
+    }
+    if style.contains(Style::SUPERSCRIPT) {
+        props.push_str(" style:text-position=\"super 58%\"");
+    } else if style.contains(Style::SUBSCRIPT) {
+        props.push_str(" style:text-position=\"sub 58%\"");
+    }
+    if style.contains(Style::ALTERNATE) {
+        props.push_str(" style:text-scale=\"83%\""); // Condense 10 pitch to 12 pitch
+    }
+    format!(
+        "<style:style style:name=\"{}\" style:family=\"text\"><style:text-properties{}/></style:style>\n",
+        style_name(style),
+        props
+    )
+}
+
+/// Appends a run of spaces to the result, using a literal space for the
+/// first (unless following another space or the start of a line) and a
+/// `<text:s/>` element for the rest
+///
+/// # Arguments
+///
+/// * `result` - String to which the spaces are appended
+/// * `count` - Number of spaces in the run
+/// * `after_space` - Whether the run follows a space or the start of a line
+///
+fn push_spaces(result: &mut String, count: usize, after_space: bool) {
+    let mut count = count;
+    if count > 0 && !after_space {
+        result.push(' ');
+        count -= 1;
+    }
+    match count {
+        0 => {}
+        1 => result.push_str("<text:s/>"),
+        _ => result.push_str(&format!("<text:s text:c=\"{}\"/>", count)),
+    }
+}
+
+/// Returns the given spans split at the first (preserved) form feed in their
+/// text, leaving out the form feed and any empty spans, or `None` if there
+/// is no form feed
+///
+/// # Arguments
+///
+/// * `spans` - Spans of text in a line
+///
+fn split_form_feed(spans: &[Span]) -> Option<(Vec<Span>, Vec<Span>)> {
+    let index = spans.iter().position(|span| span.text.contains('\x0C'))?;
+    let (before, after) = spans[index].text.split_once('\x0C')?;
+    let part = |text: &str| Span {
+        style: spans[index].style,
+        text: text.to_string(),
+    };
+    let mut first = spans[..index].to_vec();
+    first.extend((!before.is_empty()).then(|| part(before)));
+    let mut second = Vec::new();
+    second.extend((!after.is_empty()).then(|| part(after)));
+    second.extend_from_slice(&spans[index + 1..]);
+    Some((first, second))
+}
+
+/// Appends the given text to the result, escaping XML characters and writing
+/// spaces that OpenDocument would otherwise collapse as `<text:s/>` elements
+/// and any (preserved) tabs as `<text:tab/>` elements
+///
+/// # Arguments
+///
+/// * `result` - String to which the encoded text is appended
+/// * `s` - Slice of text to be encoded
+/// * `after_space` - Whether the preceding text ended with a space (or is
+///   the start of a line), updated to reflect the end of this text
+///
+fn push_text(result: &mut String, s: &str, after_space: &mut bool) {
+    let mut spaces = 0;
+    for c in s.chars() {
+        if c == ' ' {
+            spaces += 1;
+            continue;
+        }
+        push_spaces(result, spaces, *after_space);
+        spaces = 0;
+        if c == '\t' {
+            result.push_str("<text:tab/>");
+            *after_space = true; // Any space after a tab stop would collapse
+            continue;
+        }
+        let mut buf = [0; 4];
+        result.push_str(&render_html::escape(c.encode_utf8(&mut buf)));
+        *after_space = false;
+    }
+    if spaces > 0 {
+        push_spaces(result, spaces, *after_space);
+        *after_space = true;
+    }
+}
+
+// "ODT" RENDERER
+
+/// Renderer that groups consecutive non-blank lines into OpenDocument
+/// paragraphs and then packages them into an ODT archive when the end of
+/// the input is reached
+#[derive(Default, Debug)]
+pub struct OdtRenderer {
+    body: String,
+    lines: Vec<Vec<Span>>,
+    styles: BTreeSet<u8>,
+    page_break: bool,
+}
+
+impl OdtRenderer {
+    /// Creates a new `OdtRenderer` object with an empty document
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Returns the opening tag for a paragraph of the given element type,
+    /// which includes a page break if one is pending
+    fn start_tag(&mut self, element: &str, attributes: &str) -> String {
+        let style = if self.page_break {
+            " text:style-name=\"PageBreak\""
+        } else {
+            ""
+        };
+        self.page_break = false;
+        format!("<text:{}{}{}>", element, style, attributes)
+    }
+
+    /// Adds any lines held for the current paragraph to the document as a
+    /// `<text:p>` element and then clears them
+    fn flush_paragraph(&mut self) {
+        if self.lines.is_empty() {
+            return;
+        }
+        let mut para = self.start_tag("p", "");
+        for (index, line) in self.lines.iter().enumerate() {
+            if index > 0 {
+                para.push_str("<text:line-break/>");
+            }
+            let mut after_space = true;
+            for span in line {
+                if span.style.is_empty() {
+                    push_text(&mut para, &span.text, &mut after_space);
+                } else {
+                    self.styles.insert(span.style.bits());
+                    para.push_str(&format!(
+                        "<text:span text:style-name=\"{}\">",
+                        style_name(span.style)
+                    ));
+                    push_text(&mut para, &span.text, &mut after_space);
+                    para.push_str("</text:span>");
+                }
+            }
+        }
+        para.push_str("</text:p>\n");
+        self.body.push_str(&para);
+        self.lines.clear();
+    }
+
+    /// Returns the content part holding the automatic styles and the text
+    fn content(&self) -> String {
+        let mut content = String::from(CONTENT_START);
+        for bits in &self.styles {
+            content.push_str(&style_definition(Style::from_bits_truncate(*bits)));
+        }
+        content.push_str(CONTENT_MIDDLE);
+        content.push_str(&self.body);
+        content.push_str(CONTENT_END);
+        content
+    }
+
+    /// Returns the bytes of a zip archive containing the complete document
+    fn package(&self) -> io::Result<Vec<u8>> {
+        let stored = FileOptions::default().compression_method(CompressionMethod::Stored);
+        let deflated = FileOptions::default().compression_method(CompressionMethod::Deflated);
+        let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+
+        zip.start_file("mimetype", stored)?;
+        zip.write_all(MIMETYPE.as_bytes())?;
+        zip.start_file("META-INF/manifest.xml", deflated)?;
+        zip.write_all(MANIFEST_XML.as_bytes())?;
+        zip.start_file("styles.xml", deflated)?;
+        zip.write_all(STYLES_XML.as_bytes())?;
+        zip.start_file("content.xml", deflated)?;
+        zip.write_all(self.content().as_bytes())?;
+        Ok(zip.finish()?.into_inner())
+    }
+}
+
+impl Renderer for OdtRenderer {
+    fn wants_spans(&self) -> bool {
+        true
+    }
+
+    fn line(&mut self, output: &mut dyn Write, line: &str, line_no: usize) -> io::Result<()> {
+        let span = Span {
+            style: Style::empty(),
+            text: line.to_string(),
+        };
+        self.styled_line(output, &[span], line_no)
+    }
+
+    fn styled_line(
+        &mut self,
+        output: &mut dyn Write,
+        spans: &[Span],
+        line_no: usize,
+    ) -> io::Result<()> {
+        if let Some((before, after)) = split_form_feed(spans) {
+            self.styled_line(output, &before, line_no)?;
+            self.page_break(output, line_no)?;
+            return self.styled_line(output, &after, line_no);
+        }
+        if spans.iter().all(|span| span.text.trim().is_empty()) {
+            self.flush_paragraph();
+        } else {
+            self.lines.push(spans.to_vec());
+        }
+        Ok(())
+    }
+
+    fn heading(
+        &mut self,
+        _output: &mut dyn Write,
+        _line: &str,
+        heading: &Heading,
+        _line_no: usize,
+    ) -> io::Result<()> {
+        self.flush_paragraph();
+        let level = format!(" text:outline-level=\"{}\"", heading.level);
+        let mut para = self.start_tag("h", &level);
+        push_text(&mut para, &heading.title, &mut true);
+        para.push_str("</text:h>\n");
+        self.body.push_str(&para);
+        Ok(())
+    }
+
+    fn page_break(&mut self, _output: &mut dyn Write, _line_no: usize) -> io::Result<()> {
+        self.flush_paragraph();
+        self.page_break = true;
+        Ok(())
+    }
+
+    fn end(&mut self, output: &mut dyn Write) -> io::Result<()> {
+        self.flush_paragraph();
+        output.write_all(&self.package()?)
+    }
+}
+
+// Unit tests
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Read;
+
+    fn encode(s: &str, mut after_space: bool) -> String {
+        let mut result = String::new();
+        push_text(&mut result, s, &mut after_space);
+        result
+    }
+
+    #[test]
+    fn test_push_text() {
+        assert_eq!(encode("a b", true), "a b");
+        assert_eq!(encode("a   b", true), "a <text:s text:c=\"2\"/>b");
+        assert_eq!(encode("  a ", true), "<text:s text:c=\"2\"/>a ");
+        assert_eq!(encode(" a", false), " a");
+        assert_eq!(encode(" <&>", true), "<text:s/>&lt;&amp;&gt;");
+        assert_eq!(encode("", true), "");
+        assert_eq!(encode("a\t b", true), "a<text:tab/><text:s/>b");
+    }
+
+    #[test]
+    fn test_style_definition() {
+        assert_eq!(
+            style_definition(Style::BOLD | Style::SUPERSCRIPT),
+            "<style:style style:name=\"T33\" style:family=\"text\"><style:text-properties \
+             fo:font-weight=\"bold\" style:text-position=\"super 58%\"/></style:style>\n"
+        );
+        assert!(style_definition(Style::ALTERNATE).contains("style:text-scale=\"83%\""));
+    }
+
+    #[test]
+    fn test_paragraphs() {
+        let mut out = Vec::new();
+        let mut r = OdtRenderer::new();
+        let bold = Span {
+            style: Style::BOLD,
+            text: " b ".to_string(),
+        };
+        r.line(&mut out, "a ", 1).unwrap();
+        r.styled_line(&mut out, &[bold], 2).unwrap();
+        r.page_break(&mut out, 3).unwrap();
+        r.line(&mut out, "c", 4).unwrap();
+        r.flush_paragraph();
+        assert_eq!(
+            r.body,
+            "<text:p>a <text:line-break/><text:span text:style-name=\"T1\"><text:s/>b \
+             </text:span></text:p>\n<text:p text:style-name=\"PageBreak\">c</text:p>\n"
+        );
+        assert!(r.styles.contains(&Style::BOLD.bits()));
+    }
+
+    #[test]
+    fn test_form_feed() {
+        let mut out = Vec::new();
+        let mut r = OdtRenderer::new();
+        let bold = Span {
+            style: Style::BOLD,
+            text: "b\x0Cc".to_string(),
+        };
+        r.line(&mut out, "a", 1).unwrap();
+        r.styled_line(&mut out, &[bold], 2).unwrap();
+        r.line(&mut out, "\x0Cd", 3).unwrap();
+        r.flush_paragraph();
+        assert_eq!(
+            r.body,
+            "<text:p>a<text:line-break/><text:span text:style-name=\"T1\">b</text:span></text:p>\n\
+             <text:p text:style-name=\"PageBreak\"><text:span text:style-name=\"T1\">c</text:span>\
+             </text:p>\n<text:p text:style-name=\"PageBreak\">d</text:p>\n"
+        );
+    }
+
+    #[test]
+    fn test_package() {
+        let mut out = Vec::new();
+        let mut r = OdtRenderer::new();
+        let heading = Heading {
+            level: 2,
+            title: "TITLE".to_string(),
+        };
+        r.heading(&mut out, "TITLE", &heading, 1).unwrap();
+        r.end(&mut out).unwrap();
+
+        let mut archive = zip::ZipArchive::new(Cursor::new(out)).unwrap();
+        assert_eq!(archive.by_index(0).unwrap().name(), "mimetype");
+        let mut content = String::new();
+        archive
+            .by_name("content.xml")
+            .unwrap()
+            .read_to_string(&mut content)
+            .unwrap();
+        assert!(content.contains("<text:h text:outline-level=\"2\">TITLE</text:h>"));
+    }
+
+    #[test]
+    fn test_controls() {
+        let mut out = Vec::new();
+        let mut r = OdtRenderer::new();
+        let heading = Heading {
+            level: 1,
+            title: "A\tB\x0C".to_string(),
+        };
+        r.heading(&mut out, "A\tB\x0C", &heading, 1).unwrap();
+        r.line(&mut out, "a\tb\x0Cc\x03", 2).unwrap();
+        r.end(&mut out).unwrap();
+
+        let mut archive = zip::ZipArchive::new(Cursor::new(out)).unwrap();
+        for index in 1..archive.len() {
+            let mut part = String::new();
+            let mut file = archive.by_index(index).unwrap();
+            file.read_to_string(&mut part).unwrap();
+            assert_eq!(render_html::check_xml(&part), Ok(()), "{}", file.name());
+        }
+    }
+}
@@ -0,0 +1,230 @@
+//! Module to convert a document model into Pandoc's JSON AST
+
+// The output can be piped into `pandoc -f json` to reach any format that
+// Pandoc supports.  The lines of a paragraph are joined with soft breaks,
+// page breaks become horizontal rules and text attributes without a
+// Pandoc equivalent (overline and alternate pitch) become classed spans.
+
+use crate::document::{Block, Document};
+use crate::ws_heading::HeadingIds;
+use crate::ws_wrappers::{Span, Style};
+use serde_json::{json, Value};
+
+const PANDOC_API_VERSION: [u32; 3] = [1, 23, 1];
+
+// PRIVATE HELPER FUNCTIONS
+
+/// Returns the Pandoc inline elements for the given text slice, as `Str`
+/// elements for the words separated by `Space` elements
+///
+/// # Arguments
+///
+/// * `s` - Slice of text to be converted
+///
+/// # Examples
+/// ```
+/// assert_eq!(words("a"), vec![json!({"t": "Str", "c": "a"})]);
+/// ```
+fn words(s: &str) -> Vec<Value> {
+    let mut inlines = Vec::new();
+    for (i, word) in s.split(' ').enumerate() {
+        if i > 0 && inlines.last() != Some(&json!({ "t": "Space" })) {
+            inlines.push(json!({ "t": "Space" }));
+        }
+        if !word.is_empty() {
+            inlines.push(json!({ "t": "Str", "c": word }));
+        }
+    }
+    inlines
+}
+
+/// Returns the given inline elements wrapped in elements for each of the
+/// text attributes in the given style
+///
+/// # Arguments
+///
+/// * `inlines` - Inline elements to be wrapped
+/// * `style` - Set of text attributes to be applied
+///
+fn wrap(mut inlines: Vec<Value>, style: Style) -> Vec<Value> {
+    const WRAPPERS: [(Style, &str); 6] = [
+        (Style::SUBSCRIPT, "Subscript"),
+        (Style::SUPERSCRIPT, "Superscript"),
+        (Style::STRIKETHROUGH, "Strikeout"),
+        (Style::UNDERLINE, "Underline"),
+        (Style::ITALIC, "Emph"),
+        (Style::BOLD, "Strong"),
+    ];
+    const CLASSES: [(Style, &str); 2] = [
+        (Style::OVERLINE, "overline"),
+        (Style::ALTERNATE, "alternate"),
+    ];
+    for (flag, class) in CLASSES.iter() {
+        if style.contains(*flag) {
+            inlines = vec![json!({ "t": "Span", "c": [["", [class], []], inlines] })];
+        }
+    }
+    for (flag, element) in WRAPPERS.iter() {
+        if style.contains(*flag) {
+            inlines = vec![json!({ "t": element, "c": inlines })];
+        }
+    }
+    inlines
+}
+
+/// Returns the Pandoc inline elements for the given line of spans
+///
+/// # Arguments
+///
+/// * `line` - Slice of spans making up the line
+///
+fn inlines(line: &[Span]) -> Vec<Value> {
+    line.iter()
+        .flat_map(|span| wrap(words(&span.text), span.style))
+        .collect()
+}
+
+// EXTERNAL PUBLIC FUNCTIONS
+
+/// Returns the Pandoc JSON AST for the given document
+///
+/// # Arguments
+///
+/// * `document` - Document to be converted
+///
+/// # Examples
+/// ```
+/// let value = render_pandoc::to_pandoc(&Document::new());
+/// assert_eq!(value["blocks"], json!([]));
+/// ```
+pub fn to_pandoc(document: &Document) -> Value {
+    let mut ids = HeadingIds::new();
+    let blocks: Vec<Value> = document
+        .blocks
+        .iter()
+        .map(|block| match block {
+            Block::Paragraph(lines) => {
+                let mut para = Vec::new();
+                for line in lines {
+                    if !para.is_empty() {
+                        para.push(json!({ "t": "SoftBreak" }));
+                    }
+                    para.extend(inlines(line));
+                }
+                json!({ "t": "Para", "c": para })
+            }
+            Block::Heading(heading, _) => json!({
+                "t": "Header",
+                "c": [heading.level, [ids.id(heading), [], []], words(&heading.title)]
+            }),
+            Block::PageBreak => json!({ "t": "HorizontalRule" }),
+        })
+        .collect();
+    let mut meta = serde_json::Map::new();
+    if let Some(change) = document
+        .line_spacing
+        .first()
+        .filter(|change| change.block == 0)
+    {
+        let value = json!({ "t": "MetaString", "c": change.spacing.to_string() }); // At start
+        meta.insert("linestretch".to_string(), value);
+    }
+    if let Some(number) = document.start_page {
+        let value = json!({ "t": "MetaString", "c": number.to_string() });
+        meta.insert("start-page".to_string(), value);
+    }
+    json!({
+        "pandoc-api-version": PANDOC_API_VERSION,
+        "meta": meta,
+        "blocks": blocks
+    })
+}
+
+// Unit tests
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::document::SpacingChange;
+    use crate::ws_heading::Heading;
+
+    #[test]
+    fn test_words() {
+        assert_eq!(
+            words(" a  b"),
+            vec![
+                json!({"t": "Space"}),
+                json!({"t": "Str", "c": "a"}),
+                json!({"t": "Space"}),
+                json!({"t": "Str", "c": "b"}),
+            ]
+        );
+        assert!(words("").is_empty());
+    }
+
+    #[test]
+    fn test_wrap() {
+        let text = vec![json!({"t": "Str", "c": "a"})];
+        assert_eq!(
+            wrap(text.clone(), Style::BOLD | Style::ITALIC),
+            vec![json!({"t": "Strong", "c": [{"t": "Emph", "c": text}]})]
+        );
+        assert_eq!(
+            wrap(text.clone(), Style::OVERLINE),
+            vec![json!({"t": "Span", "c": [["", ["overline"], []], text]})]
+        );
+        assert_eq!(wrap(text.clone(), Style::empty()), text);
+    }
+
+    #[test]
+    fn test_to_pandoc() {
+        let heading = Heading {
+            level: 1,
+            title: "Intro".to_string(),
+        };
+        let line = vec![Span {
+            style: Style::empty(),
+            text: "x".to_string(),
+        }];
+        let mut document = Document {
+            line_spacing: Vec::new(),
+            start_page: None,
+            languages: Default::default(),
+            provenance: None,
+            blocks: vec![
+                Block::Heading(heading, line.clone()),
+                Block::Paragraph(vec![line.clone(), line]),
+                Block::PageBreak,
+            ],
+        };
+        assert_eq!(
+            to_pandoc(&document),
+            json!({
+                "pandoc-api-version": [1, 23, 1],
+                "meta": {},
+                "blocks": [
+                    {"t": "Header", "c": [1, ["intro", [], []], [{"t": "Str", "c": "Intro"}]]},
+                    {"t": "Para", "c": [
+                        {"t": "Str", "c": "x"},
+                        {"t": "SoftBreak"},
+                        {"t": "Str", "c": "x"}
+                    ]},
+                    {"t": "HorizontalRule"}
+                ]
+            })
+        );
+        document.line_spacing = vec![SpacingChange {
+            block: 0,
+            spacing: 2,
+        }];
+        assert_eq!(
+            to_pandoc(&document)["meta"],
+            json!({"linestretch": {"t": "MetaString", "c": "2"}})
+        );
+        document.start_page = Some(4);
+        assert_eq!(
+            to_pandoc(&document)["meta"]["start-page"],
+            json!({"t": "MetaString", "c": "4"})
+        );
+    }
+}
@@ -0,0 +1,527 @@
+//! Module to render converted lines of text as pages simulating printed output
+
+// Reproduces the page layout that WordStar would have printed, using the
+// page length and margin dot commands (with WordStar's defaults if absent):
+//
+//   .pl  Page length in lines (66)
+//   .mt  Top margin in lines (3)      .hm  Header margin in lines (2)
+//   .mb  Bottom margin in lines (8)   .fm  Footer margin in lines (2)
+//   .pc  Column for page number (33)
+//   .he  Header text                  .fo  Footer text
+//   .op  Omit page numbers            .pg  Print page numbers again
+//   .ls  Line spacing (1)             .pn  Number of current page
+//
+// A page length, margin or column above 255 is ignored.
+//
+// Headers and footers may have up to five lines (e.g. `.he2` or `.h2`), each
+// of which may be restricted to even or odd pages (e.g. `.heE` or `.f2O`).
+// Header lines are printed so that the last one is `.hm` lines above the body,
+// and footer lines so that the first one is `.fm` lines below it.
+//
+// A '#' in a header or footer is replaced by the page number.  If no footer
+// is defined then the page number alone is printed at the page number column,
+// unless page numbers have been omitted with `.op`.
+// Each page is padded with blank lines to the full page length, and each line
+// of the body is followed by a blank line for each step of line spacing above
+// 1 (except at the foot of a page).
+
+use crate::render::Renderer;
+use crate::ws_dot_cmd::{self, MarginCmd, Pages};
+use std::io::{self, Write};
+
+const PAGE_NUMBER_MARK: char = '#';
+
+/// Holds the page layout settings, in lines (or columns for `page_column`)
+#[derive(Debug, Clone, PartialEq)]
+struct Layout {
+    page_length: usize,
+    top_margin: usize,
+    bottom_margin: usize,
+    header_margin: usize,
+    footer_margin: usize,
+    page_column: usize,
+}
+
+impl Default for Layout {
+    fn default() -> Self {
+        Layout {
+            page_length: 66,
+            top_margin: 3,
+            bottom_margin: 8,
+            header_margin: 2,
+            footer_margin: 2,
+            page_column: 33,
+        }
+    }
+}
+
+impl Layout {
+    /// Returns the number of body text lines that fit on each page (at least one)
+    fn body_lines(&self) -> usize {
+        self.page_length
+            .saturating_sub(self.top_margin.saturating_add(self.bottom_margin))
+            .max(1)
+    }
+
+    /// Returns the line (counting from zero) within the top margin on which
+    /// the last line of the header is printed, or `None` if it does not fit
+    fn header_line(&self) -> Option<usize> {
+        self.top_margin
+            .checked_sub(self.header_margin.saturating_add(1))
+    }
+
+    /// Returns the line (counting from zero) within the bottom margin on which
+    /// the first line of the footer is printed, or `None` if it does not fit
+    fn footer_line(&self) -> Option<usize> {
+        (self.footer_margin < self.bottom_margin).then_some(self.footer_margin)
+    }
+}
+
+// PRIVATE HELPER FUNCTIONS
+
+/// Returns the text of a header or footer dot command with its separating
+/// space and control characters removed
+///
+/// # Arguments
+///
+/// * `text` - Slice of text following the dot command
+///
+/// # Examples
+/// ```
+/// assert_eq!(margin_text("  Page #\x13"), " Page #");
+/// ```
+fn margin_text(text: &str) -> String {
+    let text = text.strip_prefix(' ').unwrap_or(text);
+    ws_dot_cmd::strip_control_chars(text).trim_end().to_string()
+}
+
+// "MARGIN" OBJECT
+
+/// Holds the lines of a header or footer, for all pages and for even or
+/// odd pages only, with `None` for any line that has not been set
+#[derive(Default, Debug)]
+struct Margin {
+    all: Vec<Option<String>>,
+    even: Vec<Option<String>>,
+    odd: Vec<Option<String>>,
+}
+
+impl Margin {
+    /// Sets the line given by a header or footer dot command
+    ///
+    /// # Arguments
+    ///
+    /// * `margin` - Parts of the header or footer dot command
+    ///
+    fn set(&mut self, margin: &MarginCmd) {
+        let lines = match margin.pages {
+            Pages::All => &mut self.all,
+            Pages::Even => &mut self.even,
+            Pages::Odd => &mut self.odd,
+        };
+        if lines.len() < margin.line {
+            lines.resize(margin.line, None);
+        }
+        lines[margin.line - 1] = Some(margin_text(margin.text));
+    }
+
+    /// Returns `true` if no lines have been set
+    fn is_empty(&self) -> bool {
+        self.all.is_empty() && self.even.is_empty() && self.odd.is_empty()
+    }
+
+    /// Returns the lines to be printed on the given page, with each '#'
+    /// replaced by the page number and blank lines for any that are not set
+    ///
+    /// # Arguments
+    ///
+    /// * `page_no` - Number of the page (from 1)
+    ///
+    fn lines(&self, page_no: usize) -> Vec<String> {
+        let paged = if page_no.is_multiple_of(2) {
+            &self.even
+        } else {
+            &self.odd
+        };
+        let count = self.all.len().max(paged.len());
+        (0..count)
+            .map(|i| match paged.get(i).cloned().flatten() {
+                Some(text) => text,
+                None => self.all.get(i).cloned().flatten().unwrap_or_default(),
+            })
+            .map(|text| text.replace(PAGE_NUMBER_MARK, &page_no.to_string()))
+            .collect()
+    }
+}
+
+// "PRINT" RENDERER
+
+/// Renderer that lays out converted lines on fixed-length pages with
+/// margins, headers and footers, as they would have been printed
+#[derive(Default, Debug)]
+pub struct PrintRenderer {
+    layout: Layout,
+    header: Margin,
+    footer: Margin,
+    omit_page_no: bool,
+    spacing: usize,
+    page_no: usize,
+    body: Vec<String>,
+}
+
+impl PrintRenderer {
+    /// Creates a new `PrintRenderer` object with the default page layout
+    pub fn new() -> Self {
+        PrintRenderer {
+            spacing: 1,
+            ..Default::default()
+        }
+    }
+
+    /// Returns the lines of the footer for the current page
+    fn footer_lines(&self) -> Vec<String> {
+        if self.footer.is_empty() && self.omit_page_no {
+            Vec::new()
+        } else if self.footer.is_empty() {
+            let indent = " ".repeat(self.layout.page_column.saturating_sub(1));
+            vec![format!("{}{}", indent, self.page_no)]
+        } else {
+            self.footer.lines(self.page_no)
+        }
+    }
+
+    /// Writes the current page with its margins, header and footer, padding
+    /// the body with blank lines, and then starts a new page
+    ///
+    /// # Arguments
+    ///
+    /// * `output` - Destination for bytes that implements `Write` trait
+    ///
+    fn write_page(&mut self, output: &mut dyn Write) -> io::Result<()> {
+        self.page_no += 1;
+        let header = self.header.lines(self.page_no);
+        let footer = self.footer_lines();
+
+        for i in 0..self.layout.top_margin {
+            let text = self.layout.header_line().and_then(|last| {
+                let index = (i + header.len()).checked_sub(last + 1)?;
+                (i <= last).then(|| header.get(index)).flatten()
+            });
+            match text {
+                Some(text) => writeln!(output, "{}", text)?,
+                None => writeln!(output)?,
+            }
+        }
+        let body_lines = self.layout.body_lines().max(self.body.len());
+        for i in 0..body_lines {
+            match self.body.get(i) {
+                Some(text) => writeln!(output, "{}", text)?,
+                None => writeln!(output)?,
+            }
+        }
+        for i in 0..self.layout.bottom_margin {
+            let text = self
+                .layout
+                .footer_line()
+                .and_then(|start| footer.get(i.checked_sub(start)?));
+            match text {
+                Some(text) => writeln!(output, "{}", text)?,
+                None => writeln!(output)?,
+            }
+        }
+        self.body.clear();
+        Ok(())
+    }
+}
+
+impl Renderer for PrintRenderer {
+    fn line(&mut self, output: &mut dyn Write, line: &str, _line_no: usize) -> io::Result<()> {
+        let body_lines = self.layout.body_lines();
+        self.body.push(line.to_string());
+        let room = body_lines.saturating_sub(self.body.len());
+        let blanks = self.spacing.saturating_sub(1).min(room);
+        self.body.resize(self.body.len() + blanks, String::new()); // Not carried over a page
+        if self.body.len() >= body_lines {
+            self.write_page(output)?;
+        }
+        Ok(())
+    }
+
+    fn dot_command(
+        &mut self,
+        _output: &mut dyn Write,
+        cmd: &str,
+        text: &str,
+        _line_no: usize,
+    ) -> io::Result<bool> {
+        let value = ws_dot_cmd::layout_value(cmd, text);
+        let text = ws_dot_cmd::split_suffix(cmd, text).0;
+        let layout = &mut self.layout;
+        let setting = match cmd {
+            "pl" => &mut layout.page_length,
+            "mt" => &mut layout.top_margin,
+            "mb" => &mut layout.bottom_margin,
+            "hm" => &mut layout.header_margin,
+            "fm" => &mut layout.footer_margin,
+            "pc" => &mut layout.page_column,
+            "op" | "pg" => {
+                self.omit_page_no = cmd == "op";
+                return Ok(true);
+            }
+            "ls" => {
+                if let Some(spacing) = ws_dot_cmd::line_spacing(cmd, text) {
+                    self.spacing = spacing;
+                }
+                return Ok(true);
+            }
+            "pn" => {
+                if let Some(number) = ws_dot_cmd::page_number(cmd, text) {
+                    self.page_no = number - 1; // Counted up when page is written
+                }
+                return Ok(true);
+            }
+            _ => match ws_dot_cmd::split_margin_cmd(cmd, text) {
+                Some(margin) if margin.footer => {
+                    self.footer.set(&margin);
+                    return Ok(true);
+                }
+                Some(margin) => {
+                    self.header.set(&margin);
+                    return Ok(true);
+                }
+                None => return Ok(false),
+            },
+        };
+        if let Some(value) = value {
+            *setting = value;
+        }
+        Ok(true)
+    }
+
+    fn page_break(&mut self, output: &mut dyn Write, _line_no: usize) -> io::Result<()> {
+        if !self.body.is_empty() {
+            self.write_page(output)?;
+        }
+        Ok(())
+    }
+
+    fn end(&mut self, output: &mut dyn Write) -> io::Result<()> {
+        if !self.body.is_empty() {
+            self.write_page(output)?;
+        }
+        Ok(())
+    }
+}
+
+// Unit tests
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn small_page(r: &mut PrintRenderer, out: &mut Vec<u8>) {
+        for (cmd, value) in &[
+            ("pl", " 8"),
+            ("mt", " 2"),
+            ("hm", " 1"),
+            ("mb", " 3"),
+            ("pc", " 3"),
+        ] {
+            assert!(r.dot_command(out, cmd, value, 0).unwrap());
+        }
+        r.dot_command(out, "fm", " 1", 0).unwrap();
+    }
+
+    #[test]
+    fn test_layout() {
+        let layout = Layout::default();
+        assert_eq!(layout.body_lines(), 55);
+        assert_eq!(layout.header_line(), Some(0));
+        assert_eq!(layout.footer_line(), Some(2));
+        let layout = Layout {
+            bottom_margin: usize::MAX,
+            header_margin: usize::MAX,
+            ..Default::default()
+        };
+        assert_eq!(layout.body_lines(), 1);
+        assert_eq!(layout.header_line(), None);
+    }
+
+    #[test]
+    fn test_layout_limits() {
+        let mut out = Vec::new();
+        let mut r = PrintRenderer::new();
+        assert!(r
+            .dot_command(&mut out, "mt", " 18446744073709551615", 0)
+            .unwrap());
+        assert!(r.dot_command(&mut out, "pl", " 1000000000", 0).unwrap());
+        assert_eq!(r.layout, Layout::default());
+        r.line(&mut out, "a", 0).unwrap();
+        r.end(&mut out).unwrap();
+        assert_eq!(String::from_utf8(out).unwrap().lines().count(), 66);
+    }
+
+    #[test]
+    fn test_margin_text() {
+        assert_eq!(margin_text("  Page #\x13"), " Page #");
+        assert_eq!(margin_text(""), "");
+    }
+
+    #[test]
+    fn test_pages() {
+        let mut out = Vec::new();
+        let mut r = PrintRenderer::new();
+        small_page(&mut r, &mut out);
+        r.dot_command(&mut out, "he", " Head #", 0).unwrap();
+        for line in &["a", "b", "c", "d"] {
+            r.line(&mut out, line, 0).unwrap();
+        }
+        r.end(&mut out).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "Head 1\n\na\nb\nc\n\n  1\n\nHead 2\n\nd\n\n\n\n  2\n\n"
+        );
+    }
+
+    #[test]
+    fn test_page_break_and_footer() {
+        let mut out = Vec::new();
+        let mut r = PrintRenderer::new();
+        small_page(&mut r, &mut out);
+        r.dot_command(&mut out, "fo", " - # -", 0).unwrap();
+        r.page_break(&mut out, 0).unwrap(); // Ignored at top of page
+        r.line(&mut out, "a", 0).unwrap();
+        r.page_break(&mut out, 0).unwrap();
+        r.end(&mut out).unwrap();
+        assert_eq!(String::from_utf8(out).unwrap(), "\n\na\n\n\n\n- 1 -\n\n");
+    }
+
+    #[test]
+    fn test_margin() {
+        let mut margin = Margin::default();
+        assert!(margin.is_empty());
+        for (cmd, text) in &[("he", "2 Two #"), ("he", "E Even"), ("h1", "O Odd")] {
+            margin.set(&ws_dot_cmd::split_margin_cmd(cmd, text).unwrap());
+        }
+        assert_eq!(margin.lines(1), vec!["Odd", "Two 1"]);
+        assert_eq!(margin.lines(2), vec!["Even", "Two 2"]);
+    }
+
+    #[test]
+    fn test_multi_line_margins() {
+        let mut out = Vec::new();
+        let mut r = PrintRenderer::new();
+        small_page(&mut r, &mut out);
+        r.dot_command(&mut out, "mt", " 3", 0).unwrap();
+        r.dot_command(&mut out, "he", "2 Second", 0).unwrap();
+        r.dot_command(&mut out, "he", " First", 0).unwrap();
+        r.dot_command(&mut out, "fo", "E Even #", 0).unwrap();
+        r.dot_command(&mut out, "fo", "O Odd #", 0).unwrap();
+        r.dot_command(&mut out, "f2", " Last", 0).unwrap();
+        for line in &["a", "b", "c"] {
+            r.line(&mut out, line, 0).unwrap();
+        }
+        r.end(&mut out).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "First\nSecond\n\na\nb\n\nOdd 1\nLast\n\
+             First\nSecond\n\nc\n\n\nEven 2\nLast\n"
+        );
+    }
+
+    #[test]
+    fn test_omit_page_numbers() {
+        let mut out = Vec::new();
+        let mut r = PrintRenderer::new();
+        small_page(&mut r, &mut out);
+        assert!(r.dot_command(&mut out, "op", "", 0).unwrap());
+        r.line(&mut out, "a", 0).unwrap();
+        r.page_break(&mut out, 0).unwrap();
+        assert!(r.dot_command(&mut out, "pg", "", 0).unwrap());
+        r.line(&mut out, "b", 0).unwrap();
+        r.end(&mut out).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "\n\na\n\n\n\n\n\n\n\nb\n\n\n\n  2\n\n"
+        );
+    }
+
+    #[test]
+    fn test_line_spacing() {
+        let mut out = Vec::new();
+        let mut r = PrintRenderer::new();
+        small_page(&mut r, &mut out);
+        assert!(r.dot_command(&mut out, "ls", " 2", 0).unwrap());
+        for line in &["a", "b"] {
+            r.line(&mut out, line, 0).unwrap();
+        }
+        r.end(&mut out).unwrap();
+        assert_eq!(String::from_utf8(out).unwrap(), "\n\na\n\nb\n\n  1\n\n");
+    }
+
+    #[test]
+    fn test_page_number() {
+        let mut out = Vec::new();
+        let mut r = PrintRenderer::new();
+        small_page(&mut r, &mut out);
+        r.dot_command(&mut out, "he", " Page #", 0).unwrap();
+        assert!(r.dot_command(&mut out, "pn", " 7", 0).unwrap());
+        r.line(&mut out, "a", 0).unwrap();
+        r.page_break(&mut out, 0).unwrap();
+        r.line(&mut out, "b", 0).unwrap();
+        r.end(&mut out).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "Page 7
+
+a
+
+
+
+  7
+
+Page 8
+
+b
+
+
+
+  8
+
+"
+        );
+    }
+
+    #[test]
+    fn test_suffix() {
+        let mut out = Vec::new();
+        let mut r = PrintRenderer::new();
+        for (cmd, text) in &[
+            ("pl", " 10 .. short page"),
+            ("mt", " 2 top"),
+            ("mb", " 3 .. bottom"),
+            ("hm", " 1 x"),
+            ("fm", " 2 y"),
+            ("pc", " 5 z"),
+        ] {
+            assert!(r.dot_command(&mut out, cmd, text, 0).unwrap());
+        }
+        assert_eq!(r.layout.page_length, 10);
+        assert_eq!(r.layout.body_lines(), 5);
+        assert_eq!(r.layout.footer_line(), Some(2));
+        assert_eq!(r.layout.page_column, 5);
+        r.dot_command(&mut out, "he", " Title .. draft", 0).unwrap();
+        r.line(&mut out, "a", 0).unwrap();
+        r.end(&mut out).unwrap();
+        assert!(String::from_utf8(out).unwrap().starts_with("Title\n"));
+    }
+
+    #[test]
+    fn test_unknown_dot_command() {
+        let mut out = Vec::new();
+        let mut r = PrintRenderer::new();
+        assert!(!r.dot_command(&mut out, "xx", "", 0).unwrap());
+        assert!(r.dot_command(&mut out, "pl", " bad", 0).unwrap());
+        assert_eq!(r.layout, Layout::default());
+    }
+}
@@ -0,0 +1,220 @@
+//! Module to check the fidelity of a conversion by re-encoding its text
+//! output as WordStar text and converting it again
+
+// The re-encoding reverses the Unicode mappings made by the wrapper filter
+// (styled letters and digits, superscripts and subscripts, and combining
+// underline and strikethrough characters) and turns page break lines back
+// into ".pa" dot commands.  Other characters are left as they are, since
+// the filters pass them through unchanged, so any line that differs after
+// the second conversion points to text that the conversion has lost or
+// garbled.
+
+use crate::corpus::{self, Outcome};
+use crate::render::RenderOptions;
+use crate::uni_chars;
+use crate::ws_chars;
+use crate::ws_dot_cmd;
+use crate::ws_filters::{self, Options};
+use crate::ws_wrappers::{self, Style};
+use std::io::{self, Read};
+
+// Styles that can be given to a single character by a Unicode mapping, in
+// the order that they are tried when reversing the mapping
+const MAPPED_STYLES: [Style; 5] = [
+    Style::SUPERSCRIPT,
+    Style::SUBSCRIPT,
+    Style::from_bits_truncate(Style::BOLD.bits() | Style::ITALIC.bits()),
+    Style::BOLD,
+    Style::ITALIC,
+];
+
+// Wrapper characters used to re-encode each style, in the order that they
+// are opened (and the reverse order that they are closed)
+const WRAPPER_CODES: [(Style, char); 6] = [
+    (Style::BOLD, ws_chars::BOLD),
+    (Style::ITALIC, ws_chars::ITALIC),
+    (Style::UNDERLINE, ws_chars::UNDERLINE),
+    (Style::STRIKETHROUGH, ws_chars::STRIKETHROUGH),
+    (Style::SUPERSCRIPT, ws_chars::SUPERSCRIPT),
+    (Style::SUBSCRIPT, ws_chars::SUBSCRIPT),
+];
+
+// PRIVATE HELPER FUNCTIONS
+
+/// Returns `(style, base)` holding the styles and the (unstyled) ASCII
+/// character from which the given character was mapped by the wrapper
+/// filter, or `(Style::empty(), c)` if it is not a mapped character
+///
+/// # Arguments
+///
+/// * `c` - Character to be unmapped
+///
+/// # Examples
+/// ```
+/// assert_eq!(unmap('\u{1D402}'), (Style::BOLD, 'C'));
+/// ```
+fn unmap(c: char) -> (Style, char) {
+    if c.is_ascii() {
+        return (Style::empty(), c);
+    }
+    for style in MAPPED_STYLES {
+        let base = (' '..='~').find(|&base| ws_wrappers::get_mapped(style, base) == Some(c));
+        if let Some(base) = base {
+            return (style, base);
+        }
+    }
+    (Style::empty(), c)
+}
+
+/// Appends the wrapper characters that change the given current style to
+/// the given new style to the output, closing styles before opening any
+///
+/// # Arguments
+///
+/// * `result` - String to which the wrapper characters are appended
+/// * `from` - Current style
+/// * `to` - New style
+///
+fn push_toggles(result: &mut String, from: Style, to: Style) {
+    let closing = WRAPPER_CODES
+        .iter()
+        .rev()
+        .filter(|(flag, _)| from.contains(*flag));
+    for (_, code) in closing.filter(|(flag, _)| !to.contains(*flag)) {
+        result.push(*code);
+    }
+    let opening = WRAPPER_CODES.iter().filter(|(flag, _)| to.contains(*flag));
+    for (_, code) in opening.filter(|(flag, _)| !from.contains(*flag)) {
+        result.push(*code);
+    }
+}
+
+// EXTERNAL PUBLIC FUNCTIONS
+
+/// Returns new String holding the given line of converted text re-encoded
+/// as a line of (7-bit) WordStar text, with each style represented by its
+/// wrapper characters and closed by the end of the line
+///
+/// # Arguments
+///
+/// * `s` - Line of converted text (without line ending)
+///
+/// # Examples
+/// ```
+/// assert_eq!(encode_line("a\u{1D41B}c"), "a\x02b\x02c");
+/// ```
+pub fn encode_line(s: &str) -> String {
+    if s == ws_dot_cmd::page_break_text() {
+        return ".pa".to_string();
+    }
+    let mut chars: Vec<(Style, char)> = Vec::with_capacity(s.len());
+    for c in s.chars() {
+        let line = match c {
+            uni_chars::COMB_UNDERLINE => Style::UNDERLINE,
+            uni_chars::COMB_STRIKETHROUGH => Style::STRIKETHROUGH,
+            _ => Style::empty(),
+        };
+        match chars.last_mut() {
+            Some((style, _)) if !line.is_empty() => *style |= line,
+            _ => chars.push(unmap(c)),
+        }
+    }
+    let mut result = String::with_capacity(s.len());
+    let mut current = Style::empty();
+    for (style, c) in chars {
+        push_toggles(&mut result, current, style);
+        result.push(c);
+        current = style;
+    }
+    push_toggles(&mut result, current, Style::empty());
+    result
+}
+
+/// Returns the outcome of converting the given (7-bit) intermediate text to
+/// plain text with the given settings, re-encoding the output as WordStar
+/// text (as for `encode_line()`) and converting it again, as a comparison
+/// of the second conversion with the first, or a `std::io::Error` type on
+/// failure
+///
+/// The conversions are made without reporting any statistics, and with the
+/// default rendering settings so that the output can be re-encoded.
+///
+/// # Arguments
+///
+/// * `input` - Source of intermediate text that implements `Read` trait
+/// * `options` - Settings for filter exclusions
+///
+/// # Examples
+/// ```
+/// let outcome = self_test::check(&mut &b"a\x02b\x02c\n"[..], &Options::default()).unwrap();
+/// assert_eq!(outcome, Outcome::Passed);
+/// ```
+pub fn check(input: &mut dyn Read, options: &Options) -> io::Result<Outcome> {
+    let options = Options {
+        quiet: true,
+        long_line_markup: false,
+        render: RenderOptions::default(),
+        ..options.clone()
+    };
+    let mut first = Vec::new();
+    ws_filters::transform_file(input, &mut first, &options)?;
+    let first = String::from_utf8_lossy(&first).into_owned();
+    let encoded: String = first.lines().map(|line| encode_line(line) + "\n").collect();
+    let mut second = Vec::new();
+    ws_filters::transform_file(&mut encoded.as_bytes(), &mut second, &options)?;
+    Ok(corpus::compare(&first, &String::from_utf8_lossy(&second)))
+}
+
+// Unit tests
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_unmap() {
+        assert_eq!(unmap('a'), (Style::empty(), 'a'));
+        assert_eq!(unmap('\u{1D402}'), (Style::BOLD, 'C'));
+        assert_eq!(unmap('\u{1D436}'), (Style::ITALIC, 'C'));
+        assert_eq!(unmap('\u{210E}'), (Style::ITALIC, 'h'));
+        assert_eq!(unmap('\u{00B2}'), (Style::SUPERSCRIPT, '2'));
+        assert_eq!(unmap('\u{2080}'), (Style::SUBSCRIPT, '0'));
+        assert_eq!(unmap('\u{00BD}'), (Style::empty(), '\u{00BD}'));
+    }
+
+    #[test]
+    fn test_encode_line() {
+        assert_eq!(encode_line(""), "");
+        assert_eq!(encode_line("plain"), "plain");
+        assert_eq!(encode_line("a\u{1D41B}c"), "a\x02b\x02c");
+        assert_eq!(
+            encode_line("a\u{0332} \u{0332}b\u{0336}"),
+            "\x13a \x13\x18b\x18"
+        );
+        assert_eq!(
+            encode_line("\u{1D41A}\u{0332}\u{1D41B}"),
+            "\x02\x13a\x13b\x02"
+        );
+        assert_eq!(encode_line("x\u{00B2}"), "x\x142\x14");
+        assert_eq!(encode_line(&ws_dot_cmd::page_break_text()), ".pa");
+    }
+
+    #[test]
+    fn test_check() {
+        let options = Options::default();
+        let input = "Some \x02bold\x02 and \x13underlined\x13 text\n.pa\nx\x142\x14\n";
+        assert_eq!(
+            check(&mut input.as_bytes(), &options).unwrap(),
+            Outcome::Passed
+        );
+        assert_eq!(
+            check(&mut &b"one\n\x02.\x02mt 3\n"[..], &options).unwrap(),
+            Outcome::Failed {
+                lines: 1,
+                first: 2,
+                expected: ".mt 3".to_string(),
+                actual: "<end of file>".to_string()
+            }
+        );
+    }
+}
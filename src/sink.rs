@@ -0,0 +1,137 @@
+//! Module to provide destinations for output that is split into several
+//! parts (e.g. one per page or per chapter), each a complete document
+
+// A long recovered manuscript is easier to browse as a set of files, so the
+// conversion can start a new part of the output at each page break or each
+// top-level heading (see `ws_filters::transform_split()`).  Each part is
+// written to the destination given by an `OutputSink`, so that library code
+// can supply its own (e.g. to collect the parts in memory as here).
+
+use std::io::{self, Write};
+use std::path::Path;
+
+const PART_PLACEHOLDER: &str = "{n}";
+const GZIP_EXTENSION: &str = "gz";
+
+/// Points at which the output is split into parts
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub enum Split {
+    #[default]
+    None, // Output is written as a single document
+    Page, // New part at each page break
+    H1,   // New part at each top-level heading
+}
+
+/// Trait for destinations of the parts of a split output
+pub trait OutputSink {
+    /// Returns the destination for the given part of the output (numbered
+    /// from 1), which is requested once the previous part is complete
+    fn part(&mut self, part_no: usize) -> io::Result<&mut dyn Write>;
+}
+
+/// Sink that writes every part of the output to the same destination
+pub struct SingleSink<'a> {
+    output: &'a mut dyn Write,
+}
+
+impl<'a> SingleSink<'a> {
+    /// Returns a new `SingleSink` object for the given destination
+    pub fn new(output: &'a mut dyn Write) -> Self {
+        Self { output }
+    }
+}
+
+impl OutputSink for SingleSink<'_> {
+    fn part(&mut self, _part_no: usize) -> io::Result<&mut dyn Write> {
+        Ok(self.output)
+    }
+}
+
+/// Sink that collects each part of the output in memory
+#[derive(Debug, Default)]
+pub struct MemorySink {
+    pub parts: Vec<Vec<u8>>,
+}
+
+impl MemorySink {
+    /// Returns a new `MemorySink` object with no parts
+    pub fn new() -> Self {
+        Default::default()
+    }
+}
+
+impl OutputSink for MemorySink {
+    fn part(&mut self, _part_no: usize) -> io::Result<&mut dyn Write> {
+        self.parts.push(Vec::new());
+        Ok(self.parts.last_mut().unwrap())
+    }
+}
+
+// EXTERNAL PUBLIC FUNCTIONS
+
+/// Returns the filename for the given part of a split output, made from
+/// the given template by replacing "{n}" with the part number (padded to
+/// three digits) or, if there is no "{n}", by adding "-" and the part number
+/// before the extension (or before the extension ahead of any ".gz")
+///
+/// # Arguments
+///
+/// * `template` - Template for filenames of parts
+/// * `part_no` - Number of the part (from 1)
+///
+/// # Examples
+/// ```
+/// assert_eq!(part_name("book.html", 2), "book-002.html");
+/// assert_eq!(part_name("ch{n}.md", 12), "ch012.md");
+/// ```
+pub fn part_name(template: &str, part_no: usize) -> String {
+    let number = format!("{:03}", part_no);
+    if template.contains(PART_PLACEHOLDER) {
+        return template.replace(PART_PLACEHOLDER, &number);
+    }
+    let file_start = template.len() - Path::new(template).file_name().map_or(0, |n| n.len());
+    let mut stem_end = template.len();
+    for _ in 0..2 {
+        match template[file_start..stem_end].rfind('.') {
+            Some(dot) if dot > 0 => {
+                let is_gzip = template[file_start + dot + 1..stem_end] == *GZIP_EXTENSION;
+                stem_end = file_start + dot;
+                if !is_gzip {
+                    break;
+                }
+            }
+            _ => break,
+        }
+    }
+    format!(
+        "{}-{}{}",
+        &template[..stem_end],
+        number,
+        &template[stem_end..]
+    )
+}
+
+// Unit tests
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_part_name() {
+        assert_eq!(part_name("book.html", 2), "book-002.html");
+        assert_eq!(part_name("ch{n}.md", 12), "ch012.md");
+        assert_eq!(part_name("out/book.txt.gz", 1), "out/book-001.txt.gz");
+        assert_eq!(part_name("book.gz", 1), "book-001.gz");
+        assert_eq!(part_name("dir.d/book", 1), "dir.d/book-001");
+        assert_eq!(part_name(".hidden", 1), ".hidden-001");
+    }
+
+    #[test]
+    fn test_memory_sink() {
+        let mut sink = MemorySink::new();
+        sink.part(1).unwrap().write_all(b"one").unwrap();
+        sink.part(2).unwrap().write_all(b"two").unwrap();
+        assert_eq!(sink.parts, [b"one".to_vec(), b"two".to_vec()]);
+    }
+}
@@ -0,0 +1,228 @@
+//! Module to strip WordStar files down to their words in a single pass
+
+// Full-text indexing only needs the words, so this bypasses the conversion
+// to 7-bit ASCII, the event parser and the filters altogether.  Each byte is
+// looked at once: the high bit is cleared, dot command lines (and ".."
+// comment lines) are dropped, printer escapes lose their command byte, and
+// every other control character is removed apart from tabs, line feeds, the
+// non-breaking space (as a space), the active soft hyphen (as "-", as it is
+// printed at the end of a line) and any that are to be preserved (e.g. form
+// feeds).  No emphasis or markup is added.
+
+use crate::ws_chars;
+use std::io::{self, Read, Write};
+
+const EOF_BYTE: u8 = 0x1A; // End of File (EOF) marker
+const ASCII_MASK: u8 = 0x7F; // Bit mask for 7-bit ASCII
+
+const CHUNK_SIZE: usize = 16 * 1024; // Moderate sized buffer
+
+/// States of the scan through the bytes of a line
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+enum Scan {
+    #[default]
+    LineStart, // At the start of a line
+    Dot,     // After a dot at the start of a line
+    Text,    // Within a line of text
+    DotLine, // Within a dot command (or comment) line, which is dropped
+    Escape,  // After a printer escape character
+}
+
+// "STRIPPER" OBJECT
+
+/// Holds the state of the scan through the input, so that a line can span
+/// the boundary between two chunks
+#[derive(Debug, Default)]
+struct Stripper {
+    scan: Scan,
+    ended: bool, // Whether the last byte written was a line feed (or none written)
+}
+
+impl Stripper {
+    /// Appends the stripped text of the given chunk of 8-bit input (up to
+    /// any EOF marker) to the output
+    ///
+    /// # Arguments
+    ///
+    /// * `chunk` - Byte (u8) slice of 8-bit input, up to any EOF marker
+    /// * `preserve` - Control characters to be passed through unchanged
+    /// * `output` - Vector to which the stripped bytes are appended
+    ///
+    fn strip(&mut self, chunk: &[u8], preserve: &[char], output: &mut Vec<u8>) {
+        let start = output.len();
+        for &byte in chunk {
+            let b = byte & ASCII_MASK;
+            let c = char::from(b);
+            self.scan = match (self.scan, b) {
+                (Scan::DotLine, b'\n') => Scan::LineStart,
+                (Scan::DotLine, _) => Scan::DotLine,
+                (Scan::LineStart, b'.') => Scan::Dot,
+                (Scan::Dot, _) if b == b'.' || b.is_ascii_alphabetic() => Scan::DotLine,
+                (Scan::Escape, _) => Scan::Text,
+                (scan, _) => {
+                    if scan == Scan::Dot {
+                        output.push(b'.');
+                    }
+                    match c {
+                        '\n' => {
+                            output.push(b);
+                            Scan::LineStart
+                        }
+                        _ if preserve.contains(&c) => {
+                            output.push(b);
+                            Scan::Text
+                        }
+                        ws_chars::ESCAPE => Scan::Escape,
+                        '\t' => {
+                            output.push(b);
+                            Scan::Text
+                        }
+                        ws_chars::NON_BREAKING_SPACE => {
+                            output.push(b' ');
+                            Scan::Text
+                        }
+                        ws_chars::ACTIVE_SOFT_HYPHEN => {
+                            output.push(b'-');
+                            Scan::Text
+                        }
+                        _ if b.is_ascii_control() => Scan::Text,
+                        _ => {
+                            output.push(b);
+                            Scan::Text
+                        }
+                    }
+                }
+            };
+        }
+        if output.len() > start {
+            self.ended = output.last() == Some(&b'\n');
+        }
+    }
+
+    /// Appends anything held back at the end of the input (i.e. a lone dot
+    /// at the start of the last line) to the output, followed by a line feed
+    /// if the text does not already end with one
+    ///
+    /// # Arguments
+    ///
+    /// * `output` - Vector to which the stripped bytes are appended
+    ///
+    fn finish(&mut self, output: &mut Vec<u8>) {
+        if self.scan == Scan::Dot {
+            output.push(b'.');
+            self.ended = false;
+        }
+        if !self.ended {
+            output.push(b'\n');
+            self.ended = true;
+        }
+        self.scan = Scan::LineStart;
+    }
+}
+
+// EXTERNAL PUBLIC FUNCTIONS
+
+/// Strips a WordStar file from the given input down to plain 7-bit ASCII
+/// text on the given output, in a single pass that drops dot commands,
+/// printer escapes and control characters other than those to be preserved
+/// (see the module notes)
+///
+/// Reading stops at any EOF marker (0x1A), and the text always ends with a
+/// line feed (unless it is empty), so that the text of several inputs can be
+/// joined.
+///
+/// Returns `()` on success or a `std::io::Error` type on failure
+///
+/// # Arguments
+///
+/// * `input` - Source of bytes that implements `Read` trait
+/// * `output` - Destination for bytes that implements `Write` trait
+/// * `preserve` - Control characters to be passed through unchanged
+///
+/// # Examples
+/// ```
+/// let mut output = Vec::new();
+/// strip_file(&mut &b".he Title\r\n\x02Bold\x02 text\r\n"[..], &mut output, &[]).unwrap();
+/// assert_eq!(output, b"Bold text\n");
+/// ```
+pub fn strip_file(
+    input: &mut dyn Read,
+    output: &mut dyn Write,
+    preserve: &[char],
+) -> io::Result<()> {
+    let mut stripper = Stripper {
+        ended: true,
+        ..Default::default()
+    };
+    let mut buf = vec![0; CHUNK_SIZE];
+    let mut stripped = Vec::with_capacity(CHUNK_SIZE);
+    loop {
+        let len = input.read(&mut buf)?;
+        if len == 0 {
+            break;
+        }
+        let eof = buf[..len].iter().position(|&b| b == EOF_BYTE);
+        stripper.strip(&buf[..eof.unwrap_or(len)], preserve, &mut stripped);
+        output.write_all(&stripped)?;
+        stripped.clear();
+        if eof.is_some() {
+            break;
+        }
+    }
+    stripper.finish(&mut stripped);
+    output.write_all(&stripped)
+}
+
+// Unit tests
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn strip(input: &[u8]) -> String {
+        strip_preserving(input, &[])
+    }
+
+    fn strip_preserving(input: &[u8], preserve: &[char]) -> String {
+        let mut output = Vec::new();
+        strip_file(&mut &input[..], &mut output, preserve).unwrap();
+        String::from_utf8(output).unwrap()
+    }
+
+    #[test]
+    fn test_strip_file() {
+        assert_eq!(strip(b""), "");
+        assert_eq!(strip(b".he Title\r\n\x02Bold\x02 text\r\n"), "Bold text\n");
+        assert_eq!(strip(b"..comment\r\n.pa\r\nnext"), "next\n");
+        assert_eq!(strip(b". not a command\n.5\n."), ". not a command\n.5\n.\n");
+        assert_eq!(
+            strip(b"soft\x8D\nline\r\n\r\nend\r\n"),
+            "soft\nline\n\nend\n"
+        );
+        assert_eq!(strip(b"a\x1BEb\x1B\x1Bc\x1B"), "abc\n");
+        assert_eq!(strip(b"hy\x1Ephen and line\x1F\r\n"), "hyphen and line-\n");
+        assert_eq!(strip(b"a\x0Fb\tc\x7Fd\x13e"), "a b\tcde\n");
+        assert_eq!(strip(b"word\xF3\r\n\x1Agarbage"), "words\n");
+        assert_eq!(strip(b"\x02.pa\r\n"), ".pa\n");
+    }
+
+    #[test]
+    fn test_preserve() {
+        let input = b"a\tb\x0Cc\x8Cd\x01e\x0F\r\n";
+        assert_eq!(strip_preserving(input, &[]), "a\tbcde \n");
+        assert_eq!(strip_preserving(input, &['\x0C']), "a\tb\x0Cc\x0Cde \n");
+        assert_eq!(strip_preserving(input, &['\x0F', '\r']), "a\tbcde\x0F\r\n");
+        assert_eq!(strip_preserving(b"\x0C.pa\n", &['\x0C']), "\x0C.pa\n");
+    }
+
+    #[test]
+    fn test_chunks() {
+        let mut stripper = Stripper::default();
+        let mut output = Vec::new();
+        stripper.strip(b"one\n.", &[], &mut output);
+        stripper.strip(b"pa\n.", &[], &mut output);
+        stripper.strip(b" two", &[], &mut output);
+        stripper.finish(&mut output);
+        assert_eq!(output, b"one\n. two\n");
+    }
+}
@@ -0,0 +1,165 @@
+//! Module to maintain counts of the styled spans of text converted
+
+use crate::ws_wrappers::{Span, Style, STYLE_NAMES};
+use std::fmt;
+
+/// Holds a name tag for a set of counters, a 'used' marker, the style of the
+/// last span scanned and the number of spans started for each text attribute
+/// (in the order of `ws_wrappers::STYLE_NAMES`)
+#[derive(Debug)]
+pub struct StyleCount {
+    tag: String,
+    used: bool,
+    previous: Style,
+    counts: [i32; STYLE_NAMES.len()],
+}
+
+/// Display trait implementation for StyleCount, starting with the name tag,
+/// then listing each text attribute that has been used with its count
+impl fmt::Display for StyleCount {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: ", self.tag)?;
+        if !self.used {
+            write!(f, "Skipped")?;
+        } else {
+            let mut previous = false;
+            for ((_, name), value) in STYLE_NAMES.iter().zip(&self.counts) {
+                if *value == 0 {
+                    continue;
+                }
+                if previous {
+                    write!(f, ", ")?;
+                }
+                write!(f, "{}={}", name, value)?;
+                previous = true;
+            }
+            if previous {
+                write!(f, " => {} span(s)", self.total())?;
+            } else {
+                write!(f, "None")?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl StyleCount {
+    /// Returns a set of counters with the given name tag
+    ///
+    /// # Arguments
+    ///
+    /// * `tag` - String containing name tag (moved into `StyleCount` struct)
+    ///
+    /// # Examples
+    /// ```
+    /// let mut counts = StyleCount::new("name".to_string());
+    /// ```
+    pub fn new(tag: String) -> StyleCount {
+        StyleCount {
+            tag,
+            used: false,
+            previous: Style::empty(),
+            counts: [0; STYLE_NAMES.len()],
+        }
+    }
+
+    /// Increments (by one) the count for each text attribute that is started
+    /// by each of the given spans, i.e. that was not set for the span before
+    ///
+    /// Spans carry on from one call to the next, so an attribute that is
+    /// still set at the start of a line is not counted again.
+    ///
+    /// # Arguments
+    ///
+    /// * `spans` - Slice of spans to be scanned
+    ///
+    /// # Examples
+    /// ```
+    /// let mut counts = StyleCount::new("name".to_string());
+    /// counts.scan(&[Span { style: Style::BOLD, text: "b".to_string() }]);
+    /// ```
+    pub fn scan(&mut self, spans: &[Span]) {
+        for span in spans.iter().filter(|span| !span.text.is_empty()) {
+            let started = span.style - self.previous;
+            for ((flag, _), value) in STYLE_NAMES.iter().zip(&mut self.counts) {
+                if started.contains(*flag) {
+                    *value += 1;
+                }
+            }
+            self.previous = span.style;
+        }
+        self.used = true;
+    }
+
+    /// Returns the count of spans started for the given text attribute
+    ///
+    /// # Arguments
+    ///
+    /// * `flag` - Single text attribute
+    ///
+    /// # Examples
+    /// ```
+    /// let counts = StyleCount::new("name".to_string());
+    /// assert_eq!(counts.get(Style::BOLD), 0);
+    /// ```
+    #[allow(dead_code)]
+    pub fn get(&self, flag: Style) -> i32 {
+        STYLE_NAMES
+            .iter()
+            .zip(&self.counts)
+            .find(|((name_flag, _), _)| *name_flag == flag)
+            .map_or(0, |(_, value)| *value)
+    }
+
+    /// Returns total count of spans started for all text attributes
+    pub fn total(&self) -> i32 {
+        self.counts.iter().sum()
+    }
+}
+
+// Unit tests
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn span(style: Style, text: &str) -> Span {
+        Span {
+            style,
+            text: text.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_scan() {
+        let mut counts = StyleCount::new("Emphasis".to_string());
+        counts.scan(&[
+            span(Style::empty(), "a"),
+            span(Style::BOLD, "b"),
+            span(Style::BOLD | Style::ITALIC, "c"),
+        ]);
+        counts.scan(&[span(Style::BOLD, "d"), span(Style::empty(), "e")]);
+        counts.scan(&[span(Style::SUBSCRIPT, ""), span(Style::BOLD, "f")]);
+        assert_eq!(counts.get(Style::BOLD), 2);
+        assert_eq!(counts.get(Style::ITALIC), 1);
+        assert_eq!(counts.get(Style::SUBSCRIPT), 0);
+        assert_eq!(counts.total(), 3);
+    }
+
+    #[test]
+    fn test_display() {
+        let mut counts = StyleCount::new("Emphasis".to_string());
+        assert_eq!(format!("{}", counts), "Emphasis: Skipped");
+        counts.scan(&[span(Style::empty(), "a")]);
+        assert_eq!(format!("{}", counts), "Emphasis: None");
+        counts.scan(&[
+            span(Style::UNDERLINE, "u"),
+            span(Style::empty(), " "),
+            span(Style::UNDERLINE | Style::SUPERSCRIPT, "2"),
+        ]);
+        assert_eq!(
+            format!("{}", counts),
+            "Emphasis: underline=2, superscript=1 => 3 span(s)"
+        );
+    }
+}
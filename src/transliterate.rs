@@ -0,0 +1,426 @@
+//! Module to transliterate converted Unicode text into strict Latin-1 or
+//! ASCII text, for downstream tools that cannot handle other characters
+
+// Text attributes that the filters mapped to Unicode characters (e.g. bold
+// letters from the Mathematical Alphanumeric Symbols block) are mapped back
+// to plain characters, with markers around each run of them (e.g. "*bold*").
+// Accents placed as combining characters are composed with their letters
+// where Latin-1 has a single character for them, and other symbols are
+// spelt out (e.g. "1/3" for a fraction).  Characters with no equivalent are
+// replaced with "?".
+
+use crate::uni_chars;
+use crate::ws_mappings;
+use std::borrow::Cow;
+use std::io::{self, Write};
+
+type Mapping = fn(char) -> Option<char>; // Maps a plain character with an attribute
+
+const UNKNOWN: &str = "?"; // Replaces a character with no equivalent
+const LATIN1_MAX: char = '\u{FF}'; // Last character in Latin-1
+const ASCII_MAX: char = '\u{7F}'; // Last character in ASCII
+
+// ASCII equivalents of the Latin-1 characters from U+00A0 to U+00FF
+const LATIN1_ASCII: [&str; 96] = [
+    " ", "!", "c", "GBP", "?", "JPY", "|", "S", "\"", "(C)", "a", "<<", "-", "", "(R)", "-", "o",
+    "+/-", "^2", "^3", "'", "u", "P", ".", ",", "^1", "o", ">>", "1/4", "1/2", "3/4", "?", "A",
+    "A", "A", "A", "A", "A", "AE", "C", "E", "E", "E", "E", "I", "I", "I", "I", "D", "N", "O", "O",
+    "O", "O", "O", "x", "O", "U", "U", "U", "U", "Y", "Th", "ss", "a", "a", "a", "a", "a", "a",
+    "ae", "c", "e", "e", "e", "e", "i", "i", "i", "i", "d", "n", "o", "o", "o", "o", "o", "/", "o",
+    "u", "u", "u", "u", "y", "th", "y",
+];
+
+// Latin-1 characters formed from a letter and a combining accent
+const COMPOSED: [(char, char, char); 51] = [
+    ('A', uni_chars::COMB_GRAVE, '\u{00C0}'),
+    ('A', uni_chars::COMB_ACUTE, '\u{00C1}'),
+    ('A', uni_chars::COMB_CIRCUMFLEX, '\u{00C2}'),
+    ('A', uni_chars::COMB_TILDE, '\u{00C3}'),
+    ('A', uni_chars::COMB_DIAERESIS, '\u{00C4}'),
+    ('C', uni_chars::COMB_CEDILLA, '\u{00C7}'),
+    ('E', uni_chars::COMB_GRAVE, '\u{00C8}'),
+    ('E', uni_chars::COMB_ACUTE, '\u{00C9}'),
+    ('E', uni_chars::COMB_CIRCUMFLEX, '\u{00CA}'),
+    ('E', uni_chars::COMB_DIAERESIS, '\u{00CB}'),
+    ('I', uni_chars::COMB_GRAVE, '\u{00CC}'),
+    ('I', uni_chars::COMB_ACUTE, '\u{00CD}'),
+    ('I', uni_chars::COMB_CIRCUMFLEX, '\u{00CE}'),
+    ('I', uni_chars::COMB_DIAERESIS, '\u{00CF}'),
+    ('N', uni_chars::COMB_TILDE, '\u{00D1}'),
+    ('O', uni_chars::COMB_GRAVE, '\u{00D2}'),
+    ('O', uni_chars::COMB_ACUTE, '\u{00D3}'),
+    ('O', uni_chars::COMB_CIRCUMFLEX, '\u{00D4}'),
+    ('O', uni_chars::COMB_TILDE, '\u{00D5}'),
+    ('O', uni_chars::COMB_DIAERESIS, '\u{00D6}'),
+    ('U', uni_chars::COMB_GRAVE, '\u{00D9}'),
+    ('U', uni_chars::COMB_ACUTE, '\u{00DA}'),
+    ('U', uni_chars::COMB_CIRCUMFLEX, '\u{00DB}'),
+    ('U', uni_chars::COMB_DIAERESIS, '\u{00DC}'),
+    ('Y', uni_chars::COMB_ACUTE, '\u{00DD}'),
+    ('a', uni_chars::COMB_GRAVE, '\u{00E0}'),
+    ('a', uni_chars::COMB_ACUTE, '\u{00E1}'),
+    ('a', uni_chars::COMB_CIRCUMFLEX, '\u{00E2}'),
+    ('a', uni_chars::COMB_TILDE, '\u{00E3}'),
+    ('a', uni_chars::COMB_DIAERESIS, '\u{00E4}'),
+    ('c', uni_chars::COMB_CEDILLA, '\u{00E7}'),
+    ('e', uni_chars::COMB_GRAVE, '\u{00E8}'),
+    ('e', uni_chars::COMB_ACUTE, '\u{00E9}'),
+    ('e', uni_chars::COMB_CIRCUMFLEX, '\u{00EA}'),
+    ('e', uni_chars::COMB_DIAERESIS, '\u{00EB}'),
+    ('i', uni_chars::COMB_GRAVE, '\u{00EC}'),
+    ('i', uni_chars::COMB_ACUTE, '\u{00ED}'),
+    ('i', uni_chars::COMB_CIRCUMFLEX, '\u{00EE}'),
+    ('i', uni_chars::COMB_DIAERESIS, '\u{00EF}'),
+    ('n', uni_chars::COMB_TILDE, '\u{00F1}'),
+    ('o', uni_chars::COMB_GRAVE, '\u{00F2}'),
+    ('o', uni_chars::COMB_ACUTE, '\u{00F3}'),
+    ('o', uni_chars::COMB_CIRCUMFLEX, '\u{00F4}'),
+    ('o', uni_chars::COMB_TILDE, '\u{00F5}'),
+    ('o', uni_chars::COMB_DIAERESIS, '\u{00F6}'),
+    ('u', uni_chars::COMB_GRAVE, '\u{00F9}'),
+    ('u', uni_chars::COMB_ACUTE, '\u{00FA}'),
+    ('u', uni_chars::COMB_CIRCUMFLEX, '\u{00FB}'),
+    ('u', uni_chars::COMB_DIAERESIS, '\u{00FC}'),
+    ('y', uni_chars::COMB_ACUTE, '\u{00FD}'),
+    ('y', uni_chars::COMB_DIAERESIS, '\u{00FF}'),
+];
+
+// Latin-1 (or else ASCII) equivalents of other Unicode characters
+const SYMBOLS: [(char, &str); 33] = [
+    ('\u{2010}', "-"),    // Hyphen
+    ('\u{2011}', "-"),    // Non-breaking hyphen
+    ('\u{2012}', "-"),    // Figure dash
+    ('\u{2013}', "-"),    // En dash
+    ('\u{2014}', "--"),   // Em dash
+    ('\u{2015}', "--"),   // Horizontal bar
+    ('\u{2018}', "'"),    // Left single quotation mark
+    ('\u{2019}', "'"),    // Right single quotation mark
+    ('\u{201A}', ","),    // Single low quotation mark
+    ('\u{201C}', "\""),   // Left double quotation mark
+    ('\u{201D}', "\""),   // Right double quotation mark
+    ('\u{201E}', "\""),   // Double low quotation mark
+    ('\u{2022}', "*"),    // Bullet
+    ('\u{2026}', "..."),  // Ellipsis
+    ('\u{203E}', "-"),    // Overline
+    ('\u{20AC}', "EUR"),  // Euro sign
+    ('\u{2122}', "(TM)"), // Trade mark sign
+    ('\u{2153}', "1/3"),  // Vulgar fractions
+    ('\u{2154}', "2/3"),
+    ('\u{2155}', "1/5"),
+    ('\u{2156}', "2/5"),
+    ('\u{2157}', "3/5"),
+    ('\u{2158}', "4/5"),
+    ('\u{2159}', "1/6"),
+    ('\u{215A}', "5/6"),
+    ('\u{215B}', "1/8"),
+    ('\u{215C}', "3/8"),
+    ('\u{215D}', "5/8"),
+    ('\u{215E}', "7/8"),
+    ('\u{2264}', "<="), // Less-than or equal to
+    ('\u{2265}', ">="), // Greater-than or equal to
+    ('\u{23AF}', "-"),  // Horizontal line extension (page breaks)
+    ('\u{2588}', "#"),  // Full block
+];
+
+/// Character sets to which the output can be restricted
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub enum Charset {
+    #[default]
+    Unicode, // No restriction (output is left unchanged)
+    Latin1, // ISO 8859-1 characters only (still encoded as UTF-8)
+    Ascii,  // 7-bit ASCII characters only
+}
+
+impl Charset {
+    /// Returns the last character in this character set
+    fn max_char(self) -> char {
+        match self {
+            Charset::Unicode => char::MAX,
+            Charset::Latin1 => LATIN1_MAX,
+            Charset::Ascii => ASCII_MAX,
+        }
+    }
+}
+
+/// Text attributes that are marked around runs of transliterated text
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Marker {
+    Bold,
+    Italic,
+    Underline,
+    Superscript,
+    Subscript,
+}
+
+impl Marker {
+    /// Returns the (open, close) markers placed around a run of text with
+    /// this attribute
+    fn markers(self) -> (&'static str, &'static str) {
+        match self {
+            Marker::Bold => ("*", "*"),
+            Marker::Italic | Marker::Underline => ("_", "_"),
+            Marker::Superscript => ("^", ""),
+            Marker::Subscript => ("_", ""),
+        }
+    }
+}
+
+// PRIVATE HELPER FUNCTIONS
+
+/// Returns `true` if the given character is a combining mark, otherwise
+/// `false`
+///
+/// # Arguments
+///
+/// * `c` - Character to be examined
+///
+fn is_combining(c: char) -> bool {
+    matches!(c, '\u{0300}'..='\u{036F}' | '\u{20D0}'..='\u{20FF}')
+}
+
+/// Returns `Some((plain, marker))` if the given character is a plain
+/// character mapped to Unicode with a text attribute (e.g. a bold letter),
+/// otherwise `None`
+///
+/// # Arguments
+///
+/// * `c` - Character to be examined
+///
+/// # Examples
+/// ```
+/// assert_eq!(unstyle('\u{1D402}'), Some(('C', Marker::Bold)));
+/// ```
+fn unstyle(c: char) -> Option<(char, Marker)> {
+    if c.is_ascii() {
+        return None;
+    }
+    let mappings: [(Mapping, Marker); 5] = [
+        (ws_mappings::get_bold, Marker::Bold),
+        (ws_mappings::get_italic, Marker::Italic),
+        (ws_mappings::get_bold_italic, Marker::Bold),
+        (ws_mappings::get_superscript, Marker::Superscript),
+        (ws_mappings::get_subscript, Marker::Subscript),
+    ];
+    mappings.iter().find_map(|(get, marker)| {
+        (' '..='~')
+            .find(|&plain| get(plain) == Some(c))
+            .map(|plain| (plain, *marker))
+    })
+}
+
+/// Appends the equivalent of the given character in the given character
+/// set to the given String
+///
+/// # Arguments
+///
+/// * `result` - String to which the equivalent is appended
+/// * `c` - Character to be transliterated
+/// * `charset` - Character set to which the output is restricted
+///
+fn push_char(result: &mut String, c: char, charset: Charset) {
+    if c <= charset.max_char() {
+        result.push(c);
+    } else if c <= LATIN1_MAX {
+        result.push_str(LATIN1_ASCII[c as usize - 0xA0]);
+    } else {
+        let text = SYMBOLS
+            .iter()
+            .find(|(symbol, _)| *symbol == c)
+            .map_or(UNKNOWN, |(_, text)| *text);
+        result.push_str(text);
+    }
+}
+
+// EXTERNAL PUBLIC FUNCTIONS
+
+/// Returns the given text slice transliterated into the given character set,
+/// borrowed if it is already within the character set or otherwise owned
+///
+/// # Arguments
+///
+/// * `s` - Slice of text to be transliterated
+/// * `charset` - Character set to which the output is restricted
+///
+/// # Examples
+/// ```
+/// assert_eq!(transliterate("\u{1D401}\u{1D428} \u{00BD}", Charset::Ascii), "*Bo* 1/2");
+/// ```
+pub fn transliterate(s: &str, charset: Charset) -> Cow<'_, str> {
+    let max = charset.max_char();
+    if s.chars().all(|c| c <= max) {
+        return Cow::Borrowed(s);
+    }
+
+    // Transliterate each character (with any combining marks after it)
+    let mut items: Vec<(String, Option<Marker>)> = Vec::new();
+    let mut chars = s.chars().peekable();
+    while let Some(c) = chars.next() {
+        let mut marks = Vec::new();
+        while let Some(&mark) = chars.peek().filter(|&&mark| is_combining(mark)) {
+            marks.push(mark);
+            chars.next();
+        }
+        let (mut base, mut marker) = match Some(c).filter(|&c| c > max).and_then(unstyle) {
+            Some((plain, marker)) => (plain, Some(marker)),
+            None => (c, None),
+        };
+        if marks.contains(&uni_chars::COMB_UNDERLINE) && charset != Charset::Unicode {
+            marker = marker.or(Some(Marker::Underline));
+        }
+        if let Some(&(_, _, composed)) = COMPOSED
+            .iter()
+            .find(|(letter, mark, _)| *letter == base && marks.contains(mark))
+        {
+            base = composed;
+        }
+        let mut text = String::new();
+        push_char(&mut text, base, charset);
+        items.push((text, marker));
+    }
+
+    // Extend runs across spaces between items with the same marker
+    for i in 1..items.len() {
+        if items[i].1.is_none() && items[i].0.trim().is_empty() {
+            let next = items[i + 1..]
+                .iter()
+                .find(|(text, _)| !text.trim().is_empty());
+            if next.is_some_and(|(_, marker)| marker.is_some() && *marker == items[i - 1].1) {
+                items[i].1 = items[i - 1].1;
+            }
+        }
+    }
+
+    let mut result = String::with_capacity(s.len());
+    let mut current = None;
+    for (text, marker) in items {
+        if marker != current {
+            if let Some(open) = current {
+                result.push_str(Marker::markers(open).1);
+            }
+            if let Some(new) = marker {
+                result.push_str(new.markers().0);
+            }
+            current = marker;
+        }
+        result.push_str(&text);
+    }
+    if let Some(open) = current {
+        result.push_str(open.markers().1);
+    }
+    Cow::Owned(result)
+}
+
+// "TRANSLITERATOR" OBJECT
+
+/// Destination for bytes that transliterates each line of UTF-8 text written
+/// to it into a given character set before passing it on to another
+/// destination (any incomplete last line is passed on when flushed)
+pub struct Transliterator<W: Write> {
+    inner: W,
+    charset: Charset,
+    line: Vec<u8>,
+}
+
+impl<W: Write> Transliterator<W> {
+    /// Returns a new `Transliterator` object that passes lines transliterated
+    /// into the given character set on to the given destination
+    pub fn new(inner: W, charset: Charset) -> Self {
+        Transliterator {
+            inner,
+            charset,
+            line: Vec::new(),
+        }
+    }
+
+    /// Passes on the line held so far (if any) after transliterating it
+    fn write_line(&mut self) -> io::Result<()> {
+        let line = String::from_utf8_lossy(&self.line);
+        let text = transliterate(&line, self.charset);
+        self.inner.write_all(text.as_bytes())?;
+        self.line.clear();
+        Ok(())
+    }
+}
+
+impl<W: Write> Write for Transliterator<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        for &byte in buf {
+            self.line.push(byte);
+            if byte == b'\n' {
+                self.write_line()?;
+            }
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.write_line()?;
+        self.inner.flush()
+    }
+}
+
+// Unit tests
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_unstyle() {
+        assert_eq!(unstyle('\u{1D402}'), Some(('C', Marker::Bold)));
+        assert_eq!(unstyle('\u{210E}'), Some(('h', Marker::Italic)));
+        assert_eq!(unstyle('\u{00B2}'), Some(('2', Marker::Superscript)));
+        assert_eq!(unstyle('\u{2082}'), Some(('2', Marker::Subscript)));
+        assert_eq!(unstyle('\u{00E9}'), None);
+        assert_eq!(unstyle('a'), None);
+    }
+
+    #[test]
+    fn test_transliterate() {
+        let bold = "\u{1D401}\u{1D428}\u{1D425}\u{1D41D} \u{1D42D}\u{1D41E}\u{1D431}\u{1D42D}";
+        assert_eq!(transliterate(bold, Charset::Ascii), "*Bold text*");
+        assert_eq!(
+            transliterate("\u{1D44E} \u{1D41B} x", Charset::Latin1),
+            "_a_ *b* x"
+        );
+        assert_eq!(
+            transliterate("\u{00BD} \u{2153}", Charset::Latin1),
+            "\u{00BD} 1/3"
+        );
+        assert_eq!(
+            transliterate("\u{00BD} \u{2153}", Charset::Ascii),
+            "1/2 1/3"
+        );
+        assert_eq!(
+            transliterate("e\u{0301}t\u{00E9}", Charset::Latin1),
+            "\u{00E9}t\u{00E9}"
+        );
+        assert_eq!(transliterate("e\u{0301}t\u{00E9}", Charset::Ascii), "ete");
+        assert_eq!(
+            transliterate("a\u{0332}b\u{0332} c", Charset::Ascii),
+            "_ab_ c"
+        );
+        assert_eq!(
+            transliterate("x\u{00B2} H\u{2082}O", Charset::Latin1),
+            "x\u{00B2} H_2O"
+        );
+        assert_eq!(transliterate("x\u{00B2}", Charset::Ascii), "x^2");
+        assert_eq!(transliterate("\u{4E2D}", Charset::Latin1), "?");
+        assert!(matches!(
+            transliterate("plain", Charset::Ascii),
+            Cow::Borrowed("plain")
+        ));
+        assert_eq!(transliterate("\u{1D402}", Charset::Unicode), "\u{1D402}");
+    }
+
+    #[test]
+    fn test_transliterator() {
+        let mut out = Vec::new();
+        let mut writer = Transliterator::new(&mut out, Charset::Ascii);
+        let text = "\u{00BD}\nend\u{2026}".as_bytes();
+        writer.write_all(&text[..1]).unwrap(); // Split within a character
+        writer.write_all(&text[1..]).unwrap();
+        writer.flush().unwrap();
+        assert_eq!(String::from_utf8(out).unwrap(), "1/2\nend...");
+    }
+}
@@ -21,9 +21,20 @@ pub const BOLD_ITALIC_LOWER_A: char = '\u{1D482}';
 // Unicode strings for substitution (actually all single characters)
 // (used in ws_special module)
 pub const DEGREE: &str = "\u{00B0}"; // Degree symbol
+pub const FEMININE_ORDINAL: &str = "\u{00AA}"; // Feminine ordinal indicator
+pub const MASCULINE_ORDINAL: &str = "\u{00BA}"; // Masculine ordinal indicator
+pub const COPYRIGHT: &str = "\u{00A9}"; // Copyright sign
+pub const REGISTERED: &str = "\u{00AE}"; // Registered sign
+pub const TRADE_MARK: &str = "\u{2122}"; // Trade mark sign
 pub const ONE_QUARTER: &str = "\u{00BC}"; // 1/4 symbol
 pub const HALF: &str = "\u{00BD}"; // 1/2 symbol
 pub const THREE_QUARTERS: &str = "\u{00BE}"; // 3/4 symbol
+pub const ONE_THIRD: &str = "\u{2153}"; // 1/3 symbol
+pub const TWO_THIRDS: &str = "\u{2154}"; // 2/3 symbol
+pub const ONE_EIGHTH: &str = "\u{215B}"; // 1/8 symbol
+pub const THREE_EIGHTHS: &str = "\u{215C}"; // 3/8 symbol
+pub const FIVE_EIGHTHS: &str = "\u{215D}"; // 5/8 symbol
+pub const SEVEN_EIGHTHS: &str = "\u{215E}"; // 7/8 symbol
 pub const REPLACEMENT: &str = "\u{FFFD}"; // Invalid marker
 
 // Unicode modifiers (added after relevant printable character)
@@ -32,6 +43,18 @@ pub const COMB_OVERLINE: char = '\u{0305}'; // Combining overline
 pub const COMB_UNDERLINE: char = '\u{0332}'; // Combining underline
 pub const COMB_STRIKETHROUGH: char = '\u{0336}'; // Combining strikethrough
 
+// Unicode combining marks for overprinted characters
+// (used in ws_overprint module)
+pub const COMB_GRAVE: char = '\u{0300}'; // Combining grave accent
+pub const COMB_ACUTE: char = '\u{0301}'; // Combining acute accent
+pub const COMB_CIRCUMFLEX: char = '\u{0302}'; // Combining circumflex accent
+pub const COMB_TILDE: char = '\u{0303}'; // Combining tilde
+pub const COMB_DIAERESIS: char = '\u{0308}'; // Combining diaeresis
+pub const COMB_CEDILLA: char = '\u{0327}'; // Combining cedilla
+pub const COMB_SHORT_STROKE: char = '\u{0335}'; // Combining short stroke overlay
+pub const COMB_LONG_SOLIDUS: char = '\u{0338}'; // Combining long solidus overlay
+pub const COMB_VERTICAL_LINE: char = '\u{20D2}'; // Combining long vertical line overlay
+
 // Unicode strings for substitution (actually all single characters)
 // (used in ws_control module)
 pub const NB_SPACE: &str = "\u{00A0}"; // Non-breaking space
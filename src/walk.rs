@@ -0,0 +1,281 @@
+//! Module to expand the inputs of a batch conversion, walking any
+//! directories among them (in recursive mode) to find the files inside
+
+// Archive trees often hold symbolic links that form loops and metadata
+// directories (e.g. ".git" or "__MACOSX") that should not be converted, so
+// the walk follows the given policy for symbolic links, hidden files and
+// excluded paths.  When symbolic links are followed, each directory is
+// visited only once (by its canonical path) so that a loop cannot trap it.
+
+use std::collections::HashSet;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// Holds the policy for walking directories in recursive mode
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct WalkPolicy {
+    pub follow_symlinks: bool, // Whether to follow (rather than ignore) symbolic links
+    pub skip_hidden: bool,     // Whether to skip files and directories named ".*"
+    pub exclude: Vec<String>,  // Patterns ("*" and "?" wildcards) for paths to skip
+}
+
+/// Holds the path to an input file found by `expand()`, together with its
+/// path relative to the directory given as an input (or just its file name
+/// if it was given itself), for naming its output file
+#[derive(Debug, Clone, PartialEq)]
+pub struct Input {
+    pub path: String,
+    pub relative: PathBuf,
+}
+
+// PRIVATE HELPER FUNCTIONS
+
+/// Returns `true` if the given text matches the given pattern, in which "*"
+/// matches any run of characters (including none) and "?" matches any
+/// single character, otherwise `false`
+///
+/// # Arguments
+///
+/// * `pattern` - Pattern to be matched
+/// * `text` - Text to be checked
+///
+/// # Examples
+/// ```
+/// assert!(matches_pattern("*.bak", "letter.bak"));
+/// ```
+fn matches_pattern(pattern: &str, text: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let text: Vec<char> = text.chars().collect();
+    let (mut p, mut t) = (0, 0);
+    let mut backtrack = None; // Positions after the last "*" and its match
+    while t < text.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                p += 1;
+                backtrack = Some((p, t));
+            }
+            Some(&c) if c == '?' || c == text[t] => {
+                p += 1;
+                t += 1;
+            }
+            _ => match backtrack {
+                Some((after_star, matched)) => {
+                    p = after_star;
+                    t = matched + 1;
+                    backtrack = Some((after_star, t));
+                }
+                None => return false,
+            },
+        }
+    }
+    pattern[p..].iter().all(|&c| c == '*')
+}
+
+/// Returns `true` if the given relative path (written with "/" separators)
+/// or any of its components matches one of the exclude patterns of the
+/// given policy, otherwise `false`
+///
+/// # Arguments
+///
+/// * `relative` - Path relative to the directory given as an input
+/// * `policy` - Policy holding the exclude patterns
+///
+fn is_excluded(relative: &Path, policy: &WalkPolicy) -> bool {
+    let components: Vec<String> = relative
+        .components()
+        .map(|component| component.as_os_str().to_string_lossy().into_owned())
+        .collect();
+    let joined = components.join("/");
+    policy.exclude.iter().any(|pattern| {
+        matches_pattern(pattern, &joined)
+            || components
+                .iter()
+                .any(|component| matches_pattern(pattern, component))
+    })
+}
+
+/// Adds the files in the given directory (and its subdirectories) to the
+/// given list of inputs, in order of name, following the given policy
+///
+/// Returns `()` on success or a `std::io::Error` type on failure
+///
+/// # Arguments
+///
+/// * `dir` - Path to directory to be walked
+/// * `relative` - Path of the directory relative to the input directory
+/// * `policy` - Policy for symbolic links, hidden files and excluded paths
+/// * `visited` - Canonical paths of the directories visited so far
+/// * `inputs` - List to which the files found are added
+///
+fn walk_dir(
+    dir: &Path,
+    relative: &Path,
+    policy: &WalkPolicy,
+    visited: &mut HashSet<PathBuf>,
+    inputs: &mut Vec<Input>,
+) -> io::Result<()> {
+    if !visited.insert(fs::canonicalize(dir)?) {
+        log::warn!(
+            code = "skipped-visited";
+            "Skipped '{}': directory already visited",
+            dir.display()
+        );
+        return Ok(());
+    }
+    let mut entries = fs::read_dir(dir)?.collect::<io::Result<Vec<_>>>()?;
+    entries.sort_by_key(|entry| entry.file_name());
+    for entry in entries {
+        let path = entry.path();
+        let name = entry.file_name();
+        let entry_relative = relative.join(&name);
+        if policy.skip_hidden && name.to_string_lossy().starts_with('.') {
+            log::info!(code = "skipped-hidden"; "Skipped '{}': hidden", path.display());
+            continue;
+        }
+        if is_excluded(&entry_relative, policy) {
+            log::info!(code = "skipped-excluded"; "Skipped '{}': excluded", path.display());
+            continue;
+        }
+        if entry.file_type()?.is_symlink() && !policy.follow_symlinks {
+            log::info!(code = "skipped-symlink"; "Skipped '{}': symbolic link", path.display());
+            continue;
+        }
+        let metadata = fs::metadata(&path)?; // Follows any symbolic link
+        if metadata.is_dir() {
+            walk_dir(&path, &entry_relative, policy, visited, inputs)?;
+        } else if metadata.is_file() {
+            inputs.push(Input {
+                path: path.to_string_lossy().into_owned(),
+                relative: entry_relative,
+            });
+        }
+    }
+    Ok(())
+}
+
+// EXTERNAL PUBLIC FUNCTIONS
+
+/// Returns the list of input files given by the given paths, with each
+/// directory among them replaced by the files found by walking it (in order
+/// of name) if `recursive` is `true`, or a `std::io::Error` type on failure
+///
+/// Paths that match one of the exclude patterns of the policy are left out.
+/// The other parts of the policy apply only to the files and directories
+/// found by walking a directory.
+///
+/// # Arguments
+///
+/// * `paths` - Paths to input files or directories
+/// * `recursive` - Flag to walk any directories
+/// * `policy` - Policy for symbolic links, hidden files and excluded paths
+///
+/// # Examples
+/// ```
+/// let inputs = walk::expand(&["archive".to_string()], true, &WalkPolicy::default()).unwrap();
+/// ```
+pub fn expand(paths: &[String], recursive: bool, policy: &WalkPolicy) -> io::Result<Vec<Input>> {
+    let mut inputs = Vec::new();
+    let mut visited = HashSet::new();
+    for path in paths {
+        let name = Path::new(path).file_name().map(PathBuf::from);
+        let relative = name.unwrap_or_default();
+        if is_excluded(Path::new(path), policy) {
+            log::info!("Skipped '{}': excluded", path);
+        } else if recursive && Path::new(path).is_dir() {
+            walk_dir(
+                Path::new(path),
+                Path::new(""),
+                policy,
+                &mut visited,
+                &mut inputs,
+            )?;
+        } else {
+            inputs.push(Input {
+                path: path.clone(),
+                relative,
+            });
+        }
+    }
+    Ok(inputs)
+}
+
+// Unit tests
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_matches_pattern() {
+        assert!(matches_pattern("*.bak", "letter.bak"));
+        assert!(!matches_pattern("*.bak", "letter.ws"));
+        assert!(matches_pattern("__MACOSX", "__MACOSX"));
+        assert!(matches_pattern("ch?.ws", "ch1.ws"));
+        assert!(!matches_pattern("ch?.ws", "ch10.ws"));
+        assert!(matches_pattern("old/*", "old/a/b.ws"));
+        assert!(matches_pattern("*a*b*", "xaybz"));
+        assert!(!matches_pattern("*a*b", "xaybz"));
+        assert!(matches_pattern("*", ""));
+    }
+
+    #[test]
+    fn test_is_excluded() {
+        let policy = WalkPolicy {
+            exclude: vec!["*.bak".to_string(), "old/*".to_string()],
+            ..Default::default()
+        };
+        assert!(is_excluded(Path::new("a/letter.bak"), &policy));
+        assert!(is_excluded(Path::new("old/letter.ws"), &policy));
+        assert!(!is_excluded(Path::new("new/letter.ws"), &policy));
+    }
+
+    #[test]
+    fn test_expand() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        fs::create_dir_all(root.join("sub")).unwrap();
+        fs::create_dir_all(root.join(".git")).unwrap();
+        for name in ["b.ws", "a.ws", "sub/c.ws", "sub/c.bak", ".git/d", ".e.ws"] {
+            fs::write(root.join(name), "text").unwrap();
+        }
+        let paths = [root.to_string_lossy().into_owned()];
+        let policy = WalkPolicy {
+            skip_hidden: true,
+            exclude: vec!["*.bak".to_string()],
+            ..Default::default()
+        };
+        let inputs = expand(&paths, true, &policy).unwrap();
+        let relative: Vec<&Path> = inputs
+            .iter()
+            .map(|input| input.relative.as_path())
+            .collect();
+        assert_eq!(
+            relative,
+            [Path::new("a.ws"), Path::new("b.ws"), Path::new("sub/c.ws")]
+        );
+        let inputs = expand(&paths, true, &WalkPolicy::default()).unwrap();
+        assert_eq!(inputs.len(), 6);
+        let inputs = expand(&paths, false, &policy).unwrap();
+        assert_eq!(inputs.len(), 1);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_expand_symlinks() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        fs::create_dir_all(root.join("sub")).unwrap();
+        fs::write(root.join("sub/a.ws"), "text").unwrap();
+        std::os::unix::fs::symlink(root, root.join("sub/loop")).unwrap();
+        let paths = [root.to_string_lossy().into_owned()];
+        let inputs = expand(&paths, true, &WalkPolicy::default()).unwrap();
+        assert_eq!(inputs.len(), 1);
+        let policy = WalkPolicy {
+            follow_symlinks: true,
+            ..Default::default()
+        };
+        let inputs = expand(&paths, true, &policy).unwrap();
+        assert_eq!(inputs.len(), 1); // Loop back to root is not walked again
+    }
+}
@@ -123,7 +123,7 @@ fn align_reverse(s: &str, wrapper: char) -> Option<(String, bool)> {
 ///
 /// Note also that whitespace characters may still appear within text between each
 /// pair of wrapper characters -- just not at either end.
-////
+///
 /// # Arguments
 ///
 /// * `s` - Slice of text to be scanned
@@ -135,7 +135,7 @@ fn align_reverse(s: &str, wrapper: char) -> Option<(String, bool)> {
 fn align_bothways(s: &str, wrapper: char) -> Option<String> {
     let (result, changed_rev) = align_reverse(s, wrapper)?;
     let (result, changed_fwd) = align_reverse(&result, wrapper)?;
-    (changed_fwd || changed_rev).then(|| result)
+    (changed_fwd || changed_rev).then_some(result)
 }
 
 // EXTERNAL PUBLIC FUNCTIONS
@@ -144,23 +144,24 @@ fn align_bothways(s: &str, wrapper: char) -> Option<String> {
 /// that have been re-aligned outside any pairs of wrapper characters, otherwise `None`
 ///
 /// This function calls `align_bothways()` for each of the wrapper characters defined
-/// in `WRAPPERS_TO_ALIGN`, potentially updating the result further at each successive
+/// in `WRAPPERS_TO_ALIGN` and then for each of the given additional wrapper
+/// characters, potentially updating the result further at each successive
 /// iteration.  If any changes are made at all, then `Some(replacement)` is returned,
 /// otherwise `None`.
 ///
 /// # Arguments
 ///
 /// * `s` - Slice of text to be processed
+/// * `extra` - Slice of additional (user-defined) wrapper characters
 ///
 /// # Examples
 /// ```
-/// assert_eq!(process("\x18\x13 a \x13\x18"), Some(" \x18\x13a\x13\x18 ".to_string()));
+/// assert_eq!(process("\x18\x13 a \x13\x18", &[]), Some(" \x18\x13a\x13\x18 ".to_string()));
 /// ```
-
-pub fn process(s: &str) -> Option<String> {
+pub fn process(s: &str, extra: &[char]) -> Option<String> {
     let mut result: Option<String> = None;
     let mut line = s;
-    for wrapper in &WRAPPERS_TO_ALIGN {
+    for wrapper in WRAPPERS_TO_ALIGN.iter().chain(extra) {
         result = align_bothways(line, *wrapper).or(result);
         line = result.as_deref().unwrap_or(s);
     }
@@ -234,22 +235,27 @@ mod tests {
     #[test]
     fn test_process() {
         assert_eq!(
-            process("\x13  abc  \x13"),
+            process("\x13  abc  \x13", &[]),
             Some("  \x13abc\x13  ".to_string())
         );
         assert_eq!(
-            process(" \x18 abc \x18 "),
+            process(" \x18 abc \x18 ", &[]),
             Some("  \x18abc\x18  ".to_string())
         );
         assert_eq!(
-            process("\x18\x13  abc  \x13\x18"),
+            process("\x18\x13  abc  \x13\x18", &[]),
             Some("  \x18\x13abc\x13\x18  ".to_string())
         );
         assert_eq!(
-            process(" \x18  \x13 abc \x01 def \x13 \x01\x18"),
+            process(" \x18  \x13 abc \x01 def \x13 \x01\x18", &[]),
             Some("    \x18\x13abc  \x01def\x13\x01\x18  ".to_string())
         );
-        assert_eq!(process("abcd"), None);
-        assert_eq!(process(""), None);
+        assert_eq!(process("abcd", &[]), None);
+        assert_eq!(process("", &[]), None);
+        assert_eq!(process("\x11 a \x11", &[]), None);
+        assert_eq!(
+            process("\x11 a \x11", &['\x11']),
+            Some(" \x11a\x11 ".to_string())
+        );
     }
 }
@@ -1,6 +1,6 @@
 //! Module containing WordStar control characters
 
-// Used in ws_align, ws_wrappers, ws_emphasis and ws_special modules
+// Used in ws_align, ws_wrappers, ws_emphasis, ws_pitch and ws_special modules
 pub const OVERLINE: char = '\x01';
 pub const BOLD: char = '\x02';
 pub const DOUBLE: char = '\x04';
@@ -10,8 +10,17 @@ pub const SUPERSCRIPT: char = '\x14';
 pub const SUBSCRIPT: char = '\x16';
 pub const STRIKETHROUGH: char = '\x18';
 pub const ITALIC: char = '\x19';
+pub const PITCH: char = '\x0E'; // Normalised toggle from ws_pitch module
 pub const UNDERSCORE: char = '_';
 
+// Used in ws_pitch module
+pub const ALTERNATE_PITCH: char = '\x01';
+pub const NORMAL_PITCH: char = '\x0E';
+pub const FONT_TAG: char = '\x1D'; // Starts and ends a WordStar 5+ font tag
+
+// Used in ws_escape module
+pub const ESCAPE: char = '\x1B'; // Starts a printer escape sequence
+
 // Used in ws_control module
 pub const PHANTOM_SPACE: char = '\x06'; // Daisywheel printer spare slot!
 pub const PHANTOM_RUBOUT: char = '\x07'; // Daisywheel printer spare slot!
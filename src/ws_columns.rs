@@ -0,0 +1,439 @@
+//! Module to linearize text laid out in newspaper-style columns into reading order
+
+// WordStar 5 and later can lay out text in columns (set with the `.co` dot
+// command), and some documents emulate this by hand.  Either way, each line
+// of the file holds text from every column side by side, so reading it
+// line by line interleaves the columns.  Blocks of such lines are split at
+// the gutters between columns (character positions that are blank in every
+// line of the block) and written out one column after another.
+//
+// Character positions are counted in printable characters only, so that
+// wrapper control characters do not disturb the alignment.
+
+use crate::ws_dot_cmd;
+use std::collections::VecDeque;
+use std::io;
+
+const MIN_GUTTER: usize = 3; // Narrowest gap accepted as a gutter when detecting
+const MIN_LINES: usize = 3; // Fewest lines in a block when detecting
+const MIN_WORDS: usize = 3; // Fewest average words per column line when detecting
+
+// PRIVATE HELPER FUNCTIONS
+
+/// Returns the byte offsets of each printable (non-control) character in the
+/// given text slice, so that printable column `n` starts at byte `offsets[n]`
+///
+/// # Arguments
+///
+/// * `s` - Slice of text to be scanned
+///
+/// # Examples
+/// ```
+/// assert_eq!(printable_offsets("a\x02bc"), vec![0, 2, 3]);
+/// ```
+fn printable_offsets(s: &str) -> Vec<usize> {
+    s.char_indices()
+        .filter(|(_, c)| !c.is_ascii_control())
+        .map(|(i, _)| i)
+        .collect()
+}
+
+/// Returns a list of gutters as (start, end) printable column ranges in which
+/// every one of the given lines is blank, excluding any blank columns at the
+/// left-hand side
+///
+/// # Arguments
+///
+/// * `lines` - Slice of lines to be examined
+///
+/// # Examples
+/// ```
+/// assert_eq!(find_gutters(&["ab  cd", "e   fg"]), vec![(2, 4)]);
+/// ```
+fn find_gutters(lines: &[&str]) -> Vec<(usize, usize)> {
+    let rows: Vec<Vec<char>> = lines
+        .iter()
+        .map(|line| line.chars().filter(|c| !c.is_ascii_control()).collect())
+        .collect();
+    let width = rows.iter().map(Vec::len).max().unwrap_or(0);
+    let is_blank = |col: usize| {
+        rows.iter()
+            .all(|row| row.get(col).is_none_or(|c| *c == ' '))
+    };
+
+    let mut gutters = Vec::new();
+    let mut start = None;
+    let mut seen_text = false;
+    for col in 0..width {
+        if is_blank(col) {
+            if seen_text && start.is_none() {
+                start = Some(col);
+            }
+        } else {
+            if let Some(begin) = start.take() {
+                gutters.push((begin, col));
+            }
+            seen_text = true;
+        }
+    }
+    gutters
+}
+
+/// Returns the given line split into one segment per column, with the given
+/// cut points as printable column numbers where each new column starts
+///
+/// Control characters are kept with the text that follows them, except
+/// for those immediately after the last character of a column (e.g. closing
+/// wrapper characters), which are kept with that column.
+///
+/// # Arguments
+///
+/// * `line` - Slice of text to be split
+/// * `cuts` - Printable column numbers at which to split the line
+///
+fn split_line<'a>(line: &'a str, cuts: &[usize]) -> Vec<&'a str> {
+    let offsets = printable_offsets(line);
+    let mut segments = Vec::with_capacity(cuts.len() + 1);
+    let mut begin = 0;
+    for cut in cuts {
+        let end = offsets.get(*cut).copied().unwrap_or(line.len());
+        segments.push(&line[begin..end]);
+        begin = end;
+    }
+    segments.push(&line[begin..]);
+    segments
+}
+
+/// Returns `true` if the given column segments look like running text, with
+/// text on at least half of the lines and an average of at least `MIN_WORDS`
+/// words on each of those lines (so that tables are not mistaken for columns)
+///
+/// # Arguments
+///
+/// * `segments` - Slice of text segments from one column of a block
+///
+fn is_text_column(segments: &[&str]) -> bool {
+    let used: Vec<&&str> = segments.iter().filter(|s| !s.trim().is_empty()).collect();
+    let words: usize = used.iter().map(|s| s.split_whitespace().count()).sum();
+    used.len() * 2 >= segments.len() && words >= used.len() * MIN_WORDS
+}
+
+/// Returns `Some(lines)` holding the given block of numbered lines rearranged
+/// into column order, or `None` if the columns cannot be identified
+///
+/// If a number of columns is given then the widest gutters are used to
+/// separate them.  Otherwise, the block is only treated as two columns if
+/// it has enough lines and a gutter at least `MIN_GUTTER` characters wide
+/// with running text on both sides.
+///
+/// # Arguments
+///
+/// * `block` - Slice of (line number, line) pairs to be linearized
+/// * `columns` - Number of columns (if known)
+///
+fn linearize(block: &[(usize, String)], columns: Option<usize>) -> Option<Vec<(usize, String)>> {
+    let lines: Vec<&str> = block.iter().map(|(_, line)| line.as_str()).collect();
+    let mut gutters = find_gutters(&lines);
+    gutters.sort_by(|a, b| (b.1 - b.0).cmp(&(a.1 - a.0)).then(a.0.cmp(&b.0)));
+    match columns {
+        Some(n) => gutters.truncate(n.saturating_sub(1)),
+        None => {
+            gutters.truncate(1);
+            let (start, end) = *gutters.first()?;
+            let split: Vec<Vec<&str>> = lines
+                .iter()
+                .map(|line| split_line(line, &[start]))
+                .collect();
+            let left: Vec<&str> = split.iter().map(|segments| segments[0]).collect();
+            let right: Vec<&str> = split.iter().map(|segments| segments[1]).collect();
+            if lines.len() < MIN_LINES
+                || end - start < MIN_GUTTER
+                || !is_text_column(&left)
+                || !is_text_column(&right)
+            {
+                return None;
+            }
+        }
+    }
+    if gutters.is_empty() {
+        return None;
+    }
+    gutters.sort();
+    let cuts: Vec<usize> = gutters.iter().map(|(start, _)| *start).collect();
+
+    let split: Vec<Vec<&str>> = lines.iter().map(|line| split_line(line, &cuts)).collect();
+    let mut result = Vec::with_capacity(block.len());
+    for column in 0..=cuts.len() {
+        let mut segments: Vec<(usize, String)> = block
+            .iter()
+            .zip(&split)
+            .map(|((line_no, _), segments)| {
+                let text = segments[column].trim_end();
+                let text = if column > 0 { text.trim_start() } else { text };
+                (*line_no, text.to_string())
+            })
+            .collect();
+        while segments.last().is_some_and(|(_, text)| text.is_empty()) {
+            segments.pop();
+        }
+        result.append(&mut segments);
+    }
+    Some(result)
+}
+
+/// Returns `Some(columns)` if the given line is a `.co` dot command that sets
+/// the number of columns, otherwise `None`
+fn column_command(line: &str) -> Option<usize> {
+    let (cmd, text) = ws_dot_cmd::split_dot_cmd(line)?;
+    if cmd != "co" {
+        return None;
+    }
+    let count = text.trim().split(',').next()?.trim();
+    Some(count.parse().unwrap_or(1))
+}
+
+// "COLUMNS" ITERATOR
+
+/// Iterator adapter that numbers a sequence of lines (from 1) and rearranges
+/// any blocks laid out in columns into reading order, keeping the original
+/// line number of each part of a line
+pub struct Columns<I> {
+    lines: I,
+    detect: bool,
+    line_no: usize,
+    columns: Option<usize>, // Number of columns set by `.co` (if more than one)
+    held: Option<(usize, String)>, // Line that ended a block, to be read again
+    pending: VecDeque<(usize, String)>,
+    done: bool,
+}
+
+impl<I> Columns<I>
+where
+    I: Iterator<Item = io::Result<String>>,
+{
+    /// Creates a new `Columns` iterator over the given lines, in which columns
+    /// are identified from `.co` dot commands and also (if `detect` is `true`)
+    /// from the alignment of the text in each paragraph
+    pub fn new(lines: I, detect: bool) -> Self {
+        Columns {
+            lines,
+            detect,
+            line_no: 0,
+            columns: None,
+            held: None,
+            pending: VecDeque::new(),
+            done: false,
+        }
+    }
+
+    /// Returns the line that ended the last block (if held back), otherwise
+    /// the next numbered line from the underlying iterator, if any
+    fn read(&mut self) -> io::Result<Option<(usize, String)>> {
+        if let Some(held) = self.held.take() {
+            return Ok(Some(held));
+        }
+        match self.lines.next() {
+            Some(line) => {
+                self.line_no += 1;
+                Ok(Some((self.line_no, line?)))
+            }
+            None => {
+                self.done = true;
+                Ok(None)
+            }
+        }
+    }
+
+    /// Reads lines after the given first line of a block up to the first
+    /// one that ends the block, as decided by the given function, holding
+    /// back that line to be read again (e.g. so that a `.co` dot command
+    /// ending the block takes effect), and returns the block
+    fn read_block<F>(
+        &mut self,
+        first: (usize, String),
+        is_end: F,
+    ) -> io::Result<Vec<(usize, String)>>
+    where
+        F: Fn(&str) -> bool,
+    {
+        let mut block = vec![first];
+        while let Some((line_no, line)) = self.read()? {
+            if is_end(&line) {
+                self.held = Some((line_no, line));
+                break;
+            }
+            block.push((line_no, line));
+        }
+        Ok(block)
+    }
+
+    /// Adds the given block to the pending lines, linearized if possible
+    fn queue_block(&mut self, block: Vec<(usize, String)>, columns: Option<usize>) {
+        match linearize(&block, columns) {
+            Some(lines) => self.pending.extend(lines),
+            None => self.pending.extend(block),
+        }
+    }
+
+    /// Reads one or more lines and adds them to the pending lines
+    ///
+    /// The number of columns set by a `.co` dot command stays in force (e.g.
+    /// across page breaks, which end each block of columns) until the next
+    /// `.co` dot command.
+    fn fill(&mut self) -> io::Result<()> {
+        let (line_no, line) = match self.read()? {
+            Some(numbered) => numbered,
+            None => return Ok(()),
+        };
+        if let Some(columns) = column_command(&line) {
+            self.columns = (columns > 1).then_some(columns);
+            self.pending.push_back((line_no, line));
+        } else if let Some(columns) = self.columns.filter(|_| !ws_dot_cmd::is_page_break(&line)) {
+            let block = self.read_block((line_no, line), |s| {
+                column_command(s).is_some() || ws_dot_cmd::is_page_break(s)
+            })?;
+            self.queue_block(block, Some(columns));
+        } else if self.detect
+            && !line.trim().is_empty()
+            && ws_dot_cmd::split_dot_cmd(&line).is_none()
+        {
+            let block = self.read_block((line_no, line), |s| {
+                s.trim().is_empty() || ws_dot_cmd::split_dot_cmd(s).is_some()
+            })?;
+            self.queue_block(block, None);
+        } else {
+            self.pending.push_back((line_no, line));
+        }
+        Ok(())
+    }
+}
+
+impl<I> Iterator for Columns<I>
+where
+    I: Iterator<Item = io::Result<String>>,
+{
+    type Item = io::Result<(usize, String)>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(numbered) = self.pending.pop_front() {
+                return Some(Ok(numbered));
+            }
+            if self.done && self.held.is_none() {
+                return None;
+            }
+            if let Err(e) = self.fill() {
+                return Some(Err(e));
+            }
+        }
+    }
+}
+
+// Unit tests
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn numbered(lines: &[&str]) -> Vec<(usize, String)> {
+        lines
+            .iter()
+            .enumerate()
+            .map(|(i, line)| (i + 1, line.to_string()))
+            .collect()
+    }
+
+    fn run(lines: &[&str], detect: bool) -> Vec<(usize, String)> {
+        let input = lines.iter().map(|line| Ok(line.to_string()));
+        Columns::new(input, detect).map(Result::unwrap).collect()
+    }
+
+    #[test]
+    fn test_printable_offsets() {
+        assert_eq!(printable_offsets("a\x02bc"), vec![0, 2, 3]);
+        assert_eq!(printable_offsets(""), Vec::<usize>::new());
+    }
+
+    #[test]
+    fn test_find_gutters() {
+        assert_eq!(find_gutters(&["ab  cd", "e   fg"]), vec![(2, 4)]);
+        assert_eq!(
+            find_gutters(&["  ab  c  d", "  e  f"]),
+            vec![(4, 5), (7, 9)]
+        );
+        assert_eq!(find_gutters(&["abc", "def"]), vec![]);
+        assert_eq!(find_gutters(&[]), vec![]);
+    }
+
+    #[test]
+    fn test_split_line() {
+        assert_eq!(split_line("ab\x02  cd", &[2]), vec!["ab\x02", "  cd"]);
+        assert_eq!(split_line("ab", &[4]), vec!["ab", ""]);
+    }
+
+    #[test]
+    fn test_linearize() {
+        let block = numbered(&["a b c    g h i", "d e f    j k l", "x y z"]);
+        let expected = vec![
+            (1, "a b c".to_string()),
+            (2, "d e f".to_string()),
+            (3, "x y z".to_string()),
+            (1, "g h i".to_string()),
+            (2, "j k l".to_string()),
+        ];
+        assert_eq!(linearize(&block, None), Some(expected));
+        let table = numbered(&["Clock:    On", "Trigger:  Off", "Arm:      Off"]);
+        assert_eq!(linearize(&table, None), None);
+        assert_eq!(linearize(&table, Some(2)).map(|lines| lines.len()), Some(6));
+        let narrow = numbered(&["a b c  g h i", "d e f  j k l", "x y z  m n o"]);
+        assert_eq!(linearize(&narrow, None), None);
+        assert_eq!(linearize(&numbered(&["a"]), Some(2)), None);
+    }
+
+    #[test]
+    fn test_columns_dot_command() {
+        let lines = [".co 2", "a   c", "b", ".co 1", "x   y"];
+        let expected = [
+            (1, ".co 2"),
+            (2, "a"),
+            (3, "b"),
+            (2, "c"),
+            (4, ".co 1"),
+            (5, "x   y"),
+        ];
+        let expected: Vec<(usize, String)> =
+            expected.iter().map(|(n, s)| (*n, s.to_string())).collect();
+        assert_eq!(run(&lines, false), expected);
+        let lines = [".co 2", "a   c", ".pa", "d   f", ".xl", "g   i"];
+        let text: Vec<String> = run(&lines, false).into_iter().map(|(_, s)| s).collect();
+        assert_eq!(text, [".co 2", "a", "c", ".pa", "d", "f", ".xl", "g", "i"]);
+    }
+
+    #[test]
+    fn test_columns_detect_then_command() {
+        let lines = [
+            "a a a    d d d",
+            "b b b    e e e",
+            "c c c    f f f",
+            ".co 2",
+            "x   y",
+        ];
+        let order: Vec<usize> = run(&lines, true).iter().map(|(n, _)| *n).collect();
+        assert_eq!(order, vec![1, 2, 3, 1, 2, 3, 4, 5, 5]);
+    }
+
+    #[test]
+    fn test_columns_detect() {
+        let lines = [
+            "a a a    d d d",
+            "b b b    e e e",
+            "c c c    f f f",
+            "",
+            "plain text",
+        ];
+        let order: Vec<usize> = run(&lines, true).iter().map(|(n, _)| *n).collect();
+        assert_eq!(order, vec![1, 2, 3, 1, 2, 3, 4, 5]);
+        let order: Vec<usize> = run(&lines, false).iter().map(|(n, _)| *n).collect();
+        assert_eq!(order, vec![1, 2, 3, 4, 5]);
+    }
+}
@@ -4,6 +4,12 @@ use crate::uni_chars;
 use crate::ws_chars;
 use std::char;
 
+/// Type of a function that is called with each control character that is
+/// not recognised (and the number of the line holding it), returning
+/// `Some(replacement)` to replace the character or `None` to leave it to
+/// the usual treatment
+pub type ControlHandler = fn(char, usize) -> Option<String>;
+
 // PRIVATE HELPER FUNCTIONS
 
 /// Returns `Some(replacement)` if the given character is a standalone WordStar
@@ -51,7 +57,7 @@ fn get_mapping(c: char) -> Option<&'static str> {
 /// ```
 /// assert_eq!(get_escaped('\x03'), Some("^C".to_string()));
 /// ```
-fn get_escaped(c: char) -> Option<String> {
+pub(crate) fn get_escaped(c: char) -> Option<String> {
     let printable = match c as u32 {
         u @ 0..=0x1F => char::from_u32(u + '@' as u32),
         0x7F => Some('#'),
@@ -63,7 +69,7 @@ fn get_escaped(c: char) -> Option<String> {
     Some(escaped)
 }
 
-// EXTERNAL PUBLIC FUNCTION
+// EXTERNAL PUBLIC FUNCTIONS
 
 /// Returns `Some(replacement)` if the given text slice contains control characters
 /// that have been converted to an alternative representation, otherwise `None`
@@ -72,24 +78,55 @@ fn get_escaped(c: char) -> Option<String> {
 /// WordStar control characters to suitable alternatives; the second (only carried
 /// out if `escape` is `true`), maps remaining ASCII control characters to their
 /// "escaped" form (a sequence of '^' plus a corresponding printable character).
+/// Any control characters in `preserve` (e.g. tabs) are left untouched.
+///
+/// # Arguments
+///
+/// * `s` - Slice of text to be processed
+/// * `escape` - Flag to convert unrecognised ASCII control characters to '^' format
+/// * `preserve` - Control characters to be passed through unchanged
+///
+/// # Examples
+/// ```
+/// assert_eq!(process("a\x0Fb", true, &[]), Some("a\u{00A0}b".to_string()));
+/// assert_eq!(process("a\tb", true, &['\t']), None);
+/// ```
+pub fn process(s: &str, escape: bool, preserve: &[char]) -> Option<String> {
+    process_with(s, escape, preserve, |_| None)
+}
+
+/// Returns `Some(replacement)` if the given text slice contains control
+/// characters that have been converted to an alternative representation,
+/// otherwise `None`, in the same way as `process` except that unrecognised
+/// control characters are first offered to the given function
 ///
 /// # Arguments
 ///
 /// * `s` - Slice of text to be processed
 /// * `escape` - Flag to convert unrecognised ASCII control characters to '^' format
+/// * `preserve` - Control characters to be passed through unchanged
+/// * `unknown` - Function returning `Some(replacement)` for an unrecognised
+///   control character, or `None` to leave it to the usual treatment
 ///
 /// # Examples
 /// ```
-/// assert_eq!(process("a\x0Fb", true), Some("a\u{00A0}b".to_string()));
+/// let note = |c| (c == '\x05').then(|| "[note]".to_string());
+/// assert_eq!(process_with("a\x05b", true, &[], note), Some("a[note]b".to_string()));
 /// ```
-pub fn process(s: &str, escape: bool) -> Option<String> {
+pub fn process_with<F>(s: &str, escape: bool, preserve: &[char], mut unknown: F) -> Option<String>
+where
+    F: FnMut(char) -> Option<String>,
+{
     let mut changed = false;
     let mut result = String::with_capacity(s.len() * 2);
     for c in s.chars() {
-        if c.is_ascii_control() {
+        if c.is_ascii_control() && !preserve.contains(&c) {
             if let Some(substitute) = get_mapping(c) {
                 result.push_str(substitute);
                 changed = true;
+            } else if let Some(substitute) = unknown(c) {
+                result.push_str(&substitute);
+                changed = true;
             } else if escape {
                 if let Some(substitute) = get_escaped(c) {
                     result.push_str(&substitute);
@@ -101,10 +138,29 @@ pub fn process(s: &str, escape: bool) -> Option<String> {
                 result.push(c); // Not escaping unmatched chars
             }
         } else {
-            result.push(c); // Not a control character
+            result.push(c); // Not a control character (or preserved)
         }
     }
-    changed.then(|| result)
+    changed.then_some(result)
+}
+
+/// Returns the number of ASCII control characters in the given text slice
+/// that are neither recognised standalone WordStar control characters nor
+/// preserved, i.e. those that are left unconverted or escaped by `process()`
+///
+/// # Arguments
+///
+/// * `s` - Slice of text to be examined
+/// * `preserve` - Control characters to be passed through unchanged
+///
+/// # Examples
+/// ```
+/// assert_eq!(count_unknown("a\x0Fb\x01\t", &['\t']), 1);
+/// ```
+pub fn count_unknown(s: &str, preserve: &[char]) -> usize {
+    s.chars()
+        .filter(|&c| c.is_ascii_control() && get_mapping(c).is_none() && !preserve.contains(&c))
+        .count()
 }
 
 // Unit tests
@@ -135,23 +191,53 @@ mod tests {
     #[test]
     fn test_process() {
         assert_eq!(
-            process("ab\x0Fcd\x1Eef\x1Fgh", true),
+            process("ab\x0Fcd\x1Eef\x1Fgh", true, &[]),
             Some("ab\u{00A0}cd\u{2010}ef\u{2010}gh".to_string())
         );
         assert_eq!(
-            process("\x14ab\x06cd\x1Eef\x01", true),
+            process("\x14ab\x06cd\x1Eef\x01", true, &[]),
             Some("^Tab\u{2588}cd\u{2010}ef^A".to_string())
         );
         assert_eq!(
-            process("\x14ab\x06cd\x1Eef\x01", false),
+            process("\x14ab\x06cd\x1Eef\x01", false, &[]),
             Some("\x14ab\u{2588}cd\u{2010}ef\x01".to_string())
         );
-        assert_eq!(process("\x14abcde\x01", false), None);
+        assert_eq!(process("\x14abcde\x01", false, &[]), None);
         assert_eq!(
-            process("abc\x06def", true),
+            process("abc\x06def", true, &[]),
             Some("abc\u{2588}def".to_string())
         );
-        assert_eq!(process("abcd", true), None);
-        assert_eq!(process("", true), None);
+        assert_eq!(process("abcd", true, &[]), None);
+        assert_eq!(
+            process("a\tb\x0Cc\x0Fd\x01", true, &['\t', '\x0C', '\x0F']),
+            Some("a\tb\x0Cc\x0Fd^A".to_string())
+        );
+        assert_eq!(process("a\tb\x0Cc", true, &['\t', '\x0C']), None);
+        assert_eq!(process("", true, &[]), None);
+    }
+
+    #[test]
+    fn test_count_unknown() {
+        assert_eq!(count_unknown("a\x0Fb\x01\x7F\x14", &[]), 2);
+        assert_eq!(count_unknown("abc", &[]), 0);
+        assert_eq!(count_unknown("a\tb\x0C\x01", &['\t', '\x0C']), 1);
+    }
+
+    #[test]
+    fn test_process_with() {
+        let note = |c| (c == '\x05').then(|| "[note]".to_string());
+        assert_eq!(
+            process_with("a\x05b\x01", true, &[], note),
+            Some("a[note]b^A".to_string())
+        );
+        assert_eq!(
+            process_with("a\x05b\x01", false, &[], note),
+            Some("a[note]b\x01".to_string())
+        );
+        assert_eq!(
+            process_with("a\x0Fb", true, &[], |_| Some("X".to_string())),
+            Some("a\u{00A0}b".to_string())
+        );
+        assert_eq!(process_with("a\x01b", false, &[], |_| None), None);
     }
 }
@@ -2,6 +2,151 @@
 
 use crate::uni_chars;
 use crate::ws_chars;
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::str::FromStr;
+
+// Dot commands documented for WordStar versions up to 7 (in lower case)
+const KNOWN_DOT_CMDS: [&str; 65] = [
+    "av", "bn", "bp", "cc", "co", "cp", "cs", "cv", "cw", "dm", "f1", "f2", "f3", "f4", "f5", "fi",
+    "fm", "fo", "ft", "h1", "h2", "h3", "h4", "h5", "he", "hm", "ig", "ix", "lh", "lm", "lq", "ls",
+    "ma", "mb", "mt", "oc", "oj", "op", "pa", "pc", "pe", "pf", "pg", "pl", "pm", "pn", "po", "pr",
+    "ps", "rm", "rp", "rr", "rv", "sr", "sv", "tb", "tc", "uj", "ul", "wc", "xe", "xl", "xq", "xr",
+    "xw",
+];
+
+// Dot commands for the merge printing built into NewWord (in lower case) that
+// are not in the WordStar table above
+const NEWWORD_DOT_CMDS: [&str; 1] = ["df"];
+
+// Dot commands that set the text of a header or footer line (in lower case)
+const MARGIN_DOT_CMDS: [&str; 12] = [
+    "he", "fo", "h1", "h2", "h3", "h4", "h5", "f1", "f2", "f3", "f4", "f5",
+];
+
+// Dot commands that take a single value (in lower case), so that any further
+// words on the line are a suffix rather than part of the command
+const VALUE_DOT_CMDS: [&str; 13] = [
+    "cp", "cw", "fm", "hm", "lm", "ls", "mb", "mt", "pc", "pl", "pn", "po", "rm",
+];
+
+// Dot commands that take no value (in lower case)
+const BARE_DOT_CMDS: [&str; 1] = ["pa"];
+
+const SUFFIX_MARKER: &str = ".."; // Starts a comment after the text of a command
+const COMMENT_DOT_CMD: &str = "ig"; // Whole line is a comment (so has no suffix)
+
+const MAX_MARGIN_LINES: usize = 5; // Most lines in a header or footer
+const MAX_LINE_SPACING: usize = 9; // Widest line spacing set by `.ls`
+const MAX_PAGE_NUMBER: usize = 65535; // Highest page number set by `.pn`
+const MAX_PAGE_OFFSET: usize = 255; // Widest page offset set by `.po`
+const MAX_LAYOUT_VALUE: usize = 255; // Longest page, margin or column set by `.pl` etc.
+const MAX_HEADING_LEVEL: u8 = 6; // Deepest heading level set by a handler
+
+const HANDLER_SEPARATOR: char = '='; // Separates command from action in handlers
+const HANDLER_COMMENT: char = '#'; // Starts a comment line in handlers
+const TEXT_PLACEHOLDER: &str = "{text}"; // Replaced by the text of the command
+
+/// Pages on which a header or footer line is printed
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Pages {
+    All,
+    Even,
+    Odd,
+}
+
+/// Word processors whose files can be read, which differ in the dot commands
+/// that they document and the control characters that they use
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub enum InputFormat {
+    #[default]
+    WordStar, // WordStar (up to version 7)
+    NewWord, // NewWord and other WordStar clones predating WordStar 5
+}
+
+impl InputFormat {
+    /// Returns `true` if files in this format may contain WordStar 5+ font
+    /// tags (between `ws_chars::FONT_TAG` characters), otherwise `false`
+    pub fn has_font_tags(self) -> bool {
+        self == InputFormat::WordStar
+    }
+}
+
+/// Ways of keeping any suffix (i.e. a trailing comment or continuation text)
+/// that follows the text of a dot command
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub enum SuffixMode {
+    Drop, // Remove the suffix along with the command
+    #[default]
+    Text, // Keep the suffix as a line of ordinary text after the command
+    Comment, // Keep the suffix as a comment in the output (if the format has them)
+}
+
+/// Action taken for a dot command by `process_with()`
+#[derive(Debug, Clone, PartialEq)]
+pub enum DotCmdAction {
+    Drop,             // Remove the line from the output
+    Keep,             // Leave the line in the output as ordinary text
+    Header(u8),       // Header text (level 0) or heading at the given level
+    PageBreak,        // Replace the line with a page break
+    Template(String), // Replace the line with text ("{text}" for the command's text)
+}
+
+/// Parses an action as written in a handlers file: "drop", "keep",
+/// "header", "header(n)", "page-break" or a quoted template
+impl FromStr for DotCmdAction {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        let level = s
+            .strip_prefix("header(")
+            .and_then(|rest| rest.strip_suffix(')'));
+        if let Some(level) = level {
+            return match level.trim().parse() {
+                Ok(level) if level <= MAX_HEADING_LEVEL => Ok(DotCmdAction::Header(level)),
+                _ => Err(format!("invalid heading level '{}'", level)),
+            };
+        }
+        let template = s.strip_prefix('"').and_then(|rest| rest.strip_suffix('"'));
+        match (s.to_ascii_lowercase().as_str(), template) {
+            (_, Some(template)) => Ok(DotCmdAction::Template(template.to_string())),
+            ("drop", _) => Ok(DotCmdAction::Drop),
+            ("keep", _) => Ok(DotCmdAction::Keep),
+            ("header", _) => Ok(DotCmdAction::Header(0)),
+            ("page-break", _) => Ok(DotCmdAction::PageBreak),
+            _ => Err(format!("unknown action '{}'", s)),
+        }
+    }
+}
+
+/// Holds a dot command (in lower case) and the action that replaces the
+/// standard action for it (e.g. from a handlers file)
+#[derive(Debug, Clone, PartialEq)]
+pub struct DotCmdHandler {
+    pub cmd: String,
+    pub action: DotCmdAction,
+}
+
+/// Result of applying the action for a dot command with `process_with()`
+#[derive(Debug, PartialEq)]
+pub enum Handled {
+    Removed,             // Line is to be removed from the output
+    PageBreak,           // Line is to be replaced with a page break
+    Text(String),        // Line is to be replaced with the given text
+    Heading(u8, String), // Line is to be replaced with a heading (level, title)
+}
+
+/// Holds the parts of a header or footer dot command: whether it is a footer,
+/// its line number (from 1), the pages on which it is printed and its text
+#[derive(Debug, PartialEq)]
+pub struct MarginCmd<'a> {
+    pub footer: bool,
+    pub line: usize,
+    pub pages: Pages,
+    pub text: &'a str,
+}
 
 // PRIVATE HELPER FUNCTIONS
 
@@ -41,6 +186,31 @@ fn check_dot_cmd(s: &str) -> Option<(&str, Option<&str>)> {
     }
 }
 
+/// Returns `Some(replacement)` if the optional text is present, with a replacement
+/// string as the given wrapper text slice as a prefix and suffix to the optional
+/// text with control characters removed, otherwise `None`
+///
+/// # Arguments
+///
+/// * `wrapper` - Slice of text containing prefix/suffix to the optional text content
+/// * `opt_text` - Must contain `Some(text)` in order to make a new header
+///
+/// # Examples
+/// ```
+/// assert_eq!(make_header("#", Some("hello")), Some("#hello#".to_string()));
+/// ```
+fn make_header(wrapper: &str, opt_text: Option<&str>) -> Option<String> {
+    let text = opt_text?;
+    let mut result = String::new();
+    result.push_str(wrapper);
+    let conv_text = strip_control_chars(text);
+    result.push_str(conv_text.trim());
+    result.push_str(wrapper);
+    Some(result)
+}
+
+// EXTERNAL PUBLIC FUNCTIONS
+
 /// Returns new String formed from given text slice with control characters removed
 ///
 /// # Arguments
@@ -51,42 +221,469 @@ fn check_dot_cmd(s: &str) -> Option<(&str, Option<&str>)> {
 /// ```
 /// assert_eq!(strip_control_chars("\x13ab\x08c"), "abc");
 /// ```
-fn strip_control_chars(s: &str) -> String {
+pub fn strip_control_chars(s: &str) -> String {
     s.chars()
         .filter(|c| !char::is_ascii_control(c))
         .collect::<String>()
 }
 
-/// Returns `Some(replacement)` if the optional text is present, with a replacement
-/// string as the given wrapper text slice as a prefix and suffix to the optional
-/// text with control characters removed, otherwise `None`
+/// Returns the text used to represent a page break in plain text output
+///
+/// # Examples
+/// ```
+/// assert_eq!(page_break_text().chars().count(), 39);
+/// ```
+pub fn page_break_text() -> String {
+    uni_chars::HORIZONTAL_BAR.to_string().repeat(39)
+}
+
+/// Returns `Some(tuple)` if the given text slice is a dot command, otherwise `None`
+///
+/// The returned tuple (if any) holds the two character command in lower case and
+/// the text following it (or "" if there is none).
 ///
 /// # Arguments
 ///
-/// * `wrapper` - Slice of text containing prefix/suffix to the optional text content
-/// * `opt_text` - Must contain `Some(text)` in order to make a new header
+/// * `s` - Slice of text to be examined
 ///
 /// # Examples
 /// ```
-/// assert_eq!(make_header("#", Some("hello")), Some("#hello#".to_string()));
+/// assert_eq!(split_dot_cmd(".PL 60"), Some(("pl".to_string(), " 60")));
 /// ```
-fn make_header(wrapper: &str, opt_text: Option<&str>) -> Option<String> {
-    let text = opt_text?;
-    let mut result = String::new();
-    result.push_str(wrapper);
-    let conv_text = strip_control_chars(text);
-    result.push_str(conv_text.trim());
-    result.push_str(wrapper);
-    Some(result)
+pub fn split_dot_cmd(s: &str) -> Option<(String, &str)> {
+    let (cmd, opt_text) = check_dot_cmd(s)?;
+    Some((cmd.to_ascii_lowercase(), opt_text.unwrap_or("")))
+}
+
+/// Returns `Some(MarginCmd)` if the given (lower case) two character command
+/// and its following text form a header or footer dot command, otherwise `None`
+///
+/// Later versions of WordStar allow up to five header and footer lines, set
+/// either as `.h1`-`.h5`/`.f1`-`.f5` or as `.he`/`.fo` followed immediately
+/// by the line number (e.g. `.he2`).  Either form may then be followed by `E`
+/// or `O` (e.g. `.heE`, `.f2O`) to restrict the line to even or odd pages, as
+/// long as this is followed by a space or the end of the line.
+///
+/// # Arguments
+///
+/// * `cmd` - Slice of text holding the command (in lower case)
+/// * `text` - Slice of text following the command
+///
+/// # Examples
+/// ```
+/// let margin = split_margin_cmd("he", "2O Odd").unwrap();
+/// assert_eq!((margin.line, margin.pages, margin.text), (2, Pages::Odd, " Odd"));
+/// ```
+pub fn split_margin_cmd<'a>(cmd: &str, text: &'a str) -> Option<MarginCmd<'a>> {
+    let mut chars = cmd.chars();
+    let footer = match chars.next()? {
+        'h' => false,
+        'f' => true,
+        _ => return None,
+    };
+    let mut rest = text;
+    let line = match chars.next()? {
+        'e' if !footer => None,
+        'o' if footer => None,
+        c => Some(c.to_digit(10)? as usize),
+    };
+    let line = match line {
+        Some(line) => line,
+        None => match rest.chars().next().and_then(|c| c.to_digit(10)) {
+            Some(digit) => {
+                rest = &rest[1..];
+                digit as usize
+            }
+            None => 1,
+        },
+    };
+    if line == 0 || line > MAX_MARGIN_LINES {
+        return None;
+    }
+    let mut pages = Pages::All;
+    let mut iter = rest.chars();
+    if let Some(c) = iter.next() {
+        if iter.next().is_none_or(|next| next == ' ') {
+            pages = match c {
+                'E' | 'e' => Pages::Even,
+                'O' | 'o' => Pages::Odd,
+                _ => Pages::All,
+            };
+            if pages != Pages::All {
+                rest = &rest[1..];
+            }
+        }
+    }
+    Some(MarginCmd {
+        footer,
+        line,
+        pages,
+        text: rest,
+    })
+}
+
+/// Returns `(args, suffix)` holding the text that belongs to the given
+/// (lower case) two character command and any suffix (trimmed) after it
+///
+/// A suffix is any text following a ".." marker (WordStar's own comment
+/// command) after a space, or else any words following the value of a
+/// command that takes a single value (e.g. `.lm 10 quoted letter`) or
+/// following a command that takes none (e.g. `.pa Part Two`).  The `.ig`
+/// command is itself a comment, so its text is never split.
+///
+/// # Arguments
+///
+/// * `cmd` - Slice of text holding the command (in lower case)
+/// * `text` - Slice of text following the command
+///
+/// # Examples
+/// ```
+/// assert_eq!(split_suffix("he", " Title .. draft"), (" Title", Some("draft")));
+/// assert_eq!(split_suffix("lm", " 10 letter"), (" 10", Some("letter")));
+/// ```
+pub fn split_suffix<'a>(cmd: &str, text: &'a str) -> (&'a str, Option<&'a str>) {
+    if cmd == COMMENT_DOT_CMD {
+        return (text, None);
+    }
+    let marker = text
+        .match_indices(SUFFIX_MARKER)
+        .map(|(i, _)| i)
+        .find(|&i| {
+            let after = &text[i + SUFFIX_MARKER.len()..];
+            text[..i].ends_with(' ') && (after.is_empty() || after.starts_with(' '))
+        });
+    let (args, suffix) = match marker {
+        Some(i) => (text[..i].trim_end(), &text[i + SUFFIX_MARKER.len()..]),
+        None if BARE_DOT_CMDS.contains(&cmd) => ("", text),
+        None if VALUE_DOT_CMDS.contains(&cmd) => {
+            let start = text.len() - text.trim_start().len();
+            match text[start..].find(' ') {
+                Some(end) => (&text[..start + end], &text[start + end..]),
+                None => (text, ""),
+            }
+        }
+        None => (text, ""),
+    };
+    let suffix = suffix.trim();
+    (args, (!suffix.is_empty()).then_some(suffix))
+}
+
+/// Returns `Some(suffix)` if the given text slice is a dot command with a
+/// suffix (see `split_suffix()`), otherwise `None`
+///
+/// # Arguments
+///
+/// * `s` - Slice of text to be examined
+///
+/// # Examples
+/// ```
+/// assert_eq!(suffix(".PA Part Two"), Some("Part Two"));
+/// ```
+pub fn suffix(s: &str) -> Option<&str> {
+    let (cmd, text) = check_dot_cmd(s)?;
+    split_suffix(&cmd.to_ascii_lowercase(), text.unwrap_or("")).1
+}
+
+/// Returns `Some(spacing)` if the given (lower case) two character command
+/// and its following text set the line spacing (e.g. `.ls 2` for double
+/// spacing), otherwise `None`
+///
+/// # Arguments
+///
+/// * `cmd` - Slice of text holding the command (in lower case)
+/// * `text` - Slice of text following the command
+///
+/// # Examples
+/// ```
+/// assert_eq!(line_spacing("ls", " 2"), Some(2));
+/// ```
+pub fn line_spacing(cmd: &str, text: &str) -> Option<usize> {
+    if cmd != "ls" {
+        return None;
+    }
+    split_suffix(cmd, text)
+        .0
+        .trim()
+        .parse()
+        .ok()
+        .filter(|spacing| (1..=MAX_LINE_SPACING).contains(spacing))
+}
+
+/// Returns `Some(number)` if the given (lower case) two character command
+/// and its following text set the number of the current page (e.g. `.pn 5`)
+/// to a number from 1 to 65535, otherwise `None`
+///
+/// # Arguments
+///
+/// * `cmd` - Slice of text holding the command (in lower case)
+/// * `text` - Slice of text following the command
+///
+/// # Examples
+/// ```
+/// assert_eq!(page_number("pn", " 5"), Some(5));
+/// ```
+pub fn page_number(cmd: &str, text: &str) -> Option<usize> {
+    if cmd != "pn" {
+        return None;
+    }
+    split_suffix(cmd, text)
+        .0
+        .trim()
+        .parse()
+        .ok()
+        .filter(|number| (1..=MAX_PAGE_NUMBER).contains(number))
+}
+
+/// Returns `Some(offset)` if the given (lower case) two character command
+/// and its following text set the page offset, i.e. the number of columns
+/// of left margin (e.g. `.po 8`), otherwise `None`
+///
+/// # Arguments
+///
+/// * `cmd` - Slice of text holding the command (in lower case)
+/// * `text` - Slice of text following the command
+///
+/// # Examples
+/// ```
+/// assert_eq!(page_offset("po", " 8"), Some(8));
+/// ```
+pub fn page_offset(cmd: &str, text: &str) -> Option<usize> {
+    if cmd != "po" {
+        return None;
+    }
+    split_suffix(cmd, text)
+        .0
+        .trim()
+        .parse()
+        .ok()
+        .filter(|offset| *offset <= MAX_PAGE_OFFSET)
+}
+
+/// Returns `Some(value)` if the given (lower case) two character command
+/// and its following text set the page length, a margin or the page number
+/// column to a value of at most 255 (e.g. `.pl 66` or `.mt 3`),
+/// otherwise `None`
+///
+/// # Arguments
+///
+/// * `cmd` - Slice of text holding the command (in lower case)
+/// * `text` - Slice of text following the command
+///
+/// # Examples
+/// ```
+/// assert_eq!(layout_value("mt", " 3"), Some(3));
+/// assert_eq!(layout_value("pl", " 1000000000"), None);
+/// ```
+pub fn layout_value(cmd: &str, text: &str) -> Option<usize> {
+    if !matches!(cmd, "pl" | "mt" | "mb" | "hm" | "fm" | "pc") {
+        return None;
+    }
+    split_suffix(cmd, text)
+        .0
+        .trim()
+        .parse()
+        .ok()
+        .filter(|value| *value <= MAX_LAYOUT_VALUE)
 }
 
-// EXTERNAL PUBLIC FUNCTION
+/// Returns the given text slice with up to the given number of leading
+/// spaces (the page offset) removed, so that only any indentation beyond
+/// the left margin remains
+///
+/// # Arguments
+///
+/// * `s` - Slice of text to be trimmed
+/// * `offset` - Page offset (in columns)
+///
+/// # Examples
+/// ```
+/// assert_eq!(strip_page_offset("          Quote", 8), "  Quote");
+/// ```
+pub fn strip_page_offset(s: &str, offset: usize) -> &str {
+    let spaces = s.bytes().take(offset).take_while(|&b| b == b' ').count();
+    &s[spaces..]
+}
+
+/// Returns `Some(repaired)` if the given text slice looks like a known dot
+/// command that has been damaged (e.g. in a file recovered from a bad disk
+/// sector), with the damage removed, otherwise `None`
+///
+/// The damage that can be repaired is any control characters before or
+/// after the dot and a single control character or space between the two
+/// characters of the command (e.g. `.H e`).  A line that is already a dot
+/// command known for the given input format is left unchanged, as is any
+/// line that would not give a known command once repaired.
+///
+/// # Arguments
+///
+/// * `s` - Slice of text to be examined
+/// * `format` - Word processor that created the file
+///
+/// # Examples
+/// ```
+/// assert_eq!(repair(".H e Title", InputFormat::WordStar), Some(".He Title".to_string()));
+/// ```
+pub fn repair(s: &str, format: InputFormat) -> Option<String> {
+    if split_dot_cmd(s).is_some_and(|(cmd, _)| is_known(&cmd, format)) {
+        return None;
+    }
+    let is_control = |c: char| c.is_ascii_control();
+    let rest = s.trim_start_matches(is_control).strip_prefix('.')?;
+    let rest = rest.trim_start_matches(is_control);
+    let mut chars = rest.char_indices();
+    let (_, first) = chars.next().filter(|(_, c)| c.is_ascii_alphabetic())?;
+    let (mut i, mut second) = chars.next()?;
+    if is_control(second) || second == ' ' {
+        (i, second) = chars.next()?;
+    }
+    let text = &rest[i + second.len_utf8()..];
+    let cmd = format!("{}{}", first, second).to_ascii_lowercase();
+    let repaired = format!(".{}{}{}", first, second, text);
+    (is_known(&cmd, format) && repaired != s).then_some(repaired)
+}
+
+/// Returns `true` if the given (lower case) two character command is one of
+/// the documented dot commands for the given input format, otherwise `false`
+///
+/// # Arguments
+///
+/// * `cmd` - Slice of text holding the command
+/// * `format` - Word processor that created the file
+///
+/// # Examples
+/// ```
+/// assert!(is_known("mt", InputFormat::WordStar));
+/// ```
+pub fn is_known(cmd: &str, format: InputFormat) -> bool {
+    KNOWN_DOT_CMDS.contains(&cmd)
+        || (format == InputFormat::NewWord && NEWWORD_DOT_CMDS.contains(&cmd))
+}
+
+/// Returns `true` if the given text slice is a dot command that forces a
+/// page break, otherwise `false`
+///
+/// # Arguments
+///
+/// * `s` - Slice of text to be examined
+///
+/// # Examples
+/// ```
+/// assert!(is_page_break(".PA"));
+/// ```
+pub fn is_page_break(s: &str) -> bool {
+    match check_dot_cmd(s) {
+        Some((cmd, _)) => matches!(&cmd.to_ascii_lowercase()[..], "pa" | "xl"),
+        None => false,
+    }
+}
+
+/// Returns the standard action for the given (lower case) dot command
+///
+/// # Arguments
+///
+/// * `cmd` - Slice of text holding the command (in lower case)
+///
+/// # Examples
+/// ```
+/// assert_eq!(standard_action("pa"), DotCmdAction::PageBreak);
+/// ```
+pub fn standard_action(cmd: &str) -> DotCmdAction {
+    match cmd {
+        _ if MARGIN_DOT_CMDS.contains(&cmd) => DotCmdAction::Header(0),
+        "pa" | "xl" => DotCmdAction::PageBreak,
+        _ => DotCmdAction::Drop,
+    }
+}
+
+/// Returns `true` if one of the given handlers is for the given (lower case)
+/// dot command, otherwise `false`
+///
+/// # Arguments
+///
+/// * `cmd` - Slice of text holding the command (in lower case)
+/// * `handlers` - Slice of handlers that replace the standard actions
+///
+pub fn has_handler(cmd: &str, handlers: &[DotCmdHandler]) -> bool {
+    handlers.iter().any(|handler| handler.cmd == cmd)
+}
+
+/// Returns `Some(handled)` giving the result of applying the action for the
+/// dot command in the given text slice (the last of the given handlers for
+/// it, or else its standard action), or `None` if the text slice is not a
+/// dot command or is to be left in the output as ordinary text
+///
+/// Header text is wrapped in underline characters unless a header template
+/// is given, in which "{text}" is replaced by the header text (or which
+/// removes the line if it is empty).  Any suffix after the text of the
+/// command (see `split_suffix()`) is left out, for the caller to keep.
+///
+/// # Arguments
+///
+/// * `s` - Slice of text to be processed
+/// * `handlers` - Slice of handlers that replace the standard actions
+/// * `header_template` - Template for header text (or `None` to underline)
+///
+/// # Examples
+/// ```
+/// let handlers = [DotCmdHandler { cmd: "zz".to_string(), action: DotCmdAction::Header(0) }];
+/// let handled = process_with(".zz abc", &handlers, Some("> {text}"));
+/// assert_eq!(handled, Some(Handled::Text("> abc".to_string())));
+/// ```
+pub fn process_with(
+    s: &str,
+    handlers: &[DotCmdHandler],
+    header_template: Option<&str>,
+) -> Option<Handled> {
+    let (cmd, opt_text) = check_dot_cmd(s)?;
+    let lower_cmd = cmd.to_ascii_lowercase();
+    let handler = handlers
+        .iter()
+        .rev()
+        .find(|handler| handler.cmd == lower_cmd);
+    let action = match handler {
+        Some(handler) => handler.action.clone(),
+        None => standard_action(&lower_cmd),
+    };
+    let opt_text = if MARGIN_DOT_CMDS.contains(&&lower_cmd[..]) {
+        let opt_margin = opt_text.and_then(|text| split_margin_cmd(&lower_cmd, text));
+        opt_margin.map(|margin| margin.text)
+    } else {
+        opt_text
+    };
+    let opt_text = opt_text.map(|text| split_suffix(&lower_cmd, text).0);
+    match action {
+        DotCmdAction::Drop => Some(Handled::Removed),
+        DotCmdAction::Keep => None,
+        DotCmdAction::PageBreak => Some(Handled::PageBreak),
+        DotCmdAction::Header(0) => match header_template {
+            Some("") => opt_text.map(|_| Handled::Removed),
+            Some(template) => {
+                let text = strip_control_chars(opt_text?);
+                Some(Handled::Text(
+                    template.replace(TEXT_PLACEHOLDER, text.trim()),
+                ))
+            }
+            None => make_header(&ws_chars::UNDERLINE.to_string(), opt_text).map(Handled::Text),
+        },
+        DotCmdAction::Header(level) => {
+            let title = strip_control_chars(opt_text?).trim().to_string();
+            Some(Handled::Heading(level, title))
+        }
+        DotCmdAction::Template(template) => {
+            let text = strip_control_chars(opt_text.unwrap_or_default());
+            Some(Handled::Text(
+                template.replace(TEXT_PLACEHOLDER, text.trim()),
+            ))
+        }
+    }
+}
 
 /// Returns `Some(replacement)` wrapping text to be substituted if a valid dot command
 /// is detected, otherwise `None`
 ///
 /// The replacement text may be "", indicating that the line containing the dot command
-/// needs to be eliminated entirely, rather than just replaced with a blank line.
+/// needs to be eliminated entirely, rather than just replaced with a blank line.  Each
+/// dot command is given its standard action (see `process_with()`).
 ///
 /// # Arguments
 ///
@@ -97,16 +694,70 @@ fn make_header(wrapper: &str, opt_text: Option<&str>) -> Option<String> {
 /// assert_eq!(process(".he abc"), Some("\x13abc\x13".to_string()));
 /// ```
 pub fn process(s: &str) -> Option<String> {
-    let (cmd, opt_text) = check_dot_cmd(s)?;
-    let lower_cmd = cmd.to_ascii_lowercase();
-    match &lower_cmd[..] {
-        "he" | "fo" => make_header(&ws_chars::UNDERLINE.to_string(), opt_text),
-        "h1" | "h2" | "h3" | "h4" | "h5" | "f1" | "f2" | "f3" | "f4" | "f5" => {
-            make_header(&ws_chars::UNDERLINE.to_string(), opt_text)
+    match process_with(s, &[], None)? {
+        Handled::Removed => Some("".to_string()),
+        Handled::PageBreak => Some(page_break_text()),
+        Handled::Text(text) | Handled::Heading(_, text) => Some(text),
+    }
+}
+
+/// Returns the dot command handlers given in the given text, one
+/// `COMMAND = ACTION` per line (e.g. `zz = drop` or `he = "> {text}"`), or a
+/// `std::io::Error` type if any line is invalid
+///
+/// Blank lines and lines starting with `#` are ignored.  Each action is one
+/// of "drop", "keep", "header" (for header text), "header(n)" (for a heading
+/// at level n), "page-break" or a quoted template in which "{text}" is
+/// replaced by the text following the command.
+///
+/// # Arguments
+///
+/// * `text` - Text holding the handlers
+///
+/// # Examples
+/// ```
+/// let handlers = parse_handlers("tc = header(2)\n").unwrap();
+/// assert_eq!(handlers[0].action, DotCmdAction::Header(2));
+/// ```
+pub fn parse_handlers(text: &str) -> io::Result<Vec<DotCmdHandler>> {
+    let mut handlers = Vec::new();
+    for (index, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with(HANDLER_COMMENT) {
+            continue;
         }
-        "pa" | "xl" => Some(uni_chars::HORIZONTAL_BAR.to_string().repeat(39)),
-        _ => Some("".to_string()),
+        let invalid = |reason: String| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("Handler at line {}: {}", index + 1, reason),
+            )
+        };
+        let (cmd, action) = line
+            .split_once(HANDLER_SEPARATOR)
+            .ok_or_else(|| invalid(format!("expected '{}'", HANDLER_SEPARATOR)))?;
+        let cmd = cmd.trim().trim_start_matches('.').to_ascii_lowercase();
+        if check_dot_cmd(&format!(".{}", cmd)).is_none_or(|(found, _)| found != cmd) {
+            return Err(invalid(format!("invalid dot command '{}'", cmd)));
+        }
+        let action = action.parse().map_err(invalid)?;
+        handlers.push(DotCmdHandler { cmd, action });
     }
+    Ok(handlers)
+}
+
+/// Returns the dot command handlers given in the handlers file at the given
+/// path (see `parse_handlers()`), or a `std::io::Error` type on failure
+///
+/// # Arguments
+///
+/// * `path` - Path to handlers file
+///
+/// # Examples
+/// ```
+/// let handlers = ws_dot_cmd::read_handlers(Path::new("site.dotcmds")).unwrap();
+/// ```
+pub fn read_handlers(path: &Path) -> io::Result<Vec<DotCmdHandler>> {
+    parse_handlers(&fs::read_to_string(path)?)
 }
 
 // Unit tests
@@ -145,6 +796,155 @@ mod tests {
         assert_eq!(make_header("#", None), None);
     }
 
+    #[test]
+    fn test_split_dot_cmd() {
+        assert_eq!(split_dot_cmd(".PL 60"), Some(("pl".to_string(), " 60")));
+        assert_eq!(split_dot_cmd(".op"), Some(("op".to_string(), "")));
+        assert_eq!(split_dot_cmd("abc"), None);
+        assert_eq!(split_dot_cmd(""), None);
+    }
+
+    #[test]
+    fn test_split_margin_cmd() {
+        let margin = |footer, line, pages, text| MarginCmd {
+            footer,
+            line,
+            pages,
+            text,
+        };
+        assert_eq!(
+            split_margin_cmd("he", " Title"),
+            Some(margin(false, 1, Pages::All, " Title"))
+        );
+        assert_eq!(
+            split_margin_cmd("fo", "3 Page #"),
+            Some(margin(true, 3, Pages::All, " Page #"))
+        );
+        assert_eq!(
+            split_margin_cmd("he", "E Even"),
+            Some(margin(false, 1, Pages::Even, " Even"))
+        );
+        assert_eq!(
+            split_margin_cmd("f2", "o"),
+            Some(margin(true, 2, Pages::Odd, ""))
+        );
+        assert_eq!(
+            split_margin_cmd("he", "Everything"),
+            Some(margin(false, 1, Pages::All, "Everything"))
+        );
+        assert_eq!(split_margin_cmd("h6", " x"), None);
+        assert_eq!(split_margin_cmd("he", "0 x"), None);
+        assert_eq!(split_margin_cmd("pa", ""), None);
+    }
+
+    #[test]
+    fn test_split_suffix() {
+        assert_eq!(
+            split_suffix("he", " Title .. draft"),
+            (" Title", Some("draft"))
+        );
+        assert_eq!(split_suffix("he", " Title ..."), (" Title ...", None));
+        assert_eq!(split_suffix("he", " A..B"), (" A..B", None));
+        assert_eq!(split_suffix("he", " Title .."), (" Title", None));
+        assert_eq!(
+            split_suffix("lm", " 10  quoted letter "),
+            (" 10", Some("quoted letter"))
+        );
+        assert_eq!(split_suffix("lm", " 10"), (" 10", None));
+        assert_eq!(split_suffix("pa", " Part Two"), ("", Some("Part Two")));
+        assert_eq!(split_suffix("pa", ""), ("", None));
+        assert_eq!(split_suffix("ig", " x .. y"), (" x .. y", None));
+        assert_eq!(split_suffix("ix", " term"), (" term", None));
+        assert_eq!(suffix(".PA Part Two"), Some("Part Two"));
+        assert_eq!(suffix(".po 8 .. ruler"), Some("ruler"));
+        assert_eq!(suffix(".he Title"), None);
+        assert_eq!(suffix("text"), None);
+    }
+
+    #[test]
+    fn test_line_spacing() {
+        assert_eq!(line_spacing("ls", " 2"), Some(2));
+        assert_eq!(line_spacing("ls", "3"), Some(3));
+        assert_eq!(line_spacing("ls", " 0"), None);
+        assert_eq!(line_spacing("ls", " 10"), None);
+        assert_eq!(line_spacing("ls", ""), None);
+        assert_eq!(line_spacing("ls", " 2 double"), Some(2));
+        assert_eq!(line_spacing("lm", " 2"), None);
+    }
+
+    #[test]
+    fn test_page_number() {
+        assert_eq!(page_number("pn", " 5"), Some(5));
+        assert_eq!(page_number("pn", "12 .. preface"), Some(12));
+        assert_eq!(page_number("pn", " 0"), None);
+        assert_eq!(page_number("pn", " 65535"), Some(65535));
+        assert_eq!(page_number("pn", " 65536"), None);
+        assert_eq!(page_number("pn", " 18446744073709551615"), None);
+        assert_eq!(page_number("pn", ""), None);
+        assert_eq!(page_number("pl", " 5"), None);
+    }
+
+    #[test]
+    fn test_page_offset() {
+        assert_eq!(page_offset("po", " 8"), Some(8));
+        assert_eq!(page_offset("po", "0"), Some(0));
+        assert_eq!(page_offset("po", " x"), None);
+        assert_eq!(page_offset("po", " 8 .. ruler"), Some(8));
+        assert_eq!(page_offset("pl", " 8"), None);
+        assert_eq!(strip_page_offset("          Quote", 8), "  Quote");
+        assert_eq!(strip_page_offset("   Short", 8), "Short");
+        assert_eq!(strip_page_offset("\tTab", 8), "\tTab");
+    }
+
+    #[test]
+    fn test_layout_value() {
+        assert_eq!(layout_value("mt", " 3"), Some(3));
+        assert_eq!(layout_value("pl", " 255 .. long"), Some(255));
+        assert_eq!(layout_value("pl", " 1000000000"), None);
+        assert_eq!(layout_value("mt", " 18446744073709551615"), None);
+        assert_eq!(layout_value("pc", " x"), None);
+        assert_eq!(layout_value("po", " 8"), None);
+    }
+
+    #[test]
+    fn test_repair() {
+        let ws = InputFormat::WordStar;
+        assert_eq!(repair(".H e Title", ws), Some(".He Title".to_string()));
+        assert_eq!(repair(".p\x00a", ws), Some(".pa".to_string()));
+        assert_eq!(repair("\x00.\x1Fmt 3", ws), Some(".mt 3".to_string()));
+        assert_eq!(repair(".he Title", ws), None);
+        assert_eq!(repair(". Pa", ws), None);
+        assert_eq!(repair(".z z", ws), None);
+        assert_eq!(repair(".h  e", ws), None);
+        assert_eq!(repair("text", ws), None);
+        assert_eq!(repair(".", ws), None);
+        assert_eq!(repair(".d f", ws), None);
+        assert_eq!(
+            repair(".d f", InputFormat::NewWord),
+            Some(".df".to_string())
+        );
+    }
+
+    #[test]
+    fn test_is_known() {
+        assert!(is_known("he", InputFormat::WordStar));
+        assert!(is_known("co", InputFormat::WordStar));
+        assert!(!is_known("zz", InputFormat::WordStar));
+        assert!(!is_known("HE", InputFormat::WordStar));
+        assert!(!is_known("df", InputFormat::WordStar));
+        assert!(is_known("df", InputFormat::NewWord));
+        assert!(is_known("he", InputFormat::NewWord));
+    }
+
+    #[test]
+    fn test_is_page_break() {
+        assert!(is_page_break(".PA"));
+        assert!(is_page_break(".xl"));
+        assert!(!is_page_break(".he abc"));
+        assert!(!is_page_break("pa"));
+        assert!(!is_page_break(""));
+    }
+
     #[test]
     fn test_process() {
         let text = ".He \x03 the \x04 words *¬£   \x05  ";
@@ -153,8 +953,95 @@ mod tests {
             process(".f3 \x13\x14TEST\x13\x14"),
             Some("\x13TEST\x13".to_string())
         );
+        assert_eq!(process(".fo2 Page #"), Some("\x13Page #\x13".to_string()));
+        assert_eq!(process(".heE Even"), Some("\x13Even\x13".to_string()));
         assert_eq!(process(".op"), Some("".to_string()));
         assert_eq!(process("abc"), None);
         assert_eq!(process(""), None);
     }
+
+    #[test]
+    fn test_process_with() {
+        let handler = |cmd: &str, action| DotCmdHandler {
+            cmd: cmd.to_string(),
+            action,
+        };
+        let handlers = [
+            handler("he", DotCmdAction::Template("> {text}".to_string())),
+            handler("zz", DotCmdAction::Header(2)),
+            handler("op", DotCmdAction::Keep),
+            handler("cp", DotCmdAction::PageBreak),
+        ];
+        assert_eq!(
+            process_with(".he \x02Title\x02", &handlers, None),
+            Some(Handled::Text("> Title".to_string()))
+        );
+        assert_eq!(
+            process_with(".zz Chapter 1 ", &handlers, None),
+            Some(Handled::Heading(2, "Chapter 1".to_string()))
+        );
+        assert_eq!(process_with(".op", &handlers, None), None);
+        assert_eq!(
+            process_with(".cp 3", &handlers, None),
+            Some(Handled::PageBreak)
+        );
+        assert_eq!(
+            process_with(".pa", &handlers, None),
+            Some(Handled::PageBreak)
+        );
+        assert_eq!(
+            process_with(".mt 3", &handlers, None),
+            Some(Handled::Removed)
+        );
+        assert_eq!(
+            process_with(".he Title .. draft", &[], None),
+            Some(Handled::Text("\x13Title\x13".to_string()))
+        );
+        assert_eq!(
+            process_with(".zz Chapter 1 .. check", &handlers, None),
+            Some(Handled::Heading(2, "Chapter 1".to_string()))
+        );
+        assert_eq!(
+            process_with(".fo Page", &handlers, None),
+            Some(Handled::Text("\x13Page\x13".to_string()))
+        );
+        assert_eq!(
+            process_with(".fo2 \x02Page\x02 #", &handlers, Some("## {text}")),
+            Some(Handled::Text("## Page #".to_string()))
+        );
+        assert_eq!(
+            process_with(".fo Page", &handlers, Some("")),
+            Some(Handled::Removed)
+        );
+        assert_eq!(process_with(".fo", &handlers, Some("")), None);
+        assert!(has_handler("zz", &handlers));
+        assert!(!has_handler("mt", &handlers));
+    }
+
+    #[test]
+    fn test_parse_handlers() {
+        let text = "# Site commands\n\n.zz = drop\nTC = header(2)\nhe = \"## {text}\"\n";
+        let handlers = parse_handlers(text).unwrap();
+        assert_eq!(
+            handlers,
+            [
+                DotCmdHandler {
+                    cmd: "zz".to_string(),
+                    action: DotCmdAction::Drop
+                },
+                DotCmdHandler {
+                    cmd: "tc".to_string(),
+                    action: DotCmdAction::Header(2)
+                },
+                DotCmdHandler {
+                    cmd: "he".to_string(),
+                    action: DotCmdAction::Template("## {text}".to_string())
+                },
+            ]
+        );
+        assert!(parse_handlers("zz drop").is_err());
+        assert!(parse_handlers("zzz = drop").is_err());
+        assert!(parse_handlers("zz = header(9)").is_err());
+        assert!(parse_handlers("zz = shout").is_err());
+    }
 }
@@ -0,0 +1,162 @@
+//! Module to remove printer escape sequences
+
+// WordStar files captured from print spools (or typed with a printer
+// driver's codes embedded) can hold the raw ESC-prefixed sequences sent to
+// Diablo (daisywheel) or Epson (dot matrix) printers.  These are removed
+// here rather than being escaped as "^[" gibberish.  The two families share
+// some letters, so the number of parameter bytes taken by each command is a
+// best guess: a flag parameter is only taken if it looks like a flag, and a
+// tab stop list is only taken if it ends with a NUL within a sensible length.
+// WordStar 5+ font tags hold binary data that may include an ESC character,
+// so they are left for the pitch filter (which must come after this one, as
+// ^A is also a common Epson flag parameter).
+
+use crate::ws_chars;
+
+const FLAG_CMDS: &str = "-SUWprswx"; // Epson commands with a flag parameter
+const BYTE_CMDS: &str = "!+3AJNQRajklt"; // Epson commands with a byte parameter
+const PAIR_CMDS: &str = "$\\"; // Epson commands with two parameter bytes
+const LIST_CMDS: &str = "BD"; // Epson commands with a NUL-terminated list
+const DIABLO_CMDS: &str = "\x09\x0B\x0C\x1E\x1F"; // Diablo commands with a byte parameter
+const PAGE_LENGTH: char = 'C'; // Epson page length (in inches if NUL first)
+const MAX_LIST_LEN: usize = 32; // Longest Epson tab stop list
+
+// PRIVATE HELPER FUNCTIONS
+
+/// Returns `true` if the given character looks like the parameter of an
+/// Epson on/off (or small numeric) setting, otherwise `false`
+///
+/// # Arguments
+///
+/// * `c` - Character to be checked
+///
+fn is_flag(c: char) -> bool {
+    matches!(c, '\0'..='\x07' | '0'..='7')
+}
+
+/// Returns the number of characters following a `ws_chars::ESCAPE`
+/// character that belong to its printer escape sequence (the command and
+/// any parameters, within the length of the given slice)
+///
+/// # Arguments
+///
+/// * `rest` - Slice of characters after the `ws_chars::ESCAPE` character
+///
+/// # Examples
+/// ```
+/// assert_eq!(sequence_len(&['E', 'a']), 1);
+/// assert_eq!(sequence_len(&['-', '\x01', 'a']), 2);
+/// ```
+fn sequence_len(rest: &[char]) -> usize {
+    let cmd = match rest.first() {
+        Some(&cmd) => cmd,
+        None => return 0,
+    };
+    let params = match cmd {
+        PAGE_LENGTH if rest.get(1) == Some(&'\0') => 2,
+        PAGE_LENGTH => 1,
+        c if LIST_CMDS.contains(c) => rest
+            .iter()
+            .skip(1)
+            .take(MAX_LIST_LEN + 1)
+            .position(|&t| t == '\0')
+            .map_or(0, |pos| pos + 1),
+        c if FLAG_CMDS.contains(c) => usize::from(rest.get(1).is_some_and(|&n| is_flag(n))),
+        c if BYTE_CMDS.contains(c) || DIABLO_CMDS.contains(c) => 1,
+        c if PAIR_CMDS.contains(c) => 2,
+        _ => 0,
+    };
+    (1 + params).min(rest.len())
+}
+
+// EXTERNAL PUBLIC FUNCTIONS
+
+/// Returns `Some((replacement, count))` if the given text slice contains
+/// one or more printer escape sequences, which have been removed (with the
+/// number removed as `count`), otherwise `None`
+///
+/// Each sequence is a `ws_chars::ESCAPE` character followed by a command
+/// character and any parameters that the command is expected to take.  Any
+/// font tag (between `ws_chars::FONT_TAG` characters) is left unchanged.
+///
+/// # Arguments
+///
+/// * `s` - Slice of text to be processed
+///
+/// # Examples
+/// ```
+/// assert_eq!(process("\x1BEbold\x1BF"), Some(("bold".to_string(), 2)));
+/// ```
+pub fn process(s: &str) -> Option<(String, usize)> {
+    if !s.contains(ws_chars::ESCAPE) {
+        return None;
+    }
+    let chars: Vec<char> = s.chars().collect();
+    let mut count = 0;
+    let mut result = String::with_capacity(s.len());
+    let mut i = 0;
+    while i < chars.len() {
+        if chars[i] == ws_chars::ESCAPE {
+            i += 1 + sequence_len(&chars[i + 1..]);
+            count += 1;
+        } else if chars[i] == ws_chars::FONT_TAG {
+            let len = chars[i + 1..]
+                .iter()
+                .position(|&c| c == ws_chars::FONT_TAG)
+                .map_or(1, |end| end + 2);
+            result.extend(&chars[i..i + len]);
+            i += len;
+        } else {
+            result.push(chars[i]);
+            i += 1;
+        }
+    }
+    (count > 0).then_some((result, count))
+}
+
+// Unit tests
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn strip(s: &str) -> String {
+        process(s).unwrap().0
+    }
+
+    #[test]
+    fn test_sequence_len() {
+        assert_eq!(sequence_len(&[]), 0);
+        assert_eq!(sequence_len(&['@']), 1);
+        assert_eq!(sequence_len(&['W', '1', 'x']), 2);
+        assert_eq!(sequence_len(&['W', 'x']), 1);
+        assert_eq!(sequence_len(&['3', '\x18', 'x']), 2);
+        assert_eq!(sequence_len(&['C', '\0', '\x0B', 'x']), 3);
+        assert_eq!(sequence_len(&['D', '\x08', '\x10', '\0', 'x']), 4);
+        assert_eq!(sequence_len(&['D', 'x']), 1);
+        assert_eq!(sequence_len(&['\x09', '\x20', 'x']), 2);
+        assert_eq!(sequence_len(&['$', '\x10']), 2);
+    }
+
+    #[test]
+    fn test_process_epson() {
+        assert_eq!(process("plain"), None);
+        assert_eq!(
+            process("\x1B@\x1BEBold\x1BF text"),
+            Some(("Bold text".to_string(), 3))
+        );
+        assert_eq!(strip("a \x1B-\x01line\x1B-\x00 b"), "a line b");
+        assert_eq!(strip("\x1BW1Wide\x1BW0"), "Wide");
+        assert_eq!(strip("\x1BD\x08\x10\x18\0Tabs"), "Tabs");
+    }
+
+    #[test]
+    fn test_process_diablo() {
+        assert_eq!(strip("\x1BOShadow\x1B&"), "Shadow");
+        assert_eq!(strip("\x1B\x09\x14Column"), "Column");
+        assert_eq!(strip("end\x1B"), "end");
+        assert_eq!(strip("\x1D\x1B\x00Font\x1D\x1BE"), "\x1D\x1B\x00Font\x1D");
+        assert_eq!(process("\x1D\x1B\x00Font\x1D"), None);
+        assert_eq!(strip("\x1D\x1BE"), "\x1D");
+    }
+}
@@ -0,0 +1,938 @@
+//! Module to parse WordStar text into a stream of events for the renderers
+
+// The parser applies the same filters as `ws_filters::convert_line`, but
+// yields the result of each line as a series of events (in the style of
+// the pulldown-cmark crate) rather than as a string, so that a renderer
+// can deal with the structure of the text (lines, headings, page breaks
+// and styled runs of text) without parsing it again.
+//
+// Each dot command is offered as a `DotCommand` event before the rest of
+// its line is parsed.  If the consumer acts on it, then it should call
+// `Parser::claim_line()` to remove the line, otherwise the line is parsed
+// as usual when the next event is requested.
+
+use crate::char_histogram::CharHistogram;
+use crate::diagnostics;
+use crate::doc_stats::DocStats;
+use crate::dot_cmd_count::DotCmdCount;
+use crate::profile::Profile;
+use crate::quality::Quality;
+use crate::render::Format;
+use crate::style_count::StyleCount;
+use crate::ws_align;
+use crate::ws_chars;
+use crate::ws_columns::Columns;
+use crate::ws_control;
+use crate::ws_dot_cmd;
+use crate::ws_dot_cmd::{Handled, SuffixMode};
+use crate::ws_escape;
+use crate::ws_filters::{
+    lenient_overline, limit_line, process_controls, ConvertState, Excludes, Mode, Options,
+};
+use crate::ws_heading::{self, Heading, Outline, RuledHeadings};
+use crate::ws_overline;
+use crate::ws_overprint;
+use crate::ws_special;
+use crate::ws_wrappers::{self, Style, Wrappers};
+use serde::Serialize;
+use std::collections::VecDeque;
+use std::io;
+use std::iter::Peekable;
+use std::time::Instant;
+
+/// Holds the kind of structure started or ended by an event
+#[derive(Debug, PartialEq, Clone)]
+pub enum Tag {
+    Line(usize),
+    Heading(Heading),
+    Style(Style),
+}
+
+/// Holds a single item of parsed WordStar text
+#[derive(Debug, PartialEq, Clone)]
+pub enum Event {
+    Start(Tag),
+    End(Tag),
+    Text(String),
+    PageBreak(usize),
+    DotCommand {
+        cmd: String,
+        text: String,
+        line_no: usize,
+    },
+    Comment {
+        text: String,
+        line_no: usize,
+    },
+}
+
+/// Holds the headline figures for a conversion (e.g. for a batch report)
+#[derive(Debug, Default, PartialEq, Clone, Serialize)]
+pub struct Summary {
+    pub lines: usize,      // Number of lines converted
+    pub replacements: u32, // Number of lines changed by the filters
+    pub warnings: usize,   // Number of problems found
+    pub controls: i32,     // Number of control characters left
+    pub score: usize,      // Quality score as a percentage
+}
+
+/// Holds the full set of figures for a conversion, so that library users
+/// can log, aggregate or check them rather than reading them from `stderr`
+#[derive(Debug, Default, PartialEq, Clone, Serialize)]
+pub struct ConversionStats {
+    pub lines: usize,                           // Number of lines converted
+    pub replacements: Vec<(&'static str, u32)>, // Lines changed by each filter that changed any
+    pub dot_cmds_replaced: u32,                 // Number of dot commands replaced by text
+    pub dot_cmds_removed: u32,                  // Number of dot command lines removed
+    pub dot_cmds_repaired: u32,                 // Number of damaged dot commands repaired
+    pub escapes_removed: u32,                   // Number of printer escapes removed
+    pub warnings: usize,                        // Number of problems found
+    pub controls: i32,                          // Number of control characters left
+    pub score: usize,                           // Quality score as a percentage
+}
+
+impl ConversionStats {
+    /// Returns the headline figures from these statistics
+    pub fn summary(&self) -> Summary {
+        Summary {
+            lines: self.lines,
+            replacements: self.replacements.iter().map(|(_, count)| count).sum(),
+            warnings: self.warnings,
+            controls: self.controls,
+            score: self.score,
+        }
+    }
+}
+
+// PRIVATE HELPER FUNCTIONS
+
+/// Pushes `End` and `Start` events onto the given queue for the change from
+/// one set of text attributes to another (ending attributes in the reverse
+/// order to which they are started)
+///
+/// # Arguments
+///
+/// * `events` - Queue onto which the events are pushed
+/// * `from` - Text attributes before the change
+/// * `to` - Text attributes after the change
+///
+/// # Examples
+/// ```
+/// let mut events = VecDeque::new();
+/// push_style_changes(&mut events, Style::BOLD, Style::ITALIC);
+/// assert_eq!(events[0], Event::End(Tag::Style(Style::BOLD)));
+/// ```
+fn push_style_changes(events: &mut VecDeque<Event>, from: Style, to: Style) {
+    let flags = (0..8).map(|bit| Style::from_bits_truncate(1 << bit));
+    for flag in flags.clone().rev() {
+        if from.contains(flag) && !to.contains(flag) {
+            events.push_back(Event::End(Tag::Style(flag)));
+        }
+    }
+    for flag in flags {
+        if to.contains(flag) && !from.contains(flag) {
+            events.push_back(Event::Start(Tag::Style(flag)));
+        }
+    }
+}
+
+// "PARSER" OBJECT
+
+/// Holds the state of the parser, including statistics on the control
+/// characters left after each filter and the time taken by each filter
+pub struct Parser<'a, I>
+where
+    I: Iterator<Item = io::Result<String>>,
+{
+    lines: Peekable<Columns<I>>,
+    options: &'a Options,
+    spans: bool,
+    state: ConvertState,
+    outline: Outline,
+    events: VecDeque<Event>,
+    held: Option<(usize, String, Instant)>,
+    dot_cmds_replaced: u32,
+    dot_cmds_removed: u32,
+    dot_cmds_repaired: u32,
+    dot_cmds_census: DotCmdCount,
+    escapes_removed: u32,
+    original_counts: CharHistogram,
+    dot_cmds_counts: CharHistogram,
+    escapes_counts: CharHistogram,
+    pitch_counts: CharHistogram,
+    re_align_counts: CharHistogram,
+    specials_counts: CharHistogram,
+    overline_counts: CharHistogram,
+    overprint_counts: CharHistogram,
+    wrappers_counts: CharHistogram,
+    emphasis_counts: StyleCount,
+    doc_stats: DocStats,
+    quality: Quality,
+    controls_counts: CharHistogram,
+    profile: Profile,
+}
+
+impl<'a, I> Parser<'a, I>
+where
+    I: Iterator<Item = io::Result<String>>,
+{
+    /// Returns a new `Parser` object for the given lines of 7-bit ASCII text
+    /// (e.g. from `asciify::convert_file`)
+    ///
+    /// # Arguments
+    ///
+    /// * `lines` - Iterator over lines of text (e.g. from `BufRead::lines()`)
+    /// * `options` - Settings for filter exclusions
+    /// * `spans` - Flag to give styled text as `Style` events rather than
+    ///   with text attributes mapped to Unicode characters
+    ///
+    /// # Examples
+    /// ```
+    /// let options = Options::default();
+    /// let parser = Parser::new(io::stdin().lock().lines(), &options, false);
+    /// ```
+    pub fn new(lines: I, options: &'a Options, spans: bool) -> Self {
+        Parser {
+            lines: Columns::new(lines, options.detect_columns).peekable(),
+            options,
+            spans: spans && !options.excludes.contains(Excludes::WRAPPERS),
+            state: ConvertState::new(options),
+            outline: Outline::new(),
+            events: VecDeque::new(),
+            held: None,
+            dot_cmds_replaced: 0,
+            dot_cmds_removed: 0,
+            dot_cmds_repaired: 0,
+            dot_cmds_census: DotCmdCount::new("Commands".to_string())
+                .with_format(options.input_format),
+            escapes_removed: 0,
+            original_counts: CharHistogram::new("To ASCII".to_string()),
+            dot_cmds_counts: CharHistogram::new("Dot-cmds".to_string()),
+            escapes_counts: CharHistogram::new("Escapes".to_string()),
+            pitch_counts: CharHistogram::new("Pitch".to_string()),
+            re_align_counts: CharHistogram::new("Re-align".to_string()),
+            specials_counts: CharHistogram::new("Specials".to_string()),
+            overline_counts: CharHistogram::new("Overline".to_string()),
+            overprint_counts: CharHistogram::new("Overprint".to_string()),
+            wrappers_counts: CharHistogram::new("Wrappers".to_string()),
+            emphasis_counts: StyleCount::new("Emphasis".to_string()),
+            doc_stats: DocStats::new("Document".to_string()),
+            quality: Quality::new().with_strict(options.mode == Mode::Strict),
+            controls_counts: CharHistogram::new("Controls".to_string()),
+            profile: Profile::new(),
+        }
+    }
+
+    /// Returns `true` if the text of each line is given with `Style` events,
+    /// otherwise `false` (i.e. if spans were not requested or the wrappers
+    /// filter is excluded)
+    pub fn emits_spans(&self) -> bool {
+        self.spans
+    }
+
+    /// Removes the line of the dot command given by the last `DotCommand`
+    /// event (leaving a blank line if `blank_dot_cmds` is set), to be called
+    /// when the consumer has acted on the dot command
+    pub fn claim_line(&mut self) {
+        if let Some((line_no, line, start)) = self.held.take() {
+            self.dot_cmds_removed += 1;
+            self.profile.record("Dot-cmds", start, true);
+            match self.take_suffix(line_no, &line) {
+                Some(suffix) => self.parse_suffix(line_no, suffix),
+                None => self.push_blank_line(line_no),
+            }
+        }
+    }
+
+    /// Adds the time since the given start time to the "Render" stage of the
+    /// profile, for consumers that render the events
+    pub(crate) fn record_render(&mut self, start: Instant) {
+        self.profile.record("Render", start, false);
+    }
+
+    /// Returns the summary of the problems found in the conversion so far
+    /// (e.g. for a quality score once all the events have been read)
+    pub fn quality(&self) -> &Quality {
+        &self.quality
+    }
+
+    /// Returns the headline figures for the conversion so far (e.g. for a
+    /// batch report once all the events have been read)
+    pub fn summary(&self) -> Summary {
+        Summary {
+            lines: self.quality.lines(),
+            replacements: self.profile.total_changed(),
+            warnings: self.quality.problems(),
+            controls: self.controls_counts.total(),
+            score: self.quality.score(),
+        }
+    }
+
+    /// Returns the full set of figures for the conversion so far (e.g. for
+    /// a library user once all the events have been read)
+    pub fn stats(&self) -> ConversionStats {
+        ConversionStats {
+            lines: self.quality.lines(),
+            replacements: self.profile.changes(),
+            dot_cmds_replaced: self.dot_cmds_replaced,
+            dot_cmds_removed: self.dot_cmds_removed,
+            dot_cmds_repaired: self.dot_cmds_repaired,
+            escapes_removed: self.escapes_removed,
+            warnings: self.quality.problems(),
+            controls: self.controls_counts.total(),
+            score: self.quality.score(),
+        }
+    }
+
+    /// Writes the statistics on dot commands and control characters (and
+    /// the profile if enabled in the options) to `stderr`
+    pub fn report(&self) {
+        eprintln!("Dot commands after processing:");
+        eprintln!("Replaced: {}", self.dot_cmds_replaced);
+        eprintln!("Removed:  {}", self.dot_cmds_removed);
+        if self.options.lenient_dot_cmds && self.options.mode == Mode::Permissive {
+            eprintln!("Repaired: {}", self.dot_cmds_repaired);
+        }
+        eprintln!("{}", self.dot_cmds_census);
+
+        eprintln!("Printer escapes removed: {}", self.escapes_removed);
+
+        eprintln!("Styled spans after processing:");
+        eprintln!("{}", self.emphasis_counts);
+
+        eprintln!("Text after processing:");
+        eprintln!("{}", self.doc_stats);
+        eprintln!("{}", self.quality);
+
+        eprintln!("Control characters after processing:");
+        eprintln!("{}", self.original_counts);
+        eprintln!("{}", self.dot_cmds_counts);
+        eprintln!("{}", self.escapes_counts);
+        eprintln!("{}", self.pitch_counts);
+        eprintln!("{}", self.re_align_counts);
+        eprintln!("{}", self.specials_counts);
+        eprintln!("{}", self.overline_counts);
+        eprintln!("{}", self.overprint_counts);
+        eprintln!("{}", self.wrappers_counts);
+        eprintln!("{}", self.controls_counts);
+
+        eprintln!("Control characters consumed and added by each stage:");
+        let stages = [
+            &self.dot_cmds_counts,
+            &self.escapes_counts,
+            &self.pitch_counts,
+            &self.re_align_counts,
+            &self.specials_counts,
+            &self.overline_counts,
+            &self.overprint_counts,
+            &self.wrappers_counts,
+            &self.controls_counts,
+        ];
+        let mut previous = &self.original_counts;
+        for stage in stages {
+            eprintln!("{}", stage.changes_from(previous));
+            if stage.is_used() {
+                previous = stage; // Compare with last stage that was not skipped
+            }
+        }
+
+        if self.options.profile {
+            eprintln!("Profile of pipeline stages:");
+            eprintln!("{}", self.profile);
+        }
+    }
+
+    /// Pushes the events for a blank line in place of a removed dot command
+    /// if `blank_dot_cmds` is set in the options
+    ///
+    /// # Arguments
+    ///
+    /// * `line_no` - Number of the line in the input
+    ///
+    fn push_blank_line(&mut self, line_no: usize) {
+        if self.options.blank_dot_cmds {
+            self.doc_stats.scan("");
+            self.events.push_back(Event::Start(Tag::Line(line_no)));
+            self.events.push_back(Event::End(Tag::Line(line_no)));
+        }
+    }
+
+    /// Returns `Some(suffix)` if the given dot command line has a suffix (see
+    /// `ws_dot_cmd::split_suffix()`) that is to be kept as text, otherwise
+    /// `None`, after pushing a `Comment` event for any suffix that is to be
+    /// kept as a comment
+    ///
+    /// # Arguments
+    ///
+    /// * `line_no` - Number of the line in the input
+    /// * `line` - Text of the line
+    ///
+    fn take_suffix(&mut self, line_no: usize, line: &str) -> Option<String> {
+        let text = ws_dot_cmd::suffix(line)?.to_string();
+        match self.options.dot_cmd_suffix {
+            SuffixMode::Drop => None,
+            SuffixMode::Text => Some(text),
+            SuffixMode::Comment => {
+                self.events.push_back(Event::Comment { text, line_no });
+                None
+            }
+        }
+    }
+
+    /// Returns `Some((heading, line_no))` holding the heading and the number
+    /// of the ruling line if the next line is a ruling under the given line
+    /// (see `ws_heading::detect_ruled()`), after taking the ruling from the
+    /// input, otherwise `None`
+    ///
+    /// # Arguments
+    ///
+    /// * `line` - Text of the line that may be a title
+    ///
+    fn take_ruling(&mut self, line: &str) -> Option<(Heading, usize)> {
+        let heading = match self.lines.peek() {
+            Some(Ok((_, next))) => ws_heading::detect_ruled(line, next)?,
+            _ => return None,
+        };
+        match self.lines.next() {
+            Some(Ok((line_no, _))) => {
+                self.quality.line(line_no);
+                Some((heading, line_no))
+            }
+            _ => None, // Not reached, as the line was peeked
+        }
+    }
+
+    /// Starts parsing a new line, either holding it back after pushing a
+    /// `DotCommand` event (if it is a dot command) or parsing it at once
+    ///
+    /// # Arguments
+    ///
+    /// * `line_no` - Number of the line in the input
+    /// * `line` - Text of the line
+    ///
+    fn start_line(&mut self, line_no: usize, mut line: String) {
+        self.original_counts.scan(&line);
+        self.quality.line(line_no);
+        diagnostics::set_source(line_no, &line);
+        let start = Instant::now();
+        if !self.options.excludes.contains(Excludes::DOT_CMDS) {
+            self.dot_cmds_census.activate();
+            if self.options.lenient_dot_cmds && self.options.mode == Mode::Permissive {
+                if let Some(repaired) = ws_dot_cmd::repair(&line, self.options.input_format) {
+                    log::warn!(
+                        code = "repaired-dot-command";
+                        "Line {}: repaired dot command {:?}",
+                        line_no,
+                        line
+                    );
+                    self.dot_cmds_repaired += 1;
+                    line = repaired;
+                }
+            }
+            if let Some((cmd, text)) = ws_dot_cmd::split_dot_cmd(&line) {
+                self.dot_cmds_census.up(&cmd);
+                let handled = ws_dot_cmd::has_handler(&cmd, &self.options.dot_cmd_handlers);
+                if !handled && !ws_dot_cmd::is_known(&cmd, self.options.input_format) {
+                    self.quality.unknown_dot_cmd(line_no);
+                }
+                self.events.push_back(Event::DotCommand {
+                    cmd,
+                    text: text.to_string(),
+                    line_no,
+                });
+                self.held = Some((line_no, line, start));
+                return;
+            }
+            if self.options.mode == Mode::Strict
+                && ws_dot_cmd::repair(&line, self.options.input_format).is_some()
+            {
+                self.quality.malformed_dot_cmd(line_no);
+            }
+        }
+        self.parse_line(line_no, line, start);
+    }
+
+    /// Applies the filters to a line and pushes the resulting events
+    ///
+    /// # Arguments
+    ///
+    /// * `line_no` - Number of the line in the input
+    /// * `line` - Text of the line
+    /// * `start` - Time at which the line started to be processed
+    ///
+    fn parse_line(&mut self, line_no: usize, line: String, start: Instant) {
+        self.parse_text(line_no, line, start, true);
+    }
+
+    /// Applies the filters to the suffix of a dot command line (see
+    /// `take_suffix()`) and pushes the resulting events, without looking
+    /// for a ruling under it (which would take the next line of the input)
+    ///
+    /// # Arguments
+    ///
+    /// * `line_no` - Number of the line in the input
+    /// * `suffix` - Text of the suffix
+    ///
+    fn parse_suffix(&mut self, line_no: usize, suffix: String) {
+        self.parse_text(line_no, suffix, Instant::now(), false);
+    }
+
+    /// Applies the filters to the text of a line (or of a dot command
+    /// suffix) and pushes the resulting events, followed by a line holding
+    /// any setext ruling of a ruled heading (in text output)
+    ///
+    /// # Arguments
+    ///
+    /// * `line_no` - Number of the line in the input
+    /// * `line` - Text of the line
+    /// * `start` - Time at which the line started to be processed
+    /// * `rulings` - Flag to look for a ruling under the line
+    ///
+    fn parse_text(&mut self, line_no: usize, mut line: String, start: Instant, rulings: bool) {
+        let options = self.options;
+        let excludes = options.excludes;
+        let mut heading = None;
+        let mut spans = None;
+        let mut suffix = None;
+        let mut comment = VecDeque::new();
+        let mut ruling = None;
+
+        if !excludes.contains(Excludes::DOT_CMDS) {
+            let handled = ws_dot_cmd::process_with(
+                &line,
+                &options.dot_cmd_handlers,
+                options.header_template.as_deref(),
+            );
+            if let Some(handled) = handled {
+                let queued = self.events.len();
+                suffix = self.take_suffix(line_no, &line);
+                comment = self.events.split_off(queued); // Follows the dot command
+                match handled {
+                    Handled::PageBreak => {
+                        self.dot_cmds_replaced += 1;
+                        self.doc_stats.page_break();
+                        self.events.push_back(Event::PageBreak(line_no));
+                        self.events.append(&mut comment);
+                        self.profile.record("Dot-cmds", start, true);
+                        if let Some(suffix) = suffix {
+                            self.parse_suffix(line_no, suffix);
+                        }
+                        return; // Leave page break to consumer
+                    }
+                    Handled::Removed => {
+                        self.dot_cmds_removed += 1;
+                        self.events.append(&mut comment);
+                        self.profile.record("Dot-cmds", start, true);
+                        match suffix {
+                            Some(suffix) => self.parse_suffix(line_no, suffix),
+                            None => self.push_blank_line(line_no),
+                        }
+                        return; // Remove line from output (or leave it blank)
+                    }
+                    Handled::Text(text) => line = text,
+                    Handled::Heading(level, title) => {
+                        heading = Some(Heading {
+                            level,
+                            title: title.clone(),
+                        });
+                        line = title;
+                    }
+                }
+                self.dot_cmds_replaced += 1;
+                self.profile.record("Dot-cmds", start, true);
+            } else {
+                heading = ws_heading::detect(&line);
+                if options.outline {
+                    heading = self.outline.detect(&line).or(heading);
+                }
+                if heading.is_none() && rulings && options.ruled_headings != RuledHeadings::None {
+                    let ruled = self.take_ruling(&line);
+                    match (&ruled, options.ruled_headings) {
+                        (Some((ruled, ruling_no)), RuledHeadings::Setext)
+                            if options.render.format == Format::Text =>
+                        {
+                            ruling = Some((*ruling_no, ws_heading::setext_ruling(ruled)));
+                        }
+                        (Some(_), RuledHeadings::Setext) => {} // Marked up as heading instead
+                        (Some(_), _) if !line.contains(ws_chars::UNDERLINE) => {
+                            let text = line.trim();
+                            let indent = &line[..line.len() - line.trim_start().len()];
+                            let wrapper = ws_chars::UNDERLINE;
+                            line = format!("{}{}{}{}", indent, wrapper, text, wrapper);
+                        }
+                        _ => {}
+                    }
+                    heading = ruled.map(|(heading, _)| heading);
+                }
+                self.profile.record("Dot-cmds", start, false);
+            }
+            self.dot_cmds_counts.scan(&line);
+        }
+
+        if !excludes.contains(Excludes::ESCAPES) {
+            let start = Instant::now();
+            let result = ws_escape::process(&line);
+            self.profile.record("Escapes", start, result.is_some());
+            if let Some((replacement, count)) = result {
+                self.escapes_removed += count as u32;
+                line = replacement;
+            }
+            self.escapes_counts.scan(&line);
+        }
+
+        if !excludes.contains(Excludes::PITCH) {
+            let start = Instant::now();
+            let result = self.state.pitch.process(&line);
+            self.profile.record("Pitch", start, result.is_some());
+            line = result.unwrap_or(line);
+            self.pitch_counts.scan(&line);
+        }
+
+        if !excludes.contains(Excludes::RE_ALIGN) {
+            let start = Instant::now();
+            let result = ws_align::process(&line, &self.state.custom_codes);
+            self.profile.record("Re-align", start, result.is_some());
+            line = result.unwrap_or(line);
+            self.re_align_counts.scan(&line);
+        }
+
+        if !excludes.contains(Excludes::SPECIALS) {
+            let start = Instant::now();
+            let result = ws_special::process_with(
+                &line,
+                options.fraction_style,
+                options.locale,
+                options.symbols,
+                &options.fractions,
+                &options.special_rules,
+            );
+            self.profile.record("Specials", start, result.is_some());
+            line = result.unwrap_or(line);
+            self.specials_counts.scan(&line);
+        }
+
+        if !excludes.contains(Excludes::OVERLINE) {
+            let start = Instant::now();
+            let result =
+                ws_overline::process(&line, options.overline_style, lenient_overline(options));
+            self.profile.record("Overline", start, result.is_some());
+            line = result.unwrap_or(line);
+            self.overline_counts.scan(&line);
+        }
+
+        if !excludes.contains(Excludes::OVERPRINT) {
+            let start = Instant::now();
+            let result = ws_overprint::process(&line);
+            self.profile.record("Overprint", start, result.is_some());
+            line = result.unwrap_or(line);
+            self.overprint_counts.scan(&line);
+        }
+
+        if !excludes.contains(Excludes::WRAPPERS) {
+            let start = Instant::now();
+            let styled = self.state.wrappers.split_spans(&line);
+            self.emphasis_counts.scan(&styled);
+            let joined = if self.spans {
+                ws_wrappers::join_spans(&styled)
+            } else {
+                let lossy = styled.iter().map(Wrappers::count_unmapped).sum();
+                self.quality.lossy(lossy, line_no);
+                let mapped = ws_wrappers::map_spans(&styled, options.render.underline_style); // As `process()`
+                limit_line(mapped, &styled, options, line_no)
+            };
+            if joined.trim().is_empty() {
+                self.quality.unmatched(self.state.wrappers.style(), line_no); // End of paragraph
+            }
+            self.profile.record("Wrappers", start, joined != line);
+            line = joined;
+            if self.spans {
+                spans = Some(styled);
+            }
+            self.wrappers_counts.scan(&line);
+        }
+
+        self.quality.unconverted(
+            ws_control::count_unknown(&line, &options.preserve_controls),
+            line_no,
+        );
+        if !excludes.contains(Excludes::CONTROLS) {
+            let start = Instant::now();
+            let mut changed = false;
+            if let Some(styled) = &mut spans {
+                for span in styled.iter_mut() {
+                    if let Some(replacement) = process_controls(&span.text, options, line_no) {
+                        span.text = replacement;
+                        changed = true;
+                    }
+                }
+                line = ws_wrappers::join_spans(styled);
+            } else if let Some(replacement) = process_controls(&line, options, line_no) {
+                line = replacement;
+                changed = true;
+            }
+            self.profile.record("Controls", start, changed);
+            self.controls_counts.scan(&line);
+        }
+
+        self.doc_stats.scan(&line);
+        let events = &mut self.events;
+        events.push_back(Event::Start(Tag::Line(line_no)));
+        if let Some(heading) = &heading {
+            events.push_back(Event::Start(Tag::Heading(heading.clone())));
+        }
+        match spans {
+            Some(styled) => {
+                let mut style = Style::empty();
+                for span in styled {
+                    push_style_changes(events, style, span.style);
+                    style = span.style;
+                    events.push_back(Event::Text(span.text));
+                }
+                push_style_changes(events, style, Style::empty());
+            }
+            None => events.push_back(Event::Text(line)),
+        }
+        if let Some(heading) = heading {
+            events.push_back(Event::End(Tag::Heading(heading)));
+        }
+        events.push_back(Event::End(Tag::Line(line_no)));
+        if let Some((ruling_no, ruling)) = ruling {
+            events.push_back(Event::Start(Tag::Line(ruling_no))); // Setext heading
+            events.push_back(Event::Text(ruling));
+            events.push_back(Event::End(Tag::Line(ruling_no)));
+        }
+        self.events.append(&mut comment);
+        if let Some(suffix) = suffix {
+            self.parse_suffix(line_no, suffix); // Follows replaced dot command
+        }
+    }
+}
+
+/// Iterator trait implementation for Parser, giving the events for each line
+/// in turn or a `std::io::Error` type if a line cannot be read
+impl<'a, I> Iterator for Parser<'a, I>
+where
+    I: Iterator<Item = io::Result<String>>,
+{
+    type Item = io::Result<Event>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(event) = self.events.pop_front() {
+                return Some(Ok(event));
+            }
+            if let Some((line_no, line, start)) = self.held.take() {
+                self.parse_line(line_no, line, start); // Dot command not claimed
+                continue;
+            }
+            match self.lines.next() {
+                Some(Ok((line_no, line))) => self.start_line(line_no, line),
+                Some(Err(e)) => return Some(Err(e)),
+                None => {
+                    self.quality.finish(self.state.wrappers.style());
+                    return None;
+                }
+            }
+        }
+    }
+}
+
+// Unit tests
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(input: &str, options: &Options, spans: bool) -> Vec<Event> {
+        let lines = input.lines().map(|line| Ok(line.to_string()));
+        Parser::new(lines, options, spans)
+            .map(Result::unwrap)
+            .collect()
+    }
+
+    #[test]
+    fn test_push_style_changes() {
+        let mut events = VecDeque::new();
+        push_style_changes(&mut events, Style::BOLD | Style::ITALIC, Style::UNDERLINE);
+        assert_eq!(
+            Vec::from(events),
+            vec![
+                Event::End(Tag::Style(Style::ITALIC)),
+                Event::End(Tag::Style(Style::BOLD)),
+                Event::Start(Tag::Style(Style::UNDERLINE)),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_lines() {
+        let options = Options::default();
+        assert_eq!(
+            parse("a\x13b\x13c\n.pa\n", &options, false),
+            vec![
+                Event::Start(Tag::Line(1)),
+                Event::Text("ab\u{332}c".to_string()),
+                Event::End(Tag::Line(1)),
+                Event::DotCommand {
+                    cmd: "pa".to_string(),
+                    text: "".to_string(),
+                    line_no: 2
+                },
+                Event::PageBreak(2),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_spans() {
+        let options = Options::default();
+        assert_eq!(
+            parse("a\x02b\x02c", &options, true),
+            vec![
+                Event::Start(Tag::Line(1)),
+                Event::Text("a".to_string()),
+                Event::Start(Tag::Style(Style::BOLD)),
+                Event::Text("b".to_string()),
+                Event::End(Tag::Style(Style::BOLD)),
+                Event::Text("c".to_string()),
+                Event::End(Tag::Line(1)),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_claim_line() {
+        let options = Options {
+            blank_dot_cmds: true,
+            ..Default::default()
+        };
+        let lines = ".op\n.mt 3\n".lines().map(|line| Ok(line.to_string()));
+        let mut parser = Parser::new(lines, &options, false);
+        assert!(matches!(parser.next(), Some(Ok(Event::DotCommand { .. }))));
+        parser.claim_line();
+        assert_eq!(parser.next().unwrap().unwrap(), Event::Start(Tag::Line(1)));
+        assert_eq!(parser.next().unwrap().unwrap(), Event::End(Tag::Line(1)));
+        assert!(matches!(parser.next(), Some(Ok(Event::DotCommand { .. }))));
+        assert_eq!(parser.next().unwrap().unwrap(), Event::Start(Tag::Line(2)));
+        assert_eq!(parser.next().unwrap().unwrap(), Event::End(Tag::Line(2)));
+        assert!(parser.next().is_none());
+        assert_eq!(parser.dot_cmds_removed, 2);
+    }
+
+    #[test]
+    fn test_suffix() {
+        let input = ".pa Part Two\n.mt 3 .. top\n";
+        let text = |s: &str| Event::Text(s.to_string());
+        assert_eq!(
+            parse(input, &Options::default(), false),
+            vec![
+                Event::DotCommand {
+                    cmd: "pa".to_string(),
+                    text: " Part Two".to_string(),
+                    line_no: 1,
+                },
+                Event::PageBreak(1),
+                Event::Start(Tag::Line(1)),
+                text("Part Two"),
+                Event::End(Tag::Line(1)),
+                Event::DotCommand {
+                    cmd: "mt".to_string(),
+                    text: " 3 .. top".to_string(),
+                    line_no: 2,
+                },
+                Event::Start(Tag::Line(2)),
+                text("top"),
+                Event::End(Tag::Line(2)),
+            ]
+        );
+        let options = Options {
+            dot_cmd_suffix: SuffixMode::Comment,
+            ..Default::default()
+        };
+        let events = parse(input, &options, false);
+        assert_eq!(
+            events[1..3],
+            [
+                Event::PageBreak(1),
+                Event::Comment {
+                    text: "Part Two".to_string(),
+                    line_no: 1,
+                },
+            ]
+        );
+        assert_eq!(
+            events[4],
+            Event::Comment {
+                text: "top".to_string(),
+                line_no: 2,
+            }
+        );
+        assert_eq!(events.len(), 5);
+    }
+
+    #[test]
+    fn test_ruled_headings() {
+        let input = "Scope\n=====\nText\n-\n";
+        let text = |s: &str| Event::Text(s.to_string());
+        let heading = Heading {
+            level: 1,
+            title: "Scope".to_string(),
+        };
+        assert_eq!(parse(input, &Options::default(), false).len(), 12);
+        let options = Options {
+            ruled_headings: RuledHeadings::Setext,
+            ..Default::default()
+        };
+        let events = parse(input, &options, false);
+        assert_eq!(
+            events[..8],
+            [
+                Event::Start(Tag::Line(1)),
+                Event::Start(Tag::Heading(heading.clone())),
+                text("Scope"),
+                Event::End(Tag::Heading(heading.clone())),
+                Event::End(Tag::Line(1)),
+                Event::Start(Tag::Line(2)),
+                text("====="),
+                Event::End(Tag::Line(2)),
+            ]
+        );
+        assert_eq!(events[8], Event::Start(Tag::Line(3)));
+        assert_eq!(events.len(), 14);
+        let mut html = options.clone();
+        html.render.format = Format::Html;
+        let events = parse(input, &html, false);
+        assert_eq!(events[5], Event::Start(Tag::Line(3)));
+        assert_eq!(events.len(), 11);
+        let events = parse(".pa Scope\n=====\n", &options, false);
+        assert_eq!(
+            events[2..5],
+            [
+                Event::Start(Tag::Line(1)),
+                text("Scope"),
+                Event::End(Tag::Line(1))
+            ]
+        );
+        assert_eq!(
+            events[5..],
+            [
+                Event::Start(Tag::Line(2)),
+                text("====="),
+                Event::End(Tag::Line(2))
+            ]
+        );
+        let options = Options {
+            ruled_headings: RuledHeadings::Underline,
+            ..Default::default()
+        };
+        let events = parse(input, &options, true);
+        assert_eq!(
+            events[2..5],
+            [
+                Event::Start(Tag::Style(Style::UNDERLINE)),
+                text("Scope"),
+                Event::End(Tag::Style(Style::UNDERLINE)),
+            ]
+        );
+    }
+}
@@ -1,56 +1,1412 @@
-//! Module to process input file to output file via temporary file
+//! Module to process input file to output file via temporary file (or in
+//! memory for small files)
 
 use crate::asciify;
+use crate::batch::{BatchReport, BatchSettings, FileReport, Limits, Manifest, ManifestEntry};
+use crate::bench::{self, Features};
+use crate::corpus::{self, FileResult, Outcome};
+use crate::exit_status;
+use crate::log_file;
+use crate::provenance::{self, Placement, Provenance};
+use crate::self_test;
+use crate::sink::{self, OutputSink, Split};
+use crate::strip;
+use crate::walk;
+use crate::ws_events::Summary;
 use crate::ws_filters;
-use std::fs::{File, OpenOptions};
-use std::io::{self, BufReader, BufWriter, Read, Seek, SeekFrom, Write};
+use crate::ws_windows;
+use flate2::read::MultiGzDecoder;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use std::collections::HashSet;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufRead, BufReader, BufWriter, Cursor, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::time::Instant;
+use tempfile::NamedTempFile;
 
-/// Attempts to convert a WordStar file from the input filename
-/// (or `stdin` if empty) to a new Unicode based text file at the
-/// output filename (or `stdout` if empty) via a temporary file,
-/// optionally excluding a set of `ws_filters::Excludes` filters
+const STDIN_NAME: &str = "-"; // Input filename that selects `stdin`
+const JSON_EXTENSION: &str = "json"; // Report extension that selects JSON
+const LOG_EXTENSION: &str = "log"; // Extension of log file beside each output
+const GZIP_EXTENSION: &str = "gz"; // Extension of gzip-compressed files
+const GZIP_MAGIC: &[u8] = b"\x1F\x8B"; // Start of gzip-compressed data
+const IN_MEMORY_LIMIT: u64 = 1 << 20; // Largest total input converted in memory
+
+// PRIVATE HELPER FUNCTIONS
+
+/// Returns `true` if the given filename has the extension for a
+/// gzip-compressed file (".gz"), otherwise `false`
 ///
-/// Returns `()` on success or a `std::io::Error` type on failure
+/// # Arguments
 ///
-/// Note: If an output filename is specified then an error will be
-/// returned and no further action taken if the file already exists
+/// * `filename` - Path to file
+///
+fn is_gzip(filename: &str) -> bool {
+    Path::new(filename)
+        .extension()
+        .is_some_and(|ext| ext.eq_ignore_ascii_case(GZIP_EXTENSION))
+}
+
+/// Returns the given reader, wrapped to decompress its input if it starts
+/// with the signature of gzip-compressed data (e.g. a ".gz" file piped to
+/// `stdin`, which has no filename to show that it is compressed)
+///
+/// # Arguments
+///
+/// * `reader` - Source of the input
+///
+fn gunzip_if_compressed<R>(mut reader: R) -> io::Result<Box<dyn BufRead>>
+where
+    R: BufRead + 'static,
+{
+    if reader.fill_buf()?.starts_with(GZIP_MAGIC) {
+        Ok(Box::new(BufReader::new(MultiGzDecoder::new(reader))))
+    } else {
+        Ok(Box::new(reader))
+    }
+}
+
+/// Returns a reader for the given input filename (decompressing it if it is
+/// a ".gz" file), or for `stdin` if the filename is "-" or empty (also
+/// decompressing it if it is gzip-compressed)
+///
+/// # Arguments
+///
+/// * `infile` - Path to input file (or "-" or "" to use `stdin`)
+/// * `in_memory` - Flag to read the whole of an uncompressed file at once
+///
+fn open_input(infile: &str, in_memory: bool) -> io::Result<Box<dyn BufRead>> {
+    if in_memory && !is_stdin(infile) && !is_gzip(infile) {
+        Ok(Box::new(Cursor::new(fs::read(infile)?)))
+    } else if is_stdin(infile) {
+        gunzip_if_compressed(BufReader::new(io::stdin()))
+    } else if is_gzip(infile) {
+        let file = File::open(infile)?;
+        Ok(Box::new(BufReader::new(MultiGzDecoder::new(file))))
+    } else {
+        Ok(Box::new(BufReader::new(File::open(infile)?)))
+    }
+}
+
+/// Returns an `Output` object for the given output filename, or for
+/// `stdout` if the filename is empty, which gzip-compresses the output if
+/// `compress` is `true` or the filename is a ".gz" file
+///
+/// If `compress` is `true`, ".gz" is added to an output filename that does
+/// not already end with it.
+///
+/// Note: An error is returned if the output file already exists
+///
+/// # Arguments
+///
+/// * `outfile` - Path to output file (or "" to use `stdout`)
+/// * `compress` - Flag to gzip-compress the output
+///
+fn open_output(outfile: &str, compress: bool) -> io::Result<Output> {
+    open_output_with(outfile, compress, false, false)
+}
+
+/// Returns an `Output` object as for `open_output()`, but replacing any
+/// existing output file (once the output is complete) if `replace` is `true`
+/// and holding the output in memory (to be written to the output file at
+/// once) rather than in a temporary file if `in_memory` is `true`
 ///
 /// # Arguments
 ///
-/// * `infile` - Path to input file (or "" to use `stdin`)
 /// * `outfile` - Path to output file (or "" to use `stdout`)
-/// * `excludes` - Optional set of flags to specify filters to exclude
+/// * `compress` - Flag to gzip-compress the output
+/// * `replace` - Flag to replace an existing output file
+/// * `in_memory` - Flag to hold the output in memory
+///
+fn open_output_with(
+    outfile: &str,
+    compress: bool,
+    replace: bool,
+    in_memory: bool,
+) -> io::Result<Output> {
+    if outfile.is_empty() {
+        let output = Output::Stdout(BufWriter::new(io::stdout()));
+        return Ok(if compress {
+            output.compressed()
+        } else {
+            output
+        });
+    }
+    let mut path = PathBuf::from(outfile);
+    if compress && !is_gzip(outfile) {
+        path.as_mut_os_string().push(format!(".{}", GZIP_EXTENSION));
+    }
+    if !replace && path.exists() {
+        return Err(io::Error::new(
+            io::ErrorKind::AlreadyExists,
+            format!("Output file '{}' already exists", path.display()),
+        ));
+    }
+    let dir = output_dir(&path);
+    let output = if in_memory {
+        Output::Memory {
+            buffer: Vec::new(),
+            path: path.clone(),
+            replace,
+        }
+    } else {
+        Output::File {
+            writer: BufWriter::new(NamedTempFile::new_in(dir)?),
+            path: path.clone(),
+            replace,
+        }
+    };
+    Ok(if compress || is_gzip(outfile) {
+        output.compressed()
+    } else {
+        output
+    })
+}
+
+/// Returns the directory of the given output file (or the current directory
+/// if it has none), in which its temporary file is created
+///
+/// # Arguments
+///
+/// * `path` - Path to output file
+///
+fn output_dir(path: &Path) -> &Path {
+    match path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => Path::new("."),
+    }
+}
+
+/// Returns the given path with its directory resolved to an absolute path
+/// without symbolic links (so that two paths to the same file compare equal),
+/// or the path unchanged if its directory cannot be resolved
+///
+/// # Arguments
+///
+/// * `path` - Path to an input or output file (which need not exist)
+///
+fn canonical(path: &Path) -> PathBuf {
+    if let Ok(path) = fs::canonicalize(path) {
+        return path;
+    }
+    match (fs::canonicalize(output_dir(path)), path.file_name()) {
+        (Ok(dir), Some(name)) => dir.join(name),
+        _ => path.to_path_buf(),
+    }
+}
+
+/// Moves the given temporary file to the given output file, replacing any
+/// existing file only if `replace` is `true`
+///
+/// # Arguments
+///
+/// * `temp` - Temporary file holding the complete output
+/// * `path` - Path to output file
+/// * `replace` - Flag to replace an existing output file
+///
+fn persist(temp: NamedTempFile, path: &Path, replace: bool) -> io::Result<()> {
+    if replace {
+        temp.persist(path)?;
+    } else {
+        temp.persist_noclobber(path)?;
+    }
+    Ok(())
+}
+
+/// Returns `true` if the given input filename selects `stdin`, otherwise
+/// `false`
+///
+/// # Arguments
+///
+/// * `infile` - Path to input file (or "" or "-" for `stdin`)
+///
+fn is_stdin(infile: &str) -> bool {
+    infile.is_empty() || infile == STDIN_NAME
+}
+
+/// Returns `()` if the given start of the input from `stdin` does not have
+/// the signature of a known format other than WordStar text (see
+/// `asciify::detect_foreign()`), otherwise a `std::io::Error` type naming
+/// the detected format, so that a file piped in by mistake is not converted
+/// into a screen of escaped control characters
+///
+/// # Arguments
+///
+/// * `buf` - Byte (u8) slice from the start of the input
+///
+fn check_foreign(buf: &[u8]) -> io::Result<()> {
+    match asciify::detect_foreign(buf) {
+        Some(format) => Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!(
+                "Input from stdin looks like a {} file, not a WordStar document",
+                format
+            ),
+        )),
+        None => Ok(()),
+    }
+}
+
+/// Returns `Ok(true)` if the given input is to be copied through without
+/// conversion to 7-bit ASCII because the given start of it looks like
+/// output from a previous conversion, `Ok(false)` if it is to be converted,
+/// or a `std::io::Error` type if such an input is to be refused
+///
+/// # Arguments
+///
+/// * `infile` - Path to input file (or "-" or "" for `stdin`)
+/// * `buf` - Byte (u8) slice from the start of the input
+/// * `converted` - Treatment of input that looks previously converted
+///
+fn pass_converted(infile: &str, buf: &[u8], converted: asciify::Converted) -> io::Result<bool> {
+    if converted == asciify::Converted::Convert || !asciify::is_converted(buf) {
+        return Ok(false);
+    }
+    let name = if is_stdin(infile) { "stdin" } else { infile };
+    match converted {
+        asciify::Converted::Pass => {
+            log::warn!(
+                code = "passed-converted";
+                "Input '{}' looks converted already: passed through",
+                name
+            );
+            Ok(true)
+        }
+        _ => Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!(
+                "Input '{}' looks like output from a previous conversion \
+                 (use --converted pass or convert to override)",
+                name
+            ),
+        )),
+    }
+}
+
+/// Returns `true` if the given file is empty or its last byte is a line
+/// feed, otherwise `false`, leaving the file positioned at its end
+///
+/// # Arguments
+///
+/// * `file` - File to be examined
+///
+fn ends_with_newline(file: &mut File) -> io::Result<bool> {
+    if file.seek(SeekFrom::End(0))? == 0 {
+        return Ok(true);
+    }
+    let mut last = [0u8];
+    file.seek(SeekFrom::End(-1))?;
+    file.read_exact(&mut last)?;
+    Ok(last[0] == b'\n')
+}
+
+/// Returns `true` if the given input files are to be converted in memory,
+/// i.e. if this is forced by `in_memory` in the options or if they are all
+/// uncompressed files that total no more than `IN_MEMORY_LIMIT` bytes,
+/// otherwise `false`
+///
+/// # Arguments
+///
+/// * `infiles` - Paths to input files (or none to use `stdin`)
+/// * `options` - Settings for filter exclusions and output rendering
+///
+fn fits_in_memory(infiles: &[String], options: &ws_filters::Options) -> bool {
+    if options.in_memory {
+        return true;
+    }
+    let mut total = 0;
+    for infile in infiles {
+        if is_stdin(infile) || is_gzip(infile) {
+            return false;
+        }
+        match fs::metadata(infile) {
+            Ok(metadata) => total += metadata.len(),
+            Err(_) => return false, // Leave the error to the streaming path
+        }
+    }
+    !infiles.is_empty() && total <= IN_MEMORY_LIMIT
+}
+
+/// Appends the text from the given reader to the given intermediate output,
+/// extracting the text of a WordStar for Windows file if `wsd` is `true` and
+/// otherwise converting it to 7-bit ASCII if `convert` is `true` (or else
+/// copying it)
+///
+/// # Arguments
+///
+/// * `reader` - Source of the input
+/// * `intermediate` - Destination for the intermediate text
+/// * `convert` - Flag to convert the input to 7-bit ASCII
+/// * `wsd` - Flag for a WordStar for Windows input
+/// * `options` - Settings for filter exclusions and output rendering
+///
+fn append_input(
+    reader: &mut dyn BufRead,
+    intermediate: &mut dyn Write,
+    convert: bool,
+    wsd: bool,
+    options: &ws_filters::Options,
+) -> io::Result<()> {
+    if wsd {
+        ws_windows::extract_file(reader, intermediate)
+    } else if convert {
+        asciify::convert_file(reader, intermediate, options.filler)
+    } else {
+        io::copy(reader, intermediate).map(|_| ())
+    }
+}
+
+/// Returns a reader for the 7-bit ASCII conversion of the given input files
+/// (or `stdin` if there are none), each starting on a new line, or a
+/// `std::io::Error` type on failure
+///
+/// A single input file that is already 7-bit ASCII (or is not to be
+/// converted) is read directly, while any other input is converted (or
+/// copied) into a temporary file positioned at its start.  Small inputs (see
+/// `fits_in_memory()`) are instead read whole and converted in memory.  An input that
+/// looks like output from a previous conversion is refused, copied or
+/// converted as given by `converted` in the options.  The names of the
+/// inputs are given to `log_file::set_input()` for any JSON log records.
+///
+/// # Arguments
+///
+/// * `infiles` - Paths to input files (or none to use `stdin`)
+/// * `options` - Settings for filter exclusions and output rendering
+/// * `convert` - Flag to convert the inputs to 7-bit ASCII (otherwise they
+///   are taken to be clean already)
+///
+fn read_inputs(
+    infiles: &[String],
+    options: &ws_filters::Options,
+    convert: bool,
+) -> io::Result<Box<dyn Read>> {
+    log_file::set_input(Some(&match infiles {
+        [] => STDIN_NAME.to_string(),
+        _ => infiles.join(", "),
+    }));
+    let in_memory = fits_in_memory(infiles, options);
+    if let [infile] = infiles {
+        if infile != STDIN_NAME
+            && !infile.is_empty()
+            && !is_gzip(infile)
+            && !ws_windows::is_wsd(infile)
+        {
+            if in_memory {
+                let text = fs::read(infile)?;
+                if !convert || asciify::is_clean_file(&mut &text[..], options.filler)? {
+                    log::info!("Input is already 7-bit ASCII");
+                    return Ok(Box::new(Cursor::new(text)));
+                }
+            } else {
+                let mut file = File::open(infile)?;
+                if !convert || asciify::is_clean_file(&mut BufReader::new(&file), options.filler)? {
+                    log::info!("Input is already 7-bit ASCII");
+                    file.seek(SeekFrom::Start(0))?;
+                    return Ok(Box::new(file));
+                }
+            }
+        }
+    }
+
+    let stdin = [STDIN_NAME.to_string()];
+    let infiles = if infiles.is_empty() { &stdin } else { infiles };
+    let mut readers = Vec::with_capacity(infiles.len());
+    for infile in infiles {
+        let mut reader = open_input(infile, in_memory)?; // Check all inputs before starting
+        if is_stdin(infile) {
+            check_foreign(reader.fill_buf()?)?;
+        }
+        let wsd = convert && ws_windows::is_wsd(infile);
+        let pass =
+            convert && !wsd && pass_converted(infile, reader.fill_buf()?, options.converted)?;
+        readers.push((reader, convert && !pass, wsd));
+    }
+
+    if in_memory {
+        let mut intermediate = Vec::new();
+        for (mut reader, convert, wsd) in readers {
+            if intermediate.last().is_some_and(|&last| last != b'\n') {
+                intermediate.push(b'\n');
+            }
+            append_input(&mut reader, &mut intermediate, convert, wsd, options)?;
+        }
+        return Ok(Box::new(Cursor::new(intermediate)));
+    }
+
+    let mut intermediate = tempfile::tempfile()?;
+
+    for (mut reader, convert, wsd) in readers {
+        if !ends_with_newline(&mut intermediate)? {
+            intermediate.write_all(b"\n")?;
+        }
+        append_input(&mut reader, &mut intermediate, convert, wsd, options)?;
+    }
+    intermediate.seek(SeekFrom::Start(0))?;
+    Ok(Box::new(intermediate))
+}
+
+/// Returns the path of the output file for the given input file in a batch
+/// conversion, with the extension for the output format (and ".gz" if the
+/// output is compressed) and placed in the given output directory (if any)
+/// at the input's relative path, so that any subdirectories of a recursive
+/// batch are mirrored, or otherwise alongside the input
+///
+/// # Arguments
+///
+/// * `infile` - Path to input file
+/// * `relative` - Path of input file relative to the directory given as an
+///   input (or just its file name)
+/// * `out_dir` - Path to output directory (or `None` to use that of input)
+/// * `options` - Settings for filter exclusions and output rendering
 ///
 /// # Examples
 /// ```
-/// ws_file::process("input.ws", "output.txt", None).unwrap();
+/// let relative = Path::new("ch1/one.ws");
+/// let path = batch_output("docs/ch1/one.ws", relative, Some("out"), &Options::default());
+/// assert_eq!(path, PathBuf::from("out/ch1/one.txt"));
 /// ```
-pub fn process(
+fn batch_output(
     infile: &str,
+    relative: &Path,
+    out_dir: Option<&str>,
+    options: &ws_filters::Options,
+) -> PathBuf {
+    let mut path = Path::new(infile).to_path_buf();
+    if is_gzip(infile) {
+        path.set_extension(""); // Name output after the compressed file
+    }
+    let mut path = path.with_extension(options.render.format.extension());
+    if options.compress {
+        path.as_mut_os_string().push(format!(".{}", GZIP_EXTENSION));
+    }
+    match (out_dir, path.file_name()) {
+        (Some(dir), Some(name)) => Path::new(dir).join(relative.with_file_name(name)),
+        _ => path,
+    }
+}
+
+/// Writes the given report (e.g. a `BatchReport`) to the given new file as
+/// JSON if its extension is ".json", otherwise as CSV
+///
+/// # Arguments
+///
+/// * `path` - Path to report file
+/// * `to_json` - Function returning the report as JSON text
+/// * `to_csv` - Function returning the report as CSV text
+///
+fn write_report<J, C>(path: &str, to_json: J, to_csv: C) -> io::Result<()>
+where
+    J: FnOnce() -> io::Result<String>,
+    C: FnOnce() -> String,
+{
+    let is_json = Path::new(path)
+        .extension()
+        .is_some_and(|ext| ext.eq_ignore_ascii_case(JSON_EXTENSION));
+    let text = if is_json { to_json()? } else { to_csv() };
+    let mut writer = open_output(path, false)?;
+    writer.write_all(text.as_bytes())?;
+    writer.finish()
+}
+
+/// Returns `true` if the given output file exists and was modified no
+/// earlier than the given input file (as for `make`), otherwise `false`
+///
+/// # Arguments
+///
+/// * `infile` - Path to input file
+/// * `outfile` - Path to output file
+///
+fn is_up_to_date(infile: &str, outfile: &Path) -> bool {
+    let modified = |path: &Path| fs::metadata(path).and_then(|meta| meta.modified());
+    match (modified(Path::new(infile)), modified(outfile)) {
+        (Ok(input), Ok(output)) => output >= input,
+        _ => false,
+    }
+}
+
+/// Writes the planned mapping of each given input file to its output file
+/// to `stdout` (noting any output that is up to date and would be skipped),
+/// without converting anything or creating any directories
+///
+/// Returns `()` on success or a `std::io::Error` type on failure
+///
+/// # Arguments
+///
+/// * `inputs` - Input files (as found by `walk::expand()`)
+/// * `settings` - Output directory (or `None` to write each output alongside
+///   its input) and flag to force conversion
+/// * `options` - Settings for output rendering
+///
+fn list_batch(
+    inputs: &[walk::Input],
+    settings: &BatchSettings,
+    options: &ws_filters::Options,
+) -> io::Result<()> {
+    let stdout = io::stdout();
+    let mut writer = stdout.lock();
+    for walk::Input { path, relative } in inputs {
+        let outfile = batch_output(path, relative, settings.out_dir.as_deref(), options);
+        let note = if !settings.force && is_up_to_date(path, &outfile) {
+            " (up to date)"
+        } else {
+            ""
+        };
+        writeln!(writer, "{} -> {}{}", path, outfile.display(), note)?;
+    }
+    writer.flush()
+}
+
+/// Converts the given input file to the given new output file within the
+/// given limits, returning the headline figures for the conversion or a
+/// `std::io::Error` type on failure (in which case no output file is left)
+///
+/// # Arguments
+///
+/// * `infile` - Path to input file
+/// * `outfile` - Path to output file
+/// * `options` - Settings for filter exclusions and output rendering
+/// * `limits` - Limits on the time taken and the size of the output
+///
+fn convert_one(
+    infile: &str,
+    outfile: &str,
+    options: &ws_filters::Options,
+    limits: Limits,
+) -> io::Result<Summary> {
+    let start = Instant::now();
+    let intermediate = read_inputs(&[infile.to_string()], options, true)?;
+    let mut intermediate = limits.timed(intermediate, start);
+    let in_memory = fits_in_memory(&[infile.to_string()], options);
+    let output = open_output_with(outfile, options.compress, true, in_memory)?;
+    let mut writer = limits.limited(output);
+    let stats = ws_filters::transform_file(&mut intermediate, &mut writer, options)?;
+    writer.into_inner().finish()?;
+    exit_status::add_warnings(stats.warnings);
+    Ok(stats.summary())
+}
+
+/// Returns a copy of the given options with the provenance of the
+/// conversion completed with the checksums of the given inputs, or a
+/// `std::io::Error` type if an input cannot be read or the provenance is to
+/// be written to a sidecar without an output filename
+///
+/// A warning is logged if the provenance is to be embedded in output in a
+/// format that has no place for it.
+///
+/// # Arguments
+///
+/// * `provenance` - Provenance given in the options
+/// * `infiles` - Paths to input files (or none for `stdin`)
+/// * `outfile` - Path to output file (or "" for `stdout`)
+/// * `options` - Settings for filter exclusions and output rendering
+///
+fn with_inputs(
+    provenance: &Provenance,
+    infiles: &[String],
+    outfile: &str,
+    options: &ws_filters::Options,
+) -> io::Result<ws_filters::Options> {
+    let format = options.render.format;
+    match provenance.placement {
+        Placement::Sidecar if outfile.is_empty() => {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "Provenance sidecar needs an output filename",
+            ))
+        }
+        Placement::Embed if !format.embeds_provenance() => log::warn!(
+            code = "provenance-not-embedded";
+            "Provenance cannot be embedded in {:?} output (use a sidecar instead)",
+            format
+        ),
+        _ => {}
+    }
+    let mut provenance = provenance.clone();
+    provenance.add_inputs(infiles)?;
+    let mut options = options.clone();
+    options.render.provenance = Some(provenance);
+    Ok(options)
+}
+
+/// Writes the given provenance as JSON to a new sidecar file named from the
+/// given output filename (see `provenance::sidecar_name()`)
+///
+/// Returns `()` on success or a `std::io::Error` type on failure (including
+/// if the sidecar file already exists)
+///
+/// # Arguments
+///
+/// * `outfile` - Path to output file
+/// * `provenance` - Provenance of the conversion
+///
+fn write_sidecar(outfile: &str, provenance: &Provenance) -> io::Result<()> {
+    let mut file = OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .open(provenance::sidecar_name(outfile))?;
+    writeln!(file, "{}", provenance.to_json()?)
+}
+
+/// Converts the intermediate text (see `read_inputs()`) from the given
+/// inputs to a new output file (or `stdout`) or, if `split` is set in the
+/// options, to a new output file for each part (see `SplitOutput`), and then
+/// writes any provenance sidecar
+///
+/// Returns `()` on success or a `std::io::Error` type on failure
+///
+/// # Arguments
+///
+/// * `intermediate` - Source of intermediate text
+/// * `infiles` - Paths to input files (or none for `stdin`)
+/// * `outfile` - Path to output file (or "" to use `stdout`)
+/// * `options` - Settings for filter exclusions and output rendering
+///
+fn convert_to(
+    intermediate: &mut dyn Read,
+    infiles: &[String],
     outfile: &str,
-    excludes: Option<ws_filters::Excludes>,
+    options: &ws_filters::Options,
 ) -> io::Result<()> {
-    let mut reader: Box<dyn Read> = if !infile.is_empty() {
-        Box::new(BufReader::new(File::open(infile)?))
+    let in_memory = fits_in_memory(infiles, options);
+    let recorded;
+    let options = match &options.render.provenance {
+        Some(provenance) => {
+            recorded = with_inputs(provenance, infiles, outfile, options)?;
+            &recorded
+        }
+        None => options,
+    };
+    let stats = if options.split != Split::None {
+        let mut parts = SplitOutput::new(outfile, options.compress, in_memory)?;
+        let stats = ws_filters::transform_split(intermediate, &mut parts, options)?;
+        parts.finish()?;
+        stats
     } else {
-        Box::new(BufReader::new(io::stdin()))
+        let mut writer = open_output_with(outfile, options.compress, false, in_memory)?;
+        let stats = ws_filters::transform_file(intermediate, &mut writer, options)?;
+        writer.finish()?;
+        stats
+    };
+    exit_status::add_warnings(stats.warnings);
+    match &options.render.provenance {
+        Some(provenance) if provenance.placement == Placement::Sidecar => {
+            write_sidecar(outfile, provenance)
+        }
+        _ => Ok(()),
+    }
+}
+
+// "OUTPUT" OBJECT
+
+/// Destination for output text, either `stdout` or a temporary file in the
+/// directory of the output file that is renamed to the output file by
+/// `finish()` (replacing any existing file only if `replace` is set), so
+/// that the output file only appears once it is complete, or a
+/// gzip-compressed stream to either of these
+///
+/// Note: The temporary file is deleted if the `Output` is dropped without
+/// calling `finish()` (e.g. when a conversion fails partway).  Output held
+/// in memory instead is written to a temporary file in one go by `finish()`,
+/// which is then renamed in the same way.
+enum Output {
+    Stdout(BufWriter<io::Stdout>),
+    File {
+        writer: BufWriter<NamedTempFile>,
+        path: PathBuf,
+        replace: bool,
+    },
+    Memory {
+        buffer: Vec<u8>,
+        path: PathBuf,
+        replace: bool,
+    },
+    Gzip(GzEncoder<Box<Output>>),
+}
+
+impl Output {
+    /// Returns this `Output` object wrapped to gzip-compress the output
+    fn compressed(self) -> Self {
+        Output::Gzip(GzEncoder::new(Box::new(self), Compression::default()))
+    }
+
+    /// Flushes any buffered output (ending any compressed stream) and moves
+    /// the temporary file (if any) to the output file, failing if the output
+    /// file has appeared meanwhile
+    fn finish(self) -> io::Result<()> {
+        match self {
+            Output::Stdout(mut writer) => writer.flush(),
+            Output::File {
+                writer,
+                path,
+                replace,
+            } => {
+                let temp = writer.into_inner().map_err(|e| e.into_error())?;
+                persist(temp, &path, replace)
+            }
+            Output::Memory {
+                buffer,
+                path,
+                replace,
+            } => {
+                let mut temp = NamedTempFile::new_in(output_dir(&path))?;
+                temp.write_all(&buffer)?;
+                persist(temp, &path, replace)
+            }
+            Output::Gzip(encoder) => encoder.finish()?.finish(),
+        }
+    }
+}
+
+impl Write for Output {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self {
+            Output::Stdout(writer) => writer.write(buf),
+            Output::File { writer, .. } => writer.write(buf),
+            Output::Memory { buffer, .. } => buffer.write(buf),
+            Output::Gzip(encoder) => encoder.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            Output::Stdout(writer) => writer.flush(),
+            Output::File { writer, .. } => writer.flush(),
+            Output::Memory { .. } => Ok(()),
+            Output::Gzip(encoder) => encoder.flush(),
+        }
+    }
+}
+
+// "SPLIT OUTPUT" OBJECT
+
+/// Destinations for the parts of a split output, each an `Output` for a
+/// file named from the output filename by `sink::part_name()`, which are
+/// only moved to their output files by `finish()` once every part is
+/// complete
+struct SplitOutput<'a> {
+    outfile: &'a str,
+    compress: bool,
+    in_memory: bool,
+    outputs: Vec<Output>,
+}
+
+impl<'a> SplitOutput<'a> {
+    /// Returns a new `SplitOutput` object with no parts, for output files
+    /// named from the given output filename (which must not be empty)
+    fn new(outfile: &'a str, compress: bool, in_memory: bool) -> io::Result<Self> {
+        if outfile.is_empty() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "Split output needs an output filename",
+            ));
+        }
+        Ok(SplitOutput {
+            outfile,
+            compress,
+            in_memory,
+            outputs: Vec::new(),
+        })
+    }
+
+    /// Finishes the output of each part (see `Output::finish()`)
+    fn finish(self) -> io::Result<()> {
+        self.outputs.into_iter().try_for_each(Output::finish)
+    }
+}
+
+impl OutputSink for SplitOutput<'_> {
+    fn part(&mut self, part_no: usize) -> io::Result<&mut dyn Write> {
+        let path = sink::part_name(self.outfile, part_no);
+        let output = open_output_with(&path, self.compress, false, self.in_memory)?;
+        self.outputs.push(output);
+        Ok(self.outputs.last_mut().unwrap())
+    }
+}
+
+// EXTERNAL PUBLIC FUNCTIONS
+
+/// Attempts to convert one or more WordStar files from the input filenames
+/// (or `stdin` if there are none) to a new Unicode based text file at the
+/// output filename (or `stdout` if empty) via a temporary file, using the
+/// filter and rendering settings in `ws_filters::Options`
+///
+/// The inputs are joined (each starting on a new line) and converted as a
+/// single document.  An input filename of "-" reads from `stdin`.  Only the
+/// text of an input file ending in ".wsd" (WordStar for Windows) is kept,
+/// without its formatting.  An input file ending in ".gz" is decompressed as
+/// it is read, while the output is gzip-compressed if `compress` is set in
+/// the options (adding ".gz" to the output filename) or the output filename
+/// already ends in ".gz".
+///
+/// If `split` is set in the options, the output is instead split into
+/// parts (see `ws_filters::transform_split()`), each written to a file named
+/// from the output filename by `sink::part_name()` (e.g. "book-001.html"),
+/// in which case an output filename must be given.  Likewise, if the
+/// provenance of the conversion is given in the render options, it is
+/// completed with the checksums of the input files and either embedded in
+/// the output or written to a sidecar file beside it.
+///
+/// Returns `()` on success or a `std::io::Error` type on failure
+///
+/// Note: If an output filename is specified then an error will be
+/// returned and no further action taken if the file already exists.  The
+/// output is written to a temporary file in the same directory, which is
+/// only renamed to the output filename once the conversion is complete, so
+/// that a failed conversion leaves no output file behind.
+///
+/// # Arguments
+///
+/// * `infiles` - Paths to input files (or none to use `stdin`)
+/// * `outfile` - Path to output file (or "" to use `stdout`)
+/// * `options` - Settings for filter exclusions and output rendering
+///
+/// # Examples
+/// ```
+/// let options = ws_filters::Options::default();
+/// let infiles = ["one.ws".to_string(), "two.ws".to_string()];
+/// ws_file::process(&infiles, "output.txt", &options).unwrap();
+/// ```
+pub fn process(infiles: &[String], outfile: &str, options: &ws_filters::Options) -> io::Result<()> {
+    let mut intermediate = read_inputs(infiles, options, true)?;
+    convert_to(&mut intermediate, infiles, outfile, options)
+}
+
+/// Attempts to convert one or more files of 7-bit ASCII text (e.g. from
+/// the "asciify" subcommand or another tool) from the input filenames (or
+/// `stdin` if there are none) to a new Unicode based text file at the
+/// output filename (or `stdout` if empty) in the same way as `process()`,
+/// but applying only the filters without first converting the inputs to
+/// 7-bit ASCII
+///
+/// Returns `()` on success or a `std::io::Error` type on failure
+/// (including if an input is not valid UTF-8)
+///
+/// Note: If an output filename is specified then an error will be
+/// returned and no further action taken if the file already exists
+///
+/// # Arguments
+///
+/// * `infiles` - Paths to input files (or none to use `stdin`)
+/// * `outfile` - Path to output file (or "" to use `stdout`)
+/// * `options` - Settings for filter exclusions and output rendering
+///
+/// # Examples
+/// ```
+/// let options = ws_filters::Options::default();
+/// ws_file::filters(&["one.txt".to_string()], "output.txt", &options).unwrap();
+/// ```
+pub fn filters(infiles: &[String], outfile: &str, options: &ws_filters::Options) -> io::Result<()> {
+    let mut intermediate = read_inputs(infiles, options, false)?;
+    convert_to(&mut intermediate, infiles, outfile, options)
+}
+
+/// Attempts to convert one or more WordStar files from the input filenames
+/// (or `stdin` if there are none) in the same way as `process()`, but
+/// without writing any output, so that only the statistics on the
+/// conversion and the converted text (word count etc.) are reported
+///
+/// Returns `()` on success or a `std::io::Error` type on failure
+///
+/// # Arguments
+///
+/// * `infiles` - Paths to input files (or none to use `stdin`)
+/// * `options` - Settings for filter exclusions and output rendering
+///
+/// # Examples
+/// ```
+/// let options = ws_filters::Options::default();
+/// ws_file::report(&["one.ws".to_string()], &options).unwrap();
+/// ```
+pub fn report(infiles: &[String], options: &ws_filters::Options) -> io::Result<()> {
+    let mut intermediate = read_inputs(infiles, options, true)?;
+    let stats = ws_filters::transform_file(&mut intermediate, &mut io::sink(), options)?;
+    exit_status::add_warnings(stats.warnings);
+    Ok(())
+}
+
+/// Attempts to convert one or more WordStar files from the input filenames
+/// (or `stdin` if there are none) to plain text using the settings in
+/// `ws_filters::Options`, re-encode the text as WordStar text and convert
+/// it again (see `self_test::check()`), writing the outcome of comparing the
+/// two conversions to `stdout`
+///
+/// Returns `()` if the two conversions match, otherwise a `std::io::Error`
+/// type (including if they differ, as a sign of text lost in conversion)
+///
+/// # Arguments
+///
+/// * `infiles` - Paths to input files (or none to use `stdin`)
+/// * `options` - Settings for filter exclusions
+///
+/// # Examples
+/// ```
+/// let options = ws_filters::Options::default();
+/// ws_file::self_test(&["one.ws".to_string()], &options).unwrap();
+/// ```
+pub fn self_test(infiles: &[String], options: &ws_filters::Options) -> io::Result<()> {
+    let mut intermediate = read_inputs(infiles, options, true)?;
+    let result = FileResult {
+        name: match infiles {
+            [] => STDIN_NAME.to_string(),
+            _ => infiles.join(", "),
+        },
+        outcome: self_test::check(&mut intermediate, options)?,
     };
+    println!("{}", result);
+    match result.outcome {
+        Outcome::Failed { lines, .. } => Err(exit_status::files_failed(format!(
+            "Self-test: {} line(s) changed by re-encoding and converting again",
+            lines
+        ))),
+        _ => Ok(()),
+    }
+}
 
-    let mut writer: Box<dyn Write> = if !outfile.is_empty() {
-        Box::new(BufWriter::new(
-            OpenOptions::new()
-                .write(true)
-                .create_new(true)
-                .open(outfile)?,
+/// Attempts to convert one or more WordStar files from the input filenames
+/// (or `stdin` if there are none) to 7-bit ASCII text at the output
+/// filename (or `stdout` if empty) as for the first stage of `process()`,
+/// without applying any of the filters, so that the intermediate text can
+/// be inspected or passed to other tools
+///
+/// Returns `()` on success or a `std::io::Error` type on failure
+///
+/// Note: If an output filename is specified then an error will be
+/// returned and no further action taken if the file already exists
+///
+/// # Arguments
+///
+/// * `infiles` - Paths to input files (or none to use `stdin`)
+/// * `outfile` - Path to output file (or "" to use `stdout`)
+/// * `options` - Settings for filter exclusions and output rendering
+///
+/// # Examples
+/// ```
+/// let options = ws_filters::Options::default();
+/// ws_file::asciify(&["one.ws".to_string()], "one.txt", &options).unwrap();
+/// ```
+pub fn asciify(infiles: &[String], outfile: &str, options: &ws_filters::Options) -> io::Result<()> {
+    let mut intermediate = read_inputs(infiles, options, true)?;
+
+    let in_memory = fits_in_memory(infiles, options);
+    let mut writer = open_output_with(outfile, options.compress, false, in_memory)?;
+    io::copy(&mut intermediate, &mut writer)?;
+    writer.finish()
+}
+
+/// Attempts to strip one or more WordStar files from the input filenames
+/// (or `stdin` if there are none) down to plain 7-bit ASCII text at the
+/// output filename (or `stdout` if empty) in a single fast pass (see
+/// `strip::strip_file()`), without any of the filters, emphasis or markup
+/// of `process()`, e.g. for full-text indexing
+///
+/// The inputs are joined, each starting on a new line.  Input files ending
+/// in ".wsd" are not treated differently, and no options apply other than
+/// `compress` and `preserve_controls`.
+///
+/// Returns `()` on success or a `std::io::Error` type on failure
+///
+/// Note: If an output filename is specified then an error will be
+/// returned and no further action taken if the file already exists
+///
+/// # Arguments
+///
+/// * `infiles` - Paths to input files (or none to use `stdin`)
+/// * `outfile` - Path to output file (or "" to use `stdout`)
+/// * `options` - Settings for output compression and preserved controls
+///
+/// # Examples
+/// ```
+/// let options = ws_filters::Options::default();
+/// ws_file::strip(&["one.ws".to_string()], "one.txt", &options).unwrap();
+/// ```
+pub fn strip(infiles: &[String], outfile: &str, options: &ws_filters::Options) -> io::Result<()> {
+    let stdin = [STDIN_NAME.to_string()];
+    let infiles = if infiles.is_empty() { &stdin } else { infiles };
+    let mut readers = Vec::with_capacity(infiles.len());
+    for infile in infiles {
+        let mut reader = open_input(infile, false)?; // Check all inputs before starting
+        if is_stdin(infile) {
+            check_foreign(reader.fill_buf()?)?;
+        }
+        readers.push(reader);
+    }
+
+    let in_memory = fits_in_memory(infiles, options);
+    let mut writer = open_output_with(outfile, options.compress, false, in_memory)?;
+    for mut reader in readers {
+        strip::strip_file(&mut reader, &mut writer, &options.preserve_controls)?;
+    }
+    writer.finish()
+}
+
+/// Attempts to write a generated WordStar document (as from
+/// `bench::generate_features()`) to the output filename (or `stdout` if
+/// empty), e.g. as demonstration data or input for benchmarks
+///
+/// Returns `()` on success or a `std::io::Error` type on failure
+///
+/// Note: If an output filename is specified then an error will be
+/// returned and no further action taken if the file already exists
+///
+/// # Arguments
+///
+/// * `outfile` - Path to output file (or "" to use `stdout`)
+/// * `size` - Minimum size of the document in bytes
+/// * `density` - Proportion of formatted words (from 0.0 to 1.0)
+/// * `features` - Set of WordStar features to be used
+///
+/// # Examples
+/// ```
+/// ws_file::generate("demo.ws", 64 * 1024, 0.1, Features::all()).unwrap();
+/// ```
+pub fn generate(outfile: &str, size: usize, density: f64, features: Features) -> io::Result<()> {
+    let mut writer = open_output(outfile, false)?;
+    writer.write_all(&bench::generate_features(size, density, features))?;
+    writer.finish()
+}
+
+/// Attempts to convert each of the WordStar files (with a ".ws" extension)
+/// in the given directory using the settings in `ws_filters::Options`, and
+/// compare the output with the expected output held in the file of the same
+/// name with an ".expected" extension, writing the outcome for each file
+/// and a summary to `stdout`
+///
+/// Returns `()` if all of the files match their expected outputs, otherwise
+/// a `std::io::Error` type (including if any expected outputs are missing
+/// or there are no files to verify)
+///
+/// # Arguments
+///
+/// * `dir` - Path to directory holding the corpus
+/// * `options` - Settings for filter exclusions and output rendering
+///
+/// # Examples
+/// ```
+/// let options = ws_filters::Options::default();
+/// ws_file::verify_corpus("corpus", &options).unwrap();
+/// ```
+pub fn verify_corpus(dir: &str, options: &ws_filters::Options) -> io::Result<()> {
+    let results = corpus::verify(Path::new(dir), options)?;
+    let count = |outcome: fn(&Outcome) -> bool| {
+        results
+            .iter()
+            .filter(|result| outcome(&result.outcome))
+            .count()
+    };
+    let passed = count(|outcome| *outcome == Outcome::Passed);
+    let missing = count(|outcome| *outcome == Outcome::Missing);
+    let failed = results.len() - passed - missing;
+
+    for result in &results {
+        println!("{}", result);
+    }
+    println!(
+        "Corpus: {} passed, {} failed, {} missing of {} file(s)",
+        passed,
+        failed,
+        missing,
+        results.len()
+    );
+    if results.is_empty() {
+        Err(io::Error::new(
+            io::ErrorKind::NotFound,
+            format!("No .ws files found in '{}'", dir),
         ))
+    } else if passed < results.len() {
+        Err(exit_status::files_failed(format!(
+            "{} of {} file(s) not verified",
+            failed + missing,
+            results.len()
+        )))
     } else {
-        Box::new(BufWriter::new(io::stdout()))
+        Ok(())
+    }
+}
+
+/// Attempts to convert each of the given WordStar files separately to a new
+/// file of the same name with the extension for the output format (in the
+/// given output directory, if any), using the settings in
+/// `ws_filters::Options`, and then write a table summarising the outcome for
+/// each file to `stderr` in place of the statistics for each conversion
+///
+/// The summary is also written to the given report file (if any) as JSON if
+/// its extension is ".json" or as CSV otherwise.  A failure to convert one
+/// file is recorded in the summary and does not stop the others.  If
+/// requested, the warnings logged while converting each file are written to
+/// a ".log" file beside its output file.  A manifest of the files converted,
+/// with the size and SHA-256 checksum of each input and output file, can
+/// also be written (as JSON or CSV in the same way as the report).  A file
+/// whose conversion takes longer or gives more output than the given limits
+/// allow is skipped (without leaving an output file) and listed as such in
+/// the summary.  As for `make`, a file whose output file already exists and
+/// is newer than it is not converted again (so that an interrupted batch can
+/// be resumed) unless `force` is set in the settings, and an older output
+/// file is replaced.  A file whose output would overwrite one of the input
+/// files or an output already written by the batch is not converted.
+///
+/// Returns `()` if all of the files are converted, otherwise a
+/// `std::io::Error` type
+///
+/// Note: An error will be returned for a report or manifest file that
+/// already exists
+///
+/// # Arguments
+///
+/// * `infiles` - Paths to input files
+/// * `settings` - Output directory (or `None` to write each output alongside
+///   its input), report and manifest files (if any), flag to write a log
+///   file beside each output file, limits on each file, flag to force
+///   conversion, flag and policy for walking any input directories, and
+///   flag to list the planned outputs instead of converting
+/// * `options` - Settings for filter exclusions and output rendering
+///
+/// # Examples
+/// ```
+/// let options = ws_filters::Options::default();
+/// let infiles = ["one.ws".to_string(), "two.ws".to_string()];
+/// let settings = BatchSettings {
+///     out_dir: Some("out".to_string()),
+///     ..Default::default()
+/// };
+/// ws_file::batch(&infiles, &settings, &options).unwrap();
+/// ```
+pub fn batch(
+    infiles: &[String],
+    settings: &BatchSettings,
+    options: &ws_filters::Options,
+) -> io::Result<()> {
+    let inputs = walk::expand(infiles, settings.recursive, &settings.walk)?;
+    if settings.list {
+        return list_batch(&inputs, settings, options);
+    }
+    if let Some(dir) = &settings.out_dir {
+        fs::create_dir_all(dir)?;
+    }
+    let options = ws_filters::Options {
+        quiet: true,
+        ..options.clone()
     };
+    let mut results = BatchReport::new();
+    let mut entries = Manifest::new();
+    let mut claimed: HashSet<PathBuf> = inputs
+        .iter()
+        .map(|input| canonical(Path::new(&input.path)))
+        .collect(); // Inputs and outputs that no output may overwrite
+    for walk::Input {
+        path: infile,
+        relative,
+    } in &inputs
+    {
+        let outfile = batch_output(infile, relative, settings.out_dir.as_deref(), &options);
+        if let (Some(_), Some(parent)) = (&settings.out_dir, outfile.parent()) {
+            fs::create_dir_all(parent)?; // Mirror any subdirectory of the input
+        }
+        let output = outfile.to_string_lossy().into_owned();
+        let clash = !claimed.insert(canonical(&outfile));
+        let up_to_date = !clash && !settings.force && is_up_to_date(infile, &outfile);
+        let opened = if clash {
+            Err(io::Error::new(
+                io::ErrorKind::AlreadyExists,
+                format!(
+                    "Output '{}' would overwrite an input or earlier output",
+                    output
+                ),
+            ))
+        } else if settings.log_files && !up_to_date {
+            log_file::open_input(&outfile.with_extension(LOG_EXTENSION))
+        } else {
+            Ok(())
+        };
+        let converted = opened.and_then(|_| {
+            if up_to_date {
+                log::info!("Output '{}' is up to date", output);
+                return Ok(Summary::default());
+            }
+            let summary = convert_one(infile, &output, &options, settings.limits);
+            log_file::close_input()?;
+            summary
+        });
+        let converted = converted.and_then(|summary| {
+            if settings.manifest.is_some() {
+                entries.push(ManifestEntry::new(infile, &output)?);
+            }
+            Ok(summary)
+        });
+        let (summary, error, skipped) = match converted {
+            Ok(summary) => (summary, None, false),
+            Err(e) => (
+                Summary::default(),
+                Some(e.to_string()),
+                Limits::is_exceeded(&e),
+            ),
+        };
+        if skipped {
+            log::warn!(
+                code = "skipped-limit";
+                "Skipped '{}': {}",
+                infile,
+                error.as_deref().unwrap_or_default()
+            );
+        }
+        results.push(FileReport {
+            input: infile.clone(),
+            output,
+            summary,
+            error,
+            skipped,
+            up_to_date,
+        });
+    }
+    eprintln!("{}", results);
 
-    let mut intermediate = tempfile::tempfile()?;
+    if let Some(report) = &settings.report {
+        write_report(report, || results.to_json(), || results.to_csv())?;
+    }
+    if let Some(manifest) = &settings.manifest {
+        write_report(manifest, || entries.to_json(), || entries.to_csv())?;
+    }
 
-    asciify::convert_file(&mut reader, &mut intermediate)?;
-    intermediate.seek(SeekFrom::Start(0))?;
-    ws_filters::transform_file(&mut intermediate, &mut writer, excludes)?;
-    Ok(())
+    match results.failed() {
+        0 => Ok(()),
+        failed => Err(exit_status::files_failed(format!(
+            "{} of {} file(s) not converted",
+            failed,
+            inputs.len()
+        ))),
+    }
+}
+
+// Unit tests
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn batch_in(dir: &Path, names: &[&str], force: bool) -> io::Result<()> {
+        let infiles: Vec<String> = names
+            .iter()
+            .map(|name| dir.join(name).to_string_lossy().into_owned())
+            .collect();
+        let settings = BatchSettings {
+            force,
+            ..Default::default()
+        };
+        batch(&infiles, &settings, &ws_filters::Options::default())
+    }
+
+    #[test]
+    fn test_gzip_round_trip() {
+        let dir = tempfile::tempdir().unwrap();
+        for (name, compress, in_memory) in [
+            ("a.txt.gz", false, false),
+            ("b.txt.gz", false, true),
+            ("c.txt", true, false),
+        ] {
+            let outfile = dir.path().join(name).to_string_lossy().into_owned();
+            let mut output = open_output_with(&outfile, compress, false, in_memory).unwrap();
+            output.write_all(b"Compressed\ntext\n").unwrap();
+            output.finish().unwrap();
+            let infile = if compress {
+                format!("{}.{}", outfile, GZIP_EXTENSION)
+            } else {
+                outfile
+            };
+            assert!(fs::read(&infile).unwrap().starts_with(GZIP_MAGIC));
+            for in_memory in [false, true] {
+                let mut text = String::new();
+                let mut input = open_input(&infile, in_memory).unwrap();
+                input.read_to_string(&mut text).unwrap();
+                assert_eq!(text, "Compressed\ntext\n");
+            }
+        }
+    }
+
+    fn names(dir: &Path) -> Vec<String> {
+        let mut names: Vec<String> = fs::read_dir(dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().to_string_lossy().into_owned())
+            .collect();
+        names.sort();
+        names
+    }
+
+    #[test]
+    fn test_failed_output_removed() {
+        let dir = tempfile::tempdir().unwrap();
+        let limits = Limits {
+            max_output: Some(10),
+            ..Default::default()
+        };
+        for in_memory in [false, true] {
+            let outfile = dir.path().join("out.txt").to_string_lossy().into_owned();
+            let output = open_output_with(&outfile, false, false, in_memory).unwrap();
+            let mut writer = limits.limited(output);
+            let e = writer.write_all(&[b'x'; 100]).unwrap_err();
+            assert!(Limits::is_exceeded(&e));
+            drop(writer);
+            assert!(names(dir.path()).is_empty());
+        }
+        let text = "A line of text\n".repeat(10);
+        fs::write(dir.path().join("doc.ws"), &text).unwrap();
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(text.as_bytes()).unwrap();
+        fs::write(dir.path().join("big.ws.gz"), encoder.finish().unwrap()).unwrap();
+        let options = ws_filters::Options {
+            quiet: true,
+            ..Default::default()
+        };
+        for (infile, outfile) in [("doc.ws", "doc.txt"), ("big.ws.gz", "big.txt")] {
+            let infile = dir.path().join(infile).to_string_lossy().into_owned();
+            let outfile = dir.path().join(outfile).to_string_lossy().into_owned();
+            let e = convert_one(&infile, &outfile, &options, limits).unwrap_err();
+            assert!(Limits::is_exceeded(&e));
+        }
+        assert_eq!(names(dir.path()), ["big.ws.gz", "doc.ws"]);
+    }
+
+    #[test]
+    fn test_output_appeared_meanwhile() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("out.txt");
+        for in_memory in [false, true] {
+            let outfile = path.to_string_lossy();
+            let mut output = open_output_with(&outfile, false, false, in_memory).unwrap();
+            output.write_all(b"new\n").unwrap();
+            fs::write(&path, "existing\n").unwrap();
+            let e = output.finish().unwrap_err();
+            assert_eq!(e.kind(), io::ErrorKind::AlreadyExists);
+            assert_eq!(fs::read_to_string(&path).unwrap(), "existing\n");
+            assert_eq!(names(dir.path()), ["out.txt"]);
+            fs::remove_file(&path).unwrap();
+        }
+    }
+
+    #[test]
+    fn test_batch_output_is_input() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("doc.txt"), "plain\n").unwrap();
+        fs::write(dir.path().join("doc.ws"), b"word\x8D\n").unwrap();
+        assert!(batch_in(dir.path(), &["doc.txt", "doc.ws"], false).is_err());
+        let text = fs::read_to_string(dir.path().join("doc.txt")).unwrap();
+        assert_eq!(text, "plain\n");
+    }
+
+    #[test]
+    fn test_batch_force_output_is_input() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("doc.txt"), b"plain\x8D\n").unwrap();
+        assert!(batch_in(dir.path(), &["doc.txt"], true).is_err());
+        let text = fs::read(dir.path().join("doc.txt")).unwrap();
+        assert_eq!(text, b"plain\x8D\n");
+    }
+
+    #[test]
+    fn test_batch_output_written_twice() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("doc.ws"), "first\n").unwrap();
+        fs::write(dir.path().join("doc.doc"), "second\n").unwrap();
+        assert!(batch_in(dir.path(), &["doc.ws", "doc.doc"], true).is_err());
+        let text = fs::read_to_string(dir.path().join("doc.txt")).unwrap();
+        assert_eq!(text, "first\n");
+    }
 }
@@ -6,20 +6,34 @@
 // that the filter functions can use the Rust `?` operator as a terse
 // way to exit immediately with a `None` result.
 
-use crate::control_count::ControlCount;
+use crate::asciify;
+use crate::exit_status;
+use crate::render::{self, Renderer};
+use crate::sink::{OutputSink, SingleSink, Split};
+use crate::transliterate::{self, Charset, Transliterator};
 use crate::ws_align;
 use crate::ws_control;
-use crate::ws_dot_cmd;
+use crate::ws_dot_cmd::{self, Handled, SuffixMode};
+use crate::ws_escape;
+use crate::ws_events::{ConversionStats, Event, Parser, Tag};
+use crate::ws_heading;
+use crate::ws_lines;
 use crate::ws_overline;
+use crate::ws_overprint;
+use crate::ws_pitch;
 use crate::ws_special;
-use crate::ws_wrappers;
+use crate::ws_wrappers::{self, Span, Style};
 use bitflags::bitflags;
-use std::io::{self, BufRead, BufReader, BufWriter, Read, Write};
+use std::borrow::Cow;
+use std::fmt;
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::str::FromStr;
+use std::time::Instant;
 
 // Holds a set of flags to specify filters to be excluded
 bitflags! {
     #[derive(Default)]
-    pub struct Excludes: u8 {
+    pub struct Excludes: u16 {
         const NONE = 0;
         const DOT_CMDS = (1 << 0);
         const RE_ALIGN = (1 << 1);
@@ -27,6 +41,378 @@ bitflags! {
         const OVERLINE = (1 << 3);
         const WRAPPERS = (1 << 4);
         const CONTROLS = (1 << 5);
+        const PITCH = (1 << 6);
+        const OVERPRINT = (1 << 7);
+        const ESCAPES = (1 << 8);
+    }
+}
+
+// Names of the filters that can be excluded, in pipeline order
+const EXCLUDE_NAMES: [(Excludes, &str); 9] = [
+    (Excludes::DOT_CMDS, "dot-cmds"),
+    (Excludes::ESCAPES, "escapes"),
+    (Excludes::PITCH, "pitch"),
+    (Excludes::RE_ALIGN, "re-align"),
+    (Excludes::SPECIALS, "specials"),
+    (Excludes::OVERLINE, "overline"),
+    (Excludes::OVERPRINT, "overprint"),
+    (Excludes::WRAPPERS, "wrappers"),
+    (Excludes::CONTROLS, "controls"),
+];
+
+impl Excludes {
+    /// Names of the filters that can be excluded, in pipeline order
+    pub const NAMES: [&'static str; 9] = [
+        EXCLUDE_NAMES[0].1,
+        EXCLUDE_NAMES[1].1,
+        EXCLUDE_NAMES[2].1,
+        EXCLUDE_NAMES[3].1,
+        EXCLUDE_NAMES[4].1,
+        EXCLUDE_NAMES[5].1,
+        EXCLUDE_NAMES[6].1,
+        EXCLUDE_NAMES[7].1,
+        EXCLUDE_NAMES[8].1,
+    ];
+
+    /// Returns an `Excludes` value with no filters excluded (same as `empty()`)
+    pub fn none() -> Self {
+        Self::empty()
+    }
+}
+
+/// FromStr trait implementation for Excludes, accepting a comma-separated
+/// list of filter names (in any case) or "all" or "none"
+impl FromStr for Excludes {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut excludes = Excludes::none();
+        for name in s.split(',').map(str::trim).filter(|name| !name.is_empty()) {
+            let name = name.to_lowercase();
+            match EXCLUDE_NAMES.iter().find(|(_, n)| *n == name) {
+                Some((flag, _)) => excludes.insert(*flag),
+                None if name == "all" => excludes = Excludes::all(),
+                None if name == "none" => {}
+                None => return Err(format!("Unknown filter name: {}", name)),
+            }
+        }
+        Ok(excludes)
+    }
+}
+
+/// Display trait implementation for Excludes, giving a comma-separated list
+/// of the excluded filter names (or "none")
+impl fmt::Display for Excludes {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let names: Vec<&str> = EXCLUDE_NAMES
+            .iter()
+            .filter(|(flag, _)| self.contains(*flag))
+            .map(|(_, name)| *name)
+            .collect();
+        if names.is_empty() {
+            write!(f, "none")
+        } else {
+            write!(f, "{}", names.join(","))
+        }
+    }
+}
+
+/// Ways of dealing with deviations from the WordStar format (e.g. unmatched
+/// toggles or malformed dot commands)
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub enum Mode {
+    Strict, // Deviations are not repaired, and are logged as errors that fail the conversion
+    #[default]
+    Permissive, // Best-effort recovery (as set in the options), logging deviations as warnings
+}
+
+/// Holds the settings that control the filters and the rendering of output
+#[derive(Debug, Default, Clone)]
+pub struct Options {
+    pub excludes: Excludes,
+    pub filler: asciify::Filler,
+    pub converted: asciify::Converted,
+    pub input_format: ws_dot_cmd::InputFormat,
+    pub detect_columns: bool,
+    pub outline: bool,
+    pub ruled_headings: ws_heading::RuledHeadings,
+    pub blank_dot_cmds: bool,
+    pub lenient_dot_cmds: bool,
+    pub mode: Mode,
+    pub wrappers: Vec<ws_wrappers::CustomWrapper>,
+    pub overline_style: ws_overline::OverlineStyle,
+    pub lenient_overline: bool,
+    pub max_output_line: Option<usize>,
+    pub long_line_markup: bool,
+    pub fraction_style: ws_special::FractionStyle,
+    pub locale: ws_special::Locale,
+    pub symbols: ws_special::Symbols,
+    pub fractions: Vec<ws_special::CustomFraction>,
+    pub special_rules: Vec<ws_special::SpecialRule>,
+    pub dot_cmd_handlers: Vec<ws_dot_cmd::DotCmdHandler>,
+    pub header_template: Option<String>,
+    pub dot_cmd_suffix: ws_dot_cmd::SuffixMode,
+    pub keep_unknown: bool,
+    pub preserve_controls: Vec<char>,
+    pub profile: bool,
+    pub quiet: bool,
+    pub compress: bool,
+    pub in_memory: bool,
+    pub charset: Charset,
+    pub split: Split,
+    pub unknown_handler: Option<ws_control::ControlHandler>,
+    pub render: render::RenderOptions,
+}
+
+// "CONVERT STATE" OBJECT
+
+/// Holds the state carried from line to line by the filters (i.e. the pitch
+/// and wrapper settings), so that a document can be converted one line at a
+/// time with `convert_line`
+#[derive(Debug)]
+pub struct ConvertState {
+    pub(crate) pitch: ws_pitch::Pitch,
+    pub(crate) wrappers: ws_wrappers::Wrappers,
+    pub(crate) custom_codes: Vec<char>,
+    line_no: usize,
+}
+
+impl ConvertState {
+    /// Returns a new `ConvertState` object for the start of a document,
+    /// including any custom wrappers given in the `Options`
+    ///
+    /// Lines passed to `convert_line` are numbered from 1 for the handler
+    /// of unrecognised control characters (if any).
+    pub fn new(options: &Options) -> Self {
+        ConvertState {
+            pitch: ws_pitch::Pitch::new().with_font_tags(options.input_format.has_font_tags()),
+            wrappers: ws_wrappers::Wrappers::with_custom(&options.wrappers)
+                .with_underline_style(options.render.underline_style),
+            custom_codes: options.wrappers.iter().map(|w| w.code).collect(),
+            line_no: 0,
+        }
+    }
+
+    /// Returns a snapshot of the wrapper characters toggled on so far (e.g.
+    /// to checkpoint a conversion or to start converting a later paragraph
+    /// with another `ConvertState`)
+    pub fn wrappers_state(&self) -> ws_wrappers::WrappersState {
+        self.wrappers.state()
+    }
+
+    /// Sets the wrapper characters toggled on from the given snapshot (e.g.
+    /// from `wrappers_state()`)
+    ///
+    /// # Arguments
+    ///
+    /// * `state` - Snapshot of the wrapper state to be restored
+    ///
+    pub fn restore_wrappers(&mut self, state: &ws_wrappers::WrappersState) {
+        self.wrappers.restore(state);
+    }
+}
+
+// PRIVATE HELPER FUNCTIONS
+
+/// Returns `Some(replacement)` if the given text slice contains control
+/// characters that have been converted by `ws_control`, otherwise `None`,
+/// leaving alone the control characters to be preserved and offering any
+/// unrecognised ones to the handler (if any) given in the `Options`
+///
+/// # Arguments
+///
+/// * `s` - Slice of text to be processed
+/// * `options` - Settings for escaping and handling control characters
+/// * `line_no` - Number of the line holding the text
+///
+pub(crate) fn process_controls(s: &str, options: &Options, line_no: usize) -> Option<String> {
+    let escape = !options.keep_unknown;
+    let preserve = &options.preserve_controls;
+    match options.unknown_handler {
+        Some(handler) => ws_control::process_with(s, escape, preserve, |c| handler(c, line_no)),
+        None => ws_control::process(s, escape, preserve),
+    }
+}
+
+/// Returns the given line (mapped from the given spans) unchanged if it is
+/// within the maximum output line length (in characters) given in the
+/// `Options`, otherwise logs a warning and returns either the line unchanged
+/// or the spans with markup in place of combining characters (see
+/// `ws_wrappers::markup_spans()`) if the `Options` ask for it
+///
+/// # Arguments
+///
+/// * `line` - Line of text with text attributes mapped to Unicode characters
+/// * `spans` - Slice of the spans from which the line was mapped
+/// * `options` - Settings for the maximum output line length
+/// * `line_no` - Number of the line holding the text
+///
+pub(crate) fn limit_line(
+    line: String,
+    spans: &[Span],
+    options: &Options,
+    line_no: usize,
+) -> String {
+    let max = match options.max_output_line {
+        Some(max) => max,
+        None => return line,
+    };
+    let len = line.chars().count();
+    if len <= max {
+        return line;
+    }
+    log::warn!(
+        code = "long-output-line";
+        "Line {}: output line of {} characters exceeds limit of {}",
+        line_no,
+        len,
+        max
+    );
+    if options.long_line_markup {
+        ws_wrappers::markup_spans(spans)
+    } else {
+        line
+    }
+}
+
+/// Returns `true` if overline sequences with mismatched counts are to be
+/// accepted, i.e. if this is set in the given options and not overridden by
+/// `Mode::Strict`, otherwise `false`
+pub(crate) fn lenient_overline(options: &Options) -> bool {
+    options.lenient_overline && options.mode == Mode::Permissive
+}
+
+// EXTERNAL PUBLIC FUNCTIONS
+
+/// Converts a single line of 7-bit ASCII text (e.g. from a database of
+/// recovered lines) in the same way as `transform_file` with plain text
+/// output, updating the given `ConvertState` for the next line
+///
+/// Returns the line borrowed if no filter changed it, otherwise the owned
+/// replacement.  A dot command line that is removed by the filters gives an
+/// empty line, which the caller may choose to drop.  Any suffix after the
+/// text of a dot command that is to be kept as text replaces a removed line
+/// or otherwise follows the replacement on a new line (as there is no way
+/// to keep it as a comment in plain text).  Ruled headings are left
+/// unchanged, as the ruling on the next line cannot be seen.
+///
+/// # Arguments
+///
+/// * `line` - Line of text to be converted (without line ending)
+/// * `state` - State carried from the previous line of the document
+/// * `options` - Settings for filter exclusions
+///
+/// # Examples
+/// ```
+/// let options = Options::default();
+/// let mut state = ConvertState::new(&options);
+/// assert_eq!(convert_line("a\x13b\x13c", &mut state, &options), "ab\u{332}c");
+/// ```
+pub fn convert_line<'a>(
+    line: &'a str,
+    state: &mut ConvertState,
+    options: &Options,
+) -> Cow<'a, str> {
+    let excludes = options.excludes;
+    let mut line = Cow::Borrowed(line);
+    let mut page_break = None; // Text of a page break, put before any suffix
+    state.line_no += 1;
+
+    if !excludes.contains(Excludes::DOT_CMDS) {
+        if options.lenient_dot_cmds && options.mode == Mode::Permissive {
+            if let Some(repaired) = ws_dot_cmd::repair(&line, options.input_format) {
+                log::warn!(
+                    code = "repaired-dot-command";
+                    "Line {}: repaired dot command {:?}",
+                    state.line_no,
+                    line
+                );
+                line = Cow::Owned(repaired);
+            }
+        }
+        let handlers = &options.dot_cmd_handlers;
+        let suffix = match options.dot_cmd_suffix {
+            SuffixMode::Text => ws_dot_cmd::suffix(&line).map(str::to_string),
+            SuffixMode::Drop | SuffixMode::Comment => None,
+        };
+        let follow = |text: String| match &suffix {
+            Some(suffix) => format!("{}\n{}", text, suffix),
+            None => text,
+        };
+        match ws_dot_cmd::process_with(&line, handlers, options.header_template.as_deref()) {
+            Some(Handled::PageBreak) => {
+                let text = options.render.page_break.text();
+                page_break =
+                    Some(transliterate::transliterate(&text, options.charset).into_owned());
+                line = Cow::Owned(suffix.clone().unwrap_or_default());
+            }
+            Some(Handled::Removed) => line = Cow::Owned(suffix.clone().unwrap_or_default()),
+            Some(Handled::Text(text) | Handled::Heading(_, text)) => {
+                line = Cow::Owned(follow(text))
+            }
+            None => {}
+        }
+    }
+    if !excludes.contains(Excludes::ESCAPES) {
+        if let Some((replacement, _)) = ws_escape::process(&line) {
+            line = Cow::Owned(replacement);
+        }
+    }
+    if !excludes.contains(Excludes::PITCH) {
+        if let Some(replacement) = state.pitch.process(&line) {
+            line = Cow::Owned(replacement);
+        }
+    }
+    if !excludes.contains(Excludes::RE_ALIGN) {
+        if let Some(replacement) = ws_align::process(&line, &state.custom_codes) {
+            line = Cow::Owned(replacement);
+        }
+    }
+    if !excludes.contains(Excludes::SPECIALS) {
+        if let Some(replacement) = ws_special::process_with(
+            &line,
+            options.fraction_style,
+            options.locale,
+            options.symbols,
+            &options.fractions,
+            &options.special_rules,
+        ) {
+            line = Cow::Owned(replacement);
+        }
+    }
+    if !excludes.contains(Excludes::OVERLINE) {
+        if let Some(replacement) =
+            ws_overline::process(&line, options.overline_style, lenient_overline(options))
+        {
+            line = Cow::Owned(replacement);
+        }
+    }
+    if !excludes.contains(Excludes::OVERPRINT) {
+        if let Some(replacement) = ws_overprint::process(&line) {
+            line = Cow::Owned(replacement);
+        }
+    }
+    if !excludes.contains(Excludes::WRAPPERS) {
+        if options.max_output_line.is_some() {
+            let spans = state.wrappers.split_spans(&line);
+            let mapped = ws_wrappers::map_spans(&spans, options.render.underline_style);
+            line = Cow::Owned(limit_line(mapped, &spans, options, state.line_no));
+        } else if let Some(replacement) = state.wrappers.process(&line) {
+            line = Cow::Owned(replacement);
+        }
+    }
+    if !excludes.contains(Excludes::CONTROLS) {
+        if let Some(replacement) = process_controls(&line, options, state.line_no) {
+            line = Cow::Owned(replacement);
+        }
+    }
+    if let Cow::Owned(text) = transliterate::transliterate(&line, options.charset) {
+        line = Cow::Owned(text);
+    }
+    match page_break {
+        Some(text) if line.is_empty() => Cow::Owned(text),
+        Some(text) => Cow::Owned(format!("{}\n{}", text, line)),
+        None => line,
     }
 }
 
@@ -34,105 +420,384 @@ bitflags! {
 /// (e.g. from `asciify::convert_file`) into a line-formatted stream of
 /// Unicode output characters that implement conversions of WordStar dot
 /// commands, wrapper control characters and other special sequences,
-/// optionally excluding a set of `Excludes` filters
+/// excluding any `Excludes` filters and rendering the result in the output
+/// format given in the `Options`
 ///
-/// Returns `()` on success or a `std::io::Error` type on failure
+/// Returns the statistics on the conversion (see `ConversionStats`) on
+/// success or a `std::io::Error` type on failure.  The statistics are also
+/// written to `stderr` unless `quiet` is set in the `Options`.  With
+/// `Mode::Strict`, any deviation from the WordStar format fails the
+/// conversion (once the output has been written) with an error made by
+/// `exit_status::files_failed()`.
 ///
 /// # Arguments
 ///
 /// * `input` - Source of bytes that implements `Read` trait
 /// * `output` - Destination for bytes that implements `Write` trait
-/// * `excludes` - Optional set of flags to specify filters to exclude
+/// * `options` - Settings for filter exclusions and output rendering
 ///
 /// # Examples
 /// ```
 /// use std::io;
-/// use ws_filters::transform_file;
+/// use ws_filters::{transform_file, Options};
 ///
 /// let mut input = io::stdin();
 /// let mut output = io::stdout();
-/// transform_file(&mut input, &mut output, None).unwrap();
+/// transform_file(&mut input, &mut output, &Options::default()).unwrap();
 /// ```
 pub fn transform_file(
     input: &mut dyn Read,
     output: &mut dyn Write,
-    excludes: Option<Excludes>,
-) -> io::Result<()> {
-    let mut dot_cmds_replaced = 0u32;
-    let mut dot_cmds_removed = 0u32;
-    let mut original_counts = ControlCount::new("To ASCII".to_string());
-    let mut dot_cmds_counts = ControlCount::new("Dot-cmds".to_string());
-    let mut re_align_counts = ControlCount::new("Re-align".to_string());
-    let mut specials_counts = ControlCount::new("Specials".to_string());
-    let mut overline_counts = ControlCount::new("Overline".to_string());
-    let mut wrappers_counts = ControlCount::new("Wrappers".to_string());
-    let mut controls_counts = ControlCount::new("Controls".to_string());
+    options: &Options,
+) -> io::Result<ConversionStats> {
+    transform_parts(input, &mut SingleSink::new(output), Split::None, options)
+}
 
+/// Transforms the input in the same way as `transform_file()`, but splits
+/// the output into parts at each page break or top-level heading (as set by
+/// `split` in the `Options`), writing each part as a complete document (e.g.
+/// with its own HTML preamble) to the destination given for it by the
+/// `OutputSink`
+///
+/// A page break or heading that would start a part before any text has been
+/// written to the current part does not start another part.
+///
+/// Returns the statistics on the whole conversion (see `ConversionStats`)
+/// on success or a `std::io::Error` type on failure
+///
+/// # Arguments
+///
+/// * `input` - Source of bytes that implements `Read` trait
+/// * `sink` - Destinations for the parts of the output
+/// * `options` - Settings for filter exclusions and output rendering
+///
+/// # Examples
+/// ```
+/// let mut sink = MemorySink::new();
+/// let options = Options { split: Split::Page, ..Default::default() };
+/// transform_split(&mut "one\n.pa\ntwo\n".as_bytes(), &mut sink, &options).unwrap();
+/// assert_eq!(sink.parts.len(), 2);
+/// ```
+pub fn transform_split(
+    input: &mut dyn Read,
+    sink: &mut dyn OutputSink,
+    options: &Options,
+) -> io::Result<ConversionStats> {
+    transform_parts(input, sink, options.split, options)
+}
+
+/// Holds the line being gathered from the events of the parser until it is
+/// rendered, which may span the start of a new part of a split output
+#[derive(Default)]
+struct LineEvents {
+    heading: Option<ws_heading::Heading>,
+    style: Style,
+    spans: Vec<Span>,
+    text: String,
+}
+
+/// Transforms the input as for `transform_split()`, splitting the output at
+/// the given points
+///
+/// # Arguments
+///
+/// * `input` - Source of bytes that implements `Read` trait
+/// * `sink` - Destinations for the parts of the output
+/// * `split` - Points at which to split the output
+/// * `options` - Settings for filter exclusions and output rendering
+///
+fn transform_parts(
+    input: &mut dyn Read,
+    sink: &mut dyn OutputSink,
+    split: Split,
+    options: &Options,
+) -> io::Result<ConversionStats> {
+    let format = options.render.format;
+    for &c in options
+        .preserve_controls
+        .iter()
+        .filter(|&&c| !format.writes_control(c))
+    {
+        log::warn!(
+            code = "control-not-written";
+            "Preserved control character {} cannot be written in {:?} output: shown escaped",
+            ws_control::get_escaped(c).unwrap_or_default(),
+            format
+        );
+    }
     let reader = BufReader::new(input);
-    let mut writer = BufWriter::new(output);
-    let excludes = excludes.unwrap_or_default();
-    let mut wrappers = ws_wrappers::Wrappers::new();
-
-    for line in reader.lines() {
-        let mut line = line?;
-        original_counts.scan(&line);
-
-        if !excludes.contains(Excludes::DOT_CMDS) {
-            if let Some(replacement) = ws_dot_cmd::process(&line) {
-                match &replacement[..] {
-                    "" => {
-                        dot_cmds_removed += 1;
-                        continue; // Remove line from output
-                    }
-                    _ => {
-                        dot_cmds_replaced += 1;
-                        line = replacement;
+    let mut renderer = render::new_renderer(&options.render);
+    let mut parser = Parser::new(ws_lines::lines(reader), options, renderer.wants_spans());
+    if options.max_output_line.is_some() && parser.emits_spans() {
+        log::warn!(
+            code = "output-line-not-checked";
+            "Output line length is only checked in text and print output, not {:?}",
+            format
+        );
+    }
+    let mut line = LineEvents::default();
+
+    for part_no in 1.. {
+        let output = sink.part(part_no)?;
+        let mut transliterator;
+        let output: &mut dyn Write =
+            if options.charset != Charset::Unicode && options.render.format.is_text() {
+                transliterator = Transliterator::new(output, options.charset);
+                &mut transliterator
+            } else {
+                output
+            };
+        let mut writer = BufWriter::new(output);
+        renderer.begin(&mut writer)?;
+        let more = render_part(
+            &mut parser,
+            renderer.as_mut(),
+            &mut writer,
+            &mut line,
+            split,
+        )?;
+        let start = Instant::now();
+        renderer.end(&mut writer)?;
+        writer.flush()?;
+        parser.record_render(start);
+        if !more {
+            break;
+        }
+        renderer = render::new_renderer(&options.render);
+    }
+
+    if !options.quiet {
+        parser.report();
+    }
+    let stats = parser.stats();
+    if options.mode == Mode::Strict && stats.warnings > 0 {
+        return Err(exit_status::files_failed(format!(
+            "{} deviation(s) from the WordStar format in strict mode",
+            stats.warnings
+        )));
+    }
+    Ok(stats)
+}
+
+/// Dispatches the events from the parser to the renderer until the end of
+/// the input or the start of a new part of the output (as given by `split`)
+///
+/// Returns `true` if a new part is to be started, `false` at the end of the
+/// input or a `std::io::Error` type on failure
+///
+/// # Arguments
+///
+/// * `parser` - Source of events
+/// * `renderer` - Renderer for the current part
+/// * `writer` - Destination for the current part
+/// * `line` - Line being gathered from the events
+/// * `split` - Points at which to split the output
+///
+fn render_part<I>(
+    parser: &mut Parser<I>,
+    renderer: &mut dyn Renderer,
+    writer: &mut dyn Write,
+    line: &mut LineEvents,
+    split: Split,
+) -> io::Result<bool>
+where
+    I: Iterator<Item = io::Result<String>>,
+{
+    let mut has_text = false;
+    while let Some(event) = parser.next() {
+        match event? {
+            Event::DotCommand { cmd, text, line_no } => {
+                if renderer.dot_command(writer, &cmd, &text, line_no)? {
+                    parser.claim_line(); // Leave dot command to renderer
+                }
+            }
+            Event::PageBreak(_) if split == Split::Page => {
+                if has_text {
+                    return Ok(true);
+                }
+            }
+            Event::PageBreak(line_no) => renderer.page_break(writer, line_no)?,
+            Event::Comment { text, line_no } => renderer.comment(writer, &text, line_no)?,
+            Event::Start(Tag::Line(_)) => {
+                line.heading = None;
+                line.spans.clear();
+                line.text.clear();
+            }
+            Event::Start(Tag::Heading(detected)) => {
+                let starts_part = split == Split::H1 && detected.level == 1 && has_text;
+                line.heading = Some(detected);
+                if starts_part {
+                    return Ok(true);
+                }
+            }
+            Event::Start(Tag::Style(flag)) => line.style.insert(flag),
+            Event::End(Tag::Style(flag)) => line.style.remove(flag),
+            Event::Text(run) => {
+                line.text.push_str(&run);
+                line.spans.push(Span {
+                    style: line.style,
+                    text: run,
+                });
+            }
+            Event::End(Tag::Heading(_)) => {}
+            Event::End(Tag::Line(line_no)) => {
+                let start = Instant::now();
+                match &line.heading {
+                    Some(heading) => renderer.heading(writer, &line.text, heading, line_no)?,
+                    None if parser.emits_spans() => {
+                        renderer.styled_line(writer, &line.spans, line_no)?
                     }
+                    None => renderer.line(writer, &line.text, line_no)?,
                 }
+                has_text |= !line.text.trim().is_empty();
+                parser.record_render(start);
             }
-            dot_cmds_counts.scan(&line);
         }
+    }
+    Ok(false)
+}
 
-        if !excludes.contains(Excludes::RE_ALIGN) {
-            line = ws_align::process(&line).unwrap_or(line);
-            re_align_counts.scan(&line);
-        }
+// Unit tests
 
-        if !excludes.contains(Excludes::SPECIALS) {
-            line = ws_special::process(&line).unwrap_or(line);
-            specials_counts.scan(&line);
-        }
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::render::{PageBreakStyle, RenderOptions};
 
-        if !excludes.contains(Excludes::OVERLINE) {
-            line = ws_overline::process(&line).unwrap_or(line);
-            overline_counts.scan(&line);
-        }
+    fn transform(input: &str, options: &Options) -> String {
+        let mut output = Vec::new();
+        transform_file(&mut input.as_bytes(), &mut output, options).unwrap();
+        String::from_utf8(output).unwrap()
+    }
 
-        if !excludes.contains(Excludes::WRAPPERS) {
-            line = wrappers.process(&line).unwrap_or(line);
-            wrappers_counts.scan(&line);
-        }
+    #[test]
+    fn test_excludes() {
+        assert_eq!("specials".parse(), Ok(Excludes::SPECIALS));
+        assert_eq!(
+            "OverLINE, wrappers".parse(),
+            Ok(Excludes::OVERLINE | Excludes::WRAPPERS)
+        );
+        assert_eq!("".parse(), Ok(Excludes::none()));
+        assert_eq!("all".parse(), Ok(Excludes::all()));
+        assert!("bogus".parse::<Excludes>().is_err());
+        assert_eq!(Excludes::none().to_string(), "none");
+        assert_eq!(
+            (Excludes::CONTROLS | Excludes::DOT_CMDS).to_string(),
+            "dot-cmds,controls"
+        );
+        assert_eq!(Excludes::all().to_string().parse(), Ok(Excludes::all()));
+        assert_eq!(Excludes::NAMES[1], "escapes");
+    }
 
-        if !excludes.contains(Excludes::CONTROLS) {
-            line = ws_control::process(&line, true).unwrap_or(line);
-            controls_counts.scan(&line);
-        }
+    #[test]
+    fn test_convert_line() {
+        let options = Options::default();
+        let mut state = ConvertState::new(&options);
+        assert!(matches!(
+            convert_line("plain", &mut state, &options),
+            Cow::Borrowed("plain")
+        ));
+        assert_eq!(convert_line(".mt 3", &mut state, &options), "");
+        assert_eq!(convert_line("a\x13b", &mut state, &options), "ab\u{332}");
+        assert_eq!(convert_line("c\x13d", &mut state, &options), "c\u{332}d");
+        assert_eq!(convert_line("\x1BEe\x1BF", &mut state, &options), "e");
+        let options = Options {
+            render: RenderOptions {
+                page_break: PageBreakStyle::Html,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        assert_eq!(convert_line(".pa", &mut state, &options), "<hr />");
+        assert_eq!(
+            convert_line(".pa Part Two", &mut state, &options),
+            "<hr />\nPart Two"
+        );
+        assert_eq!(
+            convert_line(".pa \x02Part\x02 Two", &mut state, &options),
+            "<hr />\n\u{1D40F}\u{1D41A}\u{1D42B}\u{1D42D} Two"
+        );
+        assert_eq!(
+            convert_line(".lm 5 Dear Sir", &mut state, &options),
+            "Dear Sir"
+        );
+        let options = Options {
+            charset: Charset::Ascii,
+            ..Default::default()
+        };
+        assert_eq!(
+            convert_line("\x02Hi\x02 1/2", &mut state, &options),
+            "*Hi* 1/2"
+        );
+        assert_eq!(convert_line(".pa", &mut state, &options), "-".repeat(39));
+    }
 
-        writeln!(writer, "{}", line)?;
+    #[test]
+    fn test_restore_wrappers() {
+        let options = Options::default();
+        let mut state = ConvertState::new(&options);
+        assert_eq!(convert_line("a\x13b", &mut state, &options), "ab\u{332}");
+        let mut other = ConvertState::new(&options);
+        other.restore_wrappers(&state.wrappers_state());
+        assert_eq!(convert_line("c\x13d", &mut other, &options), "c\u{332}d");
     }
-    writer.flush()?;
 
-    eprintln!("Dot commands after processing:");
-    eprintln!("Replaced: {}", dot_cmds_replaced);
-    eprintln!("Removed:  {}", dot_cmds_removed);
+    #[test]
+    fn test_unknown_handler() {
+        let options = Options {
+            unknown_handler: Some(|c, line_no| {
+                (c == '\x05').then(|| format!("[note {}]", line_no))
+            }),
+            ..Default::default()
+        };
+        let mut state = ConvertState::new(&options);
+        assert_eq!(convert_line("a\x05", &mut state, &options), "a[note 1]");
+        assert_eq!(
+            convert_line("b\x05\x03", &mut state, &options),
+            "b[note 2]^C"
+        );
+        assert_eq!(transform("c\x05\n", &options), "c[note 1]\n");
+    }
+
+    #[test]
+    fn test_preserve_controls() {
+        let input = "a\tb\x0Cc\x03\n";
+        assert_eq!(transform(input, &Options::default()), "a^Ib^Lc^C\n");
+        let options = Options {
+            preserve_controls: vec!['\t', '\x0C'],
+            ..Default::default()
+        };
+        assert_eq!(transform(input, &options), "a\tb\x0Cc^C\n");
+    }
 
-    eprintln!("Control characters after processing:");
-    eprintln!("{}", original_counts);
-    eprintln!("{}", dot_cmds_counts);
-    eprintln!("{}", re_align_counts);
-    eprintln!("{}", specials_counts);
-    eprintln!("{}", overline_counts);
-    eprintln!("{}", wrappers_counts);
-    eprintln!("{}", controls_counts);
-    Ok(())
+    #[test]
+    fn test_blank_dot_cmds() {
+        let input = "one\n.mt 3\ntwo\n.op\n";
+        assert_eq!(transform(input, &Options::default()), "one\ntwo\n");
+        let options = Options {
+            blank_dot_cmds: true,
+            ..Default::default()
+        };
+        assert_eq!(transform(input, &options), "one\n\ntwo\n\n");
+    }
+
+    #[test]
+    fn test_strict_mode() {
+        let options = Options {
+            lenient_dot_cmds: true,
+            quiet: true,
+            ..Default::default()
+        };
+        assert_eq!(transform("\x00.\x1Fmt 3\ntext\n", &options), "text\n");
+        let options = Options {
+            mode: Mode::Strict,
+            ..options
+        };
+        for input in ["a\x02b\n", "\x00.\x1Fmt 3\ntext\n"] {
+            let mut output = Vec::new();
+            let err = transform_file(&mut input.as_bytes(), &mut output, &options).unwrap_err();
+            assert!(exit_status::is_files_failed(&err));
+        }
+        assert_eq!(transform("\x02a\x02\n", &options), "𝐚\n");
+    }
 }
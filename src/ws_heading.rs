@@ -0,0 +1,524 @@
+//! Module to detect WordStar lines that act as headings within a document
+
+use crate::ws_chars;
+use crate::ws_string;
+use serde::Serialize;
+use std::collections::{HashMap, HashSet};
+
+const DEFAULT_SLUG: &str = "section"; // For titles without letters or digits
+const MAX_OUTLINE_DEPTH: usize = 6; // Deepest level of outline numbering
+const MAX_OUTLINE_TITLE: usize = 60; // Longer numbered lines start paragraphs
+const MIN_RULING: usize = 3; // Shortest line of characters taken as a ruling
+const RULING_CHARS: [(char, u8); 3] = [('=', 1), ('-', 2), ('_', 2)]; // With heading levels
+
+/// Ways of converting a line followed by a ruling (a line of '=', '-' or '_'
+/// characters of similar length) into a heading
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub enum RuledHeadings {
+    #[default]
+    None, // Left unchanged
+    Setext,    // Heading with a Markdown setext ruling ("===" or "---") in text output
+    Underline, // Heading with its title underlined in place of the ruling
+}
+
+/// Holds the level (1 = most significant) and plain text title of a heading
+#[derive(Debug, PartialEq, Clone, Serialize)]
+pub struct Heading {
+    pub level: u8,
+    pub title: String,
+}
+
+// PRIVATE HELPER FUNCTIONS
+
+/// Returns tuple that splits off control characters (if any) at each end of a
+/// text slice from the text contained within
+///
+/// # Arguments
+///
+/// * `s` - Slice of text to be processed
+///
+/// # Examples
+/// ```
+/// assert_eq!(split_controls_at_ends("\x13ab\x02"), ("\x13", "ab", "\x02"));
+/// ```
+fn split_controls_at_ends(s: &str) -> (&str, &str, &str) {
+    let left = s.len() - s.trim_start_matches(|c: char| c.is_ascii_control()).len();
+    let right = s.trim_end_matches(|c: char| c.is_ascii_control()).len();
+    if left >= right {
+        (&s[..left], "", &s[left..])
+    } else {
+        (&s[..left], &s[left..right], &s[right..])
+    }
+}
+
+/// Returns `true` if the given wrapper character is present in both of the
+/// text slices, otherwise `false`
+///
+/// # Arguments
+///
+/// * `start` - Slice of control characters at the start of the line
+/// * `end` - Slice of control characters at the end of the line
+/// * `wrapper` - Wrapper character to be matched
+///
+fn wraps(start: &str, end: &str, wrapper: char) -> bool {
+    start.contains(wrapper) && end.contains(wrapper)
+}
+
+/// Returns the numbers of the outline number (e.g. "1.2.3" or "1.") at the
+/// start of the given text slice if it is followed by white space and then
+/// a title with letters or digits, otherwise `None`
+///
+/// A single number must end with '.' so that a line starting with a plain
+/// number (e.g. a year) is not taken to be numbered.
+///
+/// # Arguments
+///
+/// * `s` - Slice of text to be examined
+///
+/// # Examples
+/// ```
+/// assert_eq!(split_outline_number("1.2.3 Scope"), Some(vec![1, 2, 3]));
+/// ```
+fn split_outline_number(s: &str) -> Option<Vec<u32>> {
+    let (number, title) = s.split_once(char::is_whitespace)?;
+    if !title.chars().any(char::is_alphanumeric) {
+        return None;
+    }
+    let stripped = number.strip_suffix('.');
+    let numbers = stripped
+        .unwrap_or(number)
+        .split('.')
+        .map(|part| {
+            let digits = !part.is_empty() && part.bytes().all(|b| b.is_ascii_digit());
+            digits.then(|| part.parse().ok()).flatten()
+        })
+        .collect::<Option<Vec<u32>>>()?;
+    if numbers.len() == 1 && stripped.is_none() || numbers.len() > MAX_OUTLINE_DEPTH {
+        return None;
+    }
+    Some(numbers)
+}
+
+/// Returns `true` if the given outline number can follow the previous one,
+/// i.e. it is the next number at the same or a higher level, or the first
+/// number one level deeper, otherwise `false`
+///
+/// # Arguments
+///
+/// * `previous` - Slice of numbers of the previous outline number
+/// * `numbers` - Slice of numbers of the outline number (at least one)
+///
+/// # Examples
+/// ```
+/// assert!(follows(&[1, 2], &[2]));
+/// assert!(!follows(&[1, 2], &[1, 4]));
+/// ```
+fn follows(previous: &[u32], numbers: &[u32]) -> bool {
+    let (last, parents) = match numbers.split_last() {
+        Some(split) => split,
+        None => return false,
+    };
+    if previous.len() < parents.len() || previous[..parents.len()] != *parents {
+        return false;
+    }
+    match previous.get(parents.len()) {
+        Some(&number) => *last == number + 1,
+        None => *last == 1,
+    }
+}
+
+// EXTERNAL PUBLIC FUNCTIONS
+
+/// Returns new String formed from the given title as a "slug" for use as an
+/// identifier or anchor, i.e. in lower case with each run of characters
+/// other than letters and digits replaced by a single '-'
+///
+/// # Arguments
+///
+/// * `title` - Slice of text holding the title
+///
+/// # Examples
+/// ```
+/// assert_eq!(slugify("I. INTRODUCTION"), "i-introduction");
+/// ```
+pub fn slugify(title: &str) -> String {
+    let mut slug = String::with_capacity(title.len());
+    for c in title.chars() {
+        if c.is_alphanumeric() {
+            slug.extend(c.to_lowercase());
+        } else if !slug.is_empty() && !slug.ends_with('-') {
+            slug.push('-');
+        }
+    }
+    if slug.ends_with('-') {
+        slug.pop();
+    }
+    if slug.is_empty() {
+        slug.push_str(DEFAULT_SLUG);
+    }
+    slug
+}
+
+/// Returns `Some(heading)` if the given text slice appears to be a heading,
+/// otherwise `None`
+///
+/// A heading is a line in which all of the (non-whitespace) text is held
+/// within a single pair of `ws_chars::BOLD` (or `ws_chars::DOUBLE`) and/or
+/// `ws_chars::UNDERLINE` wrapper characters, with no further control
+/// characters inside.  A heading with both emphasis and underlining is
+/// treated as level 1, and one with only one of these as level 2.
+///
+/// Note: Detection works best after the `ws_align` module has moved any
+/// whitespace outside the wrapper characters.
+///
+/// # Arguments
+///
+/// * `s` - Slice of text to be examined
+///
+/// # Examples
+/// ```
+/// let heading = detect("  \x13\x02INTRODUCTION\x13\x02").unwrap();
+/// assert_eq!(heading.level, 1);
+/// ```
+pub fn detect(s: &str) -> Option<Heading> {
+    let (start, text, end) = split_controls_at_ends(s.trim());
+    let text = text.trim();
+    if !ws_string::contains_only_print(text) || !text.chars().any(char::is_alphanumeric) {
+        return None;
+    }
+    let bold = wraps(start, end, ws_chars::BOLD) || wraps(start, end, ws_chars::DOUBLE);
+    let underline = wraps(start, end, ws_chars::UNDERLINE);
+    let level = match (bold, underline) {
+        (true, true) => 1,
+        (true, false) | (false, true) => 2,
+        (false, false) => return None,
+    };
+    Some(Heading {
+        level,
+        title: text.to_string(),
+    })
+}
+
+/// Returns `Some(heading)` if the given line appears to be the title of a
+/// heading that is ruled off by the given next line, otherwise `None`
+///
+/// A ruling is a line of at least `MIN_RULING` characters, all of which are
+/// the same one of '=', '-' or '_' (with spaces allowed at either end), that
+/// is about as long as the title.  A ruling of '=' characters makes a level
+/// 1 heading and any other a level 2 heading.  Control characters are
+/// ignored and left out of the title.
+///
+/// # Arguments
+///
+/// * `s` - Slice of text to be examined (the title)
+/// * `next` - Slice of text of the next line (the ruling)
+///
+/// # Examples
+/// ```
+/// let heading = detect_ruled("\x02Summary\x02", "=======").unwrap();
+/// assert_eq!(heading.level, 1);
+/// assert_eq!(heading.title, "Summary");
+/// ```
+pub fn detect_ruled(s: &str, next: &str) -> Option<Heading> {
+    let ruling = next.trim();
+    let len = ws_string::len_in_chars(ruling);
+    let first = ruling.chars().next()?;
+    let &(_, level) = RULING_CHARS.iter().find(|(c, _)| *c == first)?;
+    if len < MIN_RULING || !ws_string::contains_only_char(ruling, first) {
+        return None;
+    }
+    let plain: String = s.chars().filter(|c| !c.is_ascii_control()).collect();
+    let title = plain.trim();
+    let title_len = ws_string::len_in_chars(title);
+    if !title.chars().any(char::is_alphanumeric)
+        || title_len > MAX_OUTLINE_TITLE
+        || len.abs_diff(title_len) > title_len / 4 + 2
+    {
+        return None;
+    }
+    Some(Heading {
+        level,
+        title: title.to_string(),
+    })
+}
+
+/// Returns the Markdown setext ruling for the given heading, i.e. a line of
+/// '=' (for level 1) or '-' characters as long as its title
+///
+/// # Arguments
+///
+/// * `heading` - Heading to be ruled off
+///
+/// # Examples
+/// ```
+/// let heading = Heading { level: 2, title: "Scope".to_string() };
+/// assert_eq!(setext_ruling(&heading), "-----");
+/// ```
+pub fn setext_ruling(heading: &Heading) -> String {
+    let c = if heading.level == 1 { '=' } else { '-' };
+    c.to_string()
+        .repeat(ws_string::len_in_chars(&heading.title).max(MIN_RULING))
+}
+
+// "HEADING IDS" OBJECT
+
+/// Holds the number of times that each slug has been used for a heading and
+/// the identifiers already issued, so that each heading in a document can be
+/// given a unique identifier
+#[derive(Debug, Default)]
+pub struct HeadingIds {
+    used: HashMap<String, usize>,
+    issued: HashSet<String>,
+}
+
+impl HeadingIds {
+    /// Returns a new `HeadingIds` object with no identifiers used
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Returns a unique identifier for the given heading, formed from the
+    /// slug of its title with "-2", "-3" etc. added for repeated titles (or
+    /// for a title whose slug has already been issued with such a suffix)
+    ///
+    /// # Arguments
+    ///
+    /// * `heading` - Heading to be identified
+    ///
+    /// # Examples
+    /// ```
+    /// let mut ids = HeadingIds::new();
+    /// let heading = Heading { level: 1, title: "Results".to_string() };
+    /// assert_eq!(ids.id(&heading), "results");
+    /// assert_eq!(ids.id(&heading), "results-2");
+    /// ```
+    pub fn id(&mut self, heading: &Heading) -> String {
+        let slug = slugify(&heading.title);
+        let count = self.used.entry(slug.clone()).or_insert(0);
+        loop {
+            *count += 1;
+            let id = match *count {
+                1 => slug.clone(),
+                n => format!("{}-{}", slug, n),
+            };
+            if self.issued.insert(id.clone()) {
+                return id;
+            }
+        }
+    }
+}
+
+// "OUTLINE" OBJECT
+
+/// Holds the outline number of the last numbered heading in a document and
+/// the indent used for each level of numbering, together with whether the
+/// previous line was part of a paragraph
+#[derive(Debug, Default)]
+pub struct Outline {
+    numbers: Vec<u32>,
+    indents: Vec<usize>,
+    in_paragraph: bool,
+}
+
+impl Outline {
+    /// Returns a new `Outline` object for the start of a document
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Returns `Some(heading)` if the given line (the next in the document)
+    /// starts with an outline number (e.g. "1.", "1.1" or "1.1.1") that
+    /// follows on from the last one, otherwise `None`
+    ///
+    /// The level of the heading is the number of parts of its outline
+    /// number.  A numbered line is only taken to be a heading if it starts a
+    /// paragraph, is short enough to be a title and has the same indent as
+    /// previous headings at its level.  Control characters are ignored and
+    /// left out of the title, which includes the outline number.
+    ///
+    /// # Arguments
+    ///
+    /// * `s` - Slice of text to be examined
+    ///
+    /// # Examples
+    /// ```
+    /// let mut outline = Outline::new();
+    /// assert_eq!(outline.detect("1. \x02Introduction\x02").unwrap().level, 1);
+    /// assert_eq!(outline.detect("1.1 Scope").unwrap().level, 2);
+    /// ```
+    pub fn detect(&mut self, s: &str) -> Option<Heading> {
+        let plain: String = s
+            .chars()
+            .filter(|&c| !c.is_ascii_control() || c == '\t')
+            .collect();
+        let text = plain.trim();
+        if text.is_empty() {
+            self.in_paragraph = false;
+            return None;
+        }
+        if std::mem::replace(&mut self.in_paragraph, true)
+            || ws_string::len_in_chars(text) > MAX_OUTLINE_TITLE
+        {
+            return None;
+        }
+        let numbers = split_outline_number(text).filter(|n| follows(&self.numbers, n))?;
+        let indent = plain.len() - plain.trim_start().len();
+        match self.indents.get(numbers.len() - 1) {
+            Some(&expected) if expected != indent => return None,
+            Some(_) => {}
+            None => self.indents.push(indent),
+        }
+        let level = numbers.len() as u8;
+        self.numbers = numbers;
+        self.in_paragraph = false; // Heading is a block of its own
+        Some(Heading {
+            level,
+            title: text.to_string(),
+        })
+    }
+}
+
+// Unit tests
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_split_controls_at_ends() {
+        assert_eq!(split_controls_at_ends("\x13ab\x02"), ("\x13", "ab", "\x02"));
+        assert_eq!(split_controls_at_ends("a\x13b"), ("", "a\x13b", ""));
+        assert_eq!(split_controls_at_ends("\x13\x02"), ("\x13\x02", "", ""));
+        assert_eq!(split_controls_at_ends(""), ("", "", ""));
+    }
+
+    #[test]
+    fn test_slugify() {
+        assert_eq!(slugify("I. INTRODUCTION"), "i-introduction");
+        assert_eq!(slugify("(1) Logic  Analyser!"), "1-logic-analyser");
+        assert_eq!(slugify("Über Maß"), "über-maß");
+        assert_eq!(slugify("..."), "section");
+    }
+
+    #[test]
+    fn test_heading_ids() {
+        let heading = |title: &str| Heading {
+            level: 2,
+            title: title.to_string(),
+        };
+        let mut ids = HeadingIds::new();
+        assert_eq!(ids.id(&heading("Results")), "results");
+        assert_eq!(ids.id(&heading("RESULTS")), "results-2");
+        assert_eq!(ids.id(&heading("Summary")), "summary");
+        assert_eq!(ids.id(&heading("results")), "results-3");
+        let mut ids = HeadingIds::new();
+        assert_eq!(ids.id(&heading("Results")), "results");
+        assert_eq!(ids.id(&heading("Results")), "results-2");
+        assert_eq!(ids.id(&heading("Results 2")), "results-2-2");
+        assert_eq!(ids.id(&heading("Results")), "results-3");
+    }
+
+    #[test]
+    fn test_split_outline_number() {
+        assert_eq!(split_outline_number("1.2.3 Scope"), Some(vec![1, 2, 3]));
+        assert_eq!(split_outline_number("2. Method"), Some(vec![2]));
+        assert_eq!(split_outline_number("1.1.\tScope"), Some(vec![1, 1]));
+        assert_eq!(split_outline_number("1990 was a year"), None);
+        assert_eq!(split_outline_number("1..2 Scope"), None);
+        assert_eq!(split_outline_number("+1. Scope"), None);
+        assert_eq!(split_outline_number("1.1 ..."), None);
+        assert_eq!(split_outline_number("1.1.1.1.1.1.1 Deep"), None);
+        assert_eq!(split_outline_number("Scope"), None);
+    }
+
+    #[test]
+    fn test_follows() {
+        assert!(follows(&[], &[1]));
+        assert!(!follows(&[], &[2]));
+        assert!(!follows(&[], &[1, 1]));
+        assert!(follows(&[1], &[1, 1]));
+        assert!(follows(&[1, 2, 3], &[1, 2, 3, 1]));
+        assert!(follows(&[1, 2, 3], &[1, 3]));
+        assert!(follows(&[1, 2, 3], &[2]));
+        assert!(!follows(&[1, 2, 3], &[1, 2, 3, 1, 1]));
+        assert!(!follows(&[1, 2], &[2, 3]));
+        assert!(!follows(&[1, 2], &[]));
+    }
+
+    #[test]
+    fn test_outline() {
+        let mut outline = Outline::new();
+        let level = |outline: &mut Outline, s: &str| outline.detect(s).map(|h| h.level);
+        assert_eq!(
+            outline.detect("1. \x02Introduction\x02"),
+            Some(Heading {
+                level: 1,
+                title: "1. Introduction".to_string()
+            })
+        );
+        assert_eq!(level(&mut outline, "  1.1 Scope"), Some(2));
+        assert_eq!(level(&mut outline, "3.5 kg of flour is needed"), None);
+        assert_eq!(level(&mut outline, ""), None);
+        assert_eq!(level(&mut outline, "  1.2 Terms"), Some(2));
+        assert_eq!(level(&mut outline, "1.3 Wrong indent"), None);
+        assert_eq!(level(&mut outline, ""), None);
+        assert_eq!(level(&mut outline, "Some text in a paragraph"), None);
+        assert_eq!(level(&mut outline, "2. Not at start of paragraph"), None);
+        assert_eq!(level(&mut outline, ""), None);
+        assert_eq!(level(&mut outline, "2. Method"), Some(1));
+        assert_eq!(level(&mut outline, "  2.1 Design"), Some(2));
+        assert_eq!(level(&mut outline, "    2.1.1 Detail"), Some(3));
+    }
+
+    #[test]
+    fn test_detect_ruled() {
+        let heading = detect_ruled("  Introduction", "------------").unwrap();
+        assert_eq!(heading.level, 2);
+        assert_eq!(heading.title, "Introduction");
+        assert_eq!(detect_ruled("\x13Methods\x13 ", "=====").unwrap().level, 1);
+        assert_eq!(detect_ruled("Results", "_______ ").unwrap().level, 2);
+        assert_eq!(detect_ruled("A much longer line of text", "---"), None);
+        assert_eq!(detect_ruled("Title", "--=--"), None);
+        assert_eq!(detect_ruled("ab", "--"), None);
+        assert_eq!(detect_ruled("----", "----"), None);
+        assert_eq!(detect_ruled("Title", ""), None);
+    }
+
+    #[test]
+    fn test_setext_ruling() {
+        let heading = Heading {
+            level: 1,
+            title: "Caf\u{E9}".to_string(),
+        };
+        assert_eq!(setext_ruling(&heading), "====");
+        let heading = Heading {
+            level: 2,
+            title: "A".to_string(),
+        };
+        assert_eq!(setext_ruling(&heading), "---");
+    }
+
+    #[test]
+    fn test_detect() {
+        assert_eq!(
+            detect("   \x13\x02I. INTRODUCTION\x13\x02"),
+            Some(Heading {
+                level: 1,
+                title: "I. INTRODUCTION".to_string()
+            })
+        );
+        assert_eq!(
+            detect("\x13(1) LOGIC ANALYSER\x13 "),
+            Some(Heading {
+                level: 2,
+                title: "(1) LOGIC ANALYSER".to_string()
+            })
+        );
+        assert_eq!(detect("\x13AB\x13CD\x13EF\x13"), None);
+        assert_eq!(detect("\x13AB\x02"), None);
+        assert_eq!(detect("\x14AB\x14"), None);
+        assert_eq!(detect("\x13...\x13"), None);
+        assert_eq!(detect("plain text"), None);
+        assert_eq!(detect(""), None);
+    }
+}
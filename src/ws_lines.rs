@@ -0,0 +1,413 @@
+//! Module to read lines of WordStar text with any kind of line ending,
+//! merging overprinted lines
+
+// WordStar ends a line with a bare carriage return (CR without a line feed)
+// when the next line is to be printed on top of it, e.g. to underline a
+// whole line or to add accents.  Such lines are merged here column by
+// column, with each overprinted character placed after the character it
+// strikes and a `ws_chars::OVERPRINT` character, so that the result can be
+// resolved by the overprint filter in the same way as a ^PH sequence.
+// Files recovered from CP/M disks often have inconsistent line endings, so
+// the lines are split here rather than with `BufRead::lines()`.
+
+use crate::ws_chars;
+use std::collections::VecDeque;
+use std::io::{self, BufRead, Read};
+
+const CR: char = '\r'; // Carriage return without line feed
+const MAX_LINE_LEN: usize = 64 * 1024; // Longer lines are split
+
+// PRIVATE HELPER FUNCTIONS
+
+/// Splits the given text slice into printed columns, each holding a
+/// printable character followed by any control characters after it, and
+/// returns them with the control characters (if any) before the first column
+///
+/// # Arguments
+///
+/// * `s` - Slice of text to be split
+///
+/// # Examples
+/// ```
+/// assert_eq!(columns("\x13ab"), ("\x13".to_string(), vec!["a".to_string(), "b".to_string()]));
+/// ```
+fn columns(s: &str) -> (String, Vec<String>) {
+    let mut prefix = String::new();
+    let mut cols: Vec<String> = Vec::with_capacity(s.len());
+    for c in s.chars() {
+        if c.is_ascii_control() {
+            cols.last_mut().unwrap_or(&mut prefix).push(c);
+        } else {
+            cols.push(c.to_string());
+        }
+    }
+    (prefix, cols)
+}
+
+/// Overprints the given text slice onto the given columns of text, column
+/// by column
+///
+/// A space leaves a column unchanged, a character struck on a space or on
+/// itself replaces it, and any other character is added after the column
+/// with a `ws_chars::OVERPRINT` character.  Control characters are kept in
+/// the position in which they occur.
+///
+/// # Arguments
+///
+/// * `prefix` - Control characters before the first column
+/// * `cols` - Columns of text onto which the text is overprinted
+/// * `s` - Slice of text to be overprinted
+///
+fn overprint(prefix: &mut String, cols: &mut Vec<String>, s: &str) {
+    let mut i = 0;
+    for c in s.chars() {
+        if c.is_ascii_control() {
+            match i {
+                0 => prefix.push(c),
+                _ => cols[i - 1].push(c),
+            }
+            continue;
+        }
+        if i == cols.len() {
+            cols.push(' '.to_string());
+        }
+        let col = &mut cols[i];
+        let base = col.chars().next().unwrap_or(' ');
+        if c == ' ' || c == base {
+            // Nothing to add
+        } else if base == ' ' && col.len() == 1 {
+            *col = c.to_string();
+        } else {
+            col.push(ws_chars::OVERPRINT);
+            col.push(c);
+        }
+        i += 1;
+    }
+}
+
+/// Returns the position at which to split the given over-long line of text
+/// so that the first part is no more than `max_len` bytes, i.e. after the
+/// last space if there is one, otherwise at the start of a character
+///
+/// # Arguments
+///
+/// * `buf` - Bytes of the line (more than `max_len` of them)
+/// * `max_len` - Maximum length of a line in bytes
+///
+/// # Examples
+/// ```
+/// assert_eq!(split_point(b"ab cd", 4), 3);
+/// ```
+fn split_point(buf: &[u8], max_len: usize) -> usize {
+    if let Some(space) = buf[..max_len].iter().rposition(|&b| b == b' ') {
+        return space + 1;
+    }
+    let mut at = max_len;
+    while at > 1 && (buf[at] & 0xC0) == 0x80 {
+        at -= 1; // Back to start of UTF-8 character
+    }
+    at
+}
+
+// EXTERNAL PUBLIC FUNCTIONS
+
+/// Returns `Some(replacement)` if the given line holds one or more bare
+/// carriage returns whose following text has been merged onto the text
+/// before them, otherwise `None`
+///
+/// # Arguments
+///
+/// * `line` - Slice of text for a line (without its line ending)
+///
+/// # Examples
+/// ```
+/// assert_eq!(merge_overprint("cafe\r   '"), Some("cafe\x08'".to_string()));
+/// ```
+pub fn merge_overprint(line: &str) -> Option<String> {
+    if !line.contains(CR) {
+        return None;
+    }
+    let mut parts = line.split(CR);
+    let (mut prefix, mut cols) = columns(parts.next().unwrap_or(""));
+    for part in parts {
+        overprint(&mut prefix, &mut cols, part);
+    }
+    let mut result = prefix;
+    for col in cols {
+        result.push_str(&col);
+    }
+    Some(result)
+}
+
+/// Returns a `Lines` iterator over the lines of the given reader
+///
+/// # Arguments
+///
+/// * `reader` - Source of text that implements `BufRead` trait
+///
+/// # Examples
+/// ```
+/// let parser = Parser::new(ws_lines::lines(io::stdin().lock()), &options, false);
+/// ```
+pub fn lines<B: BufRead>(reader: B) -> Lines<B> {
+    Lines {
+        reader,
+        max_len: MAX_LINE_LEN,
+        carry: Vec::new(),
+        pending: VecDeque::new(),
+        line_no: 0,
+        lf: 0,
+        crlf: 0,
+        cr: 0,
+        overprints: 0,
+        splits: 0,
+        done: false,
+    }
+}
+
+// "LINES" OBJECT
+
+/// Holds the state of an iterator over the lines of a reader, together with
+/// counts of each kind of line ending found
+///
+/// Lines may end with a line feed (LF), a carriage return and line feed
+/// (CRLF) or a mixture of the two.  A bare carriage return (CR) within a
+/// line marks an overprinted line, which is merged with the text after it,
+/// except that the lines of an input with no line feeds at all are taken to
+/// end with bare carriage returns.  The counts are logged at the end of the
+/// input.
+///
+/// No more than `MAX_LINE_LEN` bytes are read at a time, so that a corrupt
+/// input without line endings cannot take up unbounded memory.  Longer lines
+/// are split (after the last space if there is one) with a warning.
+pub struct Lines<B> {
+    reader: B,
+    max_len: usize,
+    carry: Vec<u8>,
+    pending: VecDeque<String>,
+    line_no: usize,
+    lf: usize,
+    crlf: usize,
+    cr: usize,
+    overprints: usize,
+    splits: usize,
+    done: bool,
+}
+
+impl<B: BufRead> Lines<B> {
+    /// Logs the counts of each kind of line ending, with a warning if more
+    /// than one kind of line ending was found or any lines were split
+    fn report(&self) {
+        log::info!(
+            "Line endings: {} LF, {} CRLF, {} CR, {} overprint CR",
+            self.lf,
+            self.crlf,
+            self.cr,
+            self.overprints
+        );
+        let kinds = [self.lf, self.crlf, self.cr];
+        if kinds.iter().filter(|&&count| count > 0).count() > 1 {
+            log::warn!(code = "mixed-line-endings"; "Input has mixed line endings");
+        }
+        if self.splits > 0 {
+            log::warn!(code = "lines-split"; "Total lines split: {}", self.splits);
+        }
+    }
+
+    /// Adds the given line of text to the pending lines after merging any
+    /// overprinted lines, or returns a `std::io::Error` type if it is not
+    /// valid UTF-8
+    ///
+    /// # Arguments
+    ///
+    /// * `buf` - Bytes of the line (without its line ending)
+    ///
+    fn push_line(&mut self, buf: Vec<u8>) -> io::Result<()> {
+        let line =
+            String::from_utf8(buf).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        self.line_no += 1;
+        if let Some(merged) = merge_overprint(&line) {
+            self.overprints += line.matches(CR).count();
+            self.pending.push_back(merged);
+        } else {
+            self.pending.push_back(line);
+        }
+        Ok(())
+    }
+
+    /// Reads up to `max_len` bytes (after any carried over from the previous
+    /// read) and adds the lines found to the pending lines, or returns a
+    /// `std::io::Error` type on failure
+    fn fill(&mut self) -> io::Result<()> {
+        let mut buf = std::mem::take(&mut self.carry);
+        let limit = (self.max_len + 1).saturating_sub(buf.len()).max(1);
+        let num_read = (&mut self.reader)
+            .take(limit as u64)
+            .read_until(b'\n', &mut buf)?;
+        let at_end = num_read < limit && buf.last() != Some(&b'\n');
+        if buf.last() == Some(&b'\n') {
+            buf.pop();
+            if buf.last() == Some(&b'\r') {
+                buf.pop();
+                self.crlf += 1;
+            } else {
+                self.lf += 1;
+            }
+            self.push_line(buf)
+        } else if self.lf + self.crlf == 0 && buf.contains(&b'\r') {
+            let mut parts: Vec<&[u8]> = buf.split(|&b| b == b'\r').collect();
+            let last = parts.pop().unwrap_or_default().to_vec();
+            for part in parts {
+                self.cr += 1;
+                self.push_line(part.to_vec())?;
+            }
+            if !at_end {
+                self.carry = last;
+            } else if !last.is_empty() {
+                self.push_line(last)?;
+            }
+            Ok(())
+        } else if at_end {
+            if buf.is_empty() {
+                self.done = true;
+                self.report();
+                Ok(())
+            } else {
+                self.push_line(buf) // Last line without a line ending
+            }
+        } else {
+            let at = split_point(&buf, self.max_len);
+            self.carry = buf.split_off(at);
+            self.splits += 1;
+            log::warn!(
+                code = "long-line-split";
+                "Line {} longer than {} bytes split at byte {}",
+                self.line_no + 1,
+                self.max_len,
+                at
+            );
+            self.push_line(buf)
+        }
+    }
+}
+
+/// Iterator trait implementation for Lines, giving each line in turn (without
+/// its line ending) or a `std::io::Error` type if a line cannot be read
+impl<B: BufRead> Iterator for Lines<B> {
+    type Item = io::Result<String>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(line) = self.pending.pop_front() {
+                return Some(Ok(line));
+            }
+            if self.done {
+                return None;
+            }
+            if let Err(e) = self.fill() {
+                self.done = true;
+                return Some(Err(e));
+            }
+        }
+    }
+}
+
+// Unit tests
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_columns() {
+        assert_eq!(
+            columns("\x02a\x02b"),
+            (
+                "\x02".to_string(),
+                vec!["a\x02".to_string(), "b".to_string()]
+            )
+        );
+        assert_eq!(columns(""), (String::new(), vec![]));
+    }
+
+    #[test]
+    fn test_overprint() {
+        let (mut prefix, mut cols) = columns("ab c");
+        overprint(&mut prefix, &mut cols, "_b_ \x13d");
+        assert_eq!(prefix, "");
+        assert_eq!(cols, vec!["a\x08_", "b", "_", "c\x13", "d"]);
+    }
+
+    #[test]
+    fn test_merge_overprint() {
+        assert_eq!(
+            merge_overprint("Total\r_____"),
+            Some("T\x08_o\x08_t\x08_a\x08_l\x08_".to_string())
+        );
+        assert_eq!(
+            merge_overprint("resume\r ^   '"),
+            Some("re\x08^sume\x08'".to_string())
+        );
+        assert_eq!(merge_overprint("ab\r"), Some("ab".to_string()));
+        assert_eq!(merge_overprint("abc"), None);
+    }
+
+    #[test]
+    fn test_split_point() {
+        assert_eq!(split_point(b"ab cd", 4), 3);
+        assert_eq!(split_point(b"abcde", 4), 4);
+        assert_eq!(split_point("ab\u{FFFD}".as_bytes(), 3), 2);
+    }
+
+    #[test]
+    fn test_long_lines() {
+        let mut iter = lines(&b"one two three\nfour\n"[..]);
+        iter.max_len = 8;
+        let split: Vec<String> = iter.by_ref().map(Result::unwrap).collect();
+        assert_eq!(split, vec!["one two ", "three", "four"]);
+        assert_eq!(iter.splits, 1);
+        let mut iter = lines(&b"abcdefghij\rk\rl"[..]);
+        iter.max_len = 4;
+        let split: Vec<String> = iter.map(Result::unwrap).collect();
+        assert_eq!(split, vec!["abcd", "efgh", "ij", "k", "l"]);
+    }
+
+    fn read(input: &[u8]) -> (Vec<String>, [usize; 4]) {
+        let mut iter = lines(input);
+        let lines = iter.by_ref().map(Result::unwrap).collect();
+        (lines, [iter.lf, iter.crlf, iter.cr, iter.overprints])
+    }
+
+    #[test]
+    fn test_lines() {
+        assert_eq!(
+            read(b"one\r___\r\ntwo\n"),
+            (
+                vec!["o\x08_n\x08_e\x08_".to_string(), "two".to_string()],
+                [1, 1, 0, 1]
+            )
+        );
+        assert_eq!(
+            read(b"a\nb\r\n\nc"),
+            (
+                vec!["a", "b", "", "c"]
+                    .into_iter()
+                    .map(String::from)
+                    .collect(),
+                [2, 1, 0, 0]
+            )
+        );
+        assert_eq!(
+            read(b"a\rb\r\rc\r"),
+            (
+                vec!["a", "b", "", "c"]
+                    .into_iter()
+                    .map(String::from)
+                    .collect(),
+                [0, 0, 4, 0]
+            )
+        );
+        assert_eq!(read(b""), (vec![], [0, 0, 0, 0]));
+        assert!(lines(&b"\xFF\n"[..]).next().unwrap().is_err());
+    }
+}
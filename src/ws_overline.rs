@@ -7,6 +7,138 @@
 use crate::ws_chars;
 use crate::ws_string;
 
+/// Ways of representing overlined text (e.g. active-low signal names)
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub enum OverlineStyle {
+    #[default]
+    Combining, // Overline wrapper, later rendered as combining overlines
+    Slash, // Prefixed with '/'
+    Tilde, // Prefixed with '~'
+    Star,  // Suffixed with '*'
+}
+
+// PRIVATE HELPER FUNCTIONS
+
+/// Appends the given text to the result, marked as overlined in the given style
+///
+/// # Arguments
+///
+/// * `result` - String to which the marked text is appended
+/// * `text` - Slice of text to be marked as overlined
+/// * `style` - Representation of the overline
+///
+/// # Examples
+/// ```
+/// let mut result = String::new();
+/// push_overlined(&mut result, "DAV", OverlineStyle::Slash);
+/// assert_eq!(result, "/DAV");
+/// ```
+fn push_overlined(result: &mut String, text: &str, style: OverlineStyle) {
+    match style {
+        OverlineStyle::Combining => {
+            result.push(ws_chars::OVERLINE);
+            result.push_str(text);
+            result.push(ws_chars::OVERLINE);
+        }
+        OverlineStyle::Slash => {
+            result.push('/');
+            result.push_str(text);
+        }
+        OverlineStyle::Tilde => {
+            result.push('~');
+            result.push_str(text);
+        }
+        OverlineStyle::Star => {
+            result.push_str(text);
+            result.push('*');
+        }
+    }
+}
+
+/// Returns `true` if the given text slice is a bar for an overline sequence,
+/// i.e. it holds only `ws_chars::UNDERSCORE` and space characters and at
+/// least one underscore, otherwise `false`
+///
+/// # Arguments
+///
+/// * `bars` - Slice of text between the `ws_chars::SUPERSCRIPT` characters
+///
+/// # Examples
+/// ```
+/// assert!(is_bar("__ ___"));
+/// ```
+fn is_bar(bars: &str) -> bool {
+    bars.contains(ws_chars::UNDERSCORE)
+        && bars.chars().all(|c| c == ws_chars::UNDERSCORE || c == ' ')
+}
+
+/// Appends the given text to the result, marking as overlined each run of
+/// characters that lies under the underscores of the given bar (or all of
+/// the text if the bar is not the same length as the text)
+///
+/// # Arguments
+///
+/// * `result` - String to which the marked text is appended
+/// * `text` - Slice of text to be marked as overlined
+/// * `bars` - Slice of underscores (and spaces) overprinted on the text
+/// * `style` - Representation of the overline
+///
+/// # Examples
+/// ```
+/// let mut result = String::new();
+/// push_overlined_runs(&mut result, "A, B", "_  _", OverlineStyle::Slash);
+/// assert_eq!(result, "/A, /B");
+/// ```
+fn push_overlined_runs(result: &mut String, text: &str, bars: &str, style: OverlineStyle) {
+    if ws_string::len_in_chars(bars) != ws_string::len_in_chars(text) {
+        push_overlined(result, text, style);
+        return;
+    }
+    let mut run = String::new();
+    for (c, bar) in text.chars().zip(bars.chars()) {
+        if bar == ws_chars::UNDERSCORE {
+            run.push(c);
+        } else {
+            if !run.is_empty() {
+                push_overlined(result, &run, style);
+                run.clear();
+            }
+            result.push(c);
+        }
+    }
+    if !run.is_empty() {
+        push_overlined(result, &run, style);
+    }
+}
+
+/// Returns `Some((prefix, text))` if the given text slice ends with one or
+/// more `ws_chars::OVERPRINT` characters preceded by the same number of
+/// printable characters, and the number of overprint characters is within
+/// one of the given number of underscores, otherwise `None`
+///
+/// # Arguments
+///
+/// * `left` - Slice of text before an overline sequence
+/// * `bars` - Number of underscores in the overline sequence
+///
+/// # Examples
+/// ```
+/// assert_eq!(split_near_match("See DAC\x08\x08\x08", 4), Some(("See ", "DAC")));
+/// ```
+fn split_near_match(left: &str, bars: usize) -> Option<(&str, &str)> {
+    let over = left
+        .chars()
+        .rev()
+        .take_while(|&c| c == ws_chars::OVERPRINT)
+        .count();
+    if over == 0 || over.abs_diff(bars) > 1 {
+        return None;
+    }
+    let (before, _) = ws_string::split_last_two(left, over)?;
+    let (prefix, text) = ws_string::split_last_two(before, over)?;
+    ws_string::contains_only_print(text).then_some((prefix, text))
+}
+
 // EXTERNAL PUBLIC FUNCTIONS
 
 /// Returns `Some(replacement)` if the given text slice contains one or more
@@ -17,36 +149,60 @@ use crate::ws_string;
 /// number of `ws_chars::UNDERSCORE` characters as the overprint characters and
 /// then another `ws_chars::SUPERSCRIPT` wrapper character.  The same number of
 /// non control characters must be found before this special sequence.  This text
-/// is converted by wrapping it in a pair of `ws_chars::OVERLINE` characters.
-/// The rest of the special sequence is discarded from the replacement String.
+/// is converted by wrapping it in a pair of `ws_chars::OVERLINE` characters (or
+/// marking it as given by another `OverlineStyle`).  The rest of the special
+/// sequence is discarded from the replacement String.
 ///
 /// If the above special sequence is not matched precisely, then no replacement
-/// will be made for it.
+/// will be made for it, unless `lenient` is `true` and the number of overprint
+/// characters is within one of the number of underscores.  In that case the
+/// number of overprint characters is taken as the length of the overlined text
+/// and the assumption is logged.
+///
+/// The overlined text may include spaces and punctuation (e.g. a phrase made
+/// up of several words), and the bar may include spaces where the text is not
+/// overlined (e.g. between words), as long as it has at least one underscore.
 ///
 /// # Arguments
 ///
 /// * `s` - Slice of text to be processed
+/// * `style` - Representation of overlined text
+/// * `lenient` - Flag to accept sequences with mismatched counts (by one)
 ///
 /// # Examples
 /// ```
-/// assert_eq!(process("Q\x08\x14_\x14"), Some("\x01Q\x01".to_string()));
+/// let style = OverlineStyle::Combining;
+/// assert_eq!(process("Q\x08\x14_\x14", style, false), Some("\x01Q\x01".to_string()));
 /// ```
-pub fn process(s: &str) -> Option<String> {
+pub fn process(s: &str, style: OverlineStyle, lenient: bool) -> Option<String> {
     let mut changed = false;
     let mut result = String::with_capacity(s.len());
     let mut rest = s;
     while let Some((left, bars, right)) = ws_string::split_first_three(rest, ws_chars::SUPERSCRIPT)
     {
-        if ws_string::contains_only_char(bars, ws_chars::UNDERSCORE) {
+        if is_bar(bars) {
             let len = ws_string::len_in_chars(bars);
             if let Some((prefix, text, over)) = ws_string::split_last_three(left, len) {
                 if ws_string::contains_only_char(over, ws_chars::OVERPRINT)
                     && ws_string::contains_only_print(text)
                 {
                     result.push_str(prefix);
-                    result.push(ws_chars::OVERLINE);
-                    result.push_str(text);
-                    result.push(ws_chars::OVERLINE);
+                    push_overlined_runs(&mut result, text, bars, style);
+                    rest = right;
+                    changed = true;
+                    continue;
+                }
+            }
+            if lenient {
+                if let Some((prefix, text)) = split_near_match(left, len) {
+                    log::info!(
+                        "Assumed overline of {:?} ({} underscores for {} characters)",
+                        text,
+                        len,
+                        ws_string::len_in_chars(text)
+                    );
+                    result.push_str(prefix);
+                    push_overlined(&mut result, text, style);
                     rest = right;
                     changed = true;
                     continue;
@@ -77,14 +233,115 @@ mod tests {
     #[test]
     fn test_process() {
         assert_eq!(
-            process("See DAC\x08\x08\x08\x14___\x14, RFD\x08\x08\x08\x14___\x14 and DAV"),
+            process(
+                "See DAC\x08\x08\x08\x14___\x14, RFD\x08\x08\x08\x14___\x14 and DAV",
+                OverlineStyle::Combining,
+                false
+            ),
             Some("See \x01DAC\x01, \x01RFD\x01 and DAV".to_string())
         );
         assert_eq!(
-            process("See DAC\x08?\x08\x14___\x14, RFD\x08\x08\x08\x14___\x14 and DAV"),
+            process(
+                "See DAC\x08?\x08\x14___\x14, RFD\x08\x08\x08\x14___\x14 and DAV",
+                OverlineStyle::Combining,
+                false
+            ),
             Some("See DAC\x08?\x08\x14___\x14, \x01RFD\x01 and DAV".to_string())
         );
-        assert_eq!(process("abcd"), None);
-        assert_eq!(process(""), None);
+        assert_eq!(process("abcd", OverlineStyle::Combining, false), None);
+        assert_eq!(process("", OverlineStyle::Combining, false), None);
+    }
+
+    #[test]
+    fn test_is_bar() {
+        assert!(is_bar("___"));
+        assert!(is_bar("_ _"));
+        assert!(!is_bar("   "));
+        assert!(!is_bar("_-_"));
+        assert!(!is_bar(""));
+    }
+
+    #[test]
+    fn test_push_overlined_runs() {
+        let mut result = String::new();
+        push_overlined_runs(&mut result, "A, B", "_  _", OverlineStyle::Combining);
+        assert_eq!(result, "\x01A\x01, \x01B\x01");
+        let mut result = String::new();
+        push_overlined_runs(&mut result, "A B", "___", OverlineStyle::Star);
+        assert_eq!(result, "A B*");
+        let mut result = String::new();
+        push_overlined_runs(&mut result, "A B", "__", OverlineStyle::Tilde);
+        assert_eq!(result, "~A B");
+    }
+
+    #[test]
+    fn test_phrases() {
+        assert_eq!(
+            process(
+                "A B\x08\x08\x08\x14___\x14",
+                OverlineStyle::Combining,
+                false
+            ),
+            Some("\x01A B\x01".to_string())
+        );
+        assert_eq!(
+            process(
+                "See DATA VALID\x08\x08\x08\x08\x08\x08\x08\x08\x08\x08\x14____ _____\x14.",
+                OverlineStyle::Slash,
+                false
+            ),
+            Some("See /DATA /VALID.".to_string())
+        );
+        assert_eq!(
+            process("R/W\x08\x08\x08\x14___\x14", OverlineStyle::Star, false),
+            Some("R/W*".to_string())
+        );
+    }
+
+    #[test]
+    fn test_split_near_match() {
+        assert_eq!(
+            split_near_match("See DAC\x08\x08\x08", 4),
+            Some(("See ", "DAC"))
+        );
+        assert_eq!(
+            split_near_match("See DAC\x08\x08\x08", 2),
+            Some(("See ", "DAC"))
+        );
+        assert_eq!(split_near_match("See DAC\x08\x08\x08", 5), None);
+        assert_eq!(split_near_match("See DAC", 1), None);
+        assert_eq!(split_near_match("\x02AC\x08\x08\x08", 3), None);
+    }
+
+    #[test]
+    fn test_lenient() {
+        let text = "DAC\x08\x08\x08\x14____\x14 and NRFD\x08\x08\x08\x14____\x14";
+        assert_eq!(process(text, OverlineStyle::Combining, false), None);
+        assert_eq!(
+            process(text, OverlineStyle::Combining, true),
+            Some("\x01DAC\x01 and N\x01RFD\x01".to_string())
+        );
+        let exact = "See DAC\x08\x08\x08\x14___\x14";
+        assert_eq!(
+            process(exact, OverlineStyle::Slash, true),
+            process(exact, OverlineStyle::Slash, false)
+        );
+    }
+
+    #[test]
+    fn test_styles() {
+        let text = "DAC\x08\x08\x08\x14___\x14 and NRFD\x08\x08\x08\x08\x14____\x14";
+        assert_eq!(
+            process(text, OverlineStyle::Slash, false),
+            Some("/DAC and /NRFD".to_string())
+        );
+        assert_eq!(
+            process(text, OverlineStyle::Tilde, false),
+            Some("~DAC and ~NRFD".to_string())
+        );
+        assert_eq!(
+            process(text, OverlineStyle::Star, false),
+            Some("DAC* and NRFD*".to_string())
+        );
     }
 }
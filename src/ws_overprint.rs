@@ -0,0 +1,147 @@
+//! Module to process WordStar "overprint next character" sequences
+
+// WordStar's ^PH command prints the next character on top of the previous
+// one (e.g. to add an accent to a letter on a daisywheel printer).  These
+// pairs are converted here to the letter followed by a Unicode combining
+// mark, whichever way round the letter and the accent were typed.  The
+// overprint sequences used for overlines and fractions are left alone, as
+// they have control characters either side of the overprint character.
+
+use crate::uni_chars;
+use crate::ws_chars;
+
+// PRIVATE HELPER FUNCTIONS
+
+/// Returns `Some(mark)` holding the Unicode combining mark corresponding to
+/// the given character when it is overprinted on another, otherwise `None`
+///
+/// # Arguments
+///
+/// * `c` - Character to be mapped to a combining mark (if possible)
+///
+/// # Examples
+/// ```
+/// assert_eq!(get_mark('\''), Some('\u{0301}'));
+/// ```
+fn get_mark(c: char) -> Option<char> {
+    match c {
+        '`' => Some(uni_chars::COMB_GRAVE),
+        '\'' => Some(uni_chars::COMB_ACUTE),
+        '^' => Some(uni_chars::COMB_CIRCUMFLEX),
+        '~' => Some(uni_chars::COMB_TILDE),
+        '"' => Some(uni_chars::COMB_DIAERESIS),
+        ',' => Some(uni_chars::COMB_CEDILLA),
+        '_' => Some(uni_chars::COMB_UNDERLINE),
+        '-' => Some(uni_chars::COMB_SHORT_STROKE),
+        '/' => Some(uni_chars::COMB_LONG_SOLIDUS),
+        '|' => Some(uni_chars::COMB_VERTICAL_LINE),
+        _ => None,
+    }
+}
+
+/// Returns `Some((base, mark))` holding the base character and combining
+/// mark for the given pair of overprinted characters (in either order), or
+/// `None` if neither character can be used as a mark on the other
+///
+/// # Arguments
+///
+/// * `first` - Character before the overprint character
+/// * `second` - Character after the overprint character
+///
+/// # Examples
+/// ```
+/// assert_eq!(combine('e', '\''), Some(('e', '\u{0301}')));
+/// assert_eq!(combine('\'', 'e'), Some(('e', '\u{0301}')));
+/// ```
+fn combine(first: char, second: char) -> Option<(char, char)> {
+    if !first.is_ascii_graphic() || !second.is_ascii_graphic() {
+        return None;
+    }
+    match (get_mark(first), get_mark(second)) {
+        (_, Some(mark)) => Some((first, mark)),
+        (Some(mark), None) => Some((second, mark)),
+        (None, None) => None,
+    }
+}
+
+// EXTERNAL PUBLIC FUNCTIONS
+
+/// Returns `Some(replacement)` if the given text slice contains one or more
+/// overprinted pairs of characters that have been converted to a character
+/// with a combining mark, otherwise `None`
+///
+/// An overprinted pair is a printable character, a `ws_chars::OVERPRINT`
+/// character and another printable character, one of which must be an
+/// accent or other mark (e.g. `'`, `"`, `^` or `/`).  Any other use of the
+/// overprint character is left unchanged.
+///
+/// # Arguments
+///
+/// * `s` - Slice of text to be processed
+///
+/// # Examples
+/// ```
+/// assert_eq!(process("cafe\x08'"), Some("cafe\u{0301}".to_string()));
+/// ```
+pub fn process(s: &str) -> Option<String> {
+    if !s.contains(ws_chars::OVERPRINT) {
+        return None;
+    }
+    let chars: Vec<char> = s.chars().collect();
+    let mut changed = false;
+    let mut result = String::with_capacity(s.len() + 2);
+    let mut i = 0;
+    while i < chars.len() {
+        if i + 2 < chars.len() && chars[i + 1] == ws_chars::OVERPRINT {
+            if let Some((base, mark)) = combine(chars[i], chars[i + 2]) {
+                result.push(base);
+                result.push(mark);
+                changed = true;
+                i += 3;
+                continue;
+            }
+        }
+        result.push(chars[i]);
+        i += 1;
+    }
+    changed.then_some(result)
+}
+
+// Unit tests
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_get_mark() {
+        assert_eq!(get_mark('`'), Some('\u{0300}'));
+        assert_eq!(get_mark('"'), Some('\u{0308}'));
+        assert_eq!(get_mark('/'), Some('\u{0338}'));
+        assert_eq!(get_mark('a'), None);
+    }
+
+    #[test]
+    fn test_combine() {
+        assert_eq!(combine('e', '\''), Some(('e', '\u{0301}')));
+        assert_eq!(combine('^', 'o'), Some(('o', '\u{0302}')));
+        assert_eq!(combine('=', '/'), Some(('=', '\u{0338}')));
+        assert_eq!(combine('a', 'b'), None);
+        assert_eq!(combine(' ', '\''), None);
+        assert_eq!(combine('a', '\x14'), None);
+    }
+
+    #[test]
+    fn test_process() {
+        assert_eq!(
+            process("cafe\x08' and na\"\x08ive"),
+            Some("cafe\u{0301} and nai\u{0308}ve".to_string())
+        );
+        assert_eq!(process("fa,\x08cade"), Some("fac\u{0327}ade".to_string()));
+        assert_eq!(process("DAC\x08\x08\x08\x14___\x14"), None);
+        assert_eq!(process("\x13\x141\x14\x13\x08\x162\x16"), None);
+        assert_eq!(process("ab\x08c"), None);
+        assert_eq!(process("a\x08"), None);
+        assert_eq!(process("plain"), None);
+    }
+}
@@ -0,0 +1,199 @@
+//! Module to process WordStar pitch and font changes
+
+// WordStar selects the alternate pitch with ^PA and returns to the normal
+// pitch with ^PN.  Unlike the wrapper characters, these set (rather than
+// toggle) the state, so they are normalised here into pairs of
+// `ws_chars::PITCH` characters that the wrappers stage can treat like any
+// other wrapper.  Redundant settings (e.g. ^PN when already at normal pitch)
+// are simply removed.
+//
+// WordStar 5 and later (up to WordStar 7) record font changes as tags that
+// start and end with a `ws_chars::FONT_TAG` character, with the font name
+// somewhere among the binary data between them.  The first font named in
+// the file is taken to be the normal font, and any other font is treated
+// as the alternate pitch.
+
+use crate::ws_chars;
+
+// PRIVATE HELPER FUNCTIONS
+
+/// Returns the font name held in the body of a font tag (i.e. the longest
+/// run of alphanumeric or space characters, trimmed), or an empty String if
+/// there is none
+///
+/// # Arguments
+///
+/// * `tag` - Slice of text between the `ws_chars::FONT_TAG` characters
+///
+/// # Examples
+/// ```
+/// assert_eq!(font_name("\x05\x00\x00Courier 10\x00"), "Courier 10");
+/// ```
+fn font_name(tag: &str) -> String {
+    tag.split(|c: char| !(c.is_ascii_alphanumeric() || c == ' '))
+        .map(str::trim)
+        .max_by_key(|name| name.len())
+        .unwrap_or_default()
+        .to_string()
+}
+
+// "PITCH" OBJECT
+
+// Holds the pitch state and the normal font name carried from line to line,
+// and whether font tags are recognised
+#[derive(Debug)]
+pub struct Pitch {
+    alternate: bool,
+    normal_font: Option<String>,
+    font_tags: bool,
+}
+
+impl Default for Pitch {
+    fn default() -> Self {
+        Pitch {
+            alternate: false,
+            normal_font: None,
+            font_tags: true,
+        }
+    }
+}
+
+impl Pitch {
+    /// Creates a new `Pitch` object at normal pitch with no font (default)
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Returns this `Pitch` object recognising font tags only if `font_tags`
+    /// is `true` (e.g. not for files from word processors predating WordStar
+    /// 5), with any `ws_chars::FONT_TAG` characters otherwise left unchanged
+    ///
+    /// # Arguments
+    ///
+    /// * `font_tags` - Flag to recognise font tags
+    ///
+    /// # Examples
+    /// ```
+    /// let p = Pitch::new().with_font_tags(false);
+    /// ```
+    pub fn with_font_tags(mut self, font_tags: bool) -> Self {
+        self.font_tags = font_tags;
+        self
+    }
+
+    /// Sets the pitch to alternate if `alternate` is `true` (otherwise normal),
+    /// pushing a `ws_chars::PITCH` character onto the result if it has changed
+    ///
+    /// # Arguments
+    ///
+    /// * `result` - String to which any `ws_chars::PITCH` character is appended
+    /// * `alternate` - New pitch setting
+    ///
+    fn set_alternate(&mut self, result: &mut String, alternate: bool) {
+        if self.alternate != alternate {
+            self.alternate = alternate;
+            result.push(ws_chars::PITCH);
+        }
+    }
+
+    /// Returns `Some(replacement)` if the given text slice contains any pitch
+    /// or font changes that have been converted to `ws_chars::PITCH` wrapper
+    /// characters, otherwise `None`
+    ///
+    /// An unterminated font tag is left unchanged.
+    ///
+    /// # Arguments
+    ///
+    /// * `s` - Slice of text to be processed
+    ///
+    /// # Examples
+    /// ```
+    /// let mut p = Pitch::new();
+    /// assert_eq!(p.process("a\x01b\x0Ec"), Some("a\x0Eb\x0Ec".to_string()));
+    /// ```
+    pub fn process(&mut self, s: &str) -> Option<String> {
+        let mut changed = false;
+        let mut result = String::with_capacity(s.len());
+        let mut rest = s;
+        while let Some(pos) = rest.find(|c: char| {
+            c == ws_chars::ALTERNATE_PITCH
+                || c == ws_chars::NORMAL_PITCH
+                || (c == ws_chars::FONT_TAG && self.font_tags)
+        }) {
+            result.push_str(&rest[..pos]);
+            let c = rest[pos..].chars().next().unwrap(); // Always ASCII
+            rest = &rest[pos + 1..];
+            match c {
+                ws_chars::ALTERNATE_PITCH => self.set_alternate(&mut result, true),
+                ws_chars::NORMAL_PITCH => self.set_alternate(&mut result, false),
+                _ => match rest.find(ws_chars::FONT_TAG) {
+                    Some(end) => {
+                        let name = font_name(&rest[..end]);
+                        rest = &rest[end + 1..];
+                        let normal = self.normal_font.get_or_insert_with(|| name.clone());
+                        let alternate = !name.is_empty() && name != *normal;
+                        self.set_alternate(&mut result, alternate);
+                    }
+                    None => {
+                        result.push(c); // Unterminated tag: leave alone
+                        continue;
+                    }
+                },
+            }
+            changed = true;
+        }
+        if changed {
+            result.push_str(rest);
+            Some(result)
+        } else {
+            None
+        }
+    }
+}
+
+// Unit tests
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_font_name() {
+        assert_eq!(font_name("\x05\x00\x00Courier 10\x00"), "Courier 10");
+        assert_eq!(font_name("\x01ab\x02 Times Roman \x03"), "Times Roman");
+        assert_eq!(font_name("\x01\x02"), "");
+    }
+
+    #[test]
+    fn test_pitch() {
+        let mut p = Pitch::new();
+        assert_eq!(p.process("a\x01b\x0Ec"), Some("a\x0Eb\x0Ec".to_string()));
+        assert_eq!(p.process("\x0Ea\x01b"), Some("a\x0Eb".to_string()));
+        assert_eq!(p.process("\x01c\x0E"), Some("c\x0E".to_string()));
+        assert_eq!(p.process("plain"), None);
+    }
+
+    #[test]
+    fn test_fonts() {
+        let mut p = Pitch::new();
+        assert_eq!(
+            p.process("\x1D\x02Courier\x00\x1Da\x1D\x02Elite\x1Db"),
+            Some("a\x0Eb".to_string())
+        );
+        assert_eq!(
+            p.process("c\x1D\x02Courier\x1Dd"),
+            Some("c\x0Ed".to_string())
+        );
+        assert_eq!(p.process("e\x1Df"), None);
+    }
+
+    #[test]
+    fn test_no_font_tags() {
+        let mut p = Pitch::new().with_font_tags(false);
+        assert_eq!(p.process("\x1D\x02Courier\x00\x1Da"), None);
+        assert_eq!(
+            p.process("\x1D\x02Elite\x1Db\x01c"),
+            Some("\x1D\x02Elite\x1Db\x0Ec".to_string())
+        );
+    }
+}
@@ -8,11 +8,184 @@
 // expressions (so `concat!` is not an option).  This activity is constrained
 // to occur only once (first time round) by using the `lazy_static!` macro.
 
+// Without the "regex" feature (e.g. for minimal builds) the sequences are
+// found by scanning the string directly instead, and the patterns of any
+// additional special sequences are taken as literal text.
+
 use crate::uni_chars;
 use crate::ws_chars;
+#[cfg(feature = "regex")]
 use lazy_static::lazy_static;
+#[cfg(feature = "regex")]
 use regex::Regex;
+#[cfg(feature = "regex")]
 use std::borrow::Cow;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+const RULE_SEPARATOR: &str = "=>"; // Separates pattern from replacement in rules
+const RULE_COMMENT: char = '#'; // Starts a comment line in rules
+const DEGREE_UNITS: &str = "CF"; // Temperature scales written with degrees
+const ENGLISH_ORDINALS: [&str; 4] = ["st", "nd", "rd", "th"]; // Ordinal suffixes
+const TRADE_MARK_TEXT: &str = "TM"; // Superscripted text for a trade mark sign
+const ACCENT_PREFIX: char = '`'; // Typed before a letter for an accented letter
+const GUILLEMETS: [(&str, &str); 2] = [("<<", "\u{00AB}"), (">>", "\u{00BB}")]; // Typed and Unicode
+
+/// Ways of representing simple fractions
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub enum FractionStyle {
+    #[default]
+    Unicode, // Unicode fraction symbols (e.g. "\u{00BD}")
+    Slash, // Numerator and denominator separated by '/' (e.g. "1/2")
+}
+
+/// Conventions for numbers and units in documents from different countries,
+/// used to tidy up temperatures written with spaces or a letter 'o' (e.g.
+/// "25 o C") and numbers with superscripted ordinal suffixes
+///
+/// The French, German and Spanish profiles follow the continental
+/// conventions and also convert guillemets typed as "<<" and ">>" and
+/// accented letters typed as a backtick before the letter (see `ACCENTS`).
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub enum Locale {
+    #[default]
+    None, // Left unchanged
+    English,     // Decimal point, "25.5\u{00B0}C" and plain suffixes (e.g. "1st")
+    Continental, // Decimal comma, "25,5 \u{00B0}C" and ordinal indicators (e.g. "1\u{00BA}")
+    French,      // Continental, with "\u{00AB}\u{00A0}" guillemets and "`e" for "\u{00E8}"
+    German,      // Continental, with guillemets and "`a" for "\u{00E4}"
+    Spanish,     // Continental, with guillemets and "`a" for "\u{00E1}"
+}
+
+impl Locale {
+    /// Returns `true` if this locale follows the continental conventions for
+    /// numbers and units, otherwise `false`
+    fn is_continental(self) -> bool {
+        !matches!(self, Locale::None | Locale::English)
+    }
+
+    /// Returns the character separating the whole and decimal parts of a
+    /// number
+    fn decimal_separator(self) -> char {
+        match self.is_continental() {
+            true => ',',
+            false => '.',
+        }
+    }
+
+    /// Returns the text placed between a number and a degree symbol
+    fn degree_space(self) -> &'static str {
+        match self.is_continental() {
+            true => uni_chars::NB_SPACE,
+            false => "",
+        }
+    }
+
+    /// Returns the letters typed after a backtick for accented letters in
+    /// this locale together with the letters that replace them
+    fn accents(self) -> &'static [(char, char)] {
+        match self {
+            Locale::French => &ACCENTS_FRENCH,
+            Locale::German => &ACCENTS_GERMAN,
+            Locale::Spanish => &ACCENTS_SPANISH,
+            _ => &[],
+        }
+    }
+
+    /// Returns the text placed inside guillemets (next to the text that they
+    /// enclose)
+    fn guillemet_space(self) -> &'static str {
+        match self {
+            Locale::French => uni_chars::NB_SPACE,
+            _ => "",
+        }
+    }
+}
+
+/// Ways of converting trade mark signs (a superscripted "TM"), copyright and
+/// registered signs (e.g. "(c)" after a word or before a year) and
+/// superscripted ordinal suffixes (e.g. "1^Tst^T")
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub enum Symbols {
+    #[default]
+    None, // Left unchanged
+    Superscript, // Unicode signs, with ordinal suffixes kept superscripted (e.g. "1\u{02E2}\u{1D57}")
+    Plain,       // Unicode signs, with ordinal suffixes as plain text (e.g. "1st")
+}
+
+/// Holds an additional (user-defined) fraction together with the text that
+/// replaces it, e.g. for fractions without a Unicode symbol of their own
+#[derive(Debug, Clone, PartialEq)]
+pub struct CustomFraction {
+    pub numerator: u32,
+    pub denominator: u32,
+    pub symbol: String,
+}
+
+/// Holds an additional (user-defined) special sequence as a regular
+/// expression (or literal text without the "regex" feature) together with
+/// the text that replaces each match (which may refer to captured groups as
+/// `$1` etc.)
+#[derive(Debug, Clone)]
+pub struct SpecialRule {
+    #[cfg(feature = "regex")]
+    regex: Regex,
+    #[cfg(not(feature = "regex"))]
+    pattern: String,
+    replacement: String,
+}
+
+/// Letters typed after an `ACCENT_PREFIX` and the accented letters that
+/// replace them (in French, German and Spanish documents respectively)
+const ACCENTS_FRENCH: [(char, char); 8] = [
+    ('a', '\u{00E0}'),
+    ('e', '\u{00E8}'),
+    ('u', '\u{00F9}'),
+    ('c', '\u{00E7}'),
+    ('A', '\u{00C0}'),
+    ('E', '\u{00C8}'),
+    ('U', '\u{00D9}'),
+    ('C', '\u{00C7}'),
+];
+const ACCENTS_GERMAN: [(char, char); 7] = [
+    ('a', '\u{00E4}'),
+    ('o', '\u{00F6}'),
+    ('u', '\u{00FC}'),
+    ('s', '\u{00DF}'),
+    ('A', '\u{00C4}'),
+    ('O', '\u{00D6}'),
+    ('U', '\u{00DC}'),
+];
+const ACCENTS_SPANISH: [(char, char); 14] = [
+    ('a', '\u{00E1}'),
+    ('e', '\u{00E9}'),
+    ('i', '\u{00ED}'),
+    ('o', '\u{00F3}'),
+    ('u', '\u{00FA}'),
+    ('n', '\u{00F1}'),
+    ('A', '\u{00C1}'),
+    ('E', '\u{00C9}'),
+    ('I', '\u{00CD}'),
+    ('O', '\u{00D3}'),
+    ('U', '\u{00DA}'),
+    ('N', '\u{00D1}'),
+    ('?', '\u{00BF}'),
+    ('!', '\u{00A1}'),
+];
+
+/// Numerators, denominators and Unicode symbols of the standard fractions
+const FRACTIONS: [(u32, u32, &str); 9] = [
+    (1, 2, uni_chars::HALF),
+    (1, 3, uni_chars::ONE_THIRD),
+    (2, 3, uni_chars::TWO_THIRDS),
+    (1, 4, uni_chars::ONE_QUARTER),
+    (3, 4, uni_chars::THREE_QUARTERS),
+    (1, 8, uni_chars::ONE_EIGHTH),
+    (3, 8, uni_chars::THREE_EIGHTHS),
+    (5, 8, uni_chars::FIVE_EIGHTHS),
+    (7, 8, uni_chars::SEVEN_EIGHTHS),
+];
 
 // PRIVATE HELPER FUNCTIONS
 
@@ -31,6 +204,7 @@ use std::borrow::Cow;
 /// ```
 /// assert_eq!(transform_degrees("-40\x14o\x14C"), Some("-40\u{00B0}C".to_string()));
 /// ```
+#[cfg(feature = "regex")]
 fn transform_degrees(before: &str) -> Option<String> {
     lazy_static! {
         static ref REGEX_DEGREE: Regex = {
@@ -48,36 +222,97 @@ fn transform_degrees(before: &str) -> Option<String> {
     }
 }
 
-/// Returns text slice containing Unicode fraction symbol corresponding to the "1"
-/// or "3" numerator passed in the first captured parameter and the "2" or "4"
-/// denominator passed in the second captured parameter, or `U+FFFD REPLACEMENT
-/// CHARACTER` for an invalid combination.
+/// Returns `Some(replacement)` if the given text slice contains one or more special
+/// sequences that have been converted to degree symbols, otherwise `None` (as above,
+/// but without regular expressions)
+#[cfg(not(feature = "regex"))]
+fn transform_degrees(before: &str) -> Option<String> {
+    let sequence: String = [ws_chars::SUPERSCRIPT, 'o', ws_chars::SUPERSCRIPT]
+        .iter()
+        .collect();
+    before
+        .contains(&sequence)
+        .then(|| before.replace(&sequence, uni_chars::DEGREE))
+}
+
+/// Returns the symbol for the fraction with the given numerator and
+/// denominator from the given additional fractions (which take precedence)
+/// or the standard fractions, otherwise `None`
+///
+/// # Arguments
+///
+/// * `numerator` - Numerator of the fraction
+/// * `denominator` - Denominator of the fraction
+/// * `custom` - Slice of additional fractions
+///
+/// # Examples
+/// ```
+/// assert_eq!(lookup_fraction(3, 8, &[]), Some(uni_chars::THREE_EIGHTHS));
+/// ```
+fn lookup_fraction(numerator: u32, denominator: u32, custom: &[CustomFraction]) -> Option<&str> {
+    custom
+        .iter()
+        .find(|fraction| fraction.numerator == numerator && fraction.denominator == denominator)
+        .map(|fraction| fraction.symbol.as_str())
+        .or_else(|| {
+            FRACTIONS
+                .iter()
+                .find(|(n, d, _)| *n == numerator && *d == denominator)
+                .map(|(_, _, symbol)| *symbol)
+        })
+}
+
+/// Returns text containing the symbol for the fraction with the given
+/// numerator and denominator (from the given additional fractions or the
+/// standard fractions), or `U+FFFD REPLACEMENT CHARACTER` for a combination
+/// without a symbol, preceded by the given digit (if any).  If the
+/// `FractionStyle::Slash` style is given then the numerator and denominator
+/// are instead separated by '/' (and also from any preceding digit by a
+/// space, as in "6 1/2").
 ///
 /// # Arguments
 ///
-/// * `caps` - Reference to group of captured strings for a regular expression match
+/// * `digit` - Digit before the fraction (or "" if none)
+/// * `numerator` - Digits of the numerator
+/// * `denominator` - Digits of the denominator
+/// * `style` - Representation of the fraction
+/// * `custom` - Slice of additional fractions
 ///
-fn get_fraction(caps: &regex::Captures) -> &'static str {
-    match (&caps[1], &caps[2]) {
-        ("1", "2") => uni_chars::HALF,
-        ("1", "4") => uni_chars::ONE_QUARTER,
-        ("3", "4") => uni_chars::THREE_QUARTERS,
-        _ => uni_chars::REPLACEMENT, // Unable to map 3/2
+/// # Examples
+/// ```
+/// assert_eq!(get_fraction("6", "1", "2", FractionStyle::Slash, &[]), "6 1/2");
+/// ```
+fn get_fraction(
+    digit: &str,
+    numerator: &str,
+    denominator: &str,
+    style: FractionStyle,
+    custom: &[CustomFraction],
+) -> String {
+    if style == FractionStyle::Slash {
+        let space = if digit.is_empty() { "" } else { " " };
+        return format!("{}{}{}/{}", digit, space, numerator, denominator);
     }
+    let symbol = match (numerator.parse(), denominator.parse()) {
+        (Ok(numerator), Ok(denominator)) => lookup_fraction(numerator, denominator, custom),
+        _ => None,
+    };
+    format!("{}{}", digit, symbol.unwrap_or(uni_chars::REPLACEMENT)) // e.g. for 3/2
 }
 
 /// Returns `Some(replacement)` if the given text slice contains one or more special
-/// sequences that have been converted to Unicode fraction symbols (1/2, 1/4 or 3/4),
-/// otherwise `None`
+/// sequences that have been converted to Unicode fraction symbols (e.g. 1/2, 2/3 or
+/// 7/8) or the symbols of the given additional fractions, otherwise `None`
 ///
 /// A special fraction sequence is a pair of `ws_chars::UNDERLINE` wrapper characters
 /// surrounding a pair of `ws_chars::SUPERSCRIPT` wrapper characters surrounding in
-/// turn a '1' or '3' (as appropriate), followed by a `ws_chars::OVERPRINT` character
-/// and then a pair of `ws_chars::SUBSCRIPT` wrapper characters surrounding a '2' or
-/// '4' (as appropriate).  This sequence is converted to the corresponding Unicode
-/// "one half", one quarter" or "three quarters" symbol.  A '3' followed by a '2' is
-/// converted to a Unicode `U+FFFD REPLACEMENT CHARACTER` as there is no valid symbol
-/// for this unexpected combination.
+/// turn the digits of the numerator, followed by a `ws_chars::OVERPRINT` character
+/// and then a pair of `ws_chars::SUBSCRIPT` wrapper characters surrounding the
+/// digits of the denominator.  This sequence is converted to the corresponding
+/// Unicode symbol for halves, thirds, quarters or eighths (or to the symbol of a
+/// matching additional fraction).  Any other combination (e.g. a '3' followed by a
+/// '2') is converted to a Unicode `U+FFFD REPLACEMENT CHARACTER` as there is no
+/// symbol for it.
 ///
 /// Note: Each special sequence can only be detected correctly if the input text has
 /// not previously been processed with the `ws_wrappers` module, as otherwise the
@@ -87,38 +322,544 @@ fn get_fraction(caps: &regex::Captures) -> &'static str {
 /// # Arguments
 ///
 /// * `s` - Slice of text to be processed
+/// * `style` - Representation of fractions
+/// * `custom` - Slice of additional fractions
 ///
 /// # Examples
 /// ```
-/// let before = "\x13\x141\x14\x13\x08\x164\x16";
-/// assert_eq!(transform_quarter(before), Some("\u{00BE}".to_string()));
+/// let before = "\x13\x143\x14\x13\x08\x164\x16";
+/// let style = FractionStyle::Unicode;
+/// assert_eq!(transform_fraction(before, style, &[]), Some("\u{00BE}".to_string()));
 /// ```
-fn transform_fraction(before: &str) -> Option<String> {
+#[cfg(feature = "regex")]
+fn transform_fraction(
+    before: &str,
+    style: FractionStyle,
+    custom: &[CustomFraction],
+) -> Option<String> {
     lazy_static! {
         static ref REGEX_FRACTION: Regex = {
-            let mut re = String::with_capacity(19);  // Can't calculate statically
+            let mut re = String::with_capacity(24);  // Can't calculate statically
+            re.push_str(r"(\d?)");
             re.push(ws_chars::UNDERLINE);
             re.push(ws_chars::SUPERSCRIPT);
-            re.push_str(r"([13])");
+            re.push_str(r"(\d+)");
             re.push(ws_chars::SUPERSCRIPT);
             re.push(ws_chars::UNDERLINE);
             re.push(ws_chars::OVERPRINT);
             re.push(ws_chars::SUBSCRIPT);
-            re.push_str(r"([24])");
+            re.push_str(r"(\d+)");
             re.push(ws_chars::SUBSCRIPT);
             Regex::new(&re).unwrap()
         };
     }
-    if let Cow::Owned(after) =
-        REGEX_FRACTION.replace_all(before, |caps: &regex::Captures| get_fraction(caps))
-    {
+    if let Cow::Owned(after) = REGEX_FRACTION.replace_all(before, |caps: &regex::Captures| {
+        get_fraction(&caps[1], &caps[2], &caps[3], style, custom)
+    }) {
         Some(after)
     } else {
         None
     }
 }
 
-// EXTERNAL PUBLIC FUNCTION
+/// Returns the given text slice split after its leading ASCII digits, or
+/// `None` if it does not start with a digit
+#[cfg(not(feature = "regex"))]
+fn split_digits(s: &str) -> Option<(&str, &str)> {
+    let len = s.find(|c: char| !c.is_ascii_digit()).unwrap_or(s.len());
+    (len > 0).then(|| s.split_at(len))
+}
+
+/// Returns the numerator and denominator of the special fraction sequence
+/// (as described for `transform_fraction()`) at the start of the given text
+/// slice together with the remaining text, otherwise `None`
+///
+/// # Arguments
+///
+/// * `s` - Slice of text to be scanned
+///
+/// # Examples
+/// ```
+/// assert_eq!(scan_fraction("\x13\x141\x14\x13\x08\x162\x16!"), Some(("1", "2", "!")));
+/// ```
+#[cfg(not(feature = "regex"))]
+fn scan_fraction(s: &str) -> Option<(&str, &str, &str)> {
+    let rest = s
+        .strip_prefix(ws_chars::UNDERLINE)?
+        .strip_prefix(ws_chars::SUPERSCRIPT)?;
+    let (numerator, rest) = split_digits(rest)?;
+    let rest = rest
+        .strip_prefix(ws_chars::SUPERSCRIPT)?
+        .strip_prefix(ws_chars::UNDERLINE)?
+        .strip_prefix(ws_chars::OVERPRINT)?
+        .strip_prefix(ws_chars::SUBSCRIPT)?;
+    let (denominator, rest) = split_digits(rest)?;
+    let rest = rest.strip_prefix(ws_chars::SUBSCRIPT)?;
+    Some((numerator, denominator, rest))
+}
+
+/// Returns `Some(replacement)` if the given text slice contains one or more special
+/// sequences that have been converted to fraction symbols, otherwise `None` (as
+/// above, but without regular expressions)
+#[cfg(not(feature = "regex"))]
+fn transform_fraction(
+    before: &str,
+    style: FractionStyle,
+    custom: &[CustomFraction],
+) -> Option<String> {
+    if !before.contains(ws_chars::OVERPRINT) {
+        return None; // Quick exit as every sequence has one
+    }
+    let mut after = String::with_capacity(before.len());
+    let mut found = false;
+    let mut rest = before;
+    while let Some(c) = rest.chars().next() {
+        let (digit, body) = match rest.split_at(c.len_utf8()) {
+            (digit, body) if c.is_ascii_digit() && scan_fraction(body).is_some() => (digit, body),
+            _ => ("", rest),
+        };
+        if let Some((numerator, denominator, remainder)) = scan_fraction(body) {
+            after.push_str(&get_fraction(digit, numerator, denominator, style, custom));
+            rest = remainder;
+            found = true;
+        } else {
+            after.push(c);
+            rest = &rest[c.len_utf8()..];
+        }
+    }
+    found.then_some(after)
+}
+
+/// Returns the given text slice split after the number at its start (its
+/// leading ASCII digits and any decimal part after the given separator)
+///
+/// # Arguments
+///
+/// * `s` - Slice of text starting with a digit
+/// * `separator` - Character separating the whole and decimal parts
+///
+/// # Examples
+/// ```
+/// assert_eq!(split_number("25.5 o C", '.'), ("25.5", " o C"));
+/// ```
+fn split_number(s: &str, separator: char) -> (&str, &str) {
+    let digits = |t: &str| t.find(|c: char| !c.is_ascii_digit()).unwrap_or(t.len());
+    let mut len = digits(s);
+    if let Some(decimals) = s[len..].strip_prefix(separator) {
+        let count = digits(decimals);
+        if count > 0 {
+            len += separator.len_utf8() + count;
+        }
+    }
+    s.split_at(len)
+}
+
+/// Returns the text between the pair of `ws_chars::SUPERSCRIPT` characters
+/// at the start of the given text slice together with the remaining text,
+/// otherwise `None`
+fn split_superscript(s: &str) -> Option<(&str, &str)> {
+    s.strip_prefix(ws_chars::SUPERSCRIPT)?
+        .split_once(ws_chars::SUPERSCRIPT)
+}
+
+/// Returns the replacement for a temperature unit (a superscripted or plain
+/// letter 'o' followed by 'C' or 'F', with or without spaces) or an ordinal
+/// suffix (between `ws_chars::SUPERSCRIPT` characters) at the start of the
+/// given text slice, which follows a number, together with the remaining
+/// text, otherwise `None`
+///
+/// # Arguments
+///
+/// * `s` - Slice of text after a number
+/// * `locale` - Conventions for numbers and units
+///
+/// # Examples
+/// ```
+/// let after = scan_unit(" \x14o\x14 C.", Locale::English);
+/// assert_eq!(after, Some(("\u{00B0}C".to_string(), ".")));
+/// ```
+fn scan_unit(s: &str, locale: Locale) -> Option<(String, &str)> {
+    let spaced = s.trim_start_matches(' ');
+    let degree = match split_superscript(spaced) {
+        Some(("o", rest)) => Some(rest),
+        _ => spaced.strip_prefix('o'),
+    };
+    if let Some(rest) = degree.map(|rest| rest.trim_start_matches(' ')) {
+        if let Some(unit) = rest.chars().next().filter(|&c| DEGREE_UNITS.contains(c)) {
+            let rest = &rest[1..];
+            if !rest.starts_with(|c: char| c.is_alphanumeric()) {
+                let unit = format!("{}{}{}", locale.degree_space(), uni_chars::DEGREE, unit);
+                return Some((unit, rest));
+            }
+        }
+    }
+    let (suffix, rest) = split_superscript(s)?;
+    let ordinal = match (locale, suffix) {
+        (Locale::English, suffix) if ENGLISH_ORDINALS.contains(&suffix) => suffix,
+        (locale, "o") if locale.is_continental() => uni_chars::MASCULINE_ORDINAL,
+        (locale, "a") if locale.is_continental() => uni_chars::FEMININE_ORDINAL,
+        _ => return None,
+    };
+    Some((ordinal.to_string(), rest))
+}
+
+/// Returns `Some(replacement)` if the given text slice contains one or more
+/// temperatures or ordinal numbers that have been tidied up according to the
+/// given conventions, otherwise `None`
+///
+/// A temperature is a number (with a decimal part after the separator for
+/// the locale) followed by a superscripted or plain letter 'o' and then 'C'
+/// or 'F', with or without spaces between them.  This is converted to the
+/// number followed by the Unicode "degree" symbol and the scale, separated
+/// from the number by a non-breaking space for continental locales.  An
+/// ordinal number is a number followed by a superscripted suffix, which is
+/// written as plain text for `Locale::English` (e.g. "1st") or converted to
+/// the Unicode ordinal indicator for continental locales (e.g. "1\u{00BA}").
+///
+/// # Arguments
+///
+/// * `s` - Slice of text to be processed
+/// * `locale` - Conventions for numbers and units
+///
+/// # Examples
+/// ```
+/// let after = transform_locale("25,5 o C", Locale::Continental);
+/// assert_eq!(after, Some("25,5\u{00A0}\u{00B0}C".to_string()));
+/// ```
+fn transform_locale(before: &str, locale: Locale) -> Option<String> {
+    if locale == Locale::None || !before.contains(|c: char| c.is_ascii_digit()) {
+        return None;
+    }
+    let mut after = String::with_capacity(before.len());
+    let mut found = false;
+    let mut rest = before;
+    while let Some(c) = rest.chars().next() {
+        if c.is_ascii_digit() {
+            let (number, remainder) = split_number(rest, locale.decimal_separator());
+            after.push_str(number);
+            rest = remainder;
+            if let Some((unit, remainder)) = scan_unit(rest, locale) {
+                after.push_str(&unit);
+                rest = remainder;
+                found = true;
+            }
+        } else {
+            after.push(c);
+            rest = &rest[c.len_utf8()..];
+        }
+    }
+    found.then_some(after)
+}
+
+/// Returns the replacement for a trade mark, copyright or registered sign
+/// or an ordinal suffix (if expanded for the given way of converting them)
+/// at the start of the given text slice together with the remaining text,
+/// otherwise `None`
+///
+/// "(c)" and "(r)" (in either case) are only taken as signs when attached to
+/// the end of a word (e.g. "WordStar(r)"), or for "(c)" also when followed by
+/// a number (e.g. "(c) 1985"), so that the items of lists are left alone.
+///
+/// # Arguments
+///
+/// * `s` - Slice of text to be examined
+/// * `prev` - Character before the text slice (if any)
+/// * `symbols` - Way of converting signs and ordinal suffixes
+///
+/// # Examples
+/// ```
+/// let after = scan_symbol("(r) is", Some('r'), Symbols::Plain);
+/// assert_eq!(after, Some(("\u{00AE}", " is")));
+/// ```
+fn scan_symbol(s: &str, prev: Option<char>, symbols: Symbols) -> Option<(&'static str, &str)> {
+    if let Some((text, rest)) = split_superscript(s) {
+        if text == TRADE_MARK_TEXT {
+            return Some((uni_chars::TRADE_MARK, rest));
+        }
+        let number = prev.is_some_and(|c| c.is_ascii_digit());
+        return match ENGLISH_ORDINALS.iter().find(|&&ordinal| ordinal == text) {
+            Some(ordinal) if number && symbols == Symbols::Plain => Some((ordinal, rest)),
+            _ => None,
+        };
+    }
+    let mark = s.get(..3)?;
+    let rest = &s[3..];
+    let attached = prev.is_some_and(char::is_alphanumeric);
+    let dated = rest
+        .trim_start_matches(' ')
+        .starts_with(|c: char| c.is_ascii_digit());
+    match mark {
+        "(c)" | "(C)" if attached || dated => Some((uni_chars::COPYRIGHT, rest)),
+        "(r)" | "(R)" if attached => Some((uni_chars::REGISTERED, rest)),
+        _ => None,
+    }
+}
+
+/// Returns `Some(replacement)` if the given text slice contains one or more
+/// trade mark, copyright or registered signs or ordinal suffixes that have
+/// been converted in the given way (see `Symbols`), otherwise `None`
+///
+/// # Arguments
+///
+/// * `s` - Slice of text to be processed
+/// * `symbols` - Way of converting signs and ordinal suffixes
+///
+/// # Examples
+/// ```
+/// let after = transform_symbols("WS\x14TM\x14 (c) 1985", Symbols::Superscript);
+/// assert_eq!(after, Some("WS\u{2122} \u{00A9} 1985".to_string()));
+/// ```
+fn transform_symbols(before: &str, symbols: Symbols) -> Option<String> {
+    if symbols == Symbols::None || !before.contains([ws_chars::SUPERSCRIPT, '(']) {
+        return None;
+    }
+    let mut after = String::with_capacity(before.len());
+    let mut found = false;
+    let mut prev = None;
+    let mut rest = before;
+    while let Some(c) = rest.chars().next() {
+        if let Some((symbol, remainder)) = scan_symbol(rest, prev, symbols) {
+            after.push_str(symbol);
+            prev = symbol.chars().last();
+            rest = remainder;
+            found = true;
+        } else {
+            after.push(c);
+            prev = Some(c);
+            rest = &rest[c.len_utf8()..];
+        }
+    }
+    found.then_some(after)
+}
+
+/// Returns `Some(replacement)` if the given text slice contains one or more
+/// guillemets (typed as "<<" or ">>") or accented letters (typed as an
+/// `ACCENT_PREFIX` before a letter) that have been converted to the Unicode
+/// characters for the given locale (if any), otherwise `None`
+///
+/// For `Locale::French`, a single space inside a guillemet is replaced with
+/// a non-breaking space to keep the guillemet with the text that it encloses.
+///
+/// # Arguments
+///
+/// * `s` - Slice of text to be processed
+/// * `locale` - Conventions for accents and quotation marks
+///
+/// # Examples
+/// ```
+/// let after = transform_accents("<< Voil`a >>", Locale::French);
+/// assert_eq!(after, Some("\u{00AB}\u{00A0}Voil\u{00E0}\u{00A0}\u{00BB}".to_string()));
+/// ```
+fn transform_accents(before: &str, locale: Locale) -> Option<String> {
+    let accents = locale.accents();
+    if accents.is_empty() || !before.contains([ACCENT_PREFIX, '<', '>']) {
+        return None;
+    }
+    let space = locale.guillemet_space();
+    let mut after = String::with_capacity(before.len());
+    let mut found = false;
+    let mut rest = before;
+    while let Some(c) = rest.chars().next() {
+        let accent = rest
+            .strip_prefix(ACCENT_PREFIX)
+            .and_then(|text| text.chars().next())
+            .and_then(|letter| accents.iter().find(|(typed, _)| *typed == letter));
+        if let Some((_, accented)) = accent {
+            after.push(*accented);
+            rest = &rest[ACCENT_PREFIX.len_utf8() + 1..];
+            found = true;
+        } else if let Some(&(typed, guillemet)) =
+            GUILLEMETS.iter().find(|(t, _)| rest.starts_with(t))
+        {
+            rest = &rest[typed.len()..];
+            if typed == GUILLEMETS[1].0 && !space.is_empty() && after.ends_with(' ') {
+                after.pop();
+                after.push_str(space);
+            }
+            after.push_str(guillemet);
+            if typed == GUILLEMETS[0].0 && !space.is_empty() {
+                if let Some(text) = rest.strip_prefix(' ') {
+                    after.push_str(space);
+                    rest = text;
+                }
+            }
+            found = true;
+        } else {
+            after.push(c);
+            rest = &rest[c.len_utf8()..];
+        }
+    }
+    found.then_some(after)
+}
+
+/// Returns `Some(replacement)` if the given text slice contains one or more
+/// matches for the given additional special sequences, which are replaced
+/// in turn, otherwise `None`
+///
+/// # Arguments
+///
+/// * `s` - Slice of text to be processed
+/// * `rules` - Slice of additional special sequences
+///
+/// # Examples
+/// ```
+/// let rules = parse_rules(r"\x02\x02 => !").unwrap();
+/// assert_eq!(transform_rules("a\x02\x02", &rules), Some("a!".to_string()));
+/// ```
+fn transform_rules(before: &str, rules: &[SpecialRule]) -> Option<String> {
+    let mut result: Option<String> = None;
+    for rule in rules {
+        let line = result.as_deref().unwrap_or(before);
+        result = rule.apply(line).or(result);
+    }
+    result
+}
+
+/// Returns the given text with any escapes ("\\xHH" for the character with
+/// hexadecimal code HH, "\\t" for a tab or a backslash followed by a
+/// punctuation character for that character) replaced, or `None` if it
+/// contains any other escape
+///
+/// # Arguments
+///
+/// * `s` - Slice of text with escapes
+///
+/// # Examples
+/// ```
+/// assert_eq!(unescape(r"a\x13b"), Some("a\x13b".to_string()));
+/// ```
+fn unescape(s: &str) -> Option<String> {
+    let mut result = String::with_capacity(s.len());
+    let mut chars = s.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            result.push(c);
+            continue;
+        }
+        match chars.next()? {
+            'x' => {
+                let hex: String = chars.by_ref().take(2).collect();
+                let code = u8::from_str_radix(&hex, 16)
+                    .ok()
+                    .filter(|_| hex.len() == 2)?;
+                result.push(char::from(code));
+            }
+            't' => result.push('\t'),
+            c if c.is_ascii_punctuation() => result.push(c),
+            _ => return None,
+        }
+    }
+    Some(result)
+}
+
+// "SPECIAL RULE" OBJECT
+
+impl SpecialRule {
+    /// Returns a new `SpecialRule` object for the given pattern (a regular
+    /// expression, or literal text with escapes as for a rules file without
+    /// the "regex" feature) and replacement text, or a description of the
+    /// problem if the pattern is not valid
+    ///
+    /// # Arguments
+    ///
+    /// * `pattern` - Slice of pattern text
+    /// * `replacement` - Slice of replacement text (without escapes)
+    ///
+    #[cfg(feature = "regex")]
+    pub fn new(pattern: &str, replacement: &str) -> Result<Self, String> {
+        Ok(SpecialRule {
+            regex: Regex::new(pattern).map_err(|e| e.to_string())?,
+            replacement: replacement.to_string(),
+        })
+    }
+
+    #[cfg(not(feature = "regex"))]
+    pub fn new(pattern: &str, replacement: &str) -> Result<Self, String> {
+        let pattern = unescape(pattern).ok_or("invalid escape in pattern")?;
+        if pattern.is_empty() {
+            return Err("empty pattern".to_string());
+        }
+        Ok(SpecialRule {
+            pattern,
+            replacement: replacement.to_string(),
+        })
+    }
+
+    /// Returns `Some(replacement)` if the given text slice contains one or
+    /// more matches for the pattern, otherwise `None`
+    #[cfg(feature = "regex")]
+    fn apply(&self, s: &str) -> Option<String> {
+        match self.regex.replace_all(s, self.replacement.as_str()) {
+            Cow::Owned(after) => Some(after),
+            Cow::Borrowed(_) => None,
+        }
+    }
+
+    #[cfg(not(feature = "regex"))]
+    fn apply(&self, s: &str) -> Option<String> {
+        s.contains(&self.pattern)
+            .then(|| s.replace(&self.pattern, &self.replacement))
+    }
+}
+
+// EXTERNAL PUBLIC FUNCTIONS
+
+/// Returns the additional special sequences given in the text of a rules
+/// file, or a `std::io::Error` type identifying the first line in error
+///
+/// Each rule is a line holding a regular expression (or literal text
+/// without the "regex" feature) and its replacement text separated by "=>",
+/// with surrounding spaces ignored.  WordStar control characters are written
+/// as escapes such as "\\x13" in both (and the replacement may refer to
+/// captured groups as `$1` etc.).  Blank lines and lines starting with '#'
+/// are ignored.
+///
+/// # Arguments
+///
+/// * `text` - Slice of text of the rules file
+///
+/// # Examples
+/// ```
+/// let rules = parse_rules("# Bullet\n\\x14\\*\\x14 => \u{2022}\n").unwrap();
+/// assert_eq!(rules.len(), 1);
+/// ```
+pub fn parse_rules(text: &str) -> io::Result<Vec<SpecialRule>> {
+    let mut rules = Vec::new();
+    for (index, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with(RULE_COMMENT) {
+            continue;
+        }
+        let invalid = |reason: String| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("Rule at line {}: {}", index + 1, reason),
+            )
+        };
+        let (pattern, replacement) = line
+            .split_once(RULE_SEPARATOR)
+            .ok_or_else(|| invalid(format!("expected '{}'", RULE_SEPARATOR)))?;
+        let replacement = unescape(replacement.trim())
+            .ok_or_else(|| invalid("invalid escape in replacement".to_string()))?;
+        rules.push(SpecialRule::new(pattern.trim(), &replacement).map_err(invalid)?);
+    }
+    Ok(rules)
+}
+
+/// Returns the additional special sequences given in the rules file at the
+/// given path (see `parse_rules()`), or a `std::io::Error` type on failure
+///
+/// # Arguments
+///
+/// * `path` - Path to rules file
+///
+/// # Examples
+/// ```
+/// let rules = ws_special::read_rules(Path::new("site.rules")).unwrap();
+/// ```
+pub fn read_rules(path: &Path) -> io::Result<Vec<SpecialRule>> {
+    parse_rules(&fs::read_to_string(path)?)
+}
 
 /// Returns `Some(replacement)` if the given text slice contains any of the
 /// special sequences and therefore needs to be replaced, otherwise `None`
@@ -126,20 +867,67 @@ fn transform_fraction(before: &str) -> Option<String> {
 /// # Arguments
 ///
 /// * `s` - Slice of text to be processed
+/// * `fraction_style` - Representation of fractions
 ///
 /// # Examples
 /// ```
-/// let before = "6\x141\x14\x08\x162\x16";
-/// assert_eq!(process(before), Some("6\u{00BD}".to_string()));
+/// let before = "6\x13\x141\x14\x13\x08\x162\x16";
+/// assert_eq!(process(before, FractionStyle::Unicode), Some("6\u{00BD}".to_string()));
 /// ```
-pub fn process(s: &str) -> Option<String> {
-    let mut result: Option<String> = None;
-    let mut line = s;
+pub fn process(s: &str, fraction_style: FractionStyle) -> Option<String> {
+    process_with(s, fraction_style, Locale::None, Symbols::None, &[], &[])
+}
+
+/// Returns `Some(replacement)` if the given text slice contains any of the
+/// special sequences (including the given additional fractions and special
+/// sequences) and therefore needs to be replaced, otherwise `None`
+///
+/// # Arguments
+///
+/// * `s` - Slice of text to be processed
+/// * `fraction_style` - Representation of fractions
+/// * `locale` - Conventions for numbers and units (for temperatures and
+///   ordinal numbers) and for accents and quotation marks
+/// * `symbols` - Way of converting trade mark, copyright and registered
+///   signs and ordinal suffixes
+/// * `fractions` - Slice of additional fractions (taking precedence over
+///   the standard fractions)
+/// * `rules` - Slice of additional special sequences (replaced before the
+///   standard sequences)
+///
+/// # Examples
+/// ```
+/// let before = "\x13\x145\x14\x13\x08\x166\x16";
+/// let sixths = CustomFraction { numerator: 5, denominator: 6, symbol: "\u{215A}".to_string() };
+/// let after = process_with(
+///     before, FractionStyle::Unicode, Locale::None, Symbols::None, &[sixths], &[]
+/// );
+/// assert_eq!(after, Some("\u{215A}".to_string()));
+/// ```
+pub fn process_with(
+    s: &str,
+    fraction_style: FractionStyle,
+    locale: Locale,
+    symbols: Symbols,
+    fractions: &[CustomFraction],
+    rules: &[SpecialRule],
+) -> Option<String> {
+    let mut result: Option<String> = transform_rules(s, rules);
+    let mut line = result.as_deref().unwrap_or(s);
+
+    result = transform_symbols(line, symbols).or(result);
+    line = result.as_deref().unwrap_or(s);
+
+    result = transform_locale(line, locale).or(result);
+    line = result.as_deref().unwrap_or(s);
+
+    result = transform_accents(line, locale).or(result);
+    line = result.as_deref().unwrap_or(s);
 
     result = transform_degrees(line).or(result);
     line = result.as_deref().unwrap_or(s);
 
-    result = transform_fraction(line).or(result);
+    result = transform_fraction(line, fraction_style, fractions).or(result);
 
     result
 }
@@ -164,43 +952,330 @@ mod tests {
     fn test_transform_fraction() {
         assert_eq!(
             transform_fraction(
-                "6\x13\x141\x14\x13\x08\x162\x16 has \x13\x141\x14\x13\x08\x162\x16!"
+                "6\x13\x141\x14\x13\x08\x162\x16 has \x13\x141\x14\x13\x08\x162\x16!",
+                FractionStyle::Unicode,
+                &[]
             ),
             Some("6\u{00BD} has \u{00BD}!".to_string())
         );
         assert_eq!(
             transform_fraction(
-                "6\x13\x141\x14\x13\x08\x164\x16 or 6\x13\x143\x14\x13\x08\x164\x16"
+                "6\x13\x141\x14\x13\x08\x164\x16 or 6\x13\x143\x14\x13\x08\x164\x16",
+                FractionStyle::Unicode,
+                &[]
             ),
             Some("6\u{00BC} or 6\u{00BE}".to_string())
         );
         assert_eq!(
-            transform_fraction("\x13\x141\x14\x13\x08\x162\x16"),
+            transform_fraction(
+                "\x13\x141\x14\x13\x08\x162\x16",
+                FractionStyle::Unicode,
+                &[]
+            ),
             Some("\u{00BD}".to_string())
         );
         assert_eq!(
-            transform_fraction("\x13\x143\x14\x13\x08\x162\x16"),
+            transform_fraction(
+                "\x13\x143\x14\x13\x08\x162\x16",
+                FractionStyle::Unicode,
+                &[]
+            ),
             Some("\u{FFFD}".to_string())
         );
-        assert_eq!(transform_fraction("abcd"), None);
-        assert_eq!(transform_fraction(""), None);
+        assert_eq!(
+            transform_fraction("abcd", FractionStyle::Unicode, &[]),
+            None
+        );
+        assert_eq!(transform_fraction("", FractionStyle::Unicode, &[]), None);
+    }
+
+    #[test]
+    fn test_lookup_fraction() {
+        assert_eq!(lookup_fraction(1, 2, &[]), Some(uni_chars::HALF));
+        assert_eq!(lookup_fraction(2, 3, &[]), Some(uni_chars::TWO_THIRDS));
+        assert_eq!(lookup_fraction(7, 8, &[]), Some(uni_chars::SEVEN_EIGHTHS));
+        assert_eq!(lookup_fraction(3, 2, &[]), None);
+        let custom = [CustomFraction {
+            numerator: 1,
+            denominator: 2,
+            symbol: "one half".to_string(),
+        }];
+        assert_eq!(lookup_fraction(1, 2, &custom), Some("one half"));
+    }
+
+    #[test]
+    fn test_extra_fractions() {
+        assert_eq!(
+            transform_fraction(
+                "\x13\x141\x14\x13\x08\x163\x16, \x13\x142\x14\x13\x08\x163\x16 and \
+                 5\x13\x145\x14\x13\x08\x168\x16",
+                FractionStyle::Unicode,
+                &[]
+            ),
+            Some("\u{2153}, \u{2154} and 5\u{215D}".to_string())
+        );
+        let custom = [CustomFraction {
+            numerator: 1,
+            denominator: 16,
+            symbol: "\u{215F}16".to_string(),
+        }];
+        assert_eq!(
+            process_with(
+                "\x13\x141\x14\x13\x08\x1616\x16 or \x13\x145\x14\x13\x08\x166\x16",
+                FractionStyle::Unicode,
+                Locale::None,
+                Symbols::None,
+                &custom,
+                &[]
+            ),
+            Some("\u{215F}16 or \u{FFFD}".to_string())
+        );
+    }
+
+    #[test]
+    fn test_get_fraction() {
+        assert_eq!(
+            get_fraction("", "2", "3", FractionStyle::Unicode, &[]),
+            "\u{2154}"
+        );
+        assert_eq!(
+            get_fraction("6", "1", "2", FractionStyle::Slash, &[]),
+            "6 1/2"
+        );
+        assert_eq!(
+            get_fraction("", "3", "2", FractionStyle::Unicode, &[]),
+            "\u{FFFD}"
+        );
+    }
+
+    #[test]
+    fn test_unescape() {
+        assert_eq!(unescape(r"a\x13b\tc\\"), Some("a\x13b\tc\\".to_string()));
+        assert_eq!(unescape(r"\*\$"), Some("*$".to_string()));
+        assert_eq!(unescape("$1 \u{2022}"), Some("$1 \u{2022}".to_string()));
+        assert_eq!(unescape(r"\x1"), None);
+        assert_eq!(unescape(r"\xZZ"), None);
+        assert_eq!(unescape(r"\n"), None);
+        assert_eq!(unescape("\\"), None);
+    }
+
+    #[test]
+    fn test_parse_rules() {
+        let rules = parse_rules(
+            "# Site printer tricks\n\
+             \n\
+             \\x14\\*\\x14 => \\x14\u{2022}\\x14\n\
+             \\x02x\\x02 => \u{00D7}\n",
+        )
+        .unwrap();
+        assert_eq!(rules.len(), 2);
+        assert_eq!(rules[0].replacement, "\x14\u{2022}\x14");
+        assert_eq!(
+            transform_rules("\x14*\x14 and 3\x02x\x024", &rules),
+            Some("\x14\u{2022}\x14 and 3\u{00D7}4".to_string())
+        );
+        assert_eq!(transform_rules("abc", &rules), None);
+        assert_eq!(transform_rules("abc", &[]), None);
+
+        let error = parse_rules("a => b\nno separator\n").unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+        assert_eq!(error.to_string(), "Rule at line 2: expected '=>'");
+        assert!(parse_rules(r"a => \q").is_err());
+    }
+
+    #[cfg(feature = "regex")]
+    #[test]
+    fn test_regex_rules() {
+        let rules = parse_rules("(\\d)\\x02x\\x02 => ${1}\u{00D7}").unwrap();
+        assert_eq!(
+            transform_rules("3\x02x\x024", &rules),
+            Some("3\u{00D7}4".to_string())
+        );
+        assert!(parse_rules("( => b").is_err());
+    }
+
+    #[cfg(not(feature = "regex"))]
+    #[test]
+    fn test_literal_rules() {
+        let rules = parse_rules(r"(a)\x02 => x").unwrap();
+        assert_eq!(transform_rules("a\x02", &rules), None);
+        assert_eq!(transform_rules("(a)\x02", &rules), Some("x".to_string()));
+        assert!(parse_rules(r"\d => b").is_err());
+        assert!(parse_rules(" => b").is_err());
+        assert_eq!(
+            scan_fraction("\x13\x141\x14\x13\x08\x162\x16!"),
+            Some(("1", "2", "!"))
+        );
+        assert_eq!(scan_fraction("\x13\x14\x14\x13\x08\x162\x16"), None);
+    }
+
+    #[test]
+    fn test_process_with_rules() {
+        let rules = parse_rules(r"\x14\*\x14 => \x14o\x14").unwrap();
+        assert_eq!(
+            process_with(
+                "\x14*\x14",
+                FractionStyle::Unicode,
+                Locale::None,
+                Symbols::None,
+                &[],
+                &rules
+            ),
+            Some("\u{00B0}".to_string())
+        );
+    }
+
+    #[test]
+    fn test_scan_symbol() {
+        assert_eq!(
+            scan_symbol("\x14TM\x14.", Some('S'), Symbols::Superscript),
+            Some(("\u{2122}", "."))
+        );
+        assert_eq!(
+            scan_symbol("\x14th\x14", Some('4'), Symbols::Plain),
+            Some(("th", ""))
+        );
+        assert_eq!(
+            scan_symbol("\x14th\x14", Some('4'), Symbols::Superscript),
+            None
+        );
+        assert_eq!(scan_symbol("\x14th\x14", Some('x'), Symbols::Plain), None);
+        assert_eq!(
+            scan_symbol("(C)1985", Some(' '), Symbols::Plain),
+            Some(("\u{00A9}", "1985"))
+        );
+        assert_eq!(scan_symbol("(c) item", Some(' '), Symbols::Plain), None);
+        assert_eq!(scan_symbol("(r) item", None, Symbols::Plain), None);
+        assert_eq!(scan_symbol("(", Some('a'), Symbols::Plain), None);
+    }
+
+    #[test]
+    fn test_transform_symbols() {
+        assert_eq!(
+            transform_symbols("WordStar(R)\x14TM\x14 (c) 1985", Symbols::Superscript),
+            Some("WordStar\u{00AE}\u{2122} \u{00A9} 1985".to_string())
+        );
+        assert_eq!(
+            transform_symbols("The 1\x14st\x14 and 22\x14nd\x14", Symbols::Plain),
+            Some("The 1st and 22nd".to_string())
+        );
+        assert_eq!(
+            transform_symbols("The 1\x14st\x14", Symbols::Superscript),
+            None
+        );
+        assert_eq!(transform_symbols("(a), (b) or (c)", Symbols::Plain), None);
+        assert_eq!(transform_symbols("WS(r)\x14TM\x14", Symbols::None), None);
+    }
+
+    #[test]
+    fn test_split_number() {
+        assert_eq!(split_number("25.5 o C", '.'), ("25.5", " o C"));
+        assert_eq!(split_number("25,5 o C", '.'), ("25", ",5 o C"));
+        assert_eq!(split_number("1989.", '.'), ("1989", "."));
+        assert_eq!(split_number("12", ','), ("12", ""));
+    }
+
+    #[test]
+    fn test_transform_locale() {
+        assert_eq!(
+            transform_locale("Set 25.5 o C or 78 \x14o\x14F", Locale::English),
+            Some("Set 25.5\u{00B0}C or 78\u{00B0}F".to_string())
+        );
+        assert_eq!(
+            transform_locale("Set 25,5 \x14o\x14 C.", Locale::Continental),
+            Some("Set 25,5\u{00A0}\u{00B0}C.".to_string())
+        );
+        assert_eq!(
+            transform_locale("The 1\x14st\x14 and 22\x14nd\x14", Locale::English),
+            Some("The 1st and 22nd".to_string())
+        );
+        assert_eq!(
+            transform_locale("El 1\x14o\x14 y la 2\x14a\x14", Locale::Continental),
+            Some("El 1\u{00BA} y la 2\u{00AA}".to_string())
+        );
+        assert_eq!(transform_locale("At 2 o Clock", Locale::English), None);
+        assert_eq!(transform_locale("1\x14er\x14", Locale::Continental), None);
+        assert_eq!(transform_locale("25 o C", Locale::None), None);
+        assert_eq!(transform_locale("abcd", Locale::English), None);
+    }
+
+    #[test]
+    fn test_transform_accents() {
+        assert_eq!(
+            transform_accents("Tr`es << `Ca va >>", Locale::French),
+            Some("Tr\u{00E8}s \u{00AB}\u{00A0}\u{00C7}a va\u{00A0}\u{00BB}".to_string())
+        );
+        assert_eq!(
+            transform_accents(">>Gr`u`se<< `Ubel", Locale::German),
+            Some("\u{00BB}Gr\u{00FC}\u{00DF}e\u{00AB} \u{00DC}bel".to_string())
+        );
+        assert_eq!(
+            transform_accents("`?Ma`nana?", Locale::Spanish),
+            Some("\u{00BF}Ma\u{00F1}ana?".to_string())
+        );
+        assert_eq!(transform_accents("`x and `", Locale::German), None);
+        assert_eq!(transform_accents("<< `e >>", Locale::Continental), None);
+    }
+
+    #[test]
+    fn test_process_with_locale() {
+        assert_eq!(
+            process_with(
+                "1\x14o\x14 at 40\x14o\x14C",
+                FractionStyle::Unicode,
+                Locale::Continental,
+                Symbols::None,
+                &[],
+                &[]
+            ),
+            Some("1\u{00BA} at 40\u{00A0}\u{00B0}C".to_string())
+        );
+        assert_eq!(
+            process_with(
+                "1\x14st\x14 at 40\x14o\x14",
+                FractionStyle::Unicode,
+                Locale::English,
+                Symbols::None,
+                &[],
+                &[]
+            ),
+            Some("1st at 40\u{00B0}".to_string())
+        );
+    }
+
+    #[test]
+    fn test_fraction_style() {
+        assert_eq!(
+            transform_fraction(
+                "6\x13\x141\x14\x13\x08\x162\x16, \x13\x143\x14\x13\x08\x162\x16",
+                FractionStyle::Slash,
+                &[]
+            ),
+            Some("6 1/2, 3/2".to_string())
+        );
     }
 
     #[test]
     fn test_process() {
         assert_eq!(
-            process("-40\x14o\x14C is -40\x14o\x14F"),
+            process("-40\x14o\x14C is -40\x14o\x14F", FractionStyle::Unicode),
             Some("-40°C is -40°F".to_string())
         );
         assert_eq!(
-            process("6\x13\x141\x14\x13\x08\x162\x16 has \x13\x141\x14\x13\x08\x162\x16!"),
+            process(
+                "6\x13\x141\x14\x13\x08\x162\x16 has \x13\x141\x14\x13\x08\x162\x16!",
+                FractionStyle::Unicode
+            ),
             Some("6\u{00BD} has \u{00BD}!".to_string())
         );
         assert_eq!(
-            process("6\x13\x141\x14\x13\x08\x164\x16 or 6\x13\x143\x14\x13\x08\x164\x16"),
+            process(
+                "6\x13\x141\x14\x13\x08\x164\x16 or 6\x13\x143\x14\x13\x08\x164\x16",
+                FractionStyle::Unicode
+            ),
             Some("6\u{00BC} or 6\u{00BE}".to_string())
         );
-        assert_eq!(process("abcd"), None);
-        assert_eq!(process(""), None);
+        assert_eq!(process("abcd", FractionStyle::Unicode), None);
+        assert_eq!(process("", FractionStyle::Unicode), None);
     }
 }
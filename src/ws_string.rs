@@ -30,7 +30,7 @@ pub fn len_in_chars(s: &str) -> usize {
 ///
 /// # Examples
 /// ```
-/// assert_eq!(contains_only_char("aaaa", 'a'), true);
+/// assert!(contains_only_char("aaaa", 'a'));
 /// ```
 pub fn contains_only_char(s: &str, only: char) -> bool {
     s.chars().all(|ch| ch == only)
@@ -46,7 +46,7 @@ pub fn contains_only_char(s: &str, only: char) -> bool {
 ///
 /// # Examples
 /// ```
-/// assert_eq!(contains_only_print("abc 123"), true);
+/// assert!(contains_only_print("abc 123"));
 /// ```
 pub fn contains_only_print(s: &str) -> bool {
     s.chars().all(|ch| !char::is_ascii_control(&ch))
@@ -176,16 +176,16 @@ mod tests {
 
     #[test]
     fn test_contains_only_char() {
-        assert_eq!(contains_only_char("aaaa", 'a'), true);
-        assert_eq!(contains_only_char("aaba", 'a'), false);
-        assert_eq!(contains_only_char("", 'a'), true);
+        assert!(contains_only_char("aaaa", 'a'));
+        assert!(!contains_only_char("aaba", 'a'));
+        assert!(contains_only_char("", 'a'));
     }
 
     #[test]
     fn test_contains_only_print() {
-        assert_eq!(contains_only_print("normal text"), true);
-        assert_eq!(contains_only_print("bro\x08ken text"), false);
-        assert_eq!(contains_only_print(""), true);
+        assert!(contains_only_print("normal text"));
+        assert!(!contains_only_print("bro\x08ken text"));
+        assert!(contains_only_print(""));
     }
 
     #[test]
@@ -0,0 +1,154 @@
+//! Module to extract the text from WordStar for Windows (.wsd) documents
+
+// WordStar for Windows keeps its text in a binary container, interleaved
+// with header records and the style runs that give the formatting of each
+// stretch of text, rather than marking the formatting with control
+// characters as earlier versions of WordStar do.  Without a full reader for
+// the container, the text is recovered here as the runs of printable bytes
+// (in the Windows character set) that are long enough to be unlikely to be
+// part of a binary record.  The formatting is lost, and a word broken by a
+// style run may lose a few letters, but the body of the document survives.
+
+use std::io::{self, Read, Write};
+
+const MIN_TEXT_RUN: usize = 4; // Shorter runs are taken to be binary data
+const CR_BYTE: u8 = 0x0D; // Paragraph end (usually followed by LF)
+const LF_BYTE: u8 = 0x0A; // Line feed
+const TAB_BYTE: u8 = 0x09; // Tab
+
+// PRIVATE HELPER FUNCTIONS
+
+/// Returns `true` if the given byte may be part of the text of a document,
+/// i.e. it is a printable Windows (Latin-1) character, a tab or a line
+/// ending, otherwise `false`
+///
+/// # Arguments
+///
+/// * `byte` - Byte to be checked
+///
+fn is_text(byte: u8) -> bool {
+    matches!(byte, b' '..=b'~' | 0xA0..=0xFF | TAB_BYTE | CR_BYTE | LF_BYTE)
+}
+
+/// Appends the given run of text bytes to the output as UTF-8, with each
+/// line ending (CR, LF or CR LF) replaced by a single line feed
+///
+/// # Arguments
+///
+/// * `run` - Byte (u8) slice of text bytes
+/// * `output` - String to which the text is appended
+///
+fn push_run(run: &[u8], output: &mut String) {
+    let mut previous = 0;
+    for &byte in run {
+        match byte {
+            CR_BYTE => output.push('\n'),
+            LF_BYTE if previous == CR_BYTE => (),
+            _ => output.push(char::from(byte)),
+        }
+        previous = byte;
+    }
+}
+
+// EXTERNAL PUBLIC FUNCTIONS
+
+/// Returns `true` if the given filename has the extension for a WordStar for
+/// Windows document (".wsd"), ignoring any ".gz" extension after it,
+/// otherwise `false`
+///
+/// # Arguments
+///
+/// * `filename` - Path to file
+///
+/// # Examples
+/// ```
+/// assert!(ws_windows::is_wsd("letter.wsd.gz"));
+/// ```
+pub fn is_wsd(filename: &str) -> bool {
+    let lower = filename.to_ascii_lowercase();
+    let name = lower.strip_suffix(".gz").unwrap_or(&lower);
+    name.ends_with(".wsd")
+}
+
+/// Returns the text extracted from the given WordStar for Windows document
+/// as a string, taking each run of at least `MIN_TEXT_RUN` text bytes (see
+/// `is_text()`) and discarding everything in between
+///
+/// # Arguments
+///
+/// * `buf` - Byte (u8) slice holding the whole document
+///
+/// # Examples
+/// ```
+/// assert_eq!(ws_windows::extract(b"\x01\x00Hello\r\n\x05\x00"), "Hello\n");
+/// ```
+pub fn extract(buf: &[u8]) -> String {
+    let mut output = String::with_capacity(buf.len());
+    for run in buf.split(|&byte| !is_text(byte)) {
+        if run.len() >= MIN_TEXT_RUN {
+            push_run(run, &mut output);
+        }
+    }
+    output
+}
+
+/// Extracts the text from the WordStar for Windows document read from the
+/// given input (as for `extract()`), writing it to the given output as UTF-8
+///
+/// Returns `()` on success or a `std::io::Error` type on failure
+///
+/// # Arguments
+///
+/// * `input` - Source of bytes that implements `Read` trait
+/// * `output` - Destination for bytes that implements `Write` trait
+///
+/// # Examples
+/// ```
+/// let mut output = Vec::new();
+/// ws_windows::extract_file(&mut &b"\x01Hello\r\n"[..], &mut output).unwrap();
+/// ```
+pub fn extract_file(input: &mut dyn Read, output: &mut dyn Write) -> io::Result<()> {
+    let mut buf = Vec::new();
+    input.read_to_end(&mut buf)?;
+    let text = extract(&buf);
+    log::info!(
+        "Extracted {} bytes of text from {} byte WordStar for Windows document",
+        text.len(),
+        buf.len()
+    );
+    output.write_all(text.as_bytes())
+}
+
+// Unit tests
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_wsd() {
+        assert!(is_wsd("letter.wsd"));
+        assert!(is_wsd("LETTER.WSD"));
+        assert!(is_wsd("letter.wsd.gz"));
+        assert!(!is_wsd("letter.ws"));
+        assert!(!is_wsd("letter.ws.gz"));
+    }
+
+    #[test]
+    fn test_extract() {
+        assert_eq!(extract(b""), "");
+        assert_eq!(
+            extract(b"WSWin\x02\x00\x10\x00First para\r\nSecond\x01\x07ab\x00\x03 part\r\n"),
+            "WSWinFirst para\nSecond part\n"
+        );
+        assert_eq!(extract(b"\x00\x12ab\x00Caf\xE9\rend\n"), "Caf\u{e9}\nend\n");
+        assert_eq!(extract(b"Tab\there\x13\x13\x13\x13"), "Tab\there");
+    }
+
+    #[test]
+    fn test_extract_file() {
+        let mut output = Vec::new();
+        extract_file(&mut &b"\x01\x00Some text\r\n\x00"[..], &mut output).unwrap();
+        assert_eq!(output, b"Some text\n");
+    }
+}
@@ -7,11 +7,321 @@
 use crate::uni_chars;
 use crate::ws_chars;
 use crate::ws_mappings;
+use bitflags::bitflags;
+use serde::ser::{SerializeSeq, Serializer};
+use serde::{Deserialize, Serialize};
+
+// Holds a set of flags for the text attributes applied by wrapper characters
+bitflags! {
+    #[derive(Default)]
+    pub struct Style: u8 {
+        const BOLD = (1 << 0);
+        const ITALIC = (1 << 1);
+        const UNDERLINE = (1 << 2);
+        const STRIKETHROUGH = (1 << 3);
+        const OVERLINE = (1 << 4);
+        const SUPERSCRIPT = (1 << 5);
+        const SUBSCRIPT = (1 << 6);
+        const ALTERNATE = (1 << 7);
+    }
+}
+
+// Names of the text attributes, as used in serialized output
+pub(crate) const STYLE_NAMES: [(Style, &str); 8] = [
+    (Style::BOLD, "bold"),
+    (Style::ITALIC, "italic"),
+    (Style::UNDERLINE, "underline"),
+    (Style::STRIKETHROUGH, "strikethrough"),
+    (Style::OVERLINE, "overline"),
+    (Style::SUPERSCRIPT, "superscript"),
+    (Style::SUBSCRIPT, "subscript"),
+    (Style::ALTERNATE, "alternate"),
+];
+
+/// Serialize trait implementation for Style, giving a list of the names of
+/// the text attributes that are set
+impl Serialize for Style {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let names = STYLE_NAMES.iter().filter(|(flag, _)| self.contains(*flag));
+        let mut seq = serializer.serialize_seq(None)?;
+        for (_, name) in names {
+            seq.serialize_element(name)?;
+        }
+        seq.end()
+    }
+}
+
+// Markers placed around text with line attributes by `markup_spans()`
+const LINE_MARKERS: [(Style, &str); 3] = [
+    (Style::UNDERLINE, "__"),
+    (Style::STRIKETHROUGH, "~~"),
+    (Style::OVERLINE, "\u{203E}\u{203E}"),
+];
+
+/// Ways of representing underlined text
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub enum UnderlineStyle {
+    #[default]
+    Combining, // Combining low line after each character
+    UTag,             // Enclosed in HTML `<u>` tags
+    InsTag,           // Enclosed in HTML `<ins>` tags
+    DoubleUnderscore, // Enclosed in "__" (Markdown extension syntax)
+    None,             // Underlining dropped
+}
+
+impl UnderlineStyle {
+    /// Returns `Some((open, close))` holding the markers placed around runs
+    /// of underlined text in this style, or `None` if underlining is instead
+    /// represented by combining characters
+    pub fn markers(self) -> Option<(&'static str, &'static str)> {
+        match self {
+            UnderlineStyle::Combining => None,
+            UnderlineStyle::UTag => Some(("<u>", "</u>")),
+            UnderlineStyle::InsTag => Some(("<ins>", "</ins>")),
+            UnderlineStyle::DoubleUnderscore => Some(("__", "__")),
+            UnderlineStyle::None => Some(("", "")),
+        }
+    }
+}
+
+/// Holds a run of text that shares the same set of text attributes
+#[derive(Debug, PartialEq, Clone, Serialize)]
+pub struct Span {
+    pub style: Style,
+    pub text: String,
+}
+
+/// Returns new String formed by joining the text of each of the given spans
+///
+/// # Arguments
+///
+/// * `spans` - Slice of spans to be joined
+///
+/// # Examples
+/// ```
+/// let spans = [Span { style: Style::BOLD, text: "ab".to_string() }];
+/// assert_eq!(join_spans(&spans), "ab");
+/// ```
+pub fn join_spans(spans: &[Span]) -> String {
+    spans.iter().map(|span| span.text.as_str()).collect()
+}
+
+/// Returns new String formed by mapping the text of each of the given spans
+/// with a given function, after placing runs of underlined spans between the
+/// markers for the given underline style (and removing their underline
+/// attribute) unless it is represented by combining characters
+///
+/// Runs of underlining are ended at the end of the spans given, so that each
+/// line is marked up separately.
+///
+/// # Arguments
+///
+/// * `spans` - Slice of spans to be mapped
+/// * `underline` - Representation of underlined text
+/// * `map` - Function that returns the text for a span
+///
+/// # Examples
+/// ```
+/// let spans = [Span { style: Style::UNDERLINE, text: "ab".to_string() }];
+/// let text = map_spans_with(&spans, UnderlineStyle::UTag, |span| span.text.clone());
+/// assert_eq!(text, "<u>ab</u>");
+/// ```
+pub fn map_spans_with<F>(spans: &[Span], underline: UnderlineStyle, map: F) -> String
+where
+    F: Fn(&Span) -> String,
+{
+    let (open, close) = match underline.markers() {
+        Some(markers) => markers,
+        None => return spans.iter().map(map).collect(),
+    };
+    let mut result = String::new();
+    let mut underlined = false;
+    for span in spans {
+        if span.style.contains(Style::UNDERLINE) != underlined {
+            underlined = !underlined;
+            result.push_str(if underlined { open } else { close });
+        }
+        result.push_str(&map(&Span {
+            style: span.style - Style::UNDERLINE,
+            text: span.text.clone(),
+        }));
+    }
+    if underlined {
+        result.push_str(close);
+    }
+    result
+}
+
+/// Returns new String formed from the text of the given spans with their
+/// attributes mapped to Unicode characters, and underlining represented in
+/// the given style
+///
+/// # Arguments
+///
+/// * `spans` - Slice of spans to be mapped
+/// * `underline` - Representation of underlined text
+///
+/// # Examples
+/// ```
+/// let spans = [Span { style: Style::UNDERLINE, text: "ab".to_string() }];
+/// assert_eq!(map_spans(&spans, UnderlineStyle::DoubleUnderscore), "__ab__");
+/// ```
+pub fn map_spans(spans: &[Span], underline: UnderlineStyle) -> String {
+    map_spans_with(spans, underline, Wrappers::map_span)
+}
+
+/// Returns new String formed from the text of the given spans with the
+/// line attributes (underline, strikethrough and overline) represented by
+/// markers placed around each run of spans that share them, as an
+/// alternative to combining characters that keeps the length of the line
+/// close to the original, and the other attributes mapped to Unicode
+/// characters as for `map_spans()`
+///
+/// A run is kept open across spans that add or change other attributes, so
+/// that it is marked up once, and blank spaces at the edges of a run are
+/// left outside its markers.
+///
+/// # Arguments
+///
+/// * `spans` - Slice of spans to be marked up
+///
+/// # Examples
+/// ```
+/// let spans = [Span { style: Style::STRIKETHROUGH, text: "ab".to_string() }];
+/// assert_eq!(markup_spans(&spans), "~~ab~~");
+/// ```
+pub fn markup_spans(spans: &[Span]) -> String {
+    let lines = Style::UNDERLINE | Style::STRIKETHROUGH | Style::OVERLINE;
+    let mut result = String::new();
+    let mut open: Vec<(Style, &str)> = Vec::new(); // Markers of the runs open
+    let mut blank = String::new(); // Blank text held until the runs are known
+    for span in spans {
+        if span.text.trim().is_empty() {
+            blank.push_str(&Wrappers::map_span(&Span {
+                style: span.style - lines,
+                text: span.text.clone(),
+            }));
+            continue;
+        }
+        while open.iter().any(|(flag, _)| !span.style.contains(*flag)) {
+            let (_, marker) = open.pop().unwrap_or_default();
+            result.push_str(marker);
+        }
+        result.push_str(&blank);
+        blank.clear();
+        for &(flag, marker) in &LINE_MARKERS {
+            if span.style.contains(flag) && !open.iter().any(|(open, _)| *open == flag) {
+                open.push((flag, marker));
+                result.push_str(marker);
+            }
+        }
+        result.push_str(&Wrappers::map_span(&Span {
+            style: span.style - lines,
+            text: span.text.clone(),
+        }));
+    }
+    open.iter()
+        .rev()
+        .for_each(|(_, marker)| result.push_str(marker));
+    result.push_str(&blank);
+    result
+}
+
+/// Holds an additional (user-defined) wrapper control character together with
+/// the text attributes that it toggles, e.g. for site-specific printer drivers
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CustomWrapper {
+    pub code: char,
+    pub style: Style,
+}
+
+// PRIVATE HELPER FUNCTIONS
+
+/// Returns `Some(mapped)` if the given character can be mapped to a new
+/// Unicode character that incorporates the given text attributes, otherwise
+/// `None`
+///
+/// # Arguments
+///
+/// * `style` - Set of text attributes to be applied
+/// * `c` - Character to be mapped (if possible)
+///
+pub(crate) fn get_mapped(style: Style, c: char) -> Option<char> {
+    if style.contains(Style::SUPERSCRIPT) {
+        ws_mappings::get_superscript(c)
+    } else if style.contains(Style::SUBSCRIPT) {
+        ws_mappings::get_subscript(c)
+    } else if style.contains(Style::BOLD) {
+        if style.contains(Style::ITALIC) {
+            ws_mappings::get_bold_italic(c)
+        } else {
+            ws_mappings::get_bold(c)
+        }
+    } else if style.contains(Style::ITALIC) {
+        ws_mappings::get_italic(c)
+    } else {
+        None
+    }
+}
+
+/// Appends the given (non-control) character to the result with the given
+/// text attributes represented by Unicode characters (if possible)
+///
+/// Returns `true` if the character has been changed, otherwise `false`
+///
+/// # Arguments
+///
+/// * `result` - String to which the character is appended
+/// * `style` - Set of text attributes to be applied
+/// * `c` - Character to be appended
+///
+fn push_styled(result: &mut String, style: Style, c: char) -> bool {
+    let lines = Style::UNDERLINE | Style::OVERLINE | Style::STRIKETHROUGH;
+    if !style.intersects(lines) {
+        if let Some(mapped) = get_mapped(style, c) {
+            result.push(mapped);
+            return true;
+        }
+        result.push(c);
+        return false; // No-line original character
+    }
+    result.push(c);
+    if style.contains(Style::UNDERLINE) {
+        result.push(uni_chars::COMB_UNDERLINE);
+    }
+    if style.contains(Style::OVERLINE) {
+        result.push(uni_chars::COMB_OVERLINE);
+    }
+    if style.contains(Style::STRIKETHROUGH) {
+        result.push(uni_chars::COMB_STRIKETHROUGH);
+    }
+    true
+}
+
+// "WRAPPERS STATE" OBJECT
+
+/// Holds a snapshot of the wrapper characters that are toggled on in a
+/// `Wrappers` object, which can be serialized (e.g. to checkpoint a long
+/// conversion) and restored into the same or another `Wrappers` object (e.g.
+/// to convert paragraphs in parallel)
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+pub struct WrappersState {
+    pub overline: bool,
+    pub bold: bool,
+    pub double: bool,
+    pub underline: bool,
+    pub subscript: bool,
+    pub superscript: bool,
+    pub strikethrough: bool,
+    pub italic: bool,
+    pub alternate: bool,
+    pub custom: Vec<bool>, // Whether each additional wrapper is on (in order)
+}
 
 // "WRAPPERS" OBJECT
 
 // Holds states of WordStar wrapper characters that toggle functions on and off
-#[derive(Default, Debug)]
+#[derive(Default, Debug, Clone)]
 pub struct Wrappers {
     overline: bool,
     bold: bool,
@@ -21,6 +331,9 @@ pub struct Wrappers {
     superscript: bool,
     strikethrough: bool,
     italic: bool,
+    alternate: bool,
+    custom: Vec<(CustomWrapper, bool)>,
+    underline_style: UnderlineStyle,
 }
 
 impl Wrappers {
@@ -29,6 +342,44 @@ impl Wrappers {
         Default::default()
     }
 
+    /// Creates a new `Wrapper` object that also recognises the given additional
+    /// wrapper characters, with all fields set to `false`
+    ///
+    /// Additional wrappers cannot override the standard WordStar ones.
+    ///
+    /// # Arguments
+    ///
+    /// * `custom` - Slice of additional wrapper characters and their attributes
+    ///
+    pub fn with_custom(custom: &[CustomWrapper]) -> Self {
+        Wrappers {
+            custom: custom.iter().map(|wrapper| (*wrapper, false)).collect(),
+            ..Default::default()
+        }
+    }
+
+    /// Returns this `Wrappers` object with underlined text represented in the
+    /// given style by `process()`
+    pub fn with_underline_style(mut self, style: UnderlineStyle) -> Self {
+        self.underline_style = style;
+        self
+    }
+
+    /// Returns `true` if the given character is one of the standard WordStar
+    /// wrapper characters, otherwise `false`
+    ///
+    /// # Arguments
+    ///
+    /// * `c` - Character to be examined
+    ///
+    /// # Examples
+    /// ```
+    /// assert!(Wrappers::is_standard('\x02'));
+    /// ```
+    pub fn is_standard(c: char) -> bool {
+        Wrappers::new().check_toggle(c)
+    }
+
     /// Returns `true` if the given character is a "wrapper" control character
     /// that changes the state of this `Wrappers` object, otherwise `false`
     ///
@@ -46,35 +397,152 @@ impl Wrappers {
             ws_chars::SUPERSCRIPT => self.superscript = !self.superscript,
             ws_chars::STRIKETHROUGH => self.strikethrough = !self.strikethrough,
             ws_chars::ITALIC => self.italic = !self.italic,
-            _ => return false,
+            ws_chars::PITCH => self.alternate = !self.alternate,
+            _ => match self
+                .custom
+                .iter_mut()
+                .find(|(wrapper, _)| wrapper.code == c)
+            {
+                Some((_, active)) => *active = !*active,
+                None => return false,
+            },
         };
         true
     }
 
-    /// Returns `Some(mapped)` if the given character can be mapped to a new
-    /// Unicode character that incorporates the current state of this `Wrappers`
-    /// object, otherwise `None`
+    /// Returns a snapshot of the current state of this `Wrappers` object
+    ///
+    /// # Examples
+    /// ```
+    /// let mut w = Wrappers::new();
+    /// w.process("\x02bold");
+    /// assert!(w.state().bold);
+    /// ```
+    pub fn state(&self) -> WrappersState {
+        WrappersState {
+            overline: self.overline,
+            bold: self.bold,
+            double: self.double,
+            underline: self.underline,
+            subscript: self.subscript,
+            superscript: self.superscript,
+            strikethrough: self.strikethrough,
+            italic: self.italic,
+            alternate: self.alternate,
+            custom: self.custom.iter().map(|(_, active)| *active).collect(),
+        }
+    }
+
+    /// Sets the state of this `Wrappers` object from the given snapshot
+    /// (e.g. from `state()`), leaving its settings unchanged
+    ///
+    /// The additional wrappers are restored in order, so the snapshot should
+    /// be taken from a `Wrappers` object with the same additional wrappers.
+    /// Any that are missing from the snapshot are set to off.
+    ///
+    /// # Arguments
+    ///
+    /// * `state` - Snapshot of the state to be restored
+    ///
+    /// # Examples
+    /// ```
+    /// let mut w = Wrappers::new();
+    /// w.restore(&WrappersState { bold: true, ..Default::default() });
+    /// assert_eq!(w.style(), Style::BOLD);
+    /// ```
+    pub fn restore(&mut self, state: &WrappersState) {
+        self.overline = state.overline;
+        self.bold = state.bold;
+        self.double = state.double;
+        self.underline = state.underline;
+        self.subscript = state.subscript;
+        self.superscript = state.superscript;
+        self.strikethrough = state.strikethrough;
+        self.italic = state.italic;
+        self.alternate = state.alternate;
+        for (i, (_, active)) in self.custom.iter_mut().enumerate() {
+            *active = state.custom.get(i).copied().unwrap_or(false);
+        }
+    }
+
+    /// Returns the set of text attributes for the current state of this
+    /// `Wrappers` object
+    ///
+    /// Note: Bold and double-strike are both treated as bold, but cancel each
+    /// other out if both are active.  Attributes from any active additional
+    /// wrappers are added to those of the standard ones.
+    pub fn style(&self) -> Style {
+        let mut style = Style::empty();
+        style.set(Style::BOLD, self.bold ^ self.double);
+        style.set(Style::ITALIC, self.italic);
+        style.set(Style::UNDERLINE, self.underline);
+        style.set(Style::STRIKETHROUGH, self.strikethrough);
+        style.set(Style::OVERLINE, self.overline);
+        style.set(Style::SUPERSCRIPT, self.superscript);
+        style.set(Style::SUBSCRIPT, self.subscript);
+        style.set(Style::ALTERNATE, self.alternate);
+        for (wrapper, active) in &self.custom {
+            if *active {
+                style.insert(wrapper.style);
+            }
+        }
+        style
+    }
+
+    /// Returns new String formed from the text of the given span with its
+    /// attributes mapped to Unicode characters, as by `process()`
+    ///
+    /// This allows output formats that handle only some attributes directly
+    /// to fall back on the Unicode mappings for the rest.  The alternate pitch
+    /// attribute has no Unicode mapping, so it is ignored here.
     ///
     /// # Arguments
     ///
-    /// * `c` - Character to be mapped (if possible)
+    /// * `span` - Span of text to be mapped
     ///
-    fn get_mapped(&self, c: char) -> Option<char> {
-        if self.superscript {
-            ws_mappings::get_superscript(c)
-        } else if self.subscript {
-            ws_mappings::get_subscript(c)
-        } else if self.bold ^ self.double {
-            if self.italic {
-                ws_mappings::get_bold_italic(c)
+    /// # Examples
+    /// ```
+    /// let span = Span { style: Style::BOLD, text: "C".to_string() };
+    /// assert_eq!(Wrappers::map_span(&span), "\u{1D402}");
+    /// ```
+    pub fn map_span(span: &Span) -> String {
+        if (span.style - Style::ALTERNATE).is_empty() {
+            return span.text.clone(); // Nothing to map
+        }
+        let mut result = String::with_capacity(span.text.len() * 7); // Worst case
+        for c in span.text.chars() {
+            if c.is_ascii_control() {
+                result.push(c); // Retain control character
             } else {
-                ws_mappings::get_bold(c)
+                push_styled(&mut result, span.style, c);
             }
-        } else if self.italic {
-            ws_mappings::get_italic(c)
-        } else {
-            None
         }
+        result
+    }
+
+    /// Returns the number of characters in the given span whose text
+    /// attributes cannot be represented by `map_span()`, i.e. printable
+    /// characters with no Unicode mapping for the attributes applied
+    ///
+    /// # Arguments
+    ///
+    /// * `span` - Span of text to be examined
+    ///
+    /// # Examples
+    /// ```
+    /// let span = Span { style: Style::SUPERSCRIPT, text: "2q".to_string() };
+    /// assert_eq!(Wrappers::count_unmapped(&span), 1);
+    /// ```
+    pub fn count_unmapped(span: &Span) -> usize {
+        let lines = Style::UNDERLINE | Style::OVERLINE | Style::STRIKETHROUGH;
+        let mapped = Style::BOLD | Style::ITALIC | Style::SUPERSCRIPT | Style::SUBSCRIPT;
+        if span.style.intersects(lines) || !span.style.intersects(mapped) {
+            return 0;
+        }
+        span.text
+            .chars()
+            .filter(|&c| c.is_ascii_graphic() && get_mapped(span.style, c).is_none())
+            .count()
     }
 
     /// Returns `Some(replacement)` if the given text slice can be modified to
@@ -90,41 +558,48 @@ impl Wrappers {
     /// assert_eq!(w.process("\x02C\x02"), Some("\u{1D402}".to_string()));
     /// ```
     pub fn process(&mut self, s: &str) -> Option<String> {
-        let mut changed = false;
-        let mut result = String::with_capacity(s.len() * 7); // Worst case
+        if self.style().is_empty() && !s.contains(|c: char| c.is_ascii_control()) {
+            return None; // Nothing to toggle or map
+        }
+        let spans = self.split_spans(s);
+        let result = map_spans(&spans, self.underline_style);
+        (result != s).then_some(result)
+    }
+
+    /// Returns the given text slice split into spans of text that share the
+    /// same attributes, with the wrapper characters removed and the state of
+    /// this `Wrappers` object updated accordingly
+    ///
+    /// This is an alternative to `process()` for output formats that can
+    /// represent text attributes directly rather than via Unicode mappings.
+    /// Control characters other than wrappers are retained in the spans.
+    ///
+    /// # Arguments
+    ///
+    /// * `s` - Slice of text to be processed
+    ///
+    /// # Examples
+    /// ```
+    /// let mut w = Wrappers::new();
+    /// assert_eq!(w.split_spans("a\x02b\x02").len(), 2);
+    /// ```
+    pub fn split_spans(&mut self, s: &str) -> Vec<Span> {
+        let mut spans: Vec<Span> = Vec::new();
+        let mut style = self.style();
         for c in s.chars() {
-            if c.is_ascii_control() {
-                if self.check_toggle(c) {
-                    changed = true; // Eat wrapper control character
-                } else {
-                    result.push(c); // Retain other control character
-                }
-                continue; // Finished with control characters
+            if c.is_ascii_control() && self.check_toggle(c) {
+                style = self.style();
+                continue; // Eat wrapper control character
             }
-            if !self.underline && !self.overline && !self.strikethrough {
-                if let Some(mapped) = self.get_mapped(c) {
-                    result.push(mapped);
-                    changed = true;
-                } else {
-                    result.push(c);
-                }
-                continue; // Finished with mapped or no-line original character
-            }
-            result.push(c);
-            if self.underline {
-                result.push(uni_chars::COMB_UNDERLINE);
-                changed = true;
-            }
-            if self.overline {
-                result.push(uni_chars::COMB_OVERLINE);
-                changed = true;
-            }
-            if self.strikethrough {
-                result.push(uni_chars::COMB_STRIKETHROUGH);
-                changed = true;
+            match spans.last_mut() {
+                Some(span) if span.style == style => span.text.push(c),
+                _ => spans.push(Span {
+                    style,
+                    text: c.to_string(),
+                }),
             }
         }
-        changed.then(|| result)
+        spans
     }
 }
 
@@ -229,6 +704,144 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_split_spans() {
+        let mut w = Wrappers::new();
+        let span = |style, text: &str| Span {
+            style,
+            text: text.to_string(),
+        };
+        assert_eq!(
+            w.split_spans("a\x02b\x19c\x02d\x19\x06"),
+            vec![
+                span(Style::empty(), "a"),
+                span(Style::BOLD, "b"),
+                span(Style::BOLD | Style::ITALIC, "c"),
+                span(Style::ITALIC, "d"),
+                span(Style::empty(), "\x06"),
+            ]
+        );
+        assert_eq!(w.split_spans("\x13ab"), vec![span(Style::UNDERLINE, "ab")]);
+        assert_eq!(w.split_spans("c\x13"), vec![span(Style::UNDERLINE, "c")]);
+        assert_eq!(
+            w.split_spans("\x02\x04x\x04\x02"),
+            vec![span(Style::empty(), "x")]
+        );
+        assert_eq!(w.split_spans(""), vec![]);
+        assert_eq!(
+            w.split_spans("a\x0Eb\x0E"),
+            vec![span(Style::empty(), "a"), span(Style::ALTERNATE, "b")]
+        );
+    }
+
+    #[test]
+    fn test_pitch() {
+        let mut w = Wrappers::new();
+        assert_eq!(w.process("a\x0Eb\x0Ec"), Some("abc".to_string()));
+    }
+
+    #[test]
+    fn test_custom() {
+        let custom = [
+            CustomWrapper {
+                code: '\x11',
+                style: Style::BOLD,
+            },
+            CustomWrapper {
+                code: '\x12',
+                style: Style::empty(),
+            },
+        ];
+        let mut w = Wrappers::with_custom(&custom);
+        assert_eq!(w.process("\x11C\x11"), Some("\u{1D402}".to_string()));
+        assert_eq!(w.process("\x12a\x12\x17"), Some("a\x17".to_string()));
+        assert_eq!(
+            w.split_spans("\x11a\x13b"),
+            vec![
+                Span {
+                    style: Style::BOLD,
+                    text: "a".to_string()
+                },
+                Span {
+                    style: Style::BOLD | Style::UNDERLINE,
+                    text: "b".to_string()
+                },
+            ]
+        );
+        assert_eq!(Wrappers::new().process("\x11C\x11"), None);
+        assert!(Wrappers::is_standard('\x13'));
+        assert!(!Wrappers::is_standard('\x11'));
+    }
+
+    #[test]
+    fn test_map_span() {
+        let span = |style, text: &str| Span {
+            style,
+            text: text.to_string(),
+        };
+        assert_eq!(Wrappers::map_span(&span(Style::BOLD, "C")), "\u{1D402}");
+        assert_eq!(
+            Wrappers::map_span(&span(Style::UNDERLINE | Style::ALTERNATE, "a")),
+            "a\u{0332}"
+        );
+        assert_eq!(Wrappers::map_span(&span(Style::ALTERNATE, "ab")), "ab");
+    }
+
+    #[test]
+    fn test_markup_spans() {
+        let mut w = Wrappers::new();
+        let spans = w.split_spans("a\x13b c\x13 \x02d\x18e\x18\x02");
+        assert_eq!(markup_spans(&spans), "a__b c__ \u{1D41D}~~\u{1D41E}~~");
+        let spans = w.split_spans("\x13 \x01f\x01\x13");
+        assert_eq!(
+            markup_spans(&spans),
+            " __\u{203E}\u{203E}f\u{203E}\u{203E}__"
+        );
+        let spans = w.split_spans("\x13a\x02b\x02 \x18c\x18\x13 ");
+        assert_eq!(markup_spans(&spans), "__a\u{1D41B} ~~c~~__ ");
+        let spans = w.split_spans("\x13\x18a\x13b\x18");
+        assert_eq!(markup_spans(&spans), "__~~a~~__~~b~~");
+    }
+
+    #[test]
+    fn test_count_unmapped() {
+        let span = |style, text: &str| Span {
+            style,
+            text: text.to_string(),
+        };
+        assert_eq!(Wrappers::count_unmapped(&span(Style::BOLD, "C!d")), 1);
+        assert_eq!(Wrappers::count_unmapped(&span(Style::SUBSCRIPT, "2, x")), 1);
+        assert_eq!(Wrappers::count_unmapped(&span(Style::UNDERLINE, "!")), 0);
+        assert_eq!(Wrappers::count_unmapped(&span(Style::ALTERNATE, "!")), 0);
+    }
+
+    #[test]
+    fn test_join_spans() {
+        let mut w = Wrappers::new();
+        assert_eq!(join_spans(&w.split_spans("a\x02b\x02c")), "abc");
+        assert_eq!(join_spans(&[]), "");
+    }
+
+    #[test]
+    fn test_state() {
+        let custom = [CustomWrapper {
+            code: '\x11',
+            style: Style::ITALIC,
+        }];
+        let mut w = Wrappers::with_custom(&custom);
+        w.process("\x02a\x11b");
+        let state = w.state();
+        assert_eq!(state.custom, vec![true]);
+        let json = serde_json::to_string(&state).unwrap();
+        let state: WrappersState = serde_json::from_str(&json).unwrap();
+        let mut other = Wrappers::with_custom(&custom);
+        other.restore(&state);
+        assert_eq!(other.style(), Style::BOLD | Style::ITALIC);
+        assert_eq!(other.process("c"), w.process("c"));
+        other.restore(&WrappersState::default());
+        assert_eq!(other.style(), Style::empty());
+    }
+
     #[test]
     fn test_null() {
         let mut w = Wrappers::new();
@@ -236,4 +849,38 @@ mod tests {
         assert_eq!(w.process("abc"), None);
         assert_eq!(w.process(""), None);
     }
+
+    #[test]
+    fn test_underline_style() {
+        let mut w = Wrappers::new().with_underline_style(UnderlineStyle::UTag);
+        assert_eq!(
+            w.process("a\x13b\x02c\x13d\x02"),
+            Some("a<u>b\u{1D41C}</u>\u{1D41D}".to_string())
+        );
+        assert_eq!(w.process("plain"), None);
+        let mut w = Wrappers::new().with_underline_style(UnderlineStyle::None);
+        assert_eq!(w.process("\x13a\x13"), Some("a".to_string()));
+        let spans = [
+            Span {
+                style: Style::UNDERLINE,
+                text: "a".to_string(),
+            },
+            Span {
+                style: Style::UNDERLINE | Style::ITALIC,
+                text: "b".to_string(),
+            },
+        ];
+        assert_eq!(
+            map_spans(&spans, UnderlineStyle::InsTag),
+            "<ins>a\u{1D44F}</ins>"
+        );
+        assert_eq!(
+            map_spans(&spans, UnderlineStyle::DoubleUnderscore),
+            "__a\u{1D44F}__"
+        );
+        assert_eq!(
+            map_spans(&spans, UnderlineStyle::Combining),
+            "a\u{332}b\u{332}"
+        );
+    }
 }